@@ -0,0 +1,16 @@
+-- Application users (administrators and managers)
+CREATE TABLE users (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    full_name VARCHAR(100) NOT NULL,
+    password_hash TEXT NOT NULL,
+    role VARCHAR(20) NOT NULL DEFAULT 'manager'
+        CHECK (role IN ('admin', 'manager', 'viewer')),
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_users_modtime 
+BEFORE UPDATE ON users 
+FOR EACH ROW EXECUTE FUNCTION update_modified_column();
@@ -0,0 +1,15 @@
+-- History of scheduled background job runs. The unique slot keeps several
+-- instances from running the same scheduled occurrence twice.
+CREATE TABLE job_runs (
+    id SERIAL PRIMARY KEY,
+    job_name VARCHAR(50) NOT NULL,
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ,
+    status VARCHAR(20) NOT NULL DEFAULT 'running'
+        CHECK (status IN ('running', 'succeeded', 'failed')),
+    detail TEXT,
+    UNIQUE (job_name, scheduled_for)
+);
+
+CREATE INDEX idx_job_runs_started ON job_runs(job_name, started_at DESC);
@@ -0,0 +1,30 @@
+-- Recipients of the maintenance and insurance deadline digests
+CREATE TABLE notification_subscriptions (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    frequency VARCHAR(10) NOT NULL DEFAULT 'daily'
+        CHECK (frequency IN ('daily', 'weekly')),
+    days_ahead INTEGER NOT NULL DEFAULT 30
+        CHECK (days_ahead BETWEEN 1 AND 365),
+    include_maintenance BOOLEAN NOT NULL DEFAULT TRUE,
+    include_insurance BOOLEAN NOT NULL DEFAULT TRUE,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    last_sent_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_notification_subscriptions_email
+    ON notification_subscriptions(LOWER(email));
+
+-- Every deadline mailed to a subscriber, once while upcoming and once when
+-- overdue, so later digests do not repeat it
+CREATE TABLE notification_log (
+    id SERIAL PRIMARY KEY,
+    subscription_id INTEGER NOT NULL REFERENCES notification_subscriptions(id) ON DELETE CASCADE,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('maintenance', 'insurance')),
+    stage VARCHAR(10) NOT NULL CHECK (stage IN ('upcoming', 'overdue')),
+    due_date DATE NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (subscription_id, equipment_id, kind, stage, due_date)
+);
@@ -0,0 +1,2 @@
+-- Secret token in each user's .ics feed URL; NULL until one is issued
+ALTER TABLE users ADD COLUMN calendar_token VARCHAR(64) UNIQUE;
@@ -0,0 +1,19 @@
+DROP VIEW equipment_coverage;
+
+ALTER TABLE equipment ADD COLUMN insurance_renewal TIMESTAMPTZ;
+
+-- Keep the latest end date of each machine's active policies
+UPDATE equipment e
+SET insurance_renewal = latest.end_date
+FROM (
+    SELECT pe.equipment_id, MAX(p.end_date)::timestamptz AS end_date
+    FROM policy_equipment pe
+    JOIN insurance_policies p ON p.id = pe.policy_id
+    WHERE p.status = 'active'
+    GROUP BY pe.equipment_id
+) latest
+WHERE e.id = latest.equipment_id;
+
+DROP TABLE policy_documents;
+DROP TABLE policy_equipment;
+DROP TABLE insurance_policies;
@@ -0,0 +1,72 @@
+-- Insurance policies; one policy can cover many machines
+CREATE TABLE insurance_policies (
+    id SERIAL PRIMARY KEY,
+    insurer VARCHAR(100) NOT NULL,
+    policy_number VARCHAR(100) NOT NULL,
+    coverage_type VARCHAR(20) NOT NULL
+        CHECK (coverage_type IN ('all_risks', 'third_party', 'fire_theft', 'breakdown', 'other')),
+    premium DOUBLE PRECISION CHECK (premium >= 0),
+    deductible DOUBLE PRECISION CHECK (deductible >= 0),
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    -- 'renewed' once a successor policy has been recorded; expiry is derived from end_date
+    status VARCHAR(20) NOT NULL DEFAULT 'active'
+        CHECK (status IN ('active', 'renewed', 'cancelled')),
+    renewed_from_id INTEGER UNIQUE REFERENCES insurance_policies(id) ON DELETE SET NULL,
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (end_date >= start_date),
+    UNIQUE (insurer, policy_number, start_date)
+);
+
+CREATE INDEX idx_insurance_policies_end ON insurance_policies(end_date) WHERE status = 'active';
+
+CREATE TRIGGER update_insurance_policies_modtime
+BEFORE UPDATE ON insurance_policies
+FOR EACH ROW EXECUTE FUNCTION update_modified_column();
+
+CREATE TABLE policy_equipment (
+    policy_id INTEGER NOT NULL REFERENCES insurance_policies(id) ON DELETE CASCADE,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    PRIMARY KEY (policy_id, equipment_id)
+);
+
+CREATE INDEX idx_policy_equipment_equipment ON policy_equipment(equipment_id);
+
+-- Policy PDFs; the files live under paths.uploads
+CREATE TABLE policy_documents (
+    id SERIAL PRIMARY KEY,
+    policy_id INTEGER NOT NULL REFERENCES insurance_policies(id) ON DELETE CASCADE,
+    filename VARCHAR(255) NOT NULL,
+    stored_name VARCHAR(100) NOT NULL UNIQUE,
+    size_bytes BIGINT NOT NULL,
+    uploaded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One placeholder policy per machine that had a renewal date
+INSERT INTO insurance_policies (insurer, policy_number, coverage_type, start_date, end_date, notes)
+SELECT 'Unknown', 'LEGACY-' || id, 'other',
+    (insurance_renewal - INTERVAL '1 year')::date, insurance_renewal::date,
+    'Created from the former equipment insurance renewal date'
+FROM equipment
+WHERE insurance_renewal IS NOT NULL;
+
+INSERT INTO policy_equipment (policy_id, equipment_id)
+SELECT p.id, e.id
+FROM equipment e
+JOIN insurance_policies p ON p.insurer = 'Unknown' AND p.policy_number = 'LEGACY-' || e.id;
+
+ALTER TABLE equipment DROP COLUMN insurance_renewal;
+
+-- Whether each machine is insured today, and until when
+CREATE VIEW equipment_coverage AS
+SELECT e.id AS equipment_id,
+    MAX(p.end_date) AS covered_until,
+    COUNT(p.id) AS active_policies
+FROM equipment e
+LEFT JOIN policy_equipment pe ON pe.equipment_id = e.id
+LEFT JOIN insurance_policies p ON p.id = pe.policy_id
+    AND p.status = 'active'
+    AND CURRENT_DATE BETWEEN p.start_date AND p.end_date
+GROUP BY e.id;
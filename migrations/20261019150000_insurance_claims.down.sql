@@ -0,0 +1,2 @@
+DROP TABLE claim_repairs;
+DROP TABLE insurance_claims;
@@ -0,0 +1,36 @@
+-- Claims filed against a policy for damage to one covered machine
+CREATE TABLE insurance_claims (
+    id SERIAL PRIMARY KEY,
+    policy_id INTEGER NOT NULL REFERENCES insurance_policies(id),
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    claim_number VARCHAR(100) NOT NULL,
+    incident_date DATE NOT NULL,
+    filed_date DATE NOT NULL DEFAULT CURRENT_DATE,
+    description TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'filed'
+        CHECK (status IN ('filed', 'under_review', 'paid', 'rejected')),
+    claimed_amount DOUBLE PRECISION NOT NULL CHECK (claimed_amount >= 0),
+    paid_amount DOUBLE PRECISION CHECK (paid_amount >= 0),
+    -- Set when the insurer pays or rejects the claim
+    closed_date DATE,
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (filed_date >= incident_date),
+    CHECK (paid_amount IS NULL OR status = 'paid'),
+    UNIQUE (policy_id, claim_number)
+);
+
+CREATE INDEX idx_insurance_claims_equipment ON insurance_claims(equipment_id);
+CREATE INDEX idx_insurance_claims_open ON insurance_claims(policy_id) WHERE status IN ('filed', 'under_review');
+
+CREATE TRIGGER update_insurance_claims_modtime
+BEFORE UPDATE ON insurance_claims
+FOR EACH ROW EXECUTE FUNCTION update_modified_column();
+
+-- Repairs whose cost a claim seeks to recover; a repair belongs to one claim at most
+CREATE TABLE claim_repairs (
+    claim_id INTEGER NOT NULL REFERENCES insurance_claims(id) ON DELETE CASCADE,
+    maintenance_id INTEGER NOT NULL UNIQUE REFERENCES maintenance_history(id) ON DELETE CASCADE,
+    PRIMARY KEY (claim_id, maintenance_id)
+);
@@ -0,0 +1,2 @@
+DROP TABLE certification_categories;
+DROP TABLE certifications;
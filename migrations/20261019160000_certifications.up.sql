@@ -0,0 +1,35 @@
+-- Operator licences and certificates; a missing expiry date means it never expires
+CREATE TABLE certifications (
+    id SERIAL PRIMARY KEY,
+    staff_id INTEGER NOT NULL REFERENCES staff(id) ON DELETE CASCADE,
+    license_type VARCHAR(30) NOT NULL
+        CHECK (license_type IN ('driving_licence', 'heavy_vehicle', 'crane', 'earthmoving', 'forklift', 'aerial_platform', 'other')),
+    license_number VARCHAR(100),
+    issuing_authority VARCHAR(150) NOT NULL,
+    issue_date DATE NOT NULL,
+    expiry_date DATE,
+    -- Scanned certificate, stored under paths.uploads
+    document_filename VARCHAR(255),
+    document_stored_name VARCHAR(100) UNIQUE,
+    document_content_type VARCHAR(50),
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (expiry_date IS NULL OR expiry_date >= issue_date)
+);
+
+CREATE INDEX idx_certifications_staff ON certifications(staff_id);
+CREATE INDEX idx_certifications_expiry ON certifications(expiry_date);
+
+CREATE TRIGGER update_certifications_modtime
+BEFORE UPDATE ON certifications
+FOR EACH ROW EXECUTE FUNCTION update_modified_column();
+
+-- Equipment categories a certification qualifies its holder to operate
+CREATE TABLE certification_categories (
+    certification_id INTEGER NOT NULL REFERENCES certifications(id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    PRIMARY KEY (certification_id, category_id)
+);
+
+CREATE INDEX idx_certification_categories_category ON certification_categories(category_id);
@@ -0,0 +1,14 @@
+-- Only current assignments survive
+DELETE FROM equipment_operator WHERE assigned_to IS NOT NULL;
+
+DROP INDEX idx_equipment_operator_operator;
+DROP INDEX idx_equipment_operator_equipment;
+DROP INDEX idx_equipment_operator_current;
+
+ALTER TABLE equipment_operator DROP CONSTRAINT equipment_operator_period_check;
+ALTER TABLE equipment_operator DROP COLUMN assigned_to;
+ALTER TABLE equipment_operator DROP COLUMN id;
+ALTER TABLE equipment_operator RENAME COLUMN assigned_from TO assigned_date;
+
+ALTER TABLE equipment_operator ADD PRIMARY KEY (operator_id, equipment_id);
+CREATE INDEX idx_operator_equipment ON equipment_operator(operator_id, equipment_id);
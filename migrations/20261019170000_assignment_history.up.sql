@@ -0,0 +1,15 @@
+-- Operator assignments become dated periods: ending one keeps the row as history
+ALTER TABLE equipment_operator DROP CONSTRAINT equipment_operator_pkey;
+DROP INDEX idx_operator_equipment;
+
+ALTER TABLE equipment_operator RENAME COLUMN assigned_date TO assigned_from;
+ALTER TABLE equipment_operator ADD COLUMN id SERIAL PRIMARY KEY;
+ALTER TABLE equipment_operator ADD COLUMN assigned_to TIMESTAMPTZ;
+ALTER TABLE equipment_operator ADD CONSTRAINT equipment_operator_period_check
+    CHECK (assigned_to IS NULL OR assigned_to >= assigned_from);
+
+-- At most one open assignment per operator and machine
+CREATE UNIQUE INDEX idx_equipment_operator_current
+    ON equipment_operator(operator_id, equipment_id) WHERE assigned_to IS NULL;
+CREATE INDEX idx_equipment_operator_equipment ON equipment_operator(equipment_id, assigned_from);
+CREATE INDEX idx_equipment_operator_operator ON equipment_operator(operator_id, assigned_from);
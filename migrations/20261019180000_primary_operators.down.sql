@@ -0,0 +1,3 @@
+DROP INDEX idx_equipment_operator_primary;
+
+ALTER TABLE equipment_operator DROP COLUMN is_primary;
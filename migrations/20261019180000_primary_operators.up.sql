@@ -0,0 +1,5 @@
+-- A machine's main operator among those currently assigned
+ALTER TABLE equipment_operator ADD COLUMN is_primary BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE UNIQUE INDEX idx_equipment_operator_primary
+    ON equipment_operator(equipment_id) WHERE is_primary AND assigned_to IS NULL;
@@ -0,0 +1,3 @@
+DROP VIEW equipment_location;
+DROP TABLE deployments;
+DROP TABLE sites;
@@ -0,0 +1,46 @@
+-- Construction sites (projects) the fleet is deployed to
+CREATE TABLE sites (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL UNIQUE,
+    client VARCHAR(100),
+    address TEXT NOT NULL,
+    latitude DOUBLE PRECISION CHECK (latitude BETWEEN -90 AND 90),
+    longitude DOUBLE PRECISION CHECK (longitude BETWEEN -180 AND 180),
+    start_date DATE NOT NULL,
+    end_date DATE,
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((latitude IS NULL) = (longitude IS NULL)),
+    CHECK (end_date IS NULL OR end_date >= start_date)
+);
+
+CREATE TRIGGER update_sites_modtime
+BEFORE UPDATE ON sites
+FOR EACH ROW EXECUTE FUNCTION update_modified_column();
+
+-- A machine's stay on a site: from the day it arrived until the day it left
+-- (exclusive); an open stay has no deployed_until
+CREATE TABLE deployments (
+    id SERIAL PRIMARY KEY,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    site_id INTEGER NOT NULL REFERENCES sites(id),
+    deployed_from DATE NOT NULL,
+    deployed_until DATE,
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (deployed_until IS NULL OR deployed_until > deployed_from)
+);
+
+-- At most one open stay per machine
+CREATE UNIQUE INDEX idx_deployments_open ON deployments(equipment_id) WHERE deployed_until IS NULL;
+CREATE INDEX idx_deployments_equipment ON deployments(equipment_id, deployed_from);
+CREATE INDEX idx_deployments_site ON deployments(site_id, deployed_from);
+
+-- Where each machine is today; no row means it is at the yard
+CREATE VIEW equipment_location AS
+SELECT d.equipment_id, d.id AS deployment_id, d.site_id, s.name AS site_name, d.deployed_from
+FROM deployments d
+JOIN sites s ON s.id = d.site_id
+WHERE d.deployed_from <= CURRENT_DATE
+    AND (d.deployed_until IS NULL OR d.deployed_until > CURRENT_DATE);
@@ -0,0 +1,26 @@
+-- Requests to book a machine for a site over a range of days (both inclusive)
+CREATE TABLE reservations (
+    id SERIAL PRIMARY KEY,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    site_id INTEGER NOT NULL REFERENCES sites(id),
+    requested_by VARCHAR(100) NOT NULL,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    purpose TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'approved', 'rejected', 'cancelled')),
+    decided_by VARCHAR(100),
+    decided_at TIMESTAMPTZ,
+    decision_note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (end_date >= start_date)
+);
+
+CREATE INDEX idx_reservations_equipment ON reservations(equipment_id, start_date)
+    WHERE status IN ('pending', 'approved');
+CREATE INDEX idx_reservations_site ON reservations(site_id);
+
+CREATE TRIGGER update_reservations_modtime
+BEFORE UPDATE ON reservations
+FOR EACH ROW EXECUTE FUNCTION update_modified_column();
@@ -0,0 +1,2 @@
+DROP TABLE transfer_checks;
+DROP TABLE transfers;
@@ -0,0 +1,41 @@
+-- A machine moved between two locations (a site, or the yard when NULL):
+-- dispatched by a driver on one day, received on another
+CREATE TABLE transfers (
+    id SERIAL PRIMARY KEY,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    origin_site_id INTEGER REFERENCES sites(id),
+    destination_site_id INTEGER REFERENCES sites(id),
+    driver_id INTEGER REFERENCES staff(id) ON DELETE SET NULL,
+    dispatch_date DATE NOT NULL,
+    dispatch_meter DOUBLE PRECISION NOT NULL CHECK (dispatch_meter >= 0),
+    dispatch_notes TEXT,
+    -- Set together when the machine is received
+    arrival_date DATE,
+    arrival_meter DOUBLE PRECISION,
+    arrival_notes TEXT,
+    -- The arrival checklist found something worse than at dispatch
+    damage_candidate BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (origin_site_id IS DISTINCT FROM destination_site_id),
+    CHECK ((arrival_date IS NULL) = (arrival_meter IS NULL)),
+    CHECK (arrival_date >= dispatch_date),
+    CHECK (arrival_meter >= dispatch_meter)
+);
+
+-- At most one machine movement in progress at a time
+CREATE UNIQUE INDEX idx_transfers_open ON transfers(equipment_id) WHERE arrival_date IS NULL;
+CREATE INDEX idx_transfers_equipment ON transfers(equipment_id, dispatch_date);
+
+CREATE TRIGGER update_transfers_modtime
+BEFORE UPDATE ON transfers
+FOR EACH ROW EXECUTE FUNCTION update_modified_column();
+
+-- The handover checklist filled in at each end of a transfer
+CREATE TABLE transfer_checks (
+    transfer_id INTEGER NOT NULL REFERENCES transfers(id) ON DELETE CASCADE,
+    stage VARCHAR(10) NOT NULL CHECK (stage IN ('dispatch', 'arrival')),
+    item VARCHAR(30) NOT NULL,
+    condition VARCHAR(10) NOT NULL CHECK (condition IN ('ok', 'worn', 'damaged', 'missing')),
+    PRIMARY KEY (transfer_id, stage, item)
+);
@@ -0,0 +1,3 @@
+DROP VIEW equipment_telematics;
+DROP TABLE telematics_pings;
+ALTER TABLE equipment DROP COLUMN telematics_device_id;
@@ -0,0 +1,48 @@
+-- Identifier a telematics unit reports under, when it isn't the serial number
+ALTER TABLE equipment ADD COLUMN telematics_device_id VARCHAR(100) UNIQUE;
+
+-- Readings reported by telematics units: one row per machine and instant,
+-- so a resent reading is ignored. Rows past the retention period are
+-- deleted by the retention job.
+CREATE TABLE telematics_pings (
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    latitude DOUBLE PRECISION CHECK (latitude BETWEEN -90 AND 90),
+    longitude DOUBLE PRECISION CHECK (longitude BETWEEN -180 AND 180),
+    engine_hours DOUBLE PRECISION CHECK (engine_hours >= 0),
+    -- Percent of tank capacity
+    fuel_level DOUBLE PRECISION CHECK (fuel_level BETWEEN 0 AND 100),
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (equipment_id, recorded_at),
+    CHECK ((latitude IS NULL) = (longitude IS NULL))
+);
+
+-- Rows arrive roughly in time order, so a BRIN index keeps retention cheap
+CREATE INDEX idx_telematics_pings_recorded ON telematics_pings USING BRIN (recorded_at);
+
+-- The latest reading of each kind per machine; no row means it never reported
+CREATE VIEW equipment_telematics AS
+SELECT e.id AS equipment_id, last.recorded_at AS last_ping_at,
+    pos.recorded_at AS position_at, pos.latitude, pos.longitude,
+    hours.engine_hours, fuel.fuel_level
+FROM equipment e
+JOIN LATERAL (
+    SELECT p.recorded_at FROM telematics_pings p
+    WHERE p.equipment_id = e.id
+    ORDER BY p.recorded_at DESC LIMIT 1
+) last ON TRUE
+LEFT JOIN LATERAL (
+    SELECT p.recorded_at, p.latitude, p.longitude FROM telematics_pings p
+    WHERE p.equipment_id = e.id AND p.latitude IS NOT NULL
+    ORDER BY p.recorded_at DESC LIMIT 1
+) pos ON TRUE
+LEFT JOIN LATERAL (
+    SELECT p.engine_hours FROM telematics_pings p
+    WHERE p.equipment_id = e.id AND p.engine_hours IS NOT NULL
+    ORDER BY p.recorded_at DESC LIMIT 1
+) hours ON TRUE
+LEFT JOIN LATERAL (
+    SELECT p.fuel_level FROM telematics_pings p
+    WHERE p.equipment_id = e.id AND p.fuel_level IS NOT NULL
+    ORDER BY p.recorded_at DESC LIMIT 1
+) fuel ON TRUE;
@@ -0,0 +1,3 @@
+DROP TABLE geofence_events;
+DROP TABLE geofence_presence;
+DROP TABLE geofences;
@@ -0,0 +1,42 @@
+-- Zones around a site: a circle (centre and radius in metres) or a polygon
+-- of [latitude, longitude] vertices
+CREATE TABLE geofences (
+    id SERIAL PRIMARY KEY,
+    site_id INTEGER NOT NULL REFERENCES sites(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    kind VARCHAR(10) NOT NULL CHECK (kind IN ('radius', 'polygon')),
+    center_latitude DOUBLE PRECISION CHECK (center_latitude BETWEEN -90 AND 90),
+    center_longitude DOUBLE PRECISION CHECK (center_longitude BETWEEN -180 AND 180),
+    radius_m DOUBLE PRECISION CHECK (radius_m > 0),
+    vertices JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (site_id, name),
+    CHECK ((kind = 'radius') = (center_latitude IS NOT NULL AND center_longitude IS NOT NULL AND radius_m IS NOT NULL)),
+    CHECK ((kind = 'polygon') = (vertices IS NOT NULL))
+);
+
+-- Whether each machine was last seen inside each zone, and when
+CREATE TABLE geofence_presence (
+    geofence_id INTEGER NOT NULL REFERENCES geofences(id) ON DELETE CASCADE,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    inside BOOLEAN NOT NULL,
+    observed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (geofence_id, equipment_id)
+);
+
+-- Arrivals and departures detected from position pings; `alert` marks a
+-- machine leaving the site it is deployed to outside working hours
+CREATE TABLE geofence_events (
+    id BIGSERIAL PRIMARY KEY,
+    geofence_id INTEGER NOT NULL REFERENCES geofences(id) ON DELETE CASCADE,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    kind VARCHAR(5) NOT NULL CHECK (kind IN ('enter', 'exit')),
+    occurred_at TIMESTAMPTZ NOT NULL,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+    alert BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_geofence_events_fence ON geofence_events(geofence_id, occurred_at DESC);
+CREATE INDEX idx_geofence_events_alerts ON geofence_events(occurred_at DESC) WHERE alert;
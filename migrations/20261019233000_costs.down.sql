@@ -0,0 +1,2 @@
+DROP TABLE equipment_expenses;
+ALTER TABLE equipment DROP COLUMN purchase_cost;
@@ -0,0 +1,18 @@
+-- What the machine was bought for; unknown for machines recorded before
+ALTER TABLE equipment ADD COLUMN purchase_cost DOUBLE PRECISION CHECK (purchase_cost >= 0);
+
+-- Running costs paid for a machine besides its maintenance
+CREATE TABLE equipment_expenses (
+    id SERIAL PRIMARY KEY,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id) ON DELETE CASCADE,
+    expense_date DATE NOT NULL,
+    kind VARCHAR(20) NOT NULL
+        CHECK (kind IN ('fuel', 'parts', 'tyres', 'transport', 'other')),
+    amount DOUBLE PRECISION NOT NULL CHECK (amount >= 0),
+    -- Litres, for fuel
+    quantity DOUBLE PRECISION CHECK (quantity > 0),
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_equipment_expenses_equipment ON equipment_expenses(equipment_id, expense_date);
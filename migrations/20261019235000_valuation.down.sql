@@ -0,0 +1,10 @@
+ALTER TABLE categories
+    DROP COLUMN default_depreciation_method,
+    DROP COLUMN default_useful_life_years,
+    DROP COLUMN default_salvage_percent;
+
+ALTER TABLE equipment
+    DROP CONSTRAINT equipment_salvage_below_cost,
+    DROP COLUMN depreciation_method,
+    DROP COLUMN useful_life_years,
+    DROP COLUMN salvage_value;
@@ -0,0 +1,16 @@
+-- How a machine is written off; unset terms fall back to its category's
+-- defaults, then to the [costs] configuration
+ALTER TABLE equipment
+    ADD COLUMN salvage_value DOUBLE PRECISION CHECK (salvage_value >= 0),
+    ADD COLUMN useful_life_years INTEGER CHECK (useful_life_years BETWEEN 1 AND 50),
+    ADD COLUMN depreciation_method VARCHAR(20)
+        CHECK (depreciation_method IN ('straight_line', 'declining_balance')),
+    ADD CONSTRAINT equipment_salvage_below_cost CHECK (salvage_value <= purchase_cost);
+
+ALTER TABLE categories
+    -- Share of the purchase cost left at the end of the useful life
+    ADD COLUMN default_salvage_percent DOUBLE PRECISION
+        CHECK (default_salvage_percent >= 0 AND default_salvage_percent < 100),
+    ADD COLUMN default_useful_life_years INTEGER CHECK (default_useful_life_years BETWEEN 1 AND 50),
+    ADD COLUMN default_depreciation_method VARCHAR(20)
+        CHECK (default_depreciation_method IN ('straight_line', 'declining_balance'));
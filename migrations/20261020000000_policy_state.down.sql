@@ -0,0 +1 @@
+DROP FUNCTION policy_state(VARCHAR, DATE, DATE);
@@ -0,0 +1,11 @@
+-- A policy's state on the current date: in_force, upcoming, expired,
+-- renewed or cancelled
+CREATE FUNCTION policy_state(status VARCHAR, start_date DATE, end_date DATE)
+RETURNS VARCHAR AS $$
+    SELECT CASE
+        WHEN status != 'active' THEN status
+        WHEN start_date > CURRENT_DATE THEN 'upcoming'
+        WHEN end_date < CURRENT_DATE THEN 'expired'
+        ELSE 'in_force'
+    END
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,133 @@
+#!/usr/bin/env python3
+"""Simulate telematics units posting pings to kfleet.
+
+Each machine drives a small loop around a starting point, burning fuel and
+adding engine hours, and reports in batches like a real gateway would.
+Use --resend to post every batch twice and watch the duplicates being
+dropped, and --drift to walk the machines off their site and trigger
+geofence exits.
+
+    KFLEET_TELEMATICS_API_KEY=... scripts/telematics-simulator.py \\
+        --serial CAT0320-118 --device TK-0042 --batches 3
+
+Only the Python standard library is needed.
+"""
+
+import argparse
+import json
+import math
+import os
+import random
+import sys
+import urllib.error
+import urllib.request
+from datetime import datetime, timedelta, timezone
+
+
+def parse_args():
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--url", default="http://localhost:3000", help="kfleet base URL")
+    parser.add_argument("--api-key", default=os.environ.get("KFLEET_TELEMATICS_API_KEY"),
+                        help="defaults to $KFLEET_TELEMATICS_API_KEY")
+    parser.add_argument("--serial", action="append", default=[], help="machine serial number (repeatable)")
+    parser.add_argument("--device", action="append", default=[], help="telematics device ID (repeatable)")
+    parser.add_argument("--batches", type=int, default=1, help="batches to send per machine")
+    parser.add_argument("--pings", type=int, default=12, help="pings per batch")
+    parser.add_argument("--interval", type=int, default=300, help="seconds between pings")
+    parser.add_argument("--lat", type=float, default=-18.8792, help="starting latitude")
+    parser.add_argument("--lon", type=float, default=47.5079, help="starting longitude")
+    parser.add_argument("--drift", type=float, default=0.0,
+                        help="metres each machine moves north per ping, to leave a geofence")
+    parser.add_argument("--resend", action="store_true", help="post every batch twice")
+    parser.add_argument("--invalid", action="store_true", help="add a malformed ping to every batch")
+    parser.add_argument("--seed", type=int, help="random seed for repeatable runs")
+    return parser.parse_args()
+
+
+class Unit:
+    """One machine's unit: position on a loop, engine hours and fuel."""
+
+    def __init__(self, key, value, lat, lon, rng, drift):
+        self.key, self.value = key, value
+        # Work within a couple of hundred metres of the starting point
+        self.center = (lat + rng.uniform(-0.001, 0.001), lon + rng.uniform(-0.001, 0.001))
+        self.radius = rng.uniform(0.0003, 0.001)
+        self.angle = rng.uniform(0, 2 * math.pi)
+        self.engine_hours = round(rng.uniform(500, 6000), 1)
+        self.fuel = rng.uniform(40, 100)
+        self.rng = rng
+        self.drift = drift / 111_320  # metres to degrees of latitude
+
+    def ping(self, recorded_at, interval):
+        self.angle += 0.3
+        self.center = (self.center[0] + self.drift, self.center[1])
+        running = self.rng.random() < 0.8
+        if running:
+            self.engine_hours += interval / 3600
+            self.fuel = max(0.0, self.fuel - self.rng.uniform(0.2, 1.5))
+        if self.fuel < 10:
+            self.fuel = 100.0  # refuelled
+        ping = {
+            self.key: self.value,
+            "recorded_at": recorded_at.isoformat().replace("+00:00", "Z"),
+            "engine_hours": round(self.engine_hours, 2),
+            "fuel_level": round(self.fuel, 1),
+        }
+        # Units lose GPS fix under cover now and then
+        if self.rng.random() < 0.9:
+            ping["latitude"] = round(self.center[0] + self.radius * math.sin(self.angle), 6)
+            ping["longitude"] = round(self.center[1] + self.radius * math.cos(self.angle), 6)
+        return ping
+
+
+def post(url, api_key, pings):
+    body = json.dumps({"pings": pings}).encode()
+    request = urllib.request.Request(
+        url.rstrip("/") + "/api/telematics/pings",
+        data=body,
+        headers={"Content-Type": "application/json", "Authorization": f"Bearer {api_key}"},
+        method="POST",
+    )
+    try:
+        with urllib.request.urlopen(request, timeout=30) as response:
+            return json.load(response)
+    except urllib.error.HTTPError as e:
+        sys.exit(f"HTTP {e.code}: {e.read().decode(errors='replace')}")
+    except urllib.error.URLError as e:
+        sys.exit(f"Cannot reach {url}: {e.reason}")
+
+
+def main():
+    args = parse_args()
+    if not args.api_key:
+        sys.exit("Set --api-key or KFLEET_TELEMATICS_API_KEY")
+    if not args.serial and not args.device:
+        sys.exit("Name at least one machine with --serial or --device")
+
+    rng = random.Random(args.seed)
+    units = [Unit("serial_number", s, args.lat, args.lon, rng, args.drift) for s in args.serial]
+    units += [Unit("device_id", d, args.lat, args.lon, rng, args.drift) for d in args.device]
+
+    # Replay the recent past so the newest ping lands about now
+    total = args.batches * args.pings
+    start = datetime.now(timezone.utc).replace(microsecond=0) - timedelta(seconds=args.interval * (total - 1))
+
+    for batch in range(args.batches):
+        pings = []
+        for n in range(args.pings):
+            recorded_at = start + timedelta(seconds=args.interval * (batch * args.pings + n))
+            pings.extend(unit.ping(recorded_at, args.interval) for unit in units)
+        if args.invalid:
+            pings.append({units[0].key: units[0].value, "recorded_at": "soon", "fuel_level": 150})
+        for attempt in range(2 if args.resend else 1):
+            report = post(args.url, args.api_key, pings)
+            label = "resent" if attempt else "sent"
+            print(f"batch {batch + 1}/{args.batches} {label}: {len(pings)} pings, "
+                  f"{report['accepted']} accepted, {report['duplicates']} duplicates, "
+                  f"{len(report['rejected'])} rejected, {report['alerts']} after-hours alerts")
+            for rejection in report["rejected"]:
+                print(f"  #{rejection['index']}: {rejection['error']}")
+
+
+if __name__ == "__main__":
+    main()
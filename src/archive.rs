@@ -0,0 +1,1212 @@
+use crate::insurance;
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+
+pub const ARCHIVE_FORMAT: &str = "kfleet-archive";
+/// Bumped whenever the format changes, so that older builds refuse archives
+/// with sections or fields they would drop:
+/// - 2 replaced the per-machine insurance renewal date with policies
+/// - 3 added insurance claims
+/// - 4 added operator certifications
+/// - 5 dated operator assignments with assigned_to
+/// - 6 added sites and equipment deployments
+/// - 7 added reservations
+/// - 8 added transfers and their handover checklists
+/// - 9 added the telematics device ID of each machine
+/// - 10 added site geofences
+/// - 11 added purchase costs and expenses
+/// - 12 added depreciation terms and category defaults
+pub const ARCHIVE_VERSION: u32 = 12;
+
+/// A complete, self-contained dump of a kFleet instance's fleet data.
+/// IDs are those of the source instance and are remapped on restore.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub categories: Vec<ArchivedCategory>,
+    pub staff: Vec<ArchivedStaff>,
+    pub equipment: Vec<ArchivedEquipment>,
+    pub assignments: Vec<ArchivedAssignment>,
+    pub maintenance: Vec<ArchivedMaintenance>,
+    #[serde(default)]
+    pub policies: Vec<ArchivedPolicy>,
+    #[serde(default)]
+    pub claims: Vec<ArchivedClaim>,
+    #[serde(default)]
+    pub certifications: Vec<ArchivedCertification>,
+    #[serde(default)]
+    pub sites: Vec<ArchivedSite>,
+    #[serde(default)]
+    pub deployments: Vec<ArchivedDeployment>,
+    #[serde(default)]
+    pub geofences: Vec<ArchivedGeofence>,
+    #[serde(default)]
+    pub reservations: Vec<ArchivedReservation>,
+    #[serde(default)]
+    pub transfers: Vec<ArchivedTransfer>,
+    #[serde(default)]
+    pub transfer_checks: Vec<ArchivedTransferCheck>,
+    #[serde(default)]
+    pub expenses: Vec<ArchivedExpense>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedCategory {
+    pub id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub default_salvage_percent: Option<f64>,
+    #[serde(default)]
+    pub default_useful_life_years: Option<i32>,
+    #[serde(default)]
+    pub default_depreciation_method: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedStaff {
+    pub id: i32,
+    pub full_name: String,
+    pub contact_info: Option<String>,
+    pub license_number: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedEquipment {
+    pub id: i32,
+    pub name: String,
+    pub brand: String,
+    pub model: String,
+    pub serial_number: String,
+    pub acquisition_date: DateTime<Utc>,
+    pub category_id: i32,
+    /// Only present in version 1 archives; restored as a placeholder policy.
+    #[serde(default, skip_serializing)]
+    pub insurance_renewal: Option<DateTime<Utc>>,
+    pub next_maintenance: Option<DateTime<Utc>>,
+    pub fuel_capacity: Option<f64>,
+    #[serde(default)]
+    pub purchase_cost: Option<f64>,
+    #[serde(default)]
+    pub salvage_value: Option<f64>,
+    #[serde(default)]
+    pub useful_life_years: Option<i32>,
+    #[serde(default)]
+    pub depreciation_method: Option<String>,
+    #[serde(default)]
+    pub telematics_device_id: Option<String>,
+    pub last_inspection: Option<DateTime<Utc>>,
+    pub current_status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedAssignment {
+    pub operator_id: i32,
+    pub equipment_id: i32,
+    /// Start of the assignment period
+    pub assigned_date: DateTime<Utc>,
+    /// End of the period; `None` for current assignments and in archives
+    /// from before assignments were dated
+    #[serde(default)]
+    pub assigned_to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedMaintenance {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub maintenance_date: DateTime<Utc>,
+    pub description: String,
+    pub cost: Option<f64>,
+    pub technician: Option<String>,
+    pub next_maintenance_due: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedExpense {
+    pub equipment_id: i32,
+    pub expense_date: NaiveDate,
+    pub kind: String,
+    pub amount: f64,
+    pub quantity: Option<f64>,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Policy documents are not archived; their files stay with the instance.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedPolicy {
+    pub id: i32,
+    pub insurer: String,
+    pub policy_number: String,
+    pub coverage_type: String,
+    pub premium: Option<f64>,
+    pub deductible: Option<f64>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub status: String,
+    pub renewed_from_id: Option<i32>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub equipment_ids: Vec<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedClaim {
+    pub id: i32,
+    pub policy_id: i32,
+    pub equipment_id: i32,
+    pub claim_number: String,
+    pub incident_date: NaiveDate,
+    pub filed_date: NaiveDate,
+    pub description: String,
+    pub status: String,
+    pub claimed_amount: f64,
+    pub paid_amount: Option<f64>,
+    pub closed_date: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Linked `maintenance` records
+    pub maintenance_ids: Vec<i32>,
+}
+
+/// Certificate scans are not archived, like policy documents.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedCertification {
+    pub id: i32,
+    pub staff_id: i32,
+    pub license_type: String,
+    pub license_number: Option<String>,
+    pub issuing_authority: String,
+    pub issue_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub category_ids: Vec<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedSite {
+    pub id: i32,
+    pub name: String,
+    pub client: Option<String>,
+    pub address: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedGeofence {
+    pub site_id: i32,
+    pub name: String,
+    pub kind: String,
+    pub center_latitude: Option<f64>,
+    pub center_longitude: Option<f64>,
+    pub radius_m: Option<f64>,
+    pub vertices: Option<Json<Vec<[f64; 2]>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedDeployment {
+    pub equipment_id: i32,
+    pub site_id: i32,
+    pub deployed_from: NaiveDate,
+    pub deployed_until: Option<NaiveDate>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedReservation {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub site_id: i32,
+    pub requested_by: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub purpose: Option<String>,
+    pub status: String,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub decision_note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedTransfer {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub origin_site_id: Option<i32>,
+    pub destination_site_id: Option<i32>,
+    pub driver_id: Option<i32>,
+    pub dispatch_date: NaiveDate,
+    pub dispatch_meter: f64,
+    pub dispatch_notes: Option<String>,
+    pub arrival_date: Option<NaiveDate>,
+    pub arrival_meter: Option<f64>,
+    pub arrival_notes: Option<String>,
+    pub damage_candidate: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedTransferCheck {
+    pub transfer_id: i32,
+    pub stage: String,
+    pub item: String,
+    pub condition: String,
+}
+
+/// How a restore treats data already present in the target instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    /// Keep existing rows; match categories by name, equipment by serial
+    /// number and staff by name and license number.
+    #[default]
+    Merge,
+    /// Delete all existing fleet data before restoring.
+    Replace,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreSummary {
+    pub categories_created: usize,
+    pub categories_matched: usize,
+    pub staff_created: usize,
+    pub staff_matched: usize,
+    pub equipment_created: usize,
+    pub equipment_matched: usize,
+    pub assignments_created: usize,
+    pub maintenance_created: usize,
+    pub maintenance_skipped: usize,
+    pub expenses_created: usize,
+    pub policies_created: usize,
+    pub policies_matched: usize,
+    pub claims_created: usize,
+    pub claims_matched: usize,
+    pub certifications_created: usize,
+    pub certifications_matched: usize,
+    pub sites_created: usize,
+    pub sites_matched: usize,
+    pub deployments_created: usize,
+    pub geofences_created: usize,
+    pub geofences_matched: usize,
+    pub reservations_created: usize,
+    pub reservations_matched: usize,
+    pub transfers_created: usize,
+    pub transfers_matched: usize,
+}
+
+/// Reads every fleet table into an archive.
+pub async fn export(pool: &PgPool) -> Result<Archive, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    // One snapshot so rows reference each other consistently
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+
+    let categories = sqlx::query_as!(
+        ArchivedCategory,
+        r#"
+        SELECT id, name, created_at,
+            default_salvage_percent, default_useful_life_years, default_depreciation_method
+        FROM categories
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let staff = sqlx::query_as!(
+        ArchivedStaff,
+        "SELECT id, full_name, contact_info, license_number, created_at FROM staff ORDER BY id"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let equipment = sqlx::query_as!(
+        ArchivedEquipment,
+        r#"
+        SELECT
+            id, name, brand, model, serial_number, acquisition_date,
+            category_id, NULL::timestamptz AS insurance_renewal, next_maintenance,
+            fuel_capacity, purchase_cost, salvage_value, useful_life_years, depreciation_method,
+            telematics_device_id, last_inspection, current_status, created_at
+        FROM equipment
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let expenses = sqlx::query_as!(
+        ArchivedExpense,
+        r#"
+        SELECT equipment_id, expense_date, kind, amount, quantity, description, created_at
+        FROM equipment_expenses
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let assignments = sqlx::query_as!(
+        ArchivedAssignment,
+        r#"
+        SELECT operator_id, equipment_id, assigned_from AS assigned_date, assigned_to
+        FROM equipment_operator
+        ORDER BY operator_id, equipment_id, assigned_from
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let maintenance = sqlx::query_as!(
+        ArchivedMaintenance,
+        r#"
+        SELECT
+            id, equipment_id, maintenance_date, description, cost,
+            technician, next_maintenance_due, created_at
+        FROM maintenance_history
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let policies = sqlx::query_as!(
+        ArchivedPolicy,
+        r#"
+        SELECT
+            id, insurer, policy_number, coverage_type, premium, deductible,
+            start_date, end_date, status, renewed_from_id, notes, created_at,
+            ARRAY(
+                SELECT equipment_id FROM policy_equipment
+                WHERE policy_id = p.id ORDER BY equipment_id
+            ) AS "equipment_ids!"
+        FROM insurance_policies p
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let claims = sqlx::query_as!(
+        ArchivedClaim,
+        r#"
+        SELECT
+            id, policy_id, equipment_id, claim_number, incident_date, filed_date,
+            description, status, claimed_amount, paid_amount, closed_date, notes, created_at,
+            ARRAY(
+                SELECT maintenance_id FROM claim_repairs
+                WHERE claim_id = c.id ORDER BY maintenance_id
+            ) AS "maintenance_ids!"
+        FROM insurance_claims c
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let certifications = sqlx::query_as!(
+        ArchivedCertification,
+        r#"
+        SELECT
+            id, staff_id, license_type, license_number, issuing_authority,
+            issue_date, expiry_date, notes, created_at,
+            ARRAY(
+                SELECT category_id FROM certification_categories
+                WHERE certification_id = c.id ORDER BY category_id
+            ) AS "category_ids!"
+        FROM certifications c
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let sites = sqlx::query_as!(
+        ArchivedSite,
+        r#"
+        SELECT
+            id, name, client, address, latitude, longitude,
+            start_date, end_date, notes, created_at
+        FROM sites
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let deployments = sqlx::query_as!(
+        ArchivedDeployment,
+        r#"
+        SELECT equipment_id, site_id, deployed_from, deployed_until, notes
+        FROM deployments
+        ORDER BY equipment_id, deployed_from
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let geofences = sqlx::query_as!(
+        ArchivedGeofence,
+        r#"
+        SELECT
+            site_id, name, kind, center_latitude, center_longitude, radius_m,
+            vertices as "vertices: Json<Vec<[f64; 2]>>"
+        FROM geofences
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let reservations = sqlx::query_as!(
+        ArchivedReservation,
+        r#"
+        SELECT
+            id, equipment_id, site_id, requested_by, start_date, end_date, purpose,
+            status, decided_by, decided_at, decision_note, created_at
+        FROM reservations
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let transfers = sqlx::query_as!(
+        ArchivedTransfer,
+        r#"
+        SELECT
+            id, equipment_id, origin_site_id, destination_site_id, driver_id,
+            dispatch_date, dispatch_meter, dispatch_notes,
+            arrival_date, arrival_meter, arrival_notes, damage_candidate, created_at
+        FROM transfers
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let transfer_checks = sqlx::query_as!(
+        ArchivedTransferCheck,
+        "SELECT transfer_id, stage, item, condition FROM transfer_checks ORDER BY transfer_id, stage, item"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Archive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        categories,
+        staff,
+        equipment,
+        assignments,
+        maintenance,
+        policies,
+        claims,
+        certifications,
+        sites,
+        deployments,
+        geofences,
+        reservations,
+        transfers,
+        transfer_checks,
+        expenses,
+    })
+}
+
+/// Parses and checks an archive file before anything is written.
+pub fn parse(data: &[u8]) -> Result<Archive, String> {
+    let archive: Archive =
+        serde_json::from_slice(data).map_err(|e| format!("Invalid archive: {}", e))?;
+    if archive.format != ARCHIVE_FORMAT {
+        return Err(format!("Not a kFleet archive (format '{}')", archive.format));
+    }
+    if archive.version == 0 || archive.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "Unsupported archive version {} (this instance reads up to {})",
+            archive.version, ARCHIVE_VERSION
+        ));
+    }
+    Ok(archive)
+}
+
+/// Recreates the archived data in one transaction, remapping source IDs to new ones.
+pub async fn restore(
+    pool: &PgPool,
+    archive: &Archive,
+    mode: RestoreMode,
+) -> Result<RestoreSummary, String> {
+    let mut summary = RestoreSummary::default();
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+    if mode == RestoreMode::Replace {
+        warn!("Replacing all fleet data with archive from {}", archive.exported_at);
+        sqlx::query!(
+            "TRUNCATE equipment_expenses, transfer_checks, transfers, reservations, geofence_events, geofence_presence, geofences, deployments, sites, certification_categories, certifications, claim_repairs, insurance_claims, policy_documents, policy_equipment, insurance_policies, maintenance_history, equipment_operator, equipment, staff, categories RESTART IDENTITY CASCADE"
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    let mut category_ids = HashMap::new();
+    for c in &archive.categories {
+        let existing = sqlx::query_scalar!("SELECT id FROM categories WHERE name = $1", c.name)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let id = match existing {
+            Some(id) => {
+                summary.categories_matched += 1;
+                id
+            }
+            None => {
+                summary.categories_created += 1;
+                sqlx::query_scalar!(
+                    r#"
+                    INSERT INTO categories (
+                        name, created_at,
+                        default_salvage_percent, default_useful_life_years, default_depreciation_method
+                    ) VALUES ($1, $2, $3, $4, $5)
+                    RETURNING id
+                    "#,
+                    c.name,
+                    c.created_at,
+                    c.default_salvage_percent,
+                    c.default_useful_life_years,
+                    c.default_depreciation_method
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| format!("Category '{}': {}", c.name, e))?
+            }
+        };
+        category_ids.insert(c.id, id);
+    }
+
+    let mut staff_ids = HashMap::new();
+    for s in &archive.staff {
+        let existing = sqlx::query_scalar!(
+            "SELECT id FROM staff WHERE full_name = $1 AND license_number IS NOT DISTINCT FROM $2 LIMIT 1",
+            s.full_name,
+            s.license_number
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let id = match existing {
+            Some(id) => {
+                summary.staff_matched += 1;
+                id
+            }
+            None => {
+                summary.staff_created += 1;
+                sqlx::query_scalar!(
+                    r#"
+                    INSERT INTO staff (full_name, contact_info, license_number, created_at)
+                    VALUES ($1, $2, $3, $4)
+                    RETURNING id
+                    "#,
+                    s.full_name,
+                    s.contact_info,
+                    s.license_number,
+                    s.created_at
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| format!("Staff '{}': {}", s.full_name, e))?
+            }
+        };
+        staff_ids.insert(s.id, id);
+    }
+
+    let mut equipment_ids = HashMap::new();
+    let mut matched_equipment = HashSet::new();
+    for e in &archive.equipment {
+        let category_id = *category_ids
+            .get(&e.category_id)
+            .ok_or_else(|| format!("Equipment '{}' references unknown category {}", e.name, e.category_id))?;
+        let existing = sqlx::query_scalar!(
+            "SELECT id FROM equipment WHERE serial_number = $1",
+            e.serial_number
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let id = match existing {
+            Some(id) => {
+                summary.equipment_matched += 1;
+                matched_equipment.insert(id);
+                id
+            }
+            None => {
+                summary.equipment_created += 1;
+                let id = sqlx::query_scalar!(
+                    r#"
+                    INSERT INTO equipment (
+                        name, brand, model, serial_number, acquisition_date,
+                        category_id, next_maintenance, fuel_capacity, purchase_cost,
+                        salvage_value, useful_life_years, depreciation_method,
+                        telematics_device_id, last_inspection, current_status, created_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+                    RETURNING id
+                    "#,
+                    e.name,
+                    e.brand,
+                    e.model,
+                    e.serial_number,
+                    e.acquisition_date,
+                    category_id,
+                    e.next_maintenance,
+                    e.fuel_capacity,
+                    e.purchase_cost,
+                    e.salvage_value,
+                    e.useful_life_years,
+                    e.depreciation_method,
+                    e.telematics_device_id,
+                    e.last_inspection,
+                    e.current_status,
+                    e.created_at
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|err| format!("Equipment '{}': {}", e.serial_number, err))?;
+                if let Some(renewal) = e.insurance_renewal {
+                    insurance::create_legacy_policy(&mut tx, id, renewal.date_naive())
+                        .await
+                        .map_err(|err| format!("Equipment '{}': {}", e.serial_number, err))?;
+                    summary.policies_created += 1;
+                }
+                id
+            }
+        };
+        equipment_ids.insert(e.id, id);
+    }
+
+    for a in &archive.assignments {
+        let (Some(operator_id), Some(equipment_id)) =
+            (staff_ids.get(&a.operator_id), equipment_ids.get(&a.equipment_id))
+        else {
+            return Err(format!(
+                "Assignment references unknown staff {} or equipment {}",
+                a.operator_id, a.equipment_id
+            ));
+        };
+        // Periods already present (same start) are kept as they are
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO equipment_operator (operator_id, equipment_id, assigned_from, assigned_to)
+            SELECT $1::int, $2::int, $3::timestamptz, $4::timestamptz
+            WHERE NOT EXISTS (
+                SELECT 1 FROM equipment_operator
+                WHERE operator_id = $1 AND equipment_id = $2 AND assigned_from = $3
+            )
+            ON CONFLICT DO NOTHING
+            "#,
+            operator_id,
+            equipment_id,
+            a.assigned_date,
+            a.assigned_to
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        summary.assignments_created += inserted.rows_affected() as usize;
+    }
+
+    let mut maintenance_ids = HashMap::new();
+    for m in &archive.maintenance {
+        let equipment_id = equipment_ids
+            .get(&m.equipment_id)
+            .ok_or_else(|| format!("Maintenance record {} references unknown equipment {}", m.id, m.equipment_id))?;
+        // Machines already present keep their own history
+        if matched_equipment.contains(equipment_id) {
+            summary.maintenance_skipped += 1;
+            continue;
+        }
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO maintenance_history (
+                equipment_id, maintenance_date, description, cost,
+                technician, next_maintenance_due, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#,
+            equipment_id,
+            m.maintenance_date,
+            m.description,
+            m.cost,
+            m.technician,
+            m.next_maintenance_due,
+            m.created_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        maintenance_ids.insert(m.id, id);
+        summary.maintenance_created += 1;
+    }
+
+    for x in &archive.expenses {
+        let equipment_id = equipment_ids
+            .get(&x.equipment_id)
+            .ok_or_else(|| format!("Expense references unknown equipment {}", x.equipment_id))?;
+        // Like maintenance, machines already present keep their own expenses
+        if matched_equipment.contains(equipment_id) {
+            continue;
+        }
+        sqlx::query!(
+            r#"
+            INSERT INTO equipment_expenses (
+                equipment_id, expense_date, kind, amount, quantity, description, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            equipment_id,
+            x.expense_date,
+            x.kind,
+            x.amount,
+            x.quantity,
+            x.description,
+            x.created_at
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        summary.expenses_created += 1;
+    }
+
+    // Policies are stored oldest first, so a renewal's predecessor is already mapped
+    let mut policy_ids = HashMap::new();
+    for p in &archive.policies {
+        let existing = sqlx::query_scalar!(
+            "SELECT id FROM insurance_policies WHERE insurer = $1 AND policy_number = $2 AND start_date = $3",
+            p.insurer,
+            p.policy_number,
+            p.start_date
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if let Some(id) = existing {
+            summary.policies_matched += 1;
+            policy_ids.insert(p.id, id);
+            continue;
+        }
+
+        let covered = p
+            .equipment_ids
+            .iter()
+            .map(|id| {
+                equipment_ids
+                    .get(id)
+                    .copied()
+                    .ok_or_else(|| format!("Policy {} references unknown equipment {}", p.policy_number, id))
+            })
+            .collect::<Result<Vec<i32>, String>>()?;
+        let renewed_from = p.renewed_from_id.and_then(|id| policy_ids.get(&id).copied());
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO insurance_policies (
+                insurer, policy_number, coverage_type, premium, deductible,
+                start_date, end_date, status, renewed_from_id, notes, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING id
+            "#,
+            p.insurer,
+            p.policy_number,
+            p.coverage_type,
+            p.premium,
+            p.deductible,
+            p.start_date,
+            p.end_date,
+            p.status,
+            renewed_from,
+            p.notes,
+            p.created_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| format!("Policy '{}': {}", p.policy_number, e))?;
+        sqlx::query!(
+            "INSERT INTO policy_equipment (policy_id, equipment_id) SELECT $1, UNNEST($2::int[])",
+            id,
+            &covered
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        summary.policies_created += 1;
+        policy_ids.insert(p.id, id);
+    }
+
+    for c in &archive.claims {
+        let policy_id = *policy_ids
+            .get(&c.policy_id)
+            .ok_or_else(|| format!("Claim {} references unknown policy {}", c.claim_number, c.policy_id))?;
+        let equipment_id = *equipment_ids
+            .get(&c.equipment_id)
+            .ok_or_else(|| format!("Claim {} references unknown equipment {}", c.claim_number, c.equipment_id))?;
+        let existing = sqlx::query_scalar!(
+            "SELECT id FROM insurance_claims WHERE policy_id = $1 AND claim_number = $2",
+            policy_id,
+            c.claim_number
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if existing.is_some() {
+            summary.claims_matched += 1;
+            continue;
+        }
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO insurance_claims (
+                policy_id, equipment_id, claim_number, incident_date, filed_date, description,
+                status, claimed_amount, paid_amount, closed_date, notes, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING id
+            "#,
+            policy_id,
+            equipment_id,
+            c.claim_number,
+            c.incident_date,
+            c.filed_date,
+            c.description,
+            c.status,
+            c.claimed_amount,
+            c.paid_amount,
+            c.closed_date,
+            c.notes,
+            c.created_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| format!("Claim '{}': {}", c.claim_number, e))?;
+        // Repairs of machines that were already present were not restored
+        let repairs: Vec<i32> = c
+            .maintenance_ids
+            .iter()
+            .filter_map(|id| maintenance_ids.get(id).copied())
+            .collect();
+        sqlx::query!(
+            "INSERT INTO claim_repairs (claim_id, maintenance_id) SELECT $1, UNNEST($2::int[])",
+            id,
+            &repairs
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        summary.claims_created += 1;
+    }
+
+    for c in &archive.certifications {
+        let staff_id = *staff_ids
+            .get(&c.staff_id)
+            .ok_or_else(|| format!("Certification {} references unknown staff {}", c.id, c.staff_id))?;
+        let existing = sqlx::query_scalar!(
+            "SELECT id FROM certifications WHERE staff_id = $1 AND license_type = $2 AND issue_date = $3",
+            staff_id,
+            c.license_type,
+            c.issue_date
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if existing.is_some() {
+            summary.certifications_matched += 1;
+            continue;
+        }
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO certifications (
+                staff_id, license_type, license_number, issuing_authority,
+                issue_date, expiry_date, notes, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id
+            "#,
+            staff_id,
+            c.license_type,
+            c.license_number,
+            c.issuing_authority,
+            c.issue_date,
+            c.expiry_date,
+            c.notes,
+            c.created_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| format!("Certification {}: {}", c.id, e))?;
+        let categories: Vec<i32> = c
+            .category_ids
+            .iter()
+            .filter_map(|id| category_ids.get(id).copied())
+            .collect();
+        sqlx::query!(
+            "INSERT INTO certification_categories (certification_id, category_id) SELECT $1, UNNEST($2::int[])",
+            id,
+            &categories
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        summary.certifications_created += 1;
+    }
+
+    let mut site_ids = HashMap::new();
+    for site in &archive.sites {
+        let existing = sqlx::query_scalar!("SELECT id FROM sites WHERE name = $1", site.name)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(id) = existing {
+            site_ids.insert(site.id, id);
+            summary.sites_matched += 1;
+            continue;
+        }
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO sites (
+                name, client, address, latitude, longitude,
+                start_date, end_date, notes, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id
+            "#,
+            site.name,
+            site.client,
+            site.address,
+            site.latitude,
+            site.longitude,
+            site.start_date,
+            site.end_date,
+            site.notes,
+            site.created_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| format!("Site {}: {}", site.name, e))?;
+        site_ids.insert(site.id, id);
+        summary.sites_created += 1;
+    }
+
+    for d in &archive.deployments {
+        let equipment_id = *equipment_ids
+            .get(&d.equipment_id)
+            .ok_or_else(|| format!("Deployment references unknown equipment {}", d.equipment_id))?;
+        let site_id = *site_ids
+            .get(&d.site_id)
+            .ok_or_else(|| format!("Deployment references unknown site {}", d.site_id))?;
+        // Skip stays that overlap one the machine already has
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO deployments (equipment_id, site_id, deployed_from, deployed_until, notes)
+            SELECT $1::int, $2::int, $3::date, $4::date, $5::text
+            WHERE NOT EXISTS (
+                SELECT 1 FROM deployments d
+                WHERE d.equipment_id = $1
+                    AND d.deployed_from < COALESCE($4, 'infinity'::date)
+                    AND COALESCE(d.deployed_until, 'infinity'::date) > $3
+            )
+            "#,
+            equipment_id,
+            site_id,
+            d.deployed_from,
+            d.deployed_until,
+            d.notes
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        summary.deployments_created += inserted.rows_affected() as usize;
+    }
+
+    for g in &archive.geofences {
+        let site_id = *site_ids
+            .get(&g.site_id)
+            .ok_or_else(|| format!("Geofence {} references unknown site {}", g.name, g.site_id))?;
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO geofences (site_id, name, kind, center_latitude, center_longitude, radius_m, vertices)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (site_id, name) DO NOTHING
+            "#,
+            site_id,
+            g.name,
+            g.kind,
+            g.center_latitude,
+            g.center_longitude,
+            g.radius_m,
+            &g.vertices as &Option<Json<Vec<[f64; 2]>>>
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Geofence {}: {}", g.name, e))?;
+        if inserted.rows_affected() > 0 {
+            summary.geofences_created += 1;
+        } else {
+            summary.geofences_matched += 1;
+        }
+    }
+
+    for r in &archive.reservations {
+        let equipment_id = *equipment_ids
+            .get(&r.equipment_id)
+            .ok_or_else(|| format!("Reservation {} references unknown equipment {}", r.id, r.equipment_id))?;
+        let site_id = *site_ids
+            .get(&r.site_id)
+            .ok_or_else(|| format!("Reservation {} references unknown site {}", r.id, r.site_id))?;
+        let existing = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM reservations
+            WHERE equipment_id = $1 AND site_id = $2 AND start_date = $3 AND requested_by = $4
+            "#,
+            equipment_id,
+            site_id,
+            r.start_date,
+            r.requested_by
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if existing.is_some() {
+            summary.reservations_matched += 1;
+            continue;
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO reservations (
+                equipment_id, site_id, requested_by, start_date, end_date, purpose,
+                status, decided_by, decided_at, decision_note, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            equipment_id,
+            site_id,
+            r.requested_by,
+            r.start_date,
+            r.end_date,
+            r.purpose,
+            r.status,
+            r.decided_by,
+            r.decided_at,
+            r.decision_note,
+            r.created_at
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Reservation {}: {}", r.id, e))?;
+        summary.reservations_created += 1;
+    }
+
+    // Checklists are restored only for the transfers created here
+    let mut transfer_ids = HashMap::new();
+    for t in &archive.transfers {
+        let equipment_id = *equipment_ids
+            .get(&t.equipment_id)
+            .ok_or_else(|| format!("Transfer {} references unknown equipment {}", t.id, t.equipment_id))?;
+        let site = |id: Option<i32>| match id {
+            Some(id) => site_ids
+                .get(&id)
+                .copied()
+                .map(Some)
+                .ok_or_else(|| format!("Transfer {} references unknown site {}", t.id, id)),
+            None => Ok(None),
+        };
+        let origin_site_id = site(t.origin_site_id)?;
+        let destination_site_id = site(t.destination_site_id)?;
+        let driver_id = t
+            .driver_id
+            .map(|id| {
+                staff_ids
+                    .get(&id)
+                    .copied()
+                    .ok_or_else(|| format!("Transfer {} references unknown staff {}", t.id, id))
+            })
+            .transpose()?;
+        let existing = sqlx::query_scalar!(
+            "SELECT id FROM transfers WHERE equipment_id = $1 AND dispatch_date = $2",
+            equipment_id,
+            t.dispatch_date
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if existing.is_some() {
+            summary.transfers_matched += 1;
+            continue;
+        }
+
+        // A machine already on the road keeps its own open transfer
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO transfers (
+                equipment_id, origin_site_id, destination_site_id, driver_id,
+                dispatch_date, dispatch_meter, dispatch_notes,
+                arrival_date, arrival_meter, arrival_notes, damage_candidate, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            ON CONFLICT (equipment_id) WHERE arrival_date IS NULL DO NOTHING
+            RETURNING id
+            "#,
+            equipment_id,
+            origin_site_id,
+            destination_site_id,
+            driver_id,
+            t.dispatch_date,
+            t.dispatch_meter,
+            t.dispatch_notes,
+            t.arrival_date,
+            t.arrival_meter,
+            t.arrival_notes,
+            t.damage_candidate,
+            t.created_at
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| format!("Transfer {}: {}", t.id, e))?;
+        if let Some(id) = id {
+            transfer_ids.insert(t.id, id);
+            summary.transfers_created += 1;
+        }
+    }
+
+    for c in &archive.transfer_checks {
+        let Some(transfer_id) = transfer_ids.get(&c.transfer_id) else {
+            continue;
+        };
+        sqlx::query!(
+            "INSERT INTO transfer_checks (transfer_id, stage, item, condition) VALUES ($1, $2, $3, $4)",
+            transfer_id,
+            c.stage,
+            c.item,
+            c.condition
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Transfer {} checklist: {}", c.transfer_id, e))?;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    info!("Archive restored: {:?}", summary);
+    Ok(summary)
+}
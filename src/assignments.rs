@@ -0,0 +1,403 @@
+use crate::config::{AssignmentsConfig, RuleAction};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+
+/// One period during which an operator was assigned to a machine;
+/// `assigned_to` is `None` while it lasts.
+#[derive(Debug, FromRow, Serialize)]
+pub struct Assignment {
+    pub id: i32,
+    pub operator_id: i32,
+    pub operator_name: String,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub assigned_from: DateTime<Utc>,
+    pub assigned_to: Option<DateTime<Utc>>,
+    pub is_primary: bool,
+}
+
+/// An operator currently assigned to a machine.
+#[derive(Debug, FromRow, Serialize)]
+pub struct CurrentOperator {
+    pub operator_id: i32,
+    pub full_name: String,
+    pub assigned_from: DateTime<Utc>,
+    pub is_primary: bool,
+}
+
+/// Narrows a history to the assignments in force on one day.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HistoryFilter {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub date: Option<NaiveDate>,
+}
+
+/// What the rules need to know about one machine an operator is, or is
+/// about to be, assigned to.
+#[derive(Debug, Clone, FromRow)]
+pub struct AssignmentCheck {
+    /// `None` for someone not saved yet
+    pub operator_name: Option<String>,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub status: String,
+    pub category_name: String,
+    /// The operator is assigned to it already, so this is not a new assignment
+    pub already_assigned: bool,
+    /// The operator holds a certification in force for its category
+    pub certified: bool,
+    /// Operators assigned to it besides this one
+    pub other_operators: i64,
+}
+
+/// A broken assignment rule. Only new assignments are ever blocked; rules
+/// broken by existing ones, e.g. after a certification lapsed, are warnings.
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    pub operator_name: Option<String>,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub rule: &'static str,
+    pub message: String,
+    pub blocking: bool,
+}
+
+/// Loads the rule inputs for assigning `staff_id` (`None` for someone not
+/// saved yet) to each of `equipment_ids`.
+pub async fn load_checks(
+    conn: &mut PgConnection,
+    staff_id: Option<i32>,
+    equipment_ids: &[i32],
+) -> Result<Vec<AssignmentCheck>, sqlx::Error> {
+    sqlx::query_as!(
+        AssignmentCheck,
+        r#"
+        SELECT
+            (SELECT full_name FROM staff WHERE id = $1) as operator_name,
+            e.id as equipment_id,
+            e.name as equipment_name,
+            e.current_status as status,
+            c.name as category_name,
+            EXISTS(
+                SELECT 1 FROM equipment_operator eo
+                WHERE eo.equipment_id = e.id AND eo.operator_id = $1 AND eo.assigned_to IS NULL
+            ) as "already_assigned!",
+            EXISTS(
+                SELECT 1 FROM certifications ce
+                JOIN certification_categories cc ON cc.certification_id = ce.id
+                WHERE ce.staff_id = $1
+                    AND cc.category_id = e.category_id
+                    AND ce.issue_date <= CURRENT_DATE
+                    AND (ce.expiry_date IS NULL OR ce.expiry_date >= CURRENT_DATE)
+            ) as "certified!",
+            (
+                SELECT COUNT(*) FROM equipment_operator eo
+                WHERE eo.equipment_id = e.id AND eo.operator_id IS DISTINCT FROM $1
+                    AND eo.assigned_to IS NULL
+            ) as "other_operators!"
+        FROM equipment e
+        JOIN categories c ON c.id = e.category_id
+        WHERE e.id = ANY($2)
+        ORDER BY e.name
+        "#,
+        staff_id,
+        equipment_ids
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Applies the configured rules to the loaded checks.
+pub fn evaluate(checks: &[AssignmentCheck], rules: &AssignmentsConfig) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for check in checks {
+        let mut broken = |rule: &'static str, action: RuleAction, message: String| {
+            violations.push(Violation {
+                operator_name: check.operator_name.clone(),
+                equipment_id: check.equipment_id,
+                equipment_name: check.equipment_name.clone(),
+                rule,
+                message,
+                blocking: action == RuleAction::Block && !check.already_assigned,
+            });
+        };
+
+        if check.status == "retired" {
+            broken("retired", rules.retired, format!("{} is retired", check.equipment_name));
+        }
+        if !check.certified {
+            broken(
+                "uncertified",
+                rules.uncertified,
+                format!(
+                    "No valid certification for {} ({})",
+                    check.category_name, check.equipment_name
+                ),
+            );
+        }
+        if check.other_operators >= rules.max_operators {
+            let message = if check.already_assigned {
+                format!(
+                    "{} has {} operators (at most {})",
+                    check.equipment_name,
+                    check.other_operators + 1,
+                    rules.max_operators
+                )
+            } else {
+                format!(
+                    "{} already has {} operators (at most {})",
+                    check.equipment_name, check.other_operators, rules.max_operators
+                )
+            };
+            broken("over_capacity", rules.over_capacity, message);
+        }
+    }
+    violations
+}
+
+/// Checks assigning `staff_id` to `equipment_ids` against the rules.
+pub async fn check(
+    conn: &mut PgConnection,
+    staff_id: Option<i32>,
+    equipment_ids: &[i32],
+    rules: &AssignmentsConfig,
+) -> Result<Vec<Violation>, sqlx::Error> {
+    let checks = load_checks(conn, staff_id, equipment_ids).await?;
+    Ok(evaluate(&checks, rules))
+}
+
+/// Locks the machines until the transaction ends, so that concurrent
+/// assignments to them are checked one after the other and cannot both
+/// take the last place.
+async fn lock_equipment(conn: &mut PgConnection, equipment_ids: &[i32]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "SELECT id FROM equipment WHERE id = ANY($1) ORDER BY id FOR UPDATE",
+        equipment_ids
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(())
+}
+
+/// Makes `equipment_ids` the operator's current machines, unless that
+/// breaks a blocking rule. Assignments to other machines are ended now and
+/// new ones start now; unchanged ones keep their start. Returns the broken
+/// rules; nothing was written if any of them blocks. Call it on a
+/// transaction: the machines stay locked until it ends.
+pub async fn set_operator_equipment(
+    conn: &mut PgConnection,
+    staff_id: i32,
+    equipment_ids: &[i32],
+    rules: &AssignmentsConfig,
+) -> Result<Vec<Violation>, sqlx::Error> {
+    lock_equipment(conn, equipment_ids).await?;
+    let violations = check(conn, Some(staff_id), equipment_ids, rules).await?;
+    if violations.iter().any(|v| v.blocking) {
+        return Ok(violations);
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE equipment_operator SET assigned_to = NOW()
+        WHERE operator_id = $1 AND assigned_to IS NULL AND NOT (equipment_id = ANY($2))
+        "#,
+        staff_id,
+        equipment_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO equipment_operator (operator_id, equipment_id)
+        SELECT DISTINCT $1::int, UNNEST($2::int[])
+        ON CONFLICT (operator_id, equipment_id) WHERE assigned_to IS NULL DO NOTHING
+        "#,
+        staff_id,
+        equipment_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(violations)
+}
+
+/// Assigns one more operator to a machine, unless that breaks a blocking
+/// rule. Returns the broken rules and locks the machine like
+/// `set_operator_equipment`.
+pub async fn add_operator(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    staff_id: i32,
+    rules: &AssignmentsConfig,
+) -> Result<Vec<Violation>, sqlx::Error> {
+    lock_equipment(conn, &[equipment_id]).await?;
+    let violations = check(conn, Some(staff_id), &[equipment_id], rules).await?;
+    if violations.iter().any(|v| v.blocking) {
+        return Ok(violations);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO equipment_operator (operator_id, equipment_id)
+        VALUES ($1, $2)
+        ON CONFLICT (operator_id, equipment_id) WHERE assigned_to IS NULL DO NOTHING
+        "#,
+        staff_id,
+        equipment_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(violations)
+}
+
+/// Ends an operator's current assignment to a machine; returns whether
+/// there was one.
+pub async fn remove_operator(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    staff_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE equipment_operator SET assigned_to = NOW()
+        WHERE equipment_id = $1 AND operator_id = $2 AND assigned_to IS NULL
+        "#,
+        equipment_id,
+        staff_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Makes one of a machine's current operators its primary operator;
+/// returns false if they are not currently assigned to it. Call it on a
+/// transaction: the machine stays locked until it ends, so concurrent
+/// changes of primary take turns instead of tripping the unique index.
+pub async fn set_primary_operator(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    staff_id: i32,
+) -> Result<bool, sqlx::Error> {
+    lock_equipment(conn, &[equipment_id]).await?;
+    let current = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM equipment_operator
+            WHERE equipment_id = $1 AND operator_id = $2 AND assigned_to IS NULL
+        ) as "current!"
+        "#,
+        equipment_id,
+        staff_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if !current {
+        return Ok(false);
+    }
+
+    // Clear first: the unique index allows one open primary per machine
+    sqlx::query!(
+        r#"
+        UPDATE equipment_operator SET is_primary = FALSE
+        WHERE equipment_id = $1 AND assigned_to IS NULL AND is_primary AND operator_id != $2
+        "#,
+        equipment_id,
+        staff_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE equipment_operator SET is_primary = TRUE
+        WHERE equipment_id = $1 AND operator_id = $2 AND assigned_to IS NULL
+        "#,
+        equipment_id,
+        staff_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A machine's current operators, primary first.
+pub async fn current_operators(pool: &PgPool, equipment_id: i32) -> Result<Vec<CurrentOperator>, sqlx::Error> {
+    sqlx::query_as!(
+        CurrentOperator,
+        r#"
+        SELECT eo.operator_id, s.full_name, eo.assigned_from, eo.is_primary
+        FROM equipment_operator eo
+        JOIN staff s ON s.id = eo.operator_id
+        WHERE eo.equipment_id = $1 AND eo.assigned_to IS NULL
+        ORDER BY eo.is_primary DESC, s.full_name
+        "#,
+        equipment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Rules a machine's current assignments break, operator by operator.
+pub async fn equipment_conflicts(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    rules: &AssignmentsConfig,
+) -> Result<Vec<Violation>, sqlx::Error> {
+    let operator_ids = sqlx::query_scalar!(
+        "SELECT operator_id FROM equipment_operator WHERE equipment_id = $1 AND assigned_to IS NULL",
+        equipment_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut violations = Vec::new();
+    for operator_id in operator_ids {
+        violations.extend(check(conn, Some(operator_id), &[equipment_id], rules).await?);
+    }
+    Ok(violations)
+}
+
+/// IDs of the machines the operator is currently assigned to.
+pub async fn current_equipment_ids(pool: &PgPool, staff_id: i32) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT equipment_id FROM equipment_operator WHERE operator_id = $1 AND assigned_to IS NULL",
+        staff_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Assignment periods of an operator and/or a machine, latest first. With a
+/// `date`, only those in force at some point that day in `timezone`.
+pub async fn history(
+    pool: &PgPool,
+    operator_id: Option<i32>,
+    equipment_id: Option<i32>,
+    date: Option<NaiveDate>,
+    timezone: &str,
+) -> Result<Vec<Assignment>, sqlx::Error> {
+    sqlx::query_as!(
+        Assignment,
+        r#"
+        SELECT eo.id, eo.operator_id, s.full_name as operator_name,
+            eo.equipment_id, e.name as equipment_name, eo.assigned_from, eo.assigned_to,
+            eo.is_primary
+        FROM equipment_operator eo
+        JOIN staff s ON s.id = eo.operator_id
+        JOIN equipment e ON e.id = eo.equipment_id
+        WHERE ($1::int IS NULL OR eo.operator_id = $1)
+            AND ($2::int IS NULL OR eo.equipment_id = $2)
+            AND ($3::date IS NULL OR (
+                (eo.assigned_from AT TIME ZONE $4)::date <= $3
+                AND (eo.assigned_to IS NULL OR (eo.assigned_to AT TIME ZONE $4)::date >= $3)
+            ))
+        ORDER BY eo.assigned_from DESC, eo.id DESC
+        "#,
+        operator_id,
+        equipment_id,
+        date,
+        timezone
+    )
+    .fetch_all(pool)
+    .await
+}
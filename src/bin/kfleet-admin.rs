@@ -0,0 +1,464 @@
+use anyhow::{anyhow, bail, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use kfleet::archive::{self, RestoreMode};
+use kfleet::handlers::{import, maintenance};
+use kfleet::config::Config;
+use kfleet::jobs::{self, Job};
+use kfleet::notifications::{self, Mailer};
+use kfleet::{db, users};
+use sqlx::migrate::Migrate;
+use sqlx::PgPool;
+use std::path::PathBuf;
+
+/// Operations tasks for a kFleet instance, run against its database.
+#[derive(Parser)]
+#[command(name = "kfleet-admin", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply, inspect or revert database migrations
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Manage application users
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Import equipment, staff or categories from a CSV file
+    Import {
+        #[arg(value_enum)]
+        entity: Entity,
+        file: PathBuf,
+        /// Override a column mapping, e.g. --map serial_number="Serial No"
+        #[arg(long = "map", value_name = "FIELD=HEADER")]
+        mappings: Vec<String>,
+        /// Validate the file and report errors without importing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Dump or restore the whole fleet as a versioned JSON archive
+    #[command(subcommand)]
+    Archive(ArchiveCommand),
+    /// Maintenance schedule tasks
+    #[command(subcommand)]
+    Maintenance(MaintenanceCommand),
+    /// Deadline reminders
+    #[command(subcommand)]
+    Reminders(RemindersCommand),
+    /// Scheduled background jobs
+    #[command(subcommand)]
+    Jobs(JobsCommand),
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Apply all pending migrations
+    Run,
+    /// List migrations and whether they are applied
+    Status,
+    /// Revert applied migrations down to a target version (default: the latest one only)
+    Revert {
+        #[arg(long)]
+        target: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Create a user; the password is read from KFLEET_PASSWORD or prompted for
+    Create {
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "admin")]
+        role: String,
+    },
+    /// Set a new password for an existing user
+    ResetPassword {
+        #[arg(long)]
+        email: String,
+    },
+    /// List all users
+    List,
+    /// Issue a new calendar feed link for a user (the old link stops working)
+    CalendarToken {
+        #[arg(long)]
+        email: String,
+        /// Disable the user's feed instead
+        #[arg(long)]
+        revoke: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ArchiveCommand {
+    /// Write all fleet data to a JSON archive
+    Export {
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Recreate fleet data from a JSON archive
+    Import {
+        file: PathBuf,
+        /// Delete all existing fleet data before restoring
+        #[arg(long)]
+        replace: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MaintenanceCommand {
+    /// Roll next maintenance dates forward from the latest maintenance records
+    Recompute,
+}
+
+#[derive(Subcommand)]
+enum RemindersCommand {
+    /// Email the deadline digests that are due to their subscribers
+    Send {
+        /// Show who would receive what without sending or logging anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List maintenance, insurance and certification deadlines coming due
+    List {
+        /// Look-ahead in days (defaults to the configured reminder horizons)
+        #[arg(long)]
+        days: Option<i32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum JobsCommand {
+    /// Run a job now: deadline-scan, recompute-schedules or retention
+    Run { job: Job },
+    /// Show recent job runs
+    History {
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Entity {
+    Categories,
+    Equipment,
+    Staff,
+}
+
+impl From<Entity> for import::ImportEntity {
+    fn from(entity: Entity) -> Self {
+        match entity {
+            Entity::Categories => import::ImportEntity::Categories,
+            Entity::Equipment => import::ImportEntity::Equipment,
+            Entity::Staff => import::ImportEntity::Staff,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .init();
+
+    let cli = Cli::parse();
+
+    dotenvy::dotenv().ok();
+    let config = Config::load()?;
+    let pool = db::connect(&config.database).await?;
+
+    let result = run(cli.command, &config, &pool).await;
+    pool.close().await;
+    result
+}
+
+async fn run(command: Command, config: &Config, pool: &PgPool) -> anyhow::Result<()> {
+    match command {
+        Command::Migrate(MigrateCommand::Run) => {
+            db::MIGRATOR.run(pool).await?;
+            println!("Migrations applied");
+        }
+        Command::Migrate(MigrateCommand::Status) => migration_status(pool).await?,
+        Command::Migrate(MigrateCommand::Revert { target }) => revert_migrations(pool, target).await?,
+
+        Command::User(UserCommand::Create { email, name, role }) => {
+            let password = read_new_password()?;
+            let user = users::create_user(pool, &email, &name, &role, &password)
+                .await
+                .map_err(anyhow::Error::msg)?;
+            println!("Created {} user {} (id {})", user.role, user.email, user.id);
+        }
+        Command::User(UserCommand::ResetPassword { email }) => {
+            let password = read_new_password()?;
+            users::reset_password(pool, &email, &password)
+                .await
+                .map_err(anyhow::Error::msg)?;
+            println!("Password updated for {}", email);
+        }
+        Command::User(UserCommand::List) => {
+            for user in users::list_users(pool).await? {
+                println!(
+                    "{:>4}  {:<32} {:<8} {:<24} {}",
+                    user.id,
+                    user.email,
+                    user.role,
+                    user.full_name,
+                    if user.active { "active" } else { "disabled" }
+                );
+            }
+        }
+
+        Command::User(UserCommand::CalendarToken { email, revoke: true }) => {
+            users::revoke_calendar_token(pool, &email)
+                .await
+                .map_err(anyhow::Error::msg)?;
+            println!("Calendar feed disabled for {}", email);
+        }
+        Command::User(UserCommand::CalendarToken { email, revoke: false }) => {
+            let token = users::rotate_calendar_token(pool, &email)
+                .await
+                .map_err(anyhow::Error::msg)?;
+            println!(
+                "{}/calendar/{}/feed.ics",
+                config.server.public_url.trim_end_matches('/'),
+                token
+            );
+        }
+
+        Command::Import { entity, file, mappings, dry_run } => {
+            import_csv(pool, entity.into(), &file, &mappings, dry_run).await?
+        }
+
+        Command::Archive(ArchiveCommand::Export { output }) => {
+            let archive = archive::export(pool).await?;
+            let json = serde_json::to_string_pretty(&archive)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    eprintln!("Archive written to {}", path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+        Command::Archive(ArchiveCommand::Import { file, replace }) => {
+            let data = std::fs::read(&file)?;
+            let archive = archive::parse(&data).map_err(anyhow::Error::msg)?;
+            let mode = if replace { RestoreMode::Replace } else { RestoreMode::Merge };
+            let summary = archive::restore(pool, &archive, mode)
+                .await
+                .map_err(anyhow::Error::msg)?;
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+
+        Command::Maintenance(MaintenanceCommand::Recompute) => {
+            let changed = maintenance::recompute_schedules(pool).await?;
+            println!("Updated the maintenance schedule of {} machines", changed);
+        }
+
+        Command::Reminders(RemindersCommand::Send { dry_run }) => {
+            let mailer = if dry_run {
+                None
+            } else {
+                let mailer = Mailer::from_config(&config.smtp).map_err(anyhow::Error::msg)?;
+                Some(mailer.context("SMTP is not configured; set [smtp] host and from")?)
+            };
+            let templates = kfleet::load_templates(config)?;
+            let outcomes =
+                notifications::send_due_digests(pool, &templates, config, mailer.as_ref()).await?;
+            if outcomes.is_empty() {
+                println!("No digests are due");
+            }
+            for outcome in &outcomes {
+                let status = match (&outcome.error, outcome.sent) {
+                    (Some(e), _) => format!("failed: {}", e),
+                    (None, true) => "sent".to_string(),
+                    (None, false) if outcome.items == 0 => "nothing new".to_string(),
+                    (None, false) => "would send (dry run)".to_string(),
+                };
+                println!("{:<32} {:>3} deadlines  {}", outcome.email, outcome.items, status);
+            }
+            if outcomes.iter().any(|o| o.error.is_some()) {
+                bail!("Some digests could not be sent");
+            }
+        }
+        Command::Reminders(RemindersCommand::List { days }) => {
+            let maintenance_days = days.unwrap_or(config.reminders.maintenance_days);
+            let insurance_days = days.unwrap_or(config.reminders.insurance_days);
+            let maintenance =
+                kfleet::fetch_maintenance_alerts(pool, maintenance_days, i64::MAX).await?;
+            let insurance = kfleet::fetch_insurance_alerts(pool, insurance_days, i64::MAX).await?;
+            let certification_days = days.unwrap_or(config.reminders.certification_days);
+            let certifications =
+                kfleet::fetch_certification_alerts(pool, certification_days, i64::MAX).await?;
+            println!("Maintenance due in the next {} days:", maintenance_days);
+            for alert in &maintenance {
+                if let Some(due) = alert.next_maintenance {
+                    println!("  {}  {}", due.format("%Y-%m-%d"), alert.name);
+                }
+            }
+            println!("Insurance renewals in the next {} days:", insurance_days);
+            for alert in &insurance {
+                println!(
+                    "  {}  {} ({} {})",
+                    alert.insurance_renewal, alert.name, alert.insurer, alert.policy_number
+                );
+            }
+            println!("Certifications expired or expiring in the next {} days:", certification_days);
+            for alert in &certifications {
+                println!("  {}  {} ({})", alert.expiry_date, alert.full_name, alert.license_type);
+            }
+        }
+
+        Command::Jobs(JobsCommand::Run { job }) => {
+            match jobs::execute(pool, config, job, chrono::Utc::now()).await? {
+                jobs::Outcome::Finished(run) => {
+                    println!("{} {}: {}", job.name(), run.status, run.detail.unwrap_or_default());
+                    if run.status == "failed" {
+                        bail!("Job {} failed", job.name());
+                    }
+                }
+                jobs::Outcome::Locked => bail!("Job {} is running on another instance", job.name()),
+                jobs::Outcome::AlreadyRan => bail!("Job {} already ran just now", job.name()),
+            }
+        }
+        Command::Jobs(JobsCommand::History { limit }) => {
+            for run in jobs::recent_runs(pool, limit).await? {
+                println!(
+                    "{}  {:<20} {:<10} {}",
+                    run.started_at.format("%Y-%m-%d %H:%M:%S"),
+                    run.job_name,
+                    run.status,
+                    run.detail.unwrap_or_default()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn migration_status(pool: &PgPool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    for migration in db::MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()) {
+        let is_applied = applied.iter().any(|a| a.version == migration.version);
+        let reversible = db::MIGRATOR
+            .iter()
+            .any(|m| m.version == migration.version && m.migration_type.is_down_migration());
+        println!(
+            "{}  {:<8} {:<32} {}",
+            migration.version,
+            if is_applied { "applied" } else { "pending" },
+            migration.description,
+            if reversible { "" } else { "(irreversible)" }
+        );
+    }
+    Ok(())
+}
+
+async fn revert_migrations(pool: &PgPool, target: Option<i64>) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect::<Vec<_>>();
+    applied.sort_unstable();
+    drop(conn);
+
+    let Some(&latest) = applied.last() else {
+        bail!("No migrations have been applied");
+    };
+    // Default to undoing only the most recent migration
+    let target = target.unwrap_or_else(|| applied.iter().rev().nth(1).copied().unwrap_or(0));
+
+    for version in applied.iter().filter(|&&v| v > target) {
+        let reversible = db::MIGRATOR
+            .iter()
+            .any(|m| m.version == *version && m.migration_type.is_down_migration());
+        if !reversible {
+            bail!("Migration {} has no down script and cannot be reverted", version);
+        }
+    }
+    if latest <= target {
+        bail!("Nothing to revert above version {}", target);
+    }
+
+    db::MIGRATOR.undo(pool, target).await?;
+    println!("Reverted migrations down to version {}", target);
+    Ok(())
+}
+
+fn read_new_password() -> anyhow::Result<String> {
+    if let Ok(password) = std::env::var("KFLEET_PASSWORD") {
+        return Ok(password);
+    }
+    let password = rpassword::prompt_password("New password: ")?;
+    let confirmation = rpassword::prompt_password("Repeat password: ")?;
+    if password != confirmation {
+        bail!("Passwords do not match");
+    }
+    Ok(password)
+}
+
+async fn import_csv(
+    pool: &PgPool,
+    entity: import::ImportEntity,
+    file: &PathBuf,
+    overrides: &[String],
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let data = std::fs::read_to_string(file)
+        .with_context(|| format!("Cannot read {}", file.display()))?;
+    let (headers, _) = import::read_csv(&data).map_err(anyhow::Error::msg)?;
+
+    let mut mapping = import::suggest_mapping(entity, &headers);
+    for entry in overrides {
+        let (field, header) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid mapping '{}', expected FIELD=HEADER", entry))?;
+        if !entity.fields().iter().any(|f| f.name == field) {
+            bail!("Unknown field '{}' for {}", field, entity.as_str());
+        }
+        let column = headers
+            .iter()
+            .position(|h| h == header)
+            .ok_or_else(|| anyhow!("No column named '{}' in {}", header, file.display()))?;
+        mapping.insert(field.to_string(), column);
+    }
+
+    for field in entity.fields() {
+        match mapping.get(field.name) {
+            Some(&col) => println!("{:<20} <- {}", field.name, headers[col]),
+            None => println!("{:<20} <- (not mapped)", field.name),
+        }
+    }
+
+    let preview = if dry_run {
+        import::dry_run(pool, entity, &data, &mapping).await
+    } else {
+        import::commit(pool, entity, &data, &mapping).await
+    }
+    .map_err(anyhow::Error::msg)?;
+
+    for row in preview.rows.iter().filter(|r| !r.errors.is_empty()) {
+        println!("line {}: {}", row.line, row.errors.join("; "));
+    }
+    if preview.has_errors() {
+        bail!("{} rows with errors, nothing imported", preview.error_count);
+    }
+    if dry_run {
+        println!("{} rows valid (dry run, nothing imported)", preview.valid_count);
+    } else {
+        println!("Imported {} {} rows", preview.valid_count, entity.as_str());
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use crate::config::Config;
+use crate::reservations::{self, Reservation};
+use crate::{InsuranceAlert, MaintenanceAlert};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+
+/// Lines longer than this many octets are folded (RFC 5545 §3.1).
+const MAX_LINE_OCTETS: usize = 75;
+
+/// An all-day entry in a feed. `uid` must stay the same for the same
+/// deadline across refreshes so calendar apps update the entry in place.
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub date: NaiveDate,
+    /// Last day of an entry spanning several days; `None` for one day
+    pub last_date: Option<NaiveDate>,
+    pub summary: String,
+    pub description: String,
+    pub url: Option<String>,
+}
+
+/// One event per upcoming maintenance and insurance renewal, dated in the
+/// fleet's timezone, and one spanning each approved reservation.
+pub fn deadline_events(
+    maintenance: &[MaintenanceAlert],
+    insurance: &[InsuranceAlert],
+    reservations: &[Reservation],
+    tz: Tz,
+    public_url: &str,
+) -> Vec<CalendarEvent> {
+    let base = public_url.trim_end_matches('/');
+
+    let maintenance = maintenance.iter().filter_map(|alert| {
+        Some(CalendarEvent {
+            uid: format!("maintenance-{}@kfleet", alert.equipment_id),
+            date: alert.next_maintenance?.with_timezone(&tz).date_naive(),
+            last_date: None,
+            summary: format!("Maintenance due: {}", alert.name),
+            description: format!("Scheduled maintenance for {} is due.", alert.name),
+            url: Some(format!("{}/equipment/{}/edit", base, alert.equipment_id)),
+        })
+    });
+    let insurance = insurance.iter().map(|alert| CalendarEvent {
+        uid: format!("insurance-{}-{}@kfleet", alert.policy_id, alert.equipment_id),
+        date: alert.insurance_renewal,
+        last_date: None,
+        summary: format!("Insurance renewal: {}", alert.name),
+        description: format!(
+            "Policy {} with {} covering {} ends.",
+            alert.policy_number, alert.insurer, alert.name
+        ),
+        url: Some(format!("{}/insurance/{}", base, alert.policy_id)),
+    });
+
+    let reservations = reservations.iter().map(|r| CalendarEvent {
+        uid: format!("reservation-{}@kfleet", r.id),
+        date: r.start_date,
+        last_date: Some(r.end_date),
+        summary: format!("Reserved: {} at {}", r.equipment_name, r.site_name),
+        description: format!(
+            "{} is booked for {} by {} from {} to {}.",
+            r.equipment_name, r.site_name, r.requested_by, r.start_date, r.end_date
+        ),
+        url: Some(format!("{}/reservations/{}", base, r.id)),
+    });
+
+    let mut events: Vec<_> = maintenance.chain(insurance).chain(reservations).collect();
+    events.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.uid.cmp(&b.uid)));
+    events
+}
+
+/// Serializes events as an iCalendar (RFC 5545) document, each with a
+/// display alarm `alarm_days` before the deadline.
+pub fn render_feed(name: &str, events: &[CalendarEvent], alarm_days: i32, now: DateTime<Utc>) -> String {
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//kFleet//Deadlines//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+        "REFRESH-INTERVAL;VALUE=DURATION:PT1H".to_string(),
+        "X-PUBLISHED-TTL:PT1H".to_string(),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART;VALUE=DATE:{}", event.date.format("%Y%m%d")));
+        // DTEND is exclusive: the day after the last one
+        let last = event.last_date.unwrap_or(event.date);
+        lines.push(format!("DTEND;VALUE=DATE:{}", last.succ_opt().unwrap_or(last).format("%Y%m%d")));
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        lines.push(format!("DESCRIPTION:{}", escape(&event.description)));
+        if let Some(url) = &event.url {
+            lines.push(format!("URL:{}", url));
+        }
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        if alarm_days > 0 {
+            lines.push("BEGIN:VALARM".to_string());
+            lines.push("ACTION:DISPLAY".to_string());
+            lines.push(format!("TRIGGER:-P{}D", alarm_days));
+            lines.push(format!("DESCRIPTION:{}", escape(&event.summary)));
+            lines.push("END:VALARM".to_string());
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in &lines {
+        fold(&mut out, line);
+    }
+    out
+}
+
+/// The feed for the whole fleet, built from the same deadline queries as the
+/// dashboard alerts, with the approved reservations.
+pub async fn fleet_feed(pool: &PgPool, config: &Config) -> Result<String, sqlx::Error> {
+    let horizon = config.calendar.horizon_days;
+    let maintenance = crate::fetch_maintenance_alerts(pool, horizon, i64::MAX).await?;
+    let insurance = crate::fetch_insurance_alerts(pool, horizon, i64::MAX).await?;
+    let reservations = reservations::approved_reservations(pool, horizon).await?;
+    let events = deadline_events(
+        &maintenance,
+        &insurance,
+        &reservations,
+        config.locale.tz(),
+        &config.server.public_url,
+    );
+    Ok(render_feed("kFleet deadlines", &events, config.calendar.alarm_days, Utc::now()))
+}
+
+/// Escapes a TEXT value (RFC 5545 §3.3.11).
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Appends a content line, folding it without splitting UTF-8 sequences.
+fn fold(out: &mut String, line: &str) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            // The leading space counts towards the continuation line
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
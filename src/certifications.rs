@@ -0,0 +1,242 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::path::{Path, PathBuf};
+
+/// Licence types with their display labels.
+pub const LICENSE_TYPES: &[(&str, &str)] = &[
+    ("driving_licence", "Driving licence"),
+    ("heavy_vehicle", "Heavy-vehicle permit"),
+    ("crane", "Crane operator licence"),
+    ("earthmoving", "Earthmoving machinery"),
+    ("forklift", "Forklift certificate"),
+    ("aerial_platform", "Aerial work platform"),
+    ("other", "Other"),
+];
+
+/// Content types accepted for scanned certificates.
+pub const DOCUMENT_TYPES: &[&str] = &["application/pdf", "image/jpeg", "image/png"];
+
+/// A certification with the categories it qualifies for. `days_left` is
+/// negative once it has expired and `None` if it never expires.
+#[derive(Debug, FromRow, Serialize)]
+pub struct Certification {
+    pub id: i32,
+    pub staff_id: i32,
+    pub license_type: String,
+    pub license_number: Option<String>,
+    pub issuing_authority: String,
+    pub issue_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub days_left: Option<i32>,
+    pub document_filename: Option<String>,
+    pub document_stored_name: Option<String>,
+    pub document_content_type: Option<String>,
+    pub notes: Option<String>,
+    pub category_ids: Vec<i32>,
+    pub category_names: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CertificationForm {
+    pub license_type: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub license_number: Option<String>,
+    pub issuing_authority: String,
+    pub issue_date: NaiveDate,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub expiry_date: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub category_ids: Vec<i32>,
+}
+
+impl CertificationForm {
+    pub fn validate(&self) -> Result<(), String> {
+        if !LICENSE_TYPES.iter().any(|(value, _)| *value == self.license_type) {
+            return Err(format!("Unknown licence type '{}'", self.license_type));
+        }
+        if self.issuing_authority.trim().is_empty() {
+            return Err("The issuing authority is required".to_string());
+        }
+        if self.expiry_date.is_some_and(|expiry| expiry < self.issue_date) {
+            return Err("A certification cannot expire before it is issued".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub async fn list_for_staff(pool: &PgPool, staff_id: i32) -> Result<Vec<Certification>, sqlx::Error> {
+    sqlx::query_as!(
+        Certification,
+        r#"
+        SELECT c.id, c.staff_id, c.license_type, c.license_number, c.issuing_authority,
+            c.issue_date, c.expiry_date, c.expiry_date - CURRENT_DATE as days_left,
+            c.document_filename, c.document_stored_name, c.document_content_type, c.notes,
+            ARRAY(SELECT cc.category_id FROM certification_categories cc
+                  JOIN categories cat ON cat.id = cc.category_id
+                  WHERE cc.certification_id = c.id ORDER BY cat.name) as "category_ids!",
+            ARRAY(SELECT cat.name FROM certification_categories cc
+                  JOIN categories cat ON cat.id = cc.category_id
+                  WHERE cc.certification_id = c.id ORDER BY cat.name) as "category_names!"
+        FROM certifications c
+        WHERE c.staff_id = $1
+        ORDER BY c.expiry_date IS NOT NULL AND c.expiry_date < CURRENT_DATE, c.license_type, c.issue_date DESC
+        "#,
+        staff_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_certification(pool: &PgPool, id: i32) -> Result<Option<Certification>, sqlx::Error> {
+    sqlx::query_as!(
+        Certification,
+        r#"
+        SELECT c.id, c.staff_id, c.license_type, c.license_number, c.issuing_authority,
+            c.issue_date, c.expiry_date, c.expiry_date - CURRENT_DATE as days_left,
+            c.document_filename, c.document_stored_name, c.document_content_type, c.notes,
+            ARRAY(SELECT cc.category_id FROM certification_categories cc
+                  JOIN categories cat ON cat.id = cc.category_id
+                  WHERE cc.certification_id = c.id ORDER BY cat.name) as "category_ids!",
+            ARRAY(SELECT cat.name FROM certification_categories cc
+                  JOIN categories cat ON cat.id = cc.category_id
+                  WHERE cc.certification_id = c.id ORDER BY cat.name) as "category_names!"
+        FROM certifications c
+        WHERE c.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Inserts a certification and its categories; returns the new ID.
+pub async fn create_certification(
+    conn: &mut PgConnection,
+    staff_id: i32,
+    form: &CertificationForm,
+) -> Result<i32, sqlx::Error> {
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO certifications (
+            staff_id, license_type, license_number, issuing_authority,
+            issue_date, expiry_date, notes
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+        staff_id,
+        form.license_type,
+        form.license_number,
+        form.issuing_authority.trim(),
+        form.issue_date,
+        form.expiry_date,
+        form.notes
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    set_categories(conn, id, &form.category_ids).await?;
+    Ok(id)
+}
+
+pub async fn update_certification(
+    conn: &mut PgConnection,
+    id: i32,
+    form: &CertificationForm,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE certifications SET
+            license_type = $2, license_number = $3, issuing_authority = $4,
+            issue_date = $5, expiry_date = $6, notes = $7
+        WHERE id = $1
+        "#,
+        id,
+        form.license_type,
+        form.license_number,
+        form.issuing_authority.trim(),
+        form.issue_date,
+        form.expiry_date,
+        form.notes
+    )
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    set_categories(conn, id, &form.category_ids).await?;
+    Ok(true)
+}
+
+async fn set_categories(conn: &mut PgConnection, certification_id: i32, category_ids: &[i32]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM certification_categories WHERE certification_id = $1 AND NOT (category_id = ANY($2))",
+        certification_id,
+        category_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO certification_categories (certification_id, category_id)
+        SELECT $1, UNNEST($2::int[])
+        ON CONFLICT DO NOTHING
+        "#,
+        certification_id,
+        category_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Deletes a certification; returns its staff member and the stored name of
+/// its scan, if any, so the file can be removed.
+pub async fn delete_certification(pool: &PgPool, id: i32) -> Result<Option<(i32, Option<String>)>, sqlx::Error> {
+    let deleted = sqlx::query!(
+        "DELETE FROM certifications WHERE id = $1 RETURNING staff_id, document_stored_name",
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(deleted.map(|row| (row.staff_id, row.document_stored_name)))
+}
+
+/// Attaches a scan, replacing any previous one; returns the replaced file's
+/// stored name.
+pub async fn set_document(
+    pool: &PgPool,
+    id: i32,
+    filename: &str,
+    stored_name: &str,
+    content_type: &str,
+) -> Result<Option<Option<String>>, sqlx::Error> {
+    let previous = sqlx::query_scalar!(
+        r#"
+        UPDATE certifications c SET
+            document_filename = $2, document_stored_name = $3, document_content_type = $4
+        FROM certifications old
+        WHERE c.id = $1 AND old.id = c.id
+        RETURNING old.document_stored_name
+        "#,
+        id,
+        filename,
+        stored_name,
+        content_type
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(previous)
+}
+
+/// Where certificate scans are kept under `paths.uploads`.
+pub fn document_dir(uploads: &Path) -> PathBuf {
+    uploads.join("certifications")
+}
+
+pub async fn staff_name(pool: &PgPool, staff_id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!("SELECT full_name FROM staff WHERE id = $1", staff_id)
+        .fetch_optional(pool)
+        .await
+}
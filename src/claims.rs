@@ -0,0 +1,355 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+/// Claim statuses with their display labels, in workflow order.
+pub const CLAIM_STATUSES: &[(&str, &str)] = &[
+    ("filed", "Filed"),
+    ("under_review", "Under review"),
+    ("paid", "Paid"),
+    ("rejected", "Rejected"),
+];
+
+/// A claim with its policy and machine, and the total cost of the repairs
+/// linked to it.
+#[derive(Debug, FromRow, Serialize)]
+pub struct Claim {
+    pub id: i32,
+    pub policy_id: i32,
+    pub insurer: String,
+    pub policy_number: String,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub serial_number: String,
+    pub claim_number: String,
+    pub incident_date: NaiveDate,
+    pub filed_date: NaiveDate,
+    pub description: String,
+    pub status: String,
+    pub claimed_amount: f64,
+    pub paid_amount: Option<f64>,
+    pub closed_date: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub repair_cost: f64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ClaimFilter {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub policy_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub equipment_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClaimForm {
+    pub policy_id: i32,
+    pub equipment_id: i32,
+    pub claim_number: String,
+    pub incident_date: NaiveDate,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub filed_date: Option<NaiveDate>,
+    pub description: String,
+    pub claimed_amount: f64,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+impl ClaimForm {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.claim_number.trim().is_empty() || self.description.trim().is_empty() {
+            return Err("Claim number and description are required".to_string());
+        }
+        if self.claimed_amount < 0.0 {
+            return Err("The claimed amount cannot be negative".to_string());
+        }
+        if self.filed_date.is_some_and(|filed| filed < self.incident_date) {
+            return Err("A claim cannot be filed before the incident".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A step in the claim workflow, as submitted from the claim page.
+#[derive(Debug, Deserialize)]
+pub struct ClaimStatusForm {
+    pub status: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub paid_amount: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+/// Checks a status change: paid and rejected claims are closed, and only a
+/// paid claim carries a paid amount.
+pub fn check_transition(from: &str, to: &str, paid_amount: Option<f64>) -> Result<(), String> {
+    if !CLAIM_STATUSES.iter().any(|(value, _)| *value == to) {
+        return Err(format!("Unknown claim status '{}'", to));
+    }
+    if matches!(from, "paid" | "rejected") {
+        return Err(format!("The claim is already {}", from));
+    }
+    if from == "under_review" && to == "filed" {
+        return Err("A claim under review cannot go back to filed".to_string());
+    }
+    match (to, paid_amount) {
+        ("paid", None) => Err("Enter the amount the insurer paid".to_string()),
+        ("paid", Some(amount)) if amount < 0.0 => Err("The paid amount cannot be negative".to_string()),
+        ("paid", Some(_)) | (_, None) => Ok(()),
+        (_, Some(_)) => Err("Only a paid claim has a paid amount".to_string()),
+    }
+}
+
+/// A `maintenance_history` repair, linked or linkable to a claim.
+#[derive(Debug, FromRow, Serialize)]
+pub struct Repair {
+    pub id: i32,
+    pub maintenance_date: DateTime<Utc>,
+    pub description: String,
+    pub technician: Option<String>,
+    pub cost: Option<f64>,
+}
+
+/// Open (filed or under review) claims totalled per insurer.
+#[derive(Debug, FromRow, Serialize)]
+pub struct InsurerClaims {
+    pub insurer: String,
+    pub open_claims: i64,
+    pub under_review: i64,
+    pub claimed_amount: f64,
+    pub repair_cost: f64,
+    pub oldest_filed: NaiveDate,
+    /// Paid over claimed amounts on this insurer's settled claims
+    pub settled_ratio: Option<f64>,
+}
+
+pub async fn list_claims(pool: &PgPool, filter: &ClaimFilter) -> Result<Vec<Claim>, sqlx::Error> {
+    sqlx::query_as!(
+        Claim,
+        r#"
+        SELECT c.id, c.policy_id, p.insurer, p.policy_number,
+            c.equipment_id, e.name as equipment_name, e.serial_number,
+            c.claim_number, c.incident_date, c.filed_date, c.description, c.status,
+            c.claimed_amount, c.paid_amount, c.closed_date, c.notes,
+            COALESCE((SELECT SUM(m.cost) FROM claim_repairs cr
+                      JOIN maintenance_history m ON m.id = cr.maintenance_id
+                      WHERE cr.claim_id = c.id), 0) as "repair_cost!"
+        FROM insurance_claims c
+        JOIN insurance_policies p ON p.id = c.policy_id
+        JOIN equipment e ON e.id = c.equipment_id
+        WHERE ($1::text IS NULL OR c.status = $1)
+            AND ($2::int IS NULL OR c.policy_id = $2)
+            AND ($3::int IS NULL OR c.equipment_id = $3)
+        ORDER BY c.status IN ('paid', 'rejected'), c.filed_date DESC, c.id DESC
+        "#,
+        filter.status,
+        filter.policy_id,
+        filter.equipment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_claim(pool: &PgPool, id: i32) -> Result<Option<Claim>, sqlx::Error> {
+    sqlx::query_as!(
+        Claim,
+        r#"
+        SELECT c.id, c.policy_id, p.insurer, p.policy_number,
+            c.equipment_id, e.name as equipment_name, e.serial_number,
+            c.claim_number, c.incident_date, c.filed_date, c.description, c.status,
+            c.claimed_amount, c.paid_amount, c.closed_date, c.notes,
+            COALESCE((SELECT SUM(m.cost) FROM claim_repairs cr
+                      JOIN maintenance_history m ON m.id = cr.maintenance_id
+                      WHERE cr.claim_id = c.id), 0) as "repair_cost!"
+        FROM insurance_claims c
+        JOIN insurance_policies p ON p.id = c.policy_id
+        JOIN equipment e ON e.id = c.equipment_id
+        WHERE c.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Why the policy cannot take a claim for this machine and incident, if it
+/// cannot: the machine must be covered and the incident within the term.
+pub async fn coverage_problem(pool: &PgPool, form: &ClaimForm) -> Result<Option<String>, sqlx::Error> {
+    let policy = sqlx::query!(
+        r#"
+        SELECT p.policy_number, p.start_date, p.end_date,
+            EXISTS (SELECT 1 FROM policy_equipment pe
+                    WHERE pe.policy_id = p.id AND pe.equipment_id = $2) as "covers!"
+        FROM insurance_policies p
+        WHERE p.id = $1
+        "#,
+        form.policy_id,
+        form.equipment_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(policy) = policy else {
+        return Ok(Some(format!("Policy {} not found", form.policy_id)));
+    };
+    if !policy.covers {
+        return Ok(Some(format!("Policy {} does not cover this machine", policy.policy_number)));
+    }
+    if form.incident_date < policy.start_date || form.incident_date > policy.end_date {
+        return Ok(Some(format!(
+            "The incident on {} is outside the term of policy {} ({} to {})",
+            form.incident_date, policy.policy_number, policy.start_date, policy.end_date
+        )));
+    }
+    Ok(None)
+}
+
+pub async fn create_claim(pool: &PgPool, form: &ClaimForm) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO insurance_claims (
+            policy_id, equipment_id, claim_number, incident_date, filed_date,
+            description, claimed_amount, notes
+        ) VALUES ($1, $2, $3, $4, COALESCE($5, CURRENT_DATE), $6, $7, $8)
+        RETURNING id
+        "#,
+        form.policy_id,
+        form.equipment_id,
+        form.claim_number.trim(),
+        form.incident_date,
+        form.filed_date,
+        form.description.trim(),
+        form.claimed_amount,
+        form.notes
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Moves an open claim to `form.status`, closing it when paid or rejected.
+/// Returns false if the claim was closed in the meantime.
+pub async fn update_status(pool: &PgPool, id: i32, form: &ClaimStatusForm) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE insurance_claims SET
+            status = $2::varchar,
+            paid_amount = $3,
+            closed_date = CASE WHEN $2 IN ('paid', 'rejected') THEN CURRENT_DATE END,
+            notes = COALESCE($4, notes)
+        WHERE id = $1 AND status IN ('filed', 'under_review')
+        "#,
+        id,
+        form.status,
+        form.paid_amount,
+        form.notes
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn linked_repairs(pool: &PgPool, claim_id: i32) -> Result<Vec<Repair>, sqlx::Error> {
+    sqlx::query_as!(
+        Repair,
+        r#"
+        SELECT m.id, m.maintenance_date, m.description, m.technician, m.cost
+        FROM claim_repairs cr
+        JOIN maintenance_history m ON m.id = cr.maintenance_id
+        WHERE cr.claim_id = $1
+        ORDER BY m.maintenance_date
+        "#,
+        claim_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Repairs on the claim's machine since the incident that no claim has yet.
+pub async fn linkable_repairs(pool: &PgPool, claim: &Claim) -> Result<Vec<Repair>, sqlx::Error> {
+    sqlx::query_as!(
+        Repair,
+        r#"
+        SELECT m.id, m.maintenance_date, m.description, m.technician, m.cost
+        FROM maintenance_history m
+        WHERE m.equipment_id = $1
+            AND m.maintenance_date >= $2::date
+            AND NOT EXISTS (SELECT 1 FROM claim_repairs cr WHERE cr.maintenance_id = m.id)
+        ORDER BY m.maintenance_date
+        "#,
+        claim.equipment_id,
+        claim.incident_date
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Links a repair of the claim's own machine; false if it is another
+/// machine's, predates the incident or is already claimed.
+pub async fn link_repair(pool: &PgPool, claim_id: i32, maintenance_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO claim_repairs (claim_id, maintenance_id)
+        SELECT c.id, m.id
+        FROM insurance_claims c
+        JOIN maintenance_history m ON m.equipment_id = c.equipment_id
+        WHERE c.id = $1 AND m.id = $2 AND m.maintenance_date >= c.incident_date
+        ON CONFLICT DO NOTHING
+        "#,
+        claim_id,
+        maintenance_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn unlink_repair(pool: &PgPool, claim_id: i32, maintenance_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM claim_repairs WHERE claim_id = $1 AND maintenance_id = $2",
+        claim_id,
+        maintenance_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The outstanding claims report, largest amounts first.
+pub async fn outstanding_by_insurer(pool: &PgPool) -> Result<Vec<InsurerClaims>, sqlx::Error> {
+    sqlx::query_as!(
+        InsurerClaims,
+        r#"
+        WITH repairs AS (
+            SELECT cr.claim_id, SUM(m.cost) as cost
+            FROM claim_repairs cr
+            JOIN maintenance_history m ON m.id = cr.maintenance_id
+            GROUP BY cr.claim_id
+        ), settled AS (
+            SELECT p.insurer,
+                SUM(COALESCE(c.paid_amount, 0)) / NULLIF(SUM(c.claimed_amount), 0) as ratio
+            FROM insurance_claims c
+            JOIN insurance_policies p ON p.id = c.policy_id
+            WHERE c.status IN ('paid', 'rejected')
+            GROUP BY p.insurer
+        )
+        SELECT p.insurer,
+            COUNT(*) as "open_claims!",
+            COUNT(*) FILTER (WHERE c.status = 'under_review') as "under_review!",
+            SUM(c.claimed_amount) as "claimed_amount!",
+            COALESCE(SUM(r.cost), 0) as "repair_cost!",
+            MIN(c.filed_date) as "oldest_filed!",
+            MAX(s.ratio) as settled_ratio
+        FROM insurance_claims c
+        JOIN insurance_policies p ON p.id = c.policy_id
+        LEFT JOIN repairs r ON r.claim_id = c.id
+        LEFT JOIN settled s ON s.insurer = p.insurer
+        WHERE c.status IN ('filed', 'under_review')
+        GROUP BY p.insurer
+        ORDER BY SUM(c.claimed_amount) DESC, p.insurer
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
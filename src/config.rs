@@ -0,0 +1,868 @@
+use anyhow::{bail, Context};
+use chrono::{NaiveTime, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Config file read when `KFLEET_CONFIG` is not set; it is optional.
+pub const DEFAULT_CONFIG_FILE: &str = "kfleet.toml";
+
+/// Typed runtime configuration: built-in defaults, then the TOML file,
+/// then environment variable overrides.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub logging: LoggingConfig,
+    pub database: DatabaseConfig,
+    pub paths: PathsConfig,
+    pub smtp: SmtpConfig,
+    pub locale: LocaleConfig,
+    pub reminders: RemindersConfig,
+    pub assignments: AssignmentsConfig,
+    pub jobs: JobsConfig,
+    pub webhooks: WebhooksConfig,
+    pub calendar: CalendarConfig,
+    pub telematics: TelematicsConfig,
+    pub geofences: GeofencesConfig,
+    pub utilization: UtilizationConfig,
+    pub costs: CostsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_addr: SocketAddr,
+    /// How long in-flight requests may run after SIGTERM/SIGINT.
+    pub shutdown_timeout_secs: u64,
+    /// Address users reach this instance at, for links handed out of band
+    pub public_url: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_addr: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            shutdown_timeout_secs: 30,
+            public_url: "http://localhost:3000".to_string(),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Plain,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "plain" => Ok(LogFormat::Plain),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("expected plain or json, got '{}'", s)),
+        }
+    }
+}
+
+/// Log output; the level filter still comes from `RUST_LOG`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: 600,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub templates: PathBuf,
+    /// Served under `/static` when set.
+    pub static_files: Option<PathBuf>,
+    pub uploads: PathBuf,
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            templates: PathBuf::from("templates"),
+            static_files: None,
+            uploads: PathBuf::from("uploads"),
+        }
+    }
+}
+
+impl PathsConfig {
+    /// Glob handed to Tera.
+    pub fn templates_glob(&self) -> String {
+        format!("{}/**/*", self.templates.display())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection, e.g. a local MailHog
+    None,
+    StartTls,
+    Tls,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(SmtpSecurity::None),
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            _ => Err(format!("expected none, starttls or tls, got '{}'", s)),
+        }
+    }
+}
+
+/// Outgoing mail; email features stay off while `host` is unset.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: Option<String>,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Option<String>,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        SmtpConfig {
+            host: None,
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: None,
+            password: None,
+            from: None,
+        }
+    }
+}
+
+impl SmtpConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.host.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocaleConfig {
+    /// IANA name, e.g. "Indian/Antananarivo"
+    pub timezone: String,
+    /// ISO 4217 code, e.g. "MGA"
+    pub currency: String,
+    /// BCP 47 tag, e.g. "fr-MG"
+    pub locale: String,
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        LocaleConfig {
+            timezone: "UTC".to_string(),
+            currency: "EUR".to_string(),
+            locale: "en".to_string(),
+        }
+    }
+}
+
+impl LocaleConfig {
+    /// Only valid after `Config::validate` has accepted the timezone.
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    /// Formats an amount with two decimals, the locale's decimal separator
+    /// and the currency symbol.
+    pub fn format_money(&self, amount: f64) -> String {
+        let mut number = format!("{:.2}", amount);
+        if uses_decimal_comma(&self.locale) {
+            number = number.replace('.', ",");
+        }
+        match self.currency.as_str() {
+            "USD" => format!("${}", number),
+            "GBP" => format!("£{}", number),
+            "EUR" => format!("{}€", number),
+            "MGA" => format!("{} Ar", number),
+            code => format!("{} {}", number, code),
+        }
+    }
+}
+
+fn uses_decimal_comma(locale: &str) -> bool {
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    matches!(language, "fr" | "mg" | "de" | "es" | "it" | "pt" | "nl")
+}
+
+/// How far ahead deadlines show up on the dashboard and in reminders.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemindersConfig {
+    pub maintenance_days: i32,
+    pub insurance_days: i32,
+    pub certification_days: i32,
+}
+
+impl Default for RemindersConfig {
+    fn default() -> Self {
+        RemindersConfig {
+            maintenance_days: 30,
+            insurance_days: 30,
+            certification_days: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Refuse to save the assignment
+    Block,
+    /// Save it and list the problem on the staff form
+    Warn,
+}
+
+impl FromStr for RuleAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "block" => Ok(RuleAction::Block),
+            "warn" => Ok(RuleAction::Warn),
+            _ => Err(format!("expected block or warn, got '{}'", s)),
+        }
+    }
+}
+
+/// Rules checked when operators are assigned to equipment.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssignmentsConfig {
+    /// The machine is retired
+    pub retired: RuleAction,
+    /// The operator holds no valid certification for the machine's category
+    pub uncertified: RuleAction,
+    /// The machine already has `max_operators` operators
+    pub over_capacity: RuleAction,
+    pub max_operators: i64,
+}
+
+impl Default for AssignmentsConfig {
+    fn default() -> Self {
+        AssignmentsConfig {
+            retired: RuleAction::Block,
+            uncertified: RuleAction::Warn,
+            over_capacity: RuleAction::Block,
+            max_operators: 3,
+        }
+    }
+}
+
+/// Background job schedules, as cron expressions with a seconds field
+/// (`sec min hour day-of-month month day-of-week`) in `locale.timezone`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub enabled: bool,
+    pub deadline_scan: String,
+    pub recompute_schedules: String,
+    pub retention: String,
+    /// Job run history older than this is deleted by the retention job.
+    pub retention_days: i32,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            enabled: true,
+            deadline_scan: "0 0 6 * * *".to_string(),
+            recompute_schedules: "0 30 2 * * *".to_string(),
+            retention: "0 0 3 * * Sun".to_string(),
+            retention_days: 90,
+        }
+    }
+}
+
+/// Subscribable `.ics` deadline feeds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// How far ahead deadlines are published
+    pub horizon_days: i32,
+    /// Calendar apps remind this many days before each deadline
+    pub alarm_days: i32,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        CalendarConfig {
+            horizon_days: 365,
+            alarm_days: 7,
+        }
+    }
+}
+
+/// Outgoing webhook delivery.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    /// Run the delivery worker in this instance
+    pub enabled: bool,
+    pub poll_interval_secs: u64,
+    pub timeout_secs: u64,
+    /// Deliveries are marked failed after this many attempts
+    pub max_attempts: i32,
+    /// Delay before the first retry; doubles with every further attempt
+    pub backoff_base_secs: i64,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        WebhooksConfig {
+            enabled: true,
+            poll_interval_secs: 5,
+            timeout_secs: 10,
+            max_attempts: 8,
+            backoff_base_secs: 30,
+        }
+    }
+}
+
+/// Position and meter pings pushed by telematics units.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelematicsConfig {
+    /// Bearer token units send with each batch; ingestion is off while unset
+    pub api_key: Option<String>,
+    /// Most pings accepted in one request
+    pub max_batch: usize,
+    /// Pings older than this are refused and deleted by the retention job
+    pub retention_days: i32,
+    /// How far ahead of the server clock a ping's timestamp may be
+    pub max_clock_skew_secs: i64,
+}
+
+impl Default for TelematicsConfig {
+    fn default() -> Self {
+        TelematicsConfig {
+            api_key: None,
+            max_batch: 500,
+            retention_days: 180,
+            max_clock_skew_secs: 300,
+        }
+    }
+}
+
+/// When machines are expected to leave their sites. Exits outside these
+/// hours raise an alert.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeofencesConfig {
+    pub work_days: Vec<Weekday>,
+    /// Local time, in the `locale.timezone`
+    pub work_start: NaiveTime,
+    pub work_end: NaiveTime,
+}
+
+impl Default for GeofencesConfig {
+    fn default() -> Self {
+        GeofencesConfig {
+            work_days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat],
+            work_start: NaiveTime::from_hms_opt(6, 0, 0).expect("valid time"),
+            work_end: NaiveTime::from_hms_opt(19, 0, 0).expect("valid time"),
+        }
+    }
+}
+
+/// How available time and idleness are measured. Working days are the
+/// `geofences.work_days`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UtilizationConfig {
+    /// Engine hours a machine can run on a working day
+    pub shift_hours: f64,
+    /// A metered working day with fewer engine hours than this is idle
+    pub idle_hours: f64,
+    /// Days the utilization pages and the underused report cover by default
+    pub window_days: i64,
+    /// Machines idle on at least this share of working days are underused
+    pub underused_idle_percent: f64,
+}
+
+impl Default for UtilizationConfig {
+    fn default() -> Self {
+        UtilizationConfig {
+            shift_hours: 8.0,
+            idle_hours: 0.5,
+            window_days: 90,
+            underused_idle_percent: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum DepreciationMethod {
+    #[default]
+    StraightLine,
+    /// Double the straight-line rate off the remaining value each year
+    DecliningBalance,
+}
+
+impl DepreciationMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DepreciationMethod::StraightLine => "straight_line",
+            DepreciationMethod::DecliningBalance => "declining_balance",
+        }
+    }
+}
+
+impl FromStr for DepreciationMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "straight_line" => Ok(DepreciationMethod::StraightLine),
+            "declining_balance" => Ok(DepreciationMethod::DecliningBalance),
+            _ => Err(format!("expected straight_line or declining_balance, got '{}'", s)),
+        }
+    }
+}
+
+/// Cost of ownership reporting.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CostsConfig {
+    /// Years over which the purchase cost is written off, for machines
+    /// whose category sets none
+    pub useful_life_years: u32,
+    /// Likewise, for machines whose category sets no method
+    pub depreciation_method: DepreciationMethod,
+    /// A year of repairs costing this share of the book value or more
+    /// suggests replacing the machine; half of it flags it for watching
+    pub replace_ratio: f64,
+}
+
+impl Default for CostsConfig {
+    fn default() -> Self {
+        CostsConfig {
+            useful_life_years: 10,
+            depreciation_method: DepreciationMethod::StraightLine,
+            replace_ratio: 0.5,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file named by `KFLEET_CONFIG` (or `kfleet.toml` if
+    /// present), applies environment overrides and validates the result.
+    pub fn load() -> anyhow::Result<Config> {
+        let mut config = match std::env::var("KFLEET_CONFIG") {
+            Ok(path) => Config::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            Err(_) => Config::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Config> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read config file {}", path.display()))?;
+        Config::from_toml(&text)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn from_toml(text: &str) -> anyhow::Result<Config> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Overrides file values with environment variables. `DATABASE_URL` and
+    /// `PORT` are honoured for compatibility with existing deployments.
+    pub fn apply_env<F>(&mut self, var: F) -> anyhow::Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut errors = Vec::new();
+        let mut set = |name: &str, apply: &mut dyn FnMut(&str) -> Result<(), String>| {
+            if let Some(value) = var(name)
+                && let Err(e) = apply(&value)
+            {
+                errors.push(format!("{}: {}", name, e));
+            }
+        };
+
+        set("DATABASE_URL", &mut |v| assign(&mut self.database.url, v));
+        set("KFLEET_DATABASE_URL", &mut |v| assign(&mut self.database.url, v));
+        set("KFLEET_DB_MAX_CONNECTIONS", &mut |v| assign(&mut self.database.max_connections, v));
+        set("KFLEET_DB_MIN_CONNECTIONS", &mut |v| assign(&mut self.database.min_connections, v));
+        set("KFLEET_DB_ACQUIRE_TIMEOUT_SECS", &mut |v| {
+            assign(&mut self.database.acquire_timeout_secs, v)
+        });
+        set("KFLEET_DB_IDLE_TIMEOUT_SECS", &mut |v| assign(&mut self.database.idle_timeout_secs, v));
+
+        set("KFLEET_BIND_ADDR", &mut |v| assign(&mut self.server.bind_addr, v));
+        set("PORT", &mut |v| {
+            let mut port = 0u16;
+            assign(&mut port, v)?;
+            self.server.bind_addr.set_port(port);
+            Ok(())
+        });
+        set("KFLEET_PUBLIC_URL", &mut |v| assign(&mut self.server.public_url, v));
+        set("KFLEET_SHUTDOWN_TIMEOUT_SECS", &mut |v| {
+            assign(&mut self.server.shutdown_timeout_secs, v)
+        });
+        set("KFLEET_LOG_FORMAT", &mut |v| assign(&mut self.logging.format, v));
+
+        set("KFLEET_TEMPLATES_DIR", &mut |v| assign(&mut self.paths.templates, v));
+        set("KFLEET_STATIC_DIR", &mut |v| assign_opt(&mut self.paths.static_files, v));
+        set("KFLEET_UPLOADS_DIR", &mut |v| assign(&mut self.paths.uploads, v));
+
+        set("KFLEET_SMTP_HOST", &mut |v| assign_opt(&mut self.smtp.host, v));
+        set("KFLEET_SMTP_PORT", &mut |v| assign(&mut self.smtp.port, v));
+        set("KFLEET_SMTP_SECURITY", &mut |v| assign(&mut self.smtp.security, v));
+        set("KFLEET_SMTP_USERNAME", &mut |v| assign_opt(&mut self.smtp.username, v));
+        set("KFLEET_SMTP_PASSWORD", &mut |v| assign_opt(&mut self.smtp.password, v));
+        set("KFLEET_SMTP_FROM", &mut |v| assign_opt(&mut self.smtp.from, v));
+
+        set("KFLEET_TIMEZONE", &mut |v| assign(&mut self.locale.timezone, v));
+        set("KFLEET_CURRENCY", &mut |v| assign(&mut self.locale.currency, v));
+        set("KFLEET_LOCALE", &mut |v| assign(&mut self.locale.locale, v));
+
+        set("KFLEET_MAINTENANCE_REMINDER_DAYS", &mut |v| {
+            assign(&mut self.reminders.maintenance_days, v)
+        });
+        set("KFLEET_INSURANCE_REMINDER_DAYS", &mut |v| {
+            assign(&mut self.reminders.insurance_days, v)
+        });
+        set("KFLEET_CERTIFICATION_REMINDER_DAYS", &mut |v| {
+            assign(&mut self.reminders.certification_days, v)
+        });
+
+        set("KFLEET_ASSIGNMENT_RETIRED", &mut |v| assign(&mut self.assignments.retired, v));
+        set("KFLEET_ASSIGNMENT_UNCERTIFIED", &mut |v| assign(&mut self.assignments.uncertified, v));
+        set("KFLEET_ASSIGNMENT_OVER_CAPACITY", &mut |v| {
+            assign(&mut self.assignments.over_capacity, v)
+        });
+        set("KFLEET_ASSIGNMENT_MAX_OPERATORS", &mut |v| {
+            assign(&mut self.assignments.max_operators, v)
+        });
+
+        set("KFLEET_JOBS_ENABLED", &mut |v| assign(&mut self.jobs.enabled, v));
+        set("KFLEET_JOBS_DEADLINE_SCAN", &mut |v| assign(&mut self.jobs.deadline_scan, v));
+        set("KFLEET_JOBS_RECOMPUTE_SCHEDULES", &mut |v| {
+            assign(&mut self.jobs.recompute_schedules, v)
+        });
+        set("KFLEET_JOBS_RETENTION", &mut |v| assign(&mut self.jobs.retention, v));
+        set("KFLEET_JOBS_RETENTION_DAYS", &mut |v| assign(&mut self.jobs.retention_days, v));
+
+        set("KFLEET_CALENDAR_HORIZON_DAYS", &mut |v| assign(&mut self.calendar.horizon_days, v));
+        set("KFLEET_CALENDAR_ALARM_DAYS", &mut |v| assign(&mut self.calendar.alarm_days, v));
+
+        set("KFLEET_WEBHOOKS_ENABLED", &mut |v| assign(&mut self.webhooks.enabled, v));
+        set("KFLEET_WEBHOOKS_MAX_ATTEMPTS", &mut |v| assign(&mut self.webhooks.max_attempts, v));
+
+        set("KFLEET_TELEMATICS_API_KEY", &mut |v| assign_opt(&mut self.telematics.api_key, v));
+        set("KFLEET_TELEMATICS_MAX_BATCH", &mut |v| assign(&mut self.telematics.max_batch, v));
+        set("KFLEET_TELEMATICS_RETENTION_DAYS", &mut |v| {
+            assign(&mut self.telematics.retention_days, v)
+        });
+        set("KFLEET_TELEMATICS_MAX_CLOCK_SKEW_SECS", &mut |v| {
+            assign(&mut self.telematics.max_clock_skew_secs, v)
+        });
+
+        set("KFLEET_GEOFENCES_WORK_DAYS", &mut |v| assign_list(&mut self.geofences.work_days, v));
+        set("KFLEET_GEOFENCES_WORK_START", &mut |v| assign(&mut self.geofences.work_start, v));
+        set("KFLEET_GEOFENCES_WORK_END", &mut |v| assign(&mut self.geofences.work_end, v));
+
+        set("KFLEET_UTILIZATION_SHIFT_HOURS", &mut |v| assign(&mut self.utilization.shift_hours, v));
+        set("KFLEET_UTILIZATION_IDLE_HOURS", &mut |v| assign(&mut self.utilization.idle_hours, v));
+        set("KFLEET_UTILIZATION_WINDOW_DAYS", &mut |v| assign(&mut self.utilization.window_days, v));
+        set("KFLEET_UTILIZATION_UNDERUSED_IDLE_PERCENT", &mut |v| {
+            assign(&mut self.utilization.underused_idle_percent, v)
+        });
+
+        set("KFLEET_COSTS_USEFUL_LIFE_YEARS", &mut |v| assign(&mut self.costs.useful_life_years, v));
+        set("KFLEET_COSTS_DEPRECIATION_METHOD", &mut |v| {
+            assign(&mut self.costs.depreciation_method, v)
+        });
+        set("KFLEET_COSTS_REPLACE_RATIO", &mut |v| assign(&mut self.costs.replace_ratio, v));
+
+        report("Invalid environment override", errors)
+    }
+
+    /// Checks every setting and reports all problems at once.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+
+        if self.server.shutdown_timeout_secs == 0 {
+            errors.push("server.shutdown_timeout_secs must be positive".to_string());
+        }
+        if !(self.server.public_url.starts_with("http://") || self.server.public_url.starts_with("https://")) {
+            errors.push("server.public_url must be an http(s) URL".to_string());
+        }
+
+        let db = &self.database;
+        if db.url.is_empty() {
+            errors.push("database.url is not set (or DATABASE_URL)".to_string());
+        } else if !(db.url.starts_with("postgres://") || db.url.starts_with("postgresql://")) {
+            errors.push("database.url must be a postgres:// URL".to_string());
+        }
+        if db.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if db.min_connections > db.max_connections {
+            errors.push(format!(
+                "database.min_connections ({}) exceeds max_connections ({})",
+                db.min_connections, db.max_connections
+            ));
+        }
+        if db.acquire_timeout_secs == 0 {
+            errors.push("database.acquire_timeout_secs must be positive".to_string());
+        }
+
+        if !self.paths.templates.is_dir() {
+            errors.push(format!(
+                "paths.templates: {} is not a directory",
+                self.paths.templates.display()
+            ));
+        }
+        if let Some(dir) = &self.paths.static_files
+            && !dir.is_dir()
+        {
+            errors.push(format!("paths.static_files: {} is not a directory", dir.display()));
+        }
+        if self.paths.uploads.is_file() {
+            errors.push(format!(
+                "paths.uploads: {} is a file, not a directory",
+                self.paths.uploads.display()
+            ));
+        }
+
+        let smtp = &self.smtp;
+        if smtp.is_enabled() {
+            match &smtp.from {
+                None => errors.push("smtp.from is required when smtp.host is set".to_string()),
+                Some(from) if from.parse::<lettre::message::Mailbox>().is_err() => {
+                    errors.push(format!("smtp.from: '{}' is not an email address", from))
+                }
+                Some(_) => {}
+            }
+            if smtp.port == 0 {
+                errors.push("smtp.port must be positive".to_string());
+            }
+            if smtp.username.is_some() != smtp.password.is_some() {
+                errors.push("smtp.username and smtp.password must be set together".to_string());
+            }
+        }
+
+        let locale = &self.locale;
+        if locale.timezone.parse::<Tz>().is_err() {
+            errors.push(format!("locale.timezone: unknown timezone '{}'", locale.timezone));
+        }
+        if locale.currency.len() != 3 || !locale.currency.chars().all(|c| c.is_ascii_uppercase()) {
+            errors.push(format!(
+                "locale.currency: '{}' is not an ISO 4217 code like EUR or MGA",
+                locale.currency
+            ));
+        }
+        let mut parts = locale.locale.split(['-', '_']);
+        let language_ok = parts
+            .next()
+            .is_some_and(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_lowercase()));
+        if !language_ok || parts.any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_alphanumeric())) {
+            errors.push(format!("locale.locale: '{}' is not a tag like fr-MG", locale.locale));
+        }
+
+        for (name, days) in [
+            ("reminders.maintenance_days", self.reminders.maintenance_days),
+            ("reminders.insurance_days", self.reminders.insurance_days),
+            ("reminders.certification_days", self.reminders.certification_days),
+        ] {
+            if !(1..=365).contains(&days) {
+                errors.push(format!("{} must be between 1 and 365, got {}", name, days));
+            }
+        }
+
+        if self.assignments.max_operators < 1 {
+            errors.push("assignments.max_operators must be at least 1".to_string());
+        }
+
+        for (name, expr) in [
+            ("jobs.deadline_scan", &self.jobs.deadline_scan),
+            ("jobs.recompute_schedules", &self.jobs.recompute_schedules),
+            ("jobs.retention", &self.jobs.retention),
+        ] {
+            if let Err(e) = cron::Schedule::from_str(expr) {
+                errors.push(format!("{}: invalid cron expression '{}': {}", name, expr, e));
+            }
+        }
+        if self.jobs.retention_days < 1 {
+            errors.push("jobs.retention_days must be at least 1".to_string());
+        }
+
+        if !(1..=3650).contains(&self.calendar.horizon_days) {
+            errors.push("calendar.horizon_days must be between 1 and 3650".to_string());
+        }
+        if self.calendar.alarm_days < 0 {
+            errors.push("calendar.alarm_days must not be negative".to_string());
+        }
+
+        let webhooks = &self.webhooks;
+        if webhooks.poll_interval_secs == 0 || webhooks.timeout_secs == 0 {
+            errors.push("webhooks.poll_interval_secs and timeout_secs must be positive".to_string());
+        }
+        if !(1..=20).contains(&webhooks.max_attempts) {
+            errors.push("webhooks.max_attempts must be between 1 and 20".to_string());
+        }
+        if webhooks.backoff_base_secs < 1 {
+            errors.push("webhooks.backoff_base_secs must be positive".to_string());
+        }
+
+        let telematics = &self.telematics;
+        if telematics.api_key.as_ref().is_some_and(|key| key.len() < 16) {
+            errors.push("telematics.api_key must be at least 16 characters".to_string());
+        }
+        if !(1..=10_000).contains(&telematics.max_batch) {
+            errors.push("telematics.max_batch must be between 1 and 10000".to_string());
+        }
+        if telematics.retention_days < 1 {
+            errors.push("telematics.retention_days must be at least 1".to_string());
+        }
+        if telematics.max_clock_skew_secs < 0 {
+            errors.push("telematics.max_clock_skew_secs must not be negative".to_string());
+        }
+
+        let geofences = &self.geofences;
+        if geofences.work_start >= geofences.work_end {
+            errors.push("geofences.work_start must be before geofences.work_end".to_string());
+        }
+        if geofences.work_days.is_empty() {
+            errors.push("geofences.work_days must name at least one day".to_string());
+        }
+
+        let utilization = &self.utilization;
+        if !(utilization.shift_hours > 0.0 && utilization.shift_hours <= 24.0) {
+            errors.push("utilization.shift_hours must be between 0 and 24".to_string());
+        }
+        if !(utilization.idle_hours >= 0.0 && utilization.idle_hours <= utilization.shift_hours) {
+            errors.push("utilization.idle_hours must be between 0 and utilization.shift_hours".to_string());
+        }
+        if !(7..=366).contains(&utilization.window_days) {
+            errors.push("utilization.window_days must be between 7 and 366".to_string());
+        }
+        if !(0.0..=100.0).contains(&utilization.underused_idle_percent) {
+            errors.push("utilization.underused_idle_percent must be between 0 and 100".to_string());
+        }
+
+        let costs = &self.costs;
+        if !(1..=50).contains(&costs.useful_life_years) {
+            errors.push("costs.useful_life_years must be between 1 and 50".to_string());
+        }
+        if !(costs.replace_ratio > 0.0 && costs.replace_ratio <= 10.0) {
+            errors.push("costs.replace_ratio must be between 0 and 10".to_string());
+        }
+
+        report("Invalid configuration", errors)
+    }
+}
+
+fn assign<T>(target: &mut T, value: &str) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    *target = value.trim().parse().map_err(|e: T::Err| e.to_string())?;
+    Ok(())
+}
+
+fn assign_opt<T>(target: &mut Option<T>, value: &str) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    *target = match value.trim() {
+        "" => None,
+        v => Some(v.parse().map_err(|e: T::Err| e.to_string())?),
+    };
+    Ok(())
+}
+
+/// Comma-separated values, e.g. `Mon,Tue,Wed`.
+fn assign_list<T>(target: &mut Vec<T>, value: &str) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    *target = value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().map_err(|e: T::Err| format!("'{}': {}", v, e)))
+        .collect::<Result<_, _>>()?;
+    Ok(())
+}
+
+fn report(heading: &str, errors: Vec<String>) -> anyhow::Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    bail!("{}:\n  - {}", heading, errors.join("\n  - "))
+}
@@ -0,0 +1,427 @@
+//! Total cost of ownership.
+//!
+//! A machine's cost over a period is the depreciation of its purchase cost
+//! (see [`crate::valuation`]), its maintenance, the fuel and other expenses
+//! paid for it and its share of insurance premiums. Premiums are spread
+//! over the days a policy runs and split evenly between the machines it
+//! covers; cancelled policies are left out. Dividing by the engine hours
+//! the meters recorded gives the cost per hour.
+
+use crate::config::{CostsConfig, DepreciationMethod};
+use crate::valuation::{Asset, Terms};
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+
+pub const EXPENSE_KINDS: &[(&str, &str)] = &[
+    ("fuel", "Fuel"),
+    ("parts", "Parts"),
+    ("tyres", "Tyres"),
+    ("transport", "Transport"),
+    ("other", "Other"),
+];
+
+/// Reporting periods; `custom` uses the dates given.
+pub const PERIODS: &[(&str, &str)] = &[
+    ("12m", "Last 12 months"),
+    ("ytd", "Year to date"),
+    ("lifetime", "Since acquisition"),
+    ("custom", "Custom dates"),
+];
+
+/// What the repair vs replace indicator can say.
+pub const INDICATORS: &[(&str, &str)] = &[
+    ("repair", "Repair"),
+    ("watch", "Watch"),
+    ("replace", "Replace"),
+    ("unknown", "No purchase cost"),
+];
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Expense {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub expense_date: NaiveDate,
+    pub kind: String,
+    pub amount: f64,
+    pub quantity: Option<f64>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExpenseForm {
+    pub expense_date: NaiveDate,
+    pub kind: String,
+    pub amount: f64,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub quantity: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub description: Option<String>,
+}
+
+impl ExpenseForm {
+    pub fn validate(&self, today: NaiveDate) -> Result<(), String> {
+        if !EXPENSE_KINDS.iter().any(|(kind, _)| *kind == self.kind) {
+            return Err(format!("Unknown expense type '{}'", self.kind));
+        }
+        if !self.amount.is_finite() || self.amount < 0.0 {
+            return Err("The amount cannot be negative".to_string());
+        }
+        if self.quantity.is_some_and(|q| !q.is_finite() || q <= 0.0) {
+            return Err("The quantity must be positive".to_string());
+        }
+        if self.expense_date > today {
+            return Err("Expenses cannot be dated in the future".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CostQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub period: Option<String>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub to: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub category_id: Option<i32>,
+}
+
+/// A machine's costs over a period, as summed by the database.
+#[derive(Debug, Clone, FromRow)]
+pub struct CostRow {
+    pub equipment_id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub category_id: i32,
+    pub category_name: String,
+    pub acquired: NaiveDate,
+    pub purchase_cost: Option<f64>,
+    pub salvage_value: Option<f64>,
+    pub useful_life_years: Option<i32>,
+    pub depreciation_method: Option<DepreciationMethod>,
+    pub default_salvage_percent: Option<f64>,
+    pub default_useful_life_years: Option<i32>,
+    pub default_depreciation_method: Option<DepreciationMethod>,
+    pub maintenance: f64,
+    pub fuel: f64,
+    pub expenses: f64,
+    pub insurance: f64,
+    /// Maintenance over the 12 months up to the end of the period
+    pub recent_repairs: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MachineCost {
+    pub equipment_id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub category_id: i32,
+    pub category_name: String,
+    pub acquired: NaiveDate,
+    pub purchase_cost: Option<f64>,
+    /// Depreciation terms; `None` without a purchase cost
+    pub asset: Option<Asset>,
+    pub depreciation: f64,
+    pub maintenance: f64,
+    pub fuel: f64,
+    pub expenses: f64,
+    pub insurance: f64,
+    pub tco: f64,
+    /// Engine hours recorded in the period; `None` without meter readings
+    pub engine_hours: Option<f64>,
+    pub cost_per_hour: Option<f64>,
+    /// Book value at the end of the period
+    pub book_value: Option<f64>,
+    pub recent_repairs: f64,
+    /// Recent repairs over book value
+    pub repair_ratio: Option<f64>,
+    pub indicator: &'static str,
+}
+
+/// Costs summed over several machines. The cost per hour only counts the
+/// machines with meter readings.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CostTotals {
+    pub machines: usize,
+    pub depreciation: f64,
+    pub maintenance: f64,
+    pub fuel: f64,
+    pub expenses: f64,
+    pub insurance: f64,
+    pub tco: f64,
+    pub engine_hours: f64,
+    pub cost_per_hour: Option<f64>,
+    pub replace: usize,
+}
+
+impl CostTotals {
+    pub fn of<'a>(costs: impl IntoIterator<Item = &'a MachineCost>) -> CostTotals {
+        let mut totals = CostTotals::default();
+        let mut metered_cost = 0.0;
+        for cost in costs {
+            totals.machines += 1;
+            totals.depreciation += cost.depreciation;
+            totals.maintenance += cost.maintenance;
+            totals.fuel += cost.fuel;
+            totals.expenses += cost.expenses;
+            totals.insurance += cost.insurance;
+            totals.tco += cost.tco;
+            if let Some(hours) = cost.engine_hours {
+                totals.engine_hours += hours;
+                metered_cost += cost.tco;
+            }
+            if cost.indicator == "replace" {
+                totals.replace += 1;
+            }
+        }
+        totals.cost_per_hour = (totals.engine_hours > 0.0).then(|| metered_cost / totals.engine_hours);
+        totals
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryCost {
+    pub category_id: i32,
+    pub category_name: String,
+    #[serde(flatten)]
+    pub totals: CostTotals,
+}
+
+/// Whether a year of repairs still makes sense against what the machine is
+/// worth: `replace` once they reach `replace_ratio` of the book value (or
+/// the machine is written off), `watch` from half of that.
+pub fn indicator(recent_repairs: f64, book_value: Option<f64>, replace_ratio: f64) -> (&'static str, Option<f64>) {
+    let Some(book_value) = book_value else {
+        return ("unknown", None);
+    };
+    if book_value <= 0.0 {
+        return (if recent_repairs > 0.0 { "replace" } else { "repair" }, None);
+    }
+    let ratio = recent_repairs / book_value;
+    let verdict = if ratio >= replace_ratio {
+        "replace"
+    } else if ratio >= replace_ratio / 2.0 {
+        "watch"
+    } else {
+        "repair"
+    };
+    (verdict, Some(ratio))
+}
+
+/// Completes a machine's summed costs for `from` to `to` with depreciation,
+/// engine hours (from [`crate::utilization::daily_hours`]) and the indicator.
+pub fn machine_cost(
+    row: CostRow,
+    hours: Option<&BTreeMap<NaiveDate, f64>>,
+    from: NaiveDate,
+    to: NaiveDate,
+    config: &CostsConfig,
+) -> MachineCost {
+    let machine = Terms::machine(row.salvage_value, row.useful_life_years, row.depreciation_method);
+    let category = Terms::category(
+        row.default_salvage_percent,
+        row.default_useful_life_years,
+        row.default_depreciation_method,
+    );
+    let asset = row
+        .purchase_cost
+        .map(|cost| Asset::new(cost, row.acquired, &machine, &category, config));
+    let depreciation = asset.as_ref().map(|a| a.depreciation(from, to)).unwrap_or(0.0);
+    let book_value = asset.as_ref().map(|a| a.book_value(to));
+    let tco = depreciation + row.maintenance + row.fuel + row.expenses + row.insurance;
+    let engine_hours = hours
+        .map(|hours| hours.range(from..=to).map(|(_, h)| h).sum::<f64>())
+        .filter(|hours| *hours > 0.0);
+    let (indicator, repair_ratio) = indicator(row.recent_repairs, book_value, config.replace_ratio);
+    MachineCost {
+        equipment_id: row.equipment_id,
+        name: row.name,
+        serial_number: row.serial_number,
+        category_id: row.category_id,
+        category_name: row.category_name,
+        acquired: row.acquired,
+        purchase_cost: row.purchase_cost,
+        asset,
+        depreciation,
+        maintenance: row.maintenance,
+        fuel: row.fuel,
+        expenses: row.expenses,
+        insurance: row.insurance,
+        tco,
+        engine_hours,
+        cost_per_hour: engine_hours.map(|hours| tco / hours),
+        book_value,
+        recent_repairs: row.recent_repairs,
+        repair_ratio,
+        indicator,
+    }
+}
+
+/// Totals per category, in category name order.
+pub fn by_category(costs: &[MachineCost]) -> Vec<CategoryCost> {
+    let mut groups: BTreeMap<(&str, i32), Vec<&MachineCost>> = BTreeMap::new();
+    for cost in costs {
+        groups.entry((&cost.category_name, cost.category_id)).or_default().push(cost);
+    }
+    groups
+        .into_iter()
+        .map(|((name, id), costs)| CategoryCost {
+            category_id: id,
+            category_name: name.to_string(),
+            totals: CostTotals::of(costs),
+        })
+        .collect()
+}
+
+/// The dates a period covers; `earliest` is the first acquisition in the
+/// fleet, where `lifetime` starts.
+pub fn period_range(
+    period: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    today: NaiveDate,
+    earliest: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let range = match period {
+        "ytd" => (today.with_ordinal(1).expect("first day of year"), today),
+        "lifetime" => (earliest.min(today), today),
+        "custom" => {
+            let (Some(from), Some(to)) = (from, to) else {
+                return Err("Enter both dates of the custom range".to_string());
+            };
+            (from, to)
+        }
+        _ => (
+            today.checked_sub_months(Months::new(12)).unwrap_or(today) + Duration::days(1),
+            today,
+        ),
+    };
+    if range.0 > range.1 {
+        return Err("The range must start before it ends".to_string());
+    }
+    if range.1 > today {
+        return Err("The range cannot end after today".to_string());
+    }
+    Ok(range)
+}
+
+/// Costs from `from` to `to` of one machine, or of the machines not retired
+/// (optionally of one category), by name; days are local to `timezone`.
+pub async fn cost_rows(
+    pool: &PgPool,
+    from: NaiveDate,
+    to: NaiveDate,
+    timezone: &str,
+    category_id: Option<i32>,
+    equipment_id: Option<i32>,
+) -> Result<Vec<CostRow>, sqlx::Error> {
+    sqlx::query_as!(
+        CostRow,
+        r#"
+        SELECT e.id as equipment_id, e.name, e.serial_number, e.category_id, c.name as category_name,
+            (e.acquisition_date AT TIME ZONE $3)::date as "acquired!", e.purchase_cost,
+            e.salvage_value, e.useful_life_years,
+            e.depreciation_method as "depreciation_method: DepreciationMethod",
+            c.default_salvage_percent, c.default_useful_life_years,
+            c.default_depreciation_method as "default_depreciation_method: DepreciationMethod",
+            COALESCE((
+                SELECT SUM(m.cost) FROM maintenance_history m
+                WHERE m.equipment_id = e.id
+                  AND (m.maintenance_date AT TIME ZONE $3)::date BETWEEN $1 AND $2
+            ), 0) as "maintenance!",
+            COALESCE((
+                SELECT SUM(x.amount) FROM equipment_expenses x
+                WHERE x.equipment_id = e.id AND x.kind = 'fuel' AND x.expense_date BETWEEN $1 AND $2
+            ), 0) as "fuel!",
+            COALESCE((
+                SELECT SUM(x.amount) FROM equipment_expenses x
+                WHERE x.equipment_id = e.id AND x.kind <> 'fuel' AND x.expense_date BETWEEN $1 AND $2
+            ), 0) as "expenses!",
+            COALESCE((
+                SELECT SUM(
+                    p.premium
+                    * (LEAST(p.end_date, $2::date) - GREATEST(p.start_date, $1::date) + 1)
+                    / (p.end_date - p.start_date + 1)
+                    / (SELECT COUNT(*) FROM policy_equipment n WHERE n.policy_id = p.id)
+                )
+                FROM insurance_policies p
+                JOIN policy_equipment pe ON pe.policy_id = p.id
+                WHERE pe.equipment_id = e.id AND p.status <> 'cancelled'
+                  AND p.start_date <= $2 AND p.end_date >= $1
+            ), 0) as "insurance!",
+            COALESCE((
+                SELECT SUM(m.cost) FROM maintenance_history m
+                WHERE m.equipment_id = e.id
+                  AND (m.maintenance_date AT TIME ZONE $3)::date > $2::date - 365
+                  AND (m.maintenance_date AT TIME ZONE $3)::date <= $2
+            ), 0) as "recent_repairs!"
+        FROM equipment e
+        JOIN categories c ON c.id = e.category_id
+        WHERE e.id = $5
+            OR ($5::int IS NULL AND e.current_status <> 'retired' AND ($4::int IS NULL OR e.category_id = $4))
+        ORDER BY e.name
+        "#,
+        from,
+        to,
+        timezone,
+        category_id,
+        equipment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Day the first machine still in the fleet was acquired.
+pub async fn earliest_acquisition(pool: &PgPool, timezone: &str) -> Result<Option<NaiveDate>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT MIN((acquisition_date AT TIME ZONE $1)::date) FROM equipment WHERE current_status <> 'retired'",
+        timezone
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// A machine's expenses, newest first.
+pub async fn equipment_expenses(pool: &PgPool, equipment_id: i32) -> Result<Vec<Expense>, sqlx::Error> {
+    sqlx::query_as!(
+        Expense,
+        r#"
+        SELECT id, equipment_id, expense_date, kind, amount, quantity, description
+        FROM equipment_expenses
+        WHERE equipment_id = $1
+        ORDER BY expense_date DESC, id DESC
+        "#,
+        equipment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_expense(pool: &PgPool, equipment_id: i32, form: &ExpenseForm) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO equipment_expenses (equipment_id, expense_date, kind, amount, quantity, description)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+        equipment_id,
+        form.expense_date,
+        form.kind,
+        form.amount,
+        form.quantity,
+        form.description.as_deref().map(str::trim)
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Deletes an expense, returning the machine it was for.
+pub async fn delete_expense(pool: &PgPool, id: i32) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar!("DELETE FROM equipment_expenses WHERE id = $1 RETURNING equipment_id", id)
+        .fetch_optional(pool)
+        .await
+}
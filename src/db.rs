@@ -0,0 +1,17 @@
+use crate::config::DatabaseConfig;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+
+/// Migrations embedded at compile time, shared by the server and `kfleet-admin`.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Opens the connection pool used by both binaries.
+pub async fn connect(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout())
+        .idle_timeout(config.idle_timeout())
+        .connect(&config.url)
+        .await
+}
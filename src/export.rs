@@ -0,0 +1,197 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Deserialize;
+
+/// Spreadsheet formats offered on list pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+}
+
+/// A typed spreadsheet cell, so XLSX output gets real dates and numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    Integer(i64),
+    Date(NaiveDate),
+    Empty,
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(value: f64) -> Self {
+        Cell::Number(value)
+    }
+}
+
+impl From<i32> for Cell {
+    fn from(value: i32) -> Self {
+        Cell::Integer(value.into())
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Integer(value)
+    }
+}
+
+impl From<NaiveDate> for Cell {
+    fn from(value: NaiveDate) -> Self {
+        Cell::Date(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Cell {
+    fn from(value: DateTime<Utc>) -> Self {
+        Cell::Date(value.date_naive())
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Cell::Empty)
+    }
+}
+
+/// A single table of rows under a fixed header line.
+#[derive(Debug)]
+pub struct Sheet {
+    pub name: String,
+    pub headers: &'static [&'static str],
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Sheet {
+    pub fn new(name: &str, headers: &'static [&'static str]) -> Self {
+        Sheet {
+            name: name.to_string(),
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Cell>) {
+        debug_assert_eq!(row.len(), self.headers.len());
+        self.rows.push(row);
+    }
+
+    pub fn to_csv(&self) -> Result<Vec<u8>, String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(self.headers).map_err(|e| e.to_string())?;
+        for row in &self.rows {
+            writer
+                .write_record(row.iter().map(|cell| match cell {
+                    Cell::Text(s) => s.clone(),
+                    Cell::Number(n) => n.to_string(),
+                    Cell::Integer(n) => n.to_string(),
+                    Cell::Date(d) => d.format("%Y-%m-%d").to_string(),
+                    Cell::Empty => String::new(),
+                }))
+                .map_err(|e| e.to_string())?;
+        }
+        writer.into_inner().map_err(|e| e.to_string())
+    }
+
+    pub fn to_xlsx(&self) -> Result<Vec<u8>, String> {
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&self.name).map_err(|e| e.to_string())?;
+
+        let header_format = Format::new().set_bold();
+        let date_format = Format::new().set_num_format("yyyy-mm-dd");
+        let number_format = Format::new().set_num_format("#,##0.00");
+
+        for (col, header) in self.headers.iter().enumerate() {
+            worksheet
+                .write_string_with_format(0, col as u16, *header, &header_format)
+                .map_err(|e| e.to_string())?;
+        }
+
+        for (i, row) in self.rows.iter().enumerate() {
+            let r = (i + 1) as u32;
+            for (col, cell) in row.iter().enumerate() {
+                let c = col as u16;
+                let result = match cell {
+                    Cell::Text(s) => worksheet.write_string(r, c, s),
+                    Cell::Number(n) => worksheet.write_number_with_format(r, c, *n, &number_format),
+                    Cell::Integer(n) => worksheet.write_number(r, c, *n as f64),
+                    Cell::Date(d) => worksheet.write_datetime_with_format(r, c, d, &date_format),
+                    Cell::Empty => continue,
+                };
+                result.map_err(|e| e.to_string())?;
+            }
+        }
+
+        worksheet.set_freeze_panes(1, 0).map_err(|e| e.to_string())?;
+        worksheet.autofit();
+        workbook.save_to_buffer().map_err(|e| e.to_string())
+    }
+}
+
+/// Renders the sheet and wraps it in a file download response.
+pub fn download(sheet: &Sheet, format: ExportFormat, file_stem: &str) -> Result<Response, String> {
+    let body = match format {
+        ExportFormat::Csv => sheet.to_csv()?,
+        ExportFormat::Xlsx => sheet.to_xlsx()?,
+    };
+    let filename = format!(
+        "kfleet-{}-{}.{}",
+        file_stem,
+        Utc::now().format("%Y-%m-%d"),
+        format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
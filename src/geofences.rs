@@ -0,0 +1,462 @@
+use crate::config::GeofencesConfig;
+use crate::webhooks;
+use chrono::{DateTime, Datelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::{HashMap, HashSet};
+
+pub const GEOFENCE_KINDS: &[(&str, &str)] = &[("radius", "Circle"), ("polygon", "Polygon")];
+
+/// Mean Earth radius used for distances, in metres.
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// A zone around a site. Circles have a centre and radius; polygons a list
+/// of `[latitude, longitude]` vertices.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Geofence {
+    pub id: i32,
+    pub site_id: i32,
+    pub site_name: String,
+    pub name: String,
+    pub kind: String,
+    pub center_latitude: Option<f64>,
+    pub center_longitude: Option<f64>,
+    pub radius_m: Option<f64>,
+    pub vertices: Option<Json<Vec<[f64; 2]>>>,
+}
+
+impl Geofence {
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        match (self.center_latitude, self.center_longitude, self.radius_m, &self.vertices) {
+            (Some(lat), Some(lon), Some(radius), _) => distance_m(lat, lon, latitude, longitude) <= radius,
+            (_, _, _, Some(vertices)) => in_polygon(&vertices.0, latitude, longitude),
+            _ => false,
+        }
+    }
+}
+
+/// Great-circle distance between two positions, in metres.
+pub fn distance_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = (lat2 - lat1).to_radians();
+    let dlambda = (lon2 - lon1).to_radians();
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Ray casting on latitude/longitude, which is accurate enough at the scale
+/// of a site.
+pub fn in_polygon(vertices: &[[f64; 2]], latitude: f64, longitude: f64) -> bool {
+    let mut inside = false;
+    let mut j = vertices.len().wrapping_sub(1);
+    for i in 0..vertices.len() {
+        let ([lat_i, lon_i], [lat_j, lon_j]) = (vertices[i], vertices[j]);
+        if (lat_i > latitude) != (lat_j > latitude)
+            && longitude < (lon_j - lon_i) * (latitude - lat_i) / (lat_j - lat_i) + lon_i
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GeofenceForm {
+    pub name: String,
+    pub kind: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub center_latitude: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub center_longitude: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub radius_m: Option<f64>,
+    /// One `latitude, longitude` pair per line
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub vertices: Option<String>,
+}
+
+impl GeofenceForm {
+    /// Checks the zone and returns the polygon's vertices, if it is one.
+    pub fn validate(&self) -> Result<Option<Vec<[f64; 2]>>, String> {
+        if self.name.trim().is_empty() {
+            return Err("Name the zone".to_string());
+        }
+        match self.kind.as_str() {
+            "radius" => {
+                let (Some(lat), Some(lon), Some(radius)) = (self.center_latitude, self.center_longitude, self.radius_m) else {
+                    return Err("A circle needs a centre and a radius".to_string());
+                };
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err("GPS coordinates are out of range".to_string());
+                }
+                if !(radius > 0.0 && radius <= 50_000.0) {
+                    return Err("The radius must be between 0 and 50 000 metres".to_string());
+                }
+                Ok(None)
+            }
+            "polygon" => parse_vertices(self.vertices.as_deref().unwrap_or_default()).map(Some),
+            other => Err(format!("Unknown zone kind '{}'", other)),
+        }
+    }
+}
+
+/// Reads `latitude, longitude` lines into polygon vertices.
+pub fn parse_vertices(text: &str) -> Result<Vec<[f64; 2]>, String> {
+    let mut vertices = Vec::new();
+    for (n, line) in text.lines().map(str::trim).enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = line
+            .split_once(',')
+            .and_then(|(lat, lon)| Some([lat.trim().parse::<f64>().ok()?, lon.trim().parse::<f64>().ok()?]));
+        match parsed {
+            Some([lat, lon]) if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) => {
+                vertices.push([lat, lon]);
+            }
+            _ => return Err(format!("Line {}: expected 'latitude, longitude', got '{}'", n + 1, line)),
+        }
+    }
+    if vertices.len() < 3 {
+        return Err("A polygon needs at least three vertices".to_string());
+    }
+    Ok(vertices)
+}
+
+/// A position ping, as evaluated against the zones.
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    pub recorded_at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Whether a machine was last seen inside a zone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Presence {
+    pub inside: bool,
+    pub observed_at: DateTime<Utc>,
+}
+
+/// A machine crossing a zone boundary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crossing {
+    pub geofence_id: i32,
+    pub kind: &'static str,
+    pub occurred_at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Walks one machine's new positions in time order and returns the zone
+/// boundaries it crossed, updating `presence` (by geofence ID). A machine
+/// first seen inside a zone enters it; positions older than the last one
+/// seen for a zone are ignored.
+pub fn track(fences: &[Geofence], presence: &mut HashMap<i32, Presence>, positions: &[Position]) -> Vec<Crossing> {
+    let mut positions = positions.to_vec();
+    positions.sort_by_key(|p| p.recorded_at);
+    let mut crossings = Vec::new();
+    for p in &positions {
+        for fence in fences {
+            let previous = presence.get(&fence.id).copied();
+            if previous.is_some_and(|prev| p.recorded_at <= prev.observed_at) {
+                continue;
+            }
+            let inside = fence.contains(p.latitude, p.longitude);
+            let kind = match (previous.map(|prev| prev.inside), inside) {
+                (Some(false) | None, true) => Some("enter"),
+                (Some(true), false) => Some("exit"),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                crossings.push(Crossing {
+                    geofence_id: fence.id,
+                    kind,
+                    occurred_at: p.recorded_at,
+                    latitude: p.latitude,
+                    longitude: p.longitude,
+                });
+            }
+            presence.insert(fence.id, Presence { inside, observed_at: p.recorded_at });
+        }
+    }
+    crossings
+}
+
+/// Whether `at` falls within working hours in the fleet's timezone.
+pub fn is_working_time(at: DateTime<Utc>, tz: Tz, config: &GeofencesConfig) -> bool {
+    let local = at.with_timezone(&tz);
+    let time = local.time();
+    config.work_days.contains(&local.weekday()) && time >= config.work_start && time < config.work_end
+}
+
+/// An arrival or departure, for the site log and alerts.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct GeofenceEvent {
+    pub id: i64,
+    pub geofence_id: i32,
+    pub geofence_name: String,
+    pub site_id: i32,
+    pub site_name: String,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub serial_number: String,
+    pub kind: String,
+    pub occurred_at: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub alert: bool,
+}
+
+pub async fn all_geofences(conn: &mut PgConnection) -> Result<Vec<Geofence>, sqlx::Error> {
+    sqlx::query_as!(
+        Geofence,
+        r#"
+        SELECT g.id, g.site_id, s.name as site_name, g.name, g.kind,
+            g.center_latitude, g.center_longitude, g.radius_m,
+            g.vertices as "vertices: Json<Vec<[f64; 2]>>"
+        FROM geofences g
+        JOIN sites s ON s.id = g.site_id
+        ORDER BY g.id
+        "#
+    )
+    .fetch_all(conn)
+    .await
+}
+
+pub async fn site_geofences(pool: &PgPool, site_id: i32) -> Result<Vec<Geofence>, sqlx::Error> {
+    sqlx::query_as!(
+        Geofence,
+        r#"
+        SELECT g.id, g.site_id, s.name as site_name, g.name, g.kind,
+            g.center_latitude, g.center_longitude, g.radius_m,
+            g.vertices as "vertices: Json<Vec<[f64; 2]>>"
+        FROM geofences g
+        JOIN sites s ON s.id = g.site_id
+        WHERE g.site_id = $1
+        ORDER BY g.name
+        "#,
+        site_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns `None` when the site already has a zone by that name.
+pub async fn create_geofence(
+    pool: &PgPool,
+    site_id: i32,
+    form: &GeofenceForm,
+    vertices: Option<Vec<[f64; 2]>>,
+) -> Result<Option<i32>, sqlx::Error> {
+    let (center_latitude, center_longitude, radius_m) = match vertices {
+        Some(_) => (None, None, None),
+        None => (form.center_latitude, form.center_longitude, form.radius_m),
+    };
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO geofences (site_id, name, kind, center_latitude, center_longitude, radius_m, vertices)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (site_id, name) DO NOTHING
+        RETURNING id
+        "#,
+        site_id,
+        form.name.trim(),
+        form.kind,
+        center_latitude,
+        center_longitude,
+        radius_m,
+        vertices.map(Json) as Option<Json<Vec<[f64; 2]>>>
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Deletes a zone with its log; returns its site.
+pub async fn delete_geofence(pool: &PgPool, id: i32) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar!("DELETE FROM geofences WHERE id = $1 RETURNING site_id", id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Evaluates new position pings against every zone and records the
+/// crossings. An exit from the zone of the site a machine was deployed to
+/// on the day of the crossing, outside working hours, is flagged as an alert and published to webhooks.
+/// Call it on the ingesting transaction. Returns the number of alerts.
+pub async fn evaluate(
+    conn: &mut PgConnection,
+    positions: &HashMap<i32, Vec<Position>>,
+    tz: Tz,
+    config: &GeofencesConfig,
+) -> Result<usize, sqlx::Error> {
+    if positions.is_empty() {
+        return Ok(0);
+    }
+    let fences = all_geofences(&mut *conn).await?;
+    if fences.is_empty() {
+        return Ok(0);
+    }
+
+    let mut equipment_ids: Vec<i32> = positions.keys().copied().collect();
+    equipment_ids.sort_unstable();
+    // Serialises concurrent batches for the same machines
+    let machines = sqlx::query!(
+        r#"
+        SELECT id, name, serial_number
+        FROM equipment
+        WHERE id = ANY($1)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+        &equipment_ids
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    // Pings can arrive late, so each crossing is judged against the stay
+    // covering the day it happened rather than today's location
+    let stays = sqlx::query!(
+        "SELECT equipment_id, site_id, deployed_from, deployed_until FROM deployments WHERE equipment_id = ANY($1)",
+        &equipment_ids
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut alerts = 0;
+    for machine in machines {
+        let Some(machine_positions) = positions.get(&machine.id) else {
+            continue;
+        };
+        let mut presence: HashMap<i32, Presence> = sqlx::query!(
+            "SELECT geofence_id, inside, observed_at FROM geofence_presence WHERE equipment_id = $1",
+            machine.id
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.geofence_id, Presence { inside: row.inside, observed_at: row.observed_at }))
+        .collect();
+
+        let crossings = track(&fences, &mut presence, machine_positions);
+
+        for (geofence_id, state) in &presence {
+            sqlx::query!(
+                r#"
+                INSERT INTO geofence_presence (geofence_id, equipment_id, inside, observed_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (geofence_id, equipment_id)
+                DO UPDATE SET inside = EXCLUDED.inside, observed_at = EXCLUDED.observed_at
+                "#,
+                geofence_id,
+                machine.id,
+                state.inside,
+                state.observed_at
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        let mut alerted = HashSet::new();
+        for crossing in crossings {
+            let Some(fence) = fences.iter().find(|f| f.id == crossing.geofence_id) else {
+                continue;
+            };
+            // Moving between overlapping zones of the site is not leaving it
+            let off_site = !fences
+                .iter()
+                .any(|f| f.site_id == fence.site_id && f.contains(crossing.latitude, crossing.longitude));
+            let day = crossing.occurred_at.with_timezone(&tz).date_naive();
+            let deployed_there = stays.iter().any(|stay| {
+                stay.equipment_id == machine.id
+                    && stay.site_id == fence.site_id
+                    && stay.deployed_from <= day
+                    && stay.deployed_until.is_none_or(|until| until > day)
+            });
+            let alert = crossing.kind == "exit"
+                && off_site
+                && deployed_there
+                && !is_working_time(crossing.occurred_at, tz, config)
+                && alerted.insert((fence.site_id, crossing.occurred_at));
+            sqlx::query!(
+                r#"
+                INSERT INTO geofence_events (geofence_id, equipment_id, kind, occurred_at, latitude, longitude, alert)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                crossing.geofence_id,
+                machine.id,
+                crossing.kind,
+                crossing.occurred_at,
+                crossing.latitude,
+                crossing.longitude,
+                alert
+            )
+            .execute(&mut *conn)
+            .await?;
+            if alert {
+                let payload = json!({
+                    "equipment_id": machine.id,
+                    "name": machine.name,
+                    "serial_number": machine.serial_number,
+                    "site_id": fence.site_id,
+                    "site_name": fence.site_name,
+                    "geofence": fence.name,
+                    "occurred_at": crossing.occurred_at,
+                    "latitude": crossing.latitude,
+                    "longitude": crossing.longitude,
+                });
+                webhooks::enqueue(&mut *conn, "geofence.after_hours_exit", payload, None).await?;
+                alerts += 1;
+            }
+        }
+    }
+    Ok(alerts)
+}
+
+/// A site's arrivals and departures, newest first.
+pub async fn site_events(pool: &PgPool, site_id: i32, limit: i64) -> Result<Vec<GeofenceEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        GeofenceEvent,
+        r#"
+        SELECT ev.id, ev.geofence_id, g.name as geofence_name, g.site_id, s.name as site_name,
+            ev.equipment_id, e.name as equipment_name, e.serial_number,
+            ev.kind, ev.occurred_at, ev.latitude, ev.longitude, ev.alert
+        FROM geofence_events ev
+        JOIN geofences g ON g.id = ev.geofence_id
+        JOIN sites s ON s.id = g.site_id
+        JOIN equipment e ON e.id = ev.equipment_id
+        WHERE g.site_id = $1
+        ORDER BY ev.occurred_at DESC, ev.id DESC
+        LIMIT $2
+        "#,
+        site_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// After-hours departures in the last `days` days, newest first.
+pub async fn recent_alerts(pool: &PgPool, days: i32, limit: i64) -> Result<Vec<GeofenceEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        GeofenceEvent,
+        r#"
+        SELECT ev.id, ev.geofence_id, g.name as geofence_name, g.site_id, s.name as site_name,
+            ev.equipment_id, e.name as equipment_name, e.serial_number,
+            ev.kind, ev.occurred_at, ev.latitude, ev.longitude, ev.alert
+        FROM geofence_events ev
+        JOIN geofences g ON g.id = ev.geofence_id
+        JOIN sites s ON s.id = g.site_id
+        JOIN equipment e ON e.id = ev.equipment_id
+        WHERE ev.alert AND ev.occurred_at >= NOW() - make_interval(days => $1)
+        ORDER BY ev.occurred_at DESC
+        LIMIT $2
+        "#,
+        days,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
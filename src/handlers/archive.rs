@@ -0,0 +1,84 @@
+use crate::archive::{self, RestoreMode};
+use crate::AppState;
+use axum::{
+    extract::{Extension, Multipart},
+    http::header,
+    response::{Html, IntoResponse, Response},
+};
+use chrono::Utc;
+use log::{error, info};
+use std::sync::Arc;
+
+// INDEX
+pub async fn index(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Serving archive page");
+    state.render("archive/index.html", &tera::Context::new())
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// EXPORT
+pub async fn export(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Response, String> {
+    info!("Exporting fleet archive");
+
+    let archive = archive::export(&state.db).await.map_err(|e| {
+        error!("Archive export failed: {}", e);
+        e.to_string()
+    })?;
+    let body = serde_json::to_vec_pretty(&archive).map_err(|e| e.to_string())?;
+    let filename = format!("kfleet-archive-{}.json", Utc::now().format("%Y-%m-%d"));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+// RESTORE
+pub async fn restore(
+    Extension(state): Extension<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Html<String>, String> {
+    info!("Restoring fleet archive");
+
+    let mut data = None;
+    let mut mode = RestoreMode::Merge;
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        match field.name().unwrap_or_default().to_string().as_str() {
+            "file" => data = Some(field.bytes().await.map_err(|e| e.to_string())?),
+            "mode" if field.text().await.map_err(|e| e.to_string())? == "replace" => {
+                mode = RestoreMode::Replace;
+            }
+            _ => {}
+        }
+    }
+    let data = data.ok_or("No archive uploaded")?;
+
+    let archive = archive::parse(&data)?;
+    let summary = archive::restore(&state.db, &archive, mode).await.map_err(|e| {
+        error!("Archive restore failed: {}", e);
+        e
+    })?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("summary", &summary);
+    ctx.insert("exported_at", &archive.exported_at);
+    ctx.insert("flash", &serde_json::json!({
+        "type": "success",
+        "message": "Archive restored successfully",
+    }));
+    state.render("archive/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -0,0 +1,78 @@
+use crate::assignments::{self, HistoryFilter};
+use crate::AppState;
+use axum::{
+    extract::{Extension, Path, Query},
+    response::Html,
+};
+use log::{error, info};
+use std::sync::Arc;
+
+// OPERATOR HISTORY
+pub async fn staff_history(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Query(filter): Query<HistoryFilter>,
+) -> Result<Html<String>, String> {
+    info!("Showing assignment history of staff {}", id);
+
+    let name = sqlx::query_scalar!("SELECT full_name FROM staff WHERE id = $1", id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Staff {} not found", id))?;
+    render_history(&state, "staff", id, &name, &filter).await
+}
+
+// EQUIPMENT HISTORY
+pub async fn equipment_history(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Query(filter): Query<HistoryFilter>,
+) -> Result<Html<String>, String> {
+    info!("Showing operator history of equipment {}", id);
+
+    let name = sqlx::query_scalar!("SELECT name FROM equipment WHERE id = $1", id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Equipment {} not found", id))?;
+    render_history(&state, "equipment", id, &name, &filter).await
+}
+
+// Helper functions
+/// One history page serves both sides; `subject` is "staff" or "equipment".
+async fn render_history(
+    state: &AppState,
+    subject: &str,
+    id: i32,
+    name: &str,
+    filter: &HistoryFilter,
+) -> Result<Html<String>, String> {
+    let (operator_id, equipment_id) = match subject {
+        "staff" => (Some(id), None),
+        _ => (None, Some(id)),
+    };
+    let history = assignments::history(
+        &state.db,
+        operator_id,
+        equipment_id,
+        filter.date,
+        &state.config.locale.timezone,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch assignment history: {}", e);
+        e.to_string()
+    })?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("subject", subject);
+    ctx.insert("id", &id);
+    ctx.insert("name", name);
+    ctx.insert("history", &history);
+    ctx.insert("filter", filter);
+    ctx.insert("timezone", &state.config.locale.timezone);
+    state.render("assignments/history.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
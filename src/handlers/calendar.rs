@@ -0,0 +1,42 @@
+use crate::{calendar, users, AppState};
+use axum::{
+    extract::{Extension, Path},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use log::{error, info, warn};
+use std::sync::Arc;
+
+// FEED
+pub async fn feed(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    let user = users::find_by_calendar_token(&state.db, &token)
+        .await
+        .map_err(|e| {
+            error!("Calendar token lookup failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+    let Some(user) = user else {
+        warn!("Calendar feed requested with an unknown or revoked token");
+        return Err((StatusCode::NOT_FOUND, "Page not found".to_string()));
+    };
+    info!("Serving calendar feed for {}", user.email);
+
+    let body = calendar::fleet_feed(&state.db, &state.config)
+        .await
+        .map_err(|e| {
+            error!("Failed to build calendar feed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CACHE_CONTROL, "private, max-age=300"),
+        ],
+        body,
+    )
+        .into_response())
+}
@@ -1,11 +1,14 @@
+use crate::config::DepreciationMethod;
+use crate::export::{self, ExportQuery, Sheet};
+use crate::valuation::{Terms, METHODS};
 use crate::AppState;
 use axum::{
-    extract::{Extension, Form, Path},
-    response::{Html, Redirect},
+    extract::{Extension, Form, Path, Query},
+    response::{Html, Redirect, Response},
 };
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, PgPool};
 use std::sync::Arc;
 
 #[derive(Debug, FromRow, Serialize)]
@@ -13,11 +16,31 @@ pub struct Category {
     pub id: i32,
     pub name: String,
     pub equipment_count: i64,
+    pub default_salvage_percent: Option<f64>,
+    pub default_useful_life_years: Option<i32>,
+    pub default_depreciation_method: Option<String>,
 }
 
+/// A category's name and the depreciation terms its machines default to.
 #[derive(Debug, Deserialize)]
 pub struct CategoryForm {
     pub name: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub default_salvage_percent: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub default_useful_life_years: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub default_depreciation_method: Option<DepreciationMethod>,
+}
+
+impl CategoryForm {
+    pub fn terms(&self) -> Terms {
+        Terms::category(
+            self.default_salvage_percent,
+            self.default_useful_life_years,
+            self.default_depreciation_method,
+        )
+    }
 }
 
 // CREATE
@@ -26,10 +49,17 @@ pub async fn create(
     Form(form): Form<CategoryForm>,
 ) -> Result<Redirect, String> {
     info!("Creating new category: {}", form.name);
+    form.terms().validate(None)?;
     
     sqlx::query!(
-        "INSERT INTO categories (name) VALUES ($1)",
-        form.name
+        r#"
+        INSERT INTO categories (name, default_salvage_percent, default_useful_life_years, default_depreciation_method)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        form.name,
+        form.default_salvage_percent,
+        form.default_useful_life_years,
+        form.default_depreciation_method.map(|m| m.as_str())
     )
     .execute(&state.db)
     .await
@@ -48,39 +78,57 @@ pub async fn list(
 ) -> Result<Html<String>, String> {
     info!("Listing categories");
     
-    let categories = sqlx::query_as!(
-        Category,
-        r#"
-        SELECT 
-            c.id, 
-            c.name, 
-            COUNT(e.id) as "equipment_count!: i64"
-        FROM categories c
-        LEFT JOIN equipment e ON e.category_id = c.id
-        GROUP BY c.id, c.name
-        ORDER BY c.name
-        "#
-    )
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| {
-        warn!("Failed to fetch categories: {}", e);
-        e.to_string()
-    })?;
+    let categories = fetch_categories(&state.db).await?;
 
     let mut ctx = tera::Context::new();
     ctx.insert("categories", &categories);
-    state.templates.render("categories/index.html", &ctx)
+    ctx.insert("methods", METHODS);
+    state.render("categories/index.html", &ctx)
         .map_err(|e| e.to_string())
         .map(Html)
 }
 
+const CATEGORY_EXPORT_HEADERS: &[&str] = &[
+    "ID",
+    "Name",
+    "Equipment Count",
+    "Default Salvage %",
+    "Default Useful Life (years)",
+    "Default Depreciation Method",
+];
+
+// EXPORT
+pub async fn export(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+) -> Result<Response, String> {
+    info!("Exporting categories as {:?}", format);
+
+    let categories = fetch_categories(&state.db).await?;
+
+    let mut sheet = Sheet::new("Categories", CATEGORY_EXPORT_HEADERS);
+    for category in categories {
+        sheet.push(vec![
+            category.id.into(),
+            category.name.into(),
+            category.equipment_count.into(),
+            category.default_salvage_percent.into(),
+            category.default_useful_life_years.into(),
+            category.default_depreciation_method.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, "categories")
+}
+
 // NEW FORM
 pub async fn new_form(
     Extension(state): Extension<Arc<AppState>>,
 ) -> Result<Html<String>, String> {
     info!("Serving new category form");
-    state.templates.render("categories/new.html", &tera::Context::new())
+    let mut ctx = tera::Context::new();
+    ctx.insert("methods", METHODS);
+    state.render("categories/new.html", &ctx)
         .map_err(|e| e.to_string())
         .map(Html)
 }
@@ -97,11 +145,17 @@ pub async fn edit_form(
     struct CategoryRecord {
         id: i32,
         name: String,
+        default_salvage_percent: Option<f64>,
+        default_useful_life_years: Option<i32>,
+        default_depreciation_method: Option<String>,
     }
     
     let category = sqlx::query_as!(
         CategoryRecord,
-        "SELECT id, name FROM categories WHERE id = $1",
+        r#"
+        SELECT id, name, default_salvage_percent, default_useful_life_years, default_depreciation_method
+        FROM categories WHERE id = $1
+        "#,
         id
     )
     .fetch_one(&state.db)
@@ -113,7 +167,8 @@ pub async fn edit_form(
 
     let mut ctx = tera::Context::new();
     ctx.insert("category", &category);
-    state.templates.render("categories/edit.html", &ctx)
+    ctx.insert("methods", METHODS);
+    state.render("categories/edit.html", &ctx)
         .map_err(|e| e.to_string())
         .map(Html)
 }
@@ -125,10 +180,21 @@ pub async fn update(
     Form(form): Form<CategoryForm>,
 ) -> Result<Redirect, String> {
     info!("Updating category ID: {}", id);
+    form.terms().validate(None)?;
     
     sqlx::query!(
-        "UPDATE categories SET name = $1 WHERE id = $2",
+        r#"
+        UPDATE categories SET
+            name = $1,
+            default_salvage_percent = $2,
+            default_useful_life_years = $3,
+            default_depreciation_method = $4
+        WHERE id = $5
+        "#,
         form.name,
+        form.default_salvage_percent,
+        form.default_useful_life_years,
+        form.default_depreciation_method.map(|m| m.as_str()),
         id
     )
     .execute(&state.db)
@@ -181,3 +247,29 @@ pub async fn delete(
     info!("Category {} deleted", id);
     Ok(Redirect::to("/categories"))
 }
+
+// Helper functions
+pub async fn fetch_categories(pool: &PgPool) -> Result<Vec<Category>, String> {
+    sqlx::query_as!(
+        Category,
+        r#"
+        SELECT 
+            c.id, 
+            c.name, 
+            COUNT(e.id) as "equipment_count!: i64",
+            c.default_salvage_percent,
+            c.default_useful_life_years,
+            c.default_depreciation_method
+        FROM categories c
+        LEFT JOIN equipment e ON e.category_id = c.id
+        GROUP BY c.id, c.name
+        ORDER BY c.name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        warn!("Failed to fetch categories: {}", e);
+        e.to_string()
+    })
+}
@@ -0,0 +1,277 @@
+use crate::certifications::{self, CertificationForm, DOCUMENT_TYPES, LICENSE_TYPES};
+use crate::handlers::categories::fetch_categories;
+use crate::uploads;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Multipart, Path},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::Form; // Repeated category_ids checkboxes
+use log::{error, info, warn};
+use std::sync::Arc;
+
+// LIST
+pub async fn list(
+    Path(staff_id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Listing certifications of staff {}", staff_id);
+    render_list(&state, staff_id, None, None).await
+}
+
+// CREATE
+pub async fn create(
+    Path(staff_id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<CertificationForm>,
+) -> Result<Response, String> {
+    info!("Adding {} certification for staff {}", form.license_type, staff_id);
+
+    if let Err(message) = form.validate() {
+        return render_list(&state, staff_id, Some(&form), Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let id = certifications::create_certification(&mut tx, staff_id, &form)
+        .await
+        .map_err(|e| {
+            error!("Certification creation failed: {}", e);
+            e.to_string()
+        })?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    info!("Certification {} created", id);
+    Ok(Redirect::to(&format!("/staff/{}/certifications", staff_id)).into_response())
+}
+
+// EDIT FORM
+pub async fn edit_form(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Editing certification {}", id);
+    render_edit(&state, id, None, None).await
+}
+
+// UPDATE
+pub async fn update(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<CertificationForm>,
+) -> Result<Response, String> {
+    info!("Updating certification {}", id);
+
+    if let Err(message) = form.validate() {
+        return render_edit(&state, id, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let certification = certifications::get_certification(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Certification {} not found", id))?;
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let updated = certifications::update_certification(&mut tx, id, &form)
+        .await
+        .map_err(|e| {
+            error!("Certification {} update failed: {}", id, e);
+            e.to_string()
+        })?;
+    if !updated {
+        return Err(format!("Certification {} not found", id));
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/staff/{}/certifications", certification.staff_id)).into_response())
+}
+
+// DELETE
+pub async fn delete(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Redirect, String> {
+    info!("Deleting certification {}", id);
+
+    let (staff_id, stored_name) = certifications::delete_certification(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Certification {} not found", id))?;
+
+    if let Some(stored_name) = stored_name {
+        let path = certifications::document_dir(&state.config.paths.uploads).join(stored_name);
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!("Could not remove {}: {}", path.display(), e);
+        }
+    }
+
+    Ok(Redirect::to(&format!("/staff/{}/certifications", staff_id)))
+}
+
+// UPLOAD DOCUMENT
+pub async fn upload_document(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Response, String> {
+    info!("Uploading scan for certification {}", id);
+
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        if field.name() == Some("file") {
+            let filename = uploads::sanitize_filename(field.file_name().unwrap_or_default(), "certificate");
+            let data = field.bytes().await.map_err(|e| e.to_string())?;
+            upload = Some((filename, data));
+        }
+    }
+    let Some((filename, data)) = upload.filter(|(_, data)| !data.is_empty()) else {
+        let message = "Choose a file to upload".to_string();
+        return render_edit(&state, id, None, Some(message)).await.map(IntoResponse::into_response);
+    };
+    let Some((content_type, ext)) = uploads::sniff(&data, DOCUMENT_TYPES) else {
+        let message = format!("'{}' is not a PDF, JPEG or PNG file", filename);
+        return render_edit(&state, id, None, Some(message)).await.map(IntoResponse::into_response);
+    };
+
+    let dir = certifications::document_dir(&state.config.paths.uploads);
+    let stored_name = uploads::stored_name(id, ext);
+    uploads::save(&dir, &stored_name, &data)
+        .await
+        .map_err(|e| {
+            error!("Failed to store certificate scan: {}", e);
+            e.to_string()
+        })?;
+
+    let replaced = match certifications::set_document(&state.db, id, &filename, &stored_name, content_type).await {
+        Ok(Some(replaced)) => replaced,
+        result => {
+            // Don't leave an orphan file behind (e.g. the certification was deleted)
+            let _ = tokio::fs::remove_file(dir.join(&stored_name)).await;
+            return match result {
+                Err(e) => Err(e.to_string()),
+                _ => Err(format!("Certification {} not found", id)),
+            };
+        }
+    };
+    if let Some(replaced) = replaced {
+        let _ = tokio::fs::remove_file(dir.join(replaced)).await;
+    }
+
+    Ok(Redirect::to(&format!("/certifications/{}/edit", id)).into_response())
+}
+
+// DOWNLOAD DOCUMENT
+pub async fn download_document(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Response, (StatusCode, String)> {
+    let certification = certifications::get_certification(&state.db, id)
+        .await
+        .map_err(|e| {
+            error!("Certification lookup failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?
+        .ok_or((StatusCode::NOT_FOUND, "Page not found".to_string()))?;
+    let (Some(filename), Some(stored_name), Some(content_type)) = (
+        certification.document_filename,
+        certification.document_stored_name,
+        certification.document_content_type,
+    ) else {
+        return Err((StatusCode::NOT_FOUND, "Page not found".to_string()));
+    };
+    info!("Serving certificate scan {}", stored_name);
+
+    let path = certifications::document_dir(&state.config.paths.uploads).join(&stored_name);
+    let body = tokio::fs::read(&path).await.map_err(|e| {
+        error!("Cannot read {}: {}", path.display(), e);
+        (StatusCode::NOT_FOUND, "Page not found".to_string())
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", uploads::sanitize_filename(&filename, "certificate")),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+// Helper functions
+/// The staff member's certifications with the add form; `submitted` refills
+/// it after a validation error.
+async fn render_list(
+    state: &AppState,
+    staff_id: i32,
+    submitted: Option<&CertificationForm>,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let full_name = certifications::staff_name(&state.db, staff_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Staff {} not found", staff_id))?;
+    let list = certifications::list_for_staff(&state.db, staff_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let categories = fetch_categories(&state.db).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("staff_id", &staff_id);
+    ctx.insert("full_name", &full_name);
+    ctx.insert("certifications", &list);
+    ctx.insert("categories", &categories);
+    ctx.insert("license_types", LICENSE_TYPES);
+    ctx.insert("reminder_days", &state.config.reminders.certification_days);
+    if let Some(form) = submitted {
+        ctx.insert("certification", form);
+        ctx.insert("selected", &form.category_ids);
+    } else {
+        ctx.insert("selected", &Vec::<i32>::new());
+    }
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("certifications/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// The edit form; `submitted` refills it after a validation error.
+async fn render_edit(
+    state: &AppState,
+    id: i32,
+    submitted: Option<&CertificationForm>,
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let certification = certifications::get_certification(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Certification {} not found", id))?;
+    let full_name = certifications::staff_name(&state.db, certification.staff_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    let categories = fetch_categories(&state.db).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("full_name", &full_name);
+    ctx.insert("categories", &categories);
+    ctx.insert("license_types", LICENSE_TYPES);
+    ctx.insert("record", &certification);
+    if let Some(form) = submitted {
+        ctx.insert("certification", form);
+        ctx.insert("selected", &form.category_ids);
+    } else {
+        ctx.insert("certification", &certification);
+        ctx.insert("selected", &certification.category_ids);
+    }
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("certifications/edit.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -0,0 +1,277 @@
+use crate::claims::{self, ClaimFilter, ClaimForm, ClaimStatusForm, CLAIM_STATUSES};
+use crate::export::{self, ExportQuery, Sheet};
+use crate::handlers::equipment::{fetch_equipment, EquipmentFilter};
+use crate::insurance;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Path, Query},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Preselects the policy and machine when filing from a policy page.
+#[derive(Debug, Default, Deserialize)]
+pub struct NewClaimQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub policy_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub equipment_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkRepairForm {
+    pub maintenance_id: i32,
+}
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(filter): Query<ClaimFilter>,
+) -> Result<Html<String>, String> {
+    info!("Listing insurance claims");
+
+    let claims = claims::list_claims(&state.db, &filter)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch claims: {}", e);
+            e.to_string()
+        })?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("claims", &claims);
+    ctx.insert("filter", &filter);
+    ctx.insert("statuses", CLAIM_STATUSES);
+    state.render("claims/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// NEW FORM
+pub async fn new_form(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<NewClaimQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving new claim form");
+    render_form(&state, &query, None, None).await
+}
+
+// CREATE
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<ClaimForm>,
+) -> Result<Response, String> {
+    info!("Filing claim {} on policy {}", form.claim_number, form.policy_id);
+
+    let query = NewClaimQuery { policy_id: Some(form.policy_id), equipment_id: Some(form.equipment_id) };
+    if let Err(message) = form.validate() {
+        return render_form(&state, &query, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+    let problem = claims::coverage_problem(&state.db, &form)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(message) = problem {
+        return render_form(&state, &query, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let id = claims::create_claim(&state.db, &form)
+        .await
+        .map_err(|e| {
+            error!("Claim creation failed: {}", e);
+            e.to_string()
+        })?;
+
+    info!("Claim {} filed", id);
+    Ok(Redirect::to(&format!("/claims/{}", id)).into_response())
+}
+
+// SHOW
+pub async fn show(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Showing claim {}", id);
+    render_show(&state, id, None).await
+}
+
+// UPDATE STATUS
+pub async fn update_status(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<ClaimStatusForm>,
+) -> Result<Response, String> {
+    info!("Moving claim {} to {}", id, form.status);
+
+    let claim = claims::get_claim(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Claim {} not found", id))?;
+    if let Err(message) = claims::check_transition(&claim.status, &form.status, form.paid_amount) {
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    let updated = claims::update_status(&state.db, id, &form)
+        .await
+        .map_err(|e| {
+            error!("Claim {} status update failed: {}", id, e);
+            e.to_string()
+        })?;
+    if !updated {
+        warn!("Claim {} was closed concurrently", id);
+        let message = "The claim was closed in the meantime".to_string();
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    Ok(Redirect::to(&format!("/claims/{}", id)).into_response())
+}
+
+// LINK REPAIR
+pub async fn link_repair(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<LinkRepairForm>,
+) -> Result<Response, String> {
+    info!("Linking maintenance record {} to claim {}", form.maintenance_id, id);
+
+    let linked = claims::link_repair(&state.db, id, form.maintenance_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !linked {
+        let message = format!("Maintenance record {} cannot be linked to this claim", form.maintenance_id);
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    Ok(Redirect::to(&format!("/claims/{}", id)).into_response())
+}
+
+// UNLINK REPAIR
+pub async fn unlink_repair(
+    Path((id, maintenance_id)): Path<(i32, i32)>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Redirect, String> {
+    info!("Unlinking maintenance record {} from claim {}", maintenance_id, id);
+
+    claims::unlink_repair(&state.db, id, maintenance_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/claims/{}", id)))
+}
+
+// REPORT
+pub async fn report(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Serving outstanding claims report");
+
+    let insurers = claims::outstanding_by_insurer(&state.db)
+        .await
+        .map_err(|e| {
+            error!("Failed to build claims report: {}", e);
+            e.to_string()
+        })?;
+    let total_claimed: f64 = insurers.iter().map(|i| i.claimed_amount).sum();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("insurers", &insurers);
+    ctx.insert("total_claimed", &total_claimed);
+    state.render("claims/report.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+const REPORT_EXPORT_HEADERS: &[&str] = &[
+    "Insurer",
+    "Open Claims",
+    "Under Review",
+    "Claimed Amount",
+    "Linked Repair Cost",
+    "Oldest Filed",
+    "Settled Ratio",
+];
+
+// REPORT EXPORT
+pub async fn export_report(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+) -> Result<Response, String> {
+    info!("Exporting outstanding claims report as {:?}", format);
+
+    let insurers = claims::outstanding_by_insurer(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut sheet = Sheet::new("Outstanding Claims", REPORT_EXPORT_HEADERS);
+    for i in insurers {
+        sheet.push(vec![
+            i.insurer.into(),
+            i.open_claims.into(),
+            i.under_review.into(),
+            i.claimed_amount.into(),
+            i.repair_cost.into(),
+            i.oldest_filed.into(),
+            i.settled_ratio.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, "outstanding-claims")
+}
+
+// Helper functions
+async fn render_show(
+    state: &AppState,
+    id: i32,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let claim = claims::get_claim(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Claim {} not found", id))?;
+    let repairs = claims::linked_repairs(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let linkable = claims::linkable_repairs(&state.db, &claim)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("claim", &claim);
+    ctx.insert("repairs", &repairs);
+    ctx.insert("linkable", &linkable);
+    ctx.insert("statuses", CLAIM_STATUSES);
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("claims/show.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// The new claim form; `submitted` refills it after a validation error.
+async fn render_form(
+    state: &AppState,
+    query: &NewClaimQuery,
+    submitted: Option<&ClaimForm>,
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let policies = insurance::list_policies(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let equipment = fetch_equipment(&state.db, &EquipmentFilter::default()).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("policies", &policies);
+    ctx.insert("equipment", &equipment);
+    ctx.insert("policy_id", &query.policy_id);
+    ctx.insert("equipment_id", &query.equipment_id);
+    if let Some(form) = submitted {
+        ctx.insert("claim", form);
+    }
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("claims/form.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -0,0 +1,258 @@
+use crate::costs::{self, CostQuery, CostTotals, ExpenseForm, EXPENSE_KINDS, INDICATORS, PERIODS};
+use crate::export::{self, ExportQuery, Sheet};
+use crate::handlers::categories::fetch_categories;
+use crate::utilization;
+use crate::valuation::METHODS;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Path, Query},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use chrono::{Months, NaiveDate, Utc};
+use log::{error, info, warn};
+use std::sync::Arc;
+
+// REPORT
+pub async fn report(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<CostQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving cost of ownership report for category {:?}", query.category_id);
+
+    let categories = fetch_categories(&state.db).await?;
+    let mut ctx = tera::Context::new();
+    let mut period = query.period.clone().unwrap_or_else(|| "12m".to_string());
+    let (from, to) = match range(&state, &period, query.from, query.to).await {
+        Ok(range) => range,
+        Err(message) => {
+            ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+            period = "12m".to_string();
+            range(&state, &period, None, None).await?
+        }
+    };
+    let machines = fleet_costs(&state, from, to, query.category_id, None).await?;
+
+    ctx.insert("categories", &categories);
+    ctx.insert("category_id", &query.category_id);
+    ctx.insert("period", &period);
+    ctx.insert("periods", PERIODS);
+    ctx.insert("from", &from);
+    ctx.insert("to", &to);
+    ctx.insert("totals", &CostTotals::of(&machines));
+    ctx.insert("by_category", &costs::by_category(&machines));
+    ctx.insert("machines", &machines);
+    ctx.insert("indicators", INDICATORS);
+    ctx.insert("currency", &state.config.locale.currency);
+    state.render("costs/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+const COST_EXPORT_HEADERS: &[&str] = &[
+    "Equipment",
+    "Serial Number",
+    "Category",
+    "Purchase Cost",
+    "Depreciation",
+    "Maintenance",
+    "Fuel",
+    "Other Expenses",
+    "Insurance",
+    "Total Cost",
+    "Engine Hours",
+    "Cost per Hour",
+    "Book Value",
+    "Repairs (12 months)",
+    "Indicator",
+];
+
+// REPORT EXPORT
+pub async fn export_report(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<CostQuery>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+) -> Result<Response, String> {
+    info!("Exporting cost of ownership report as {:?}", format);
+
+    let period = query.period.as_deref().unwrap_or("12m");
+    let (from, to) = range(&state, period, query.from, query.to).await?;
+    let machines = fleet_costs(&state, from, to, query.category_id, None).await?;
+
+    let mut sheet = Sheet::new("Cost of Ownership", COST_EXPORT_HEADERS);
+    for m in machines {
+        let indicator = INDICATORS
+            .iter()
+            .find(|(key, _)| *key == m.indicator)
+            .map(|(_, label)| *label)
+            .unwrap_or(m.indicator);
+        sheet.push(vec![
+            m.name.into(),
+            m.serial_number.into(),
+            m.category_name.into(),
+            m.purchase_cost.into(),
+            m.depreciation.into(),
+            m.maintenance.into(),
+            m.fuel.into(),
+            m.expenses.into(),
+            m.insurance.into(),
+            m.tco.into(),
+            m.engine_hours.into(),
+            m.cost_per_hour.into(),
+            m.book_value.into(),
+            m.recent_repairs.into(),
+            indicator.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, &format!("cost-of-ownership-{}-{}", from, to))
+}
+
+// EQUIPMENT COSTS
+pub async fn equipment(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Showing costs of equipment {}", id);
+    render_equipment(&state, id, None, None).await
+}
+
+// CREATE EXPENSE
+pub async fn create_expense(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<ExpenseForm>,
+) -> Result<Response, String> {
+    info!("Recording {} expense of {} for equipment {}", form.kind, form.amount, id);
+
+    if let Err(message) = form.validate(today(&state)) {
+        return render_equipment(&state, id, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+    costs::create_expense(&state.db, id, &form)
+        .await
+        .map_err(|e| {
+            error!("Expense creation failed: {}", e);
+            e.to_string()
+        })?;
+
+    Ok(Redirect::to(&format!("/equipment/{}/costs", id)).into_response())
+}
+
+// DELETE EXPENSE
+pub async fn delete_expense(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Redirect, String> {
+    info!("Deleting expense {}", id);
+
+    let equipment_id = costs::delete_expense(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            warn!("Expense {} not found", id);
+            format!("Expense {} not found", id)
+        })?;
+
+    Ok(Redirect::to(&format!("/equipment/{}/costs", equipment_id)))
+}
+
+// Helper functions
+fn today(state: &AppState) -> NaiveDate {
+    Utc::now().with_timezone(&state.config.locale.tz()).date_naive()
+}
+
+async fn range(
+    state: &AppState,
+    period: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let today = today(state);
+    let earliest = costs::earliest_acquisition(&state.db, &state.config.locale.timezone)
+        .await
+        .map_err(|e| e.to_string())?
+        .unwrap_or(today);
+    costs::period_range(period, from, to, today, earliest)
+}
+
+/// Costs of the fleet, or of one machine, with the engine hours its meters
+/// recorded.
+async fn fleet_costs(
+    state: &AppState,
+    from: NaiveDate,
+    to: NaiveDate,
+    category_id: Option<i32>,
+    equipment_id: Option<i32>,
+) -> Result<Vec<costs::MachineCost>, String> {
+    let timezone = &state.config.locale.timezone;
+    let rows = costs::cost_rows(&state.db, from, to, timezone, category_id, equipment_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch costs: {}", e);
+            e.to_string()
+        })?;
+    let category_id = category_id.or_else(|| equipment_id.and(rows.first().map(|r| r.category_id)));
+    let fleet = utilization::load(&state.db, from, to, timezone, category_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let hours = fleet.hours.get(&row.equipment_id);
+            costs::machine_cost(row, hours, from, to, &state.config.costs)
+        })
+        .collect())
+}
+
+/// The machine's costs over the last 12 months and since it was acquired,
+/// with its expenses; `submitted` refills the form after a validation error.
+async fn render_equipment(
+    state: &AppState,
+    id: i32,
+    submitted: Option<&ExpenseForm>,
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let today = today(state);
+    let year_ago = today.checked_sub_months(Months::new(12)).unwrap_or(today) + chrono::Duration::days(1);
+    let year = fleet_costs(state, year_ago, today, None, Some(id))
+        .await?
+        .pop()
+        .ok_or_else(|| format!("Equipment {} not found", id))?;
+    let lifetime = fleet_costs(state, year.acquired.min(today), today, None, Some(id))
+        .await?
+        .pop()
+        .ok_or_else(|| format!("Equipment {} not found", id))?;
+    let expenses = costs::equipment_expenses(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    if let Some(form) = submitted {
+        ctx.insert("expense", form);
+    } else {
+        ctx.insert(
+            "expense",
+            &serde_json::json!({
+                "expense_date": today,
+                "kind": "fuel",
+                "amount": "",
+                "quantity": null,
+                "description": null,
+            }),
+        );
+    }
+    ctx.insert("year", &year);
+    ctx.insert("lifetime", &lifetime);
+    ctx.insert("expenses", &expenses);
+    ctx.insert("kinds", EXPENSE_KINDS);
+    ctx.insert("indicators", INDICATORS);
+    ctx.insert("replace_ratio", &state.config.costs.replace_ratio);
+    ctx.insert("methods", METHODS);
+    ctx.insert("currency", &state.config.locale.currency);
+    ctx.insert("today", &today);
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("costs/equipment.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
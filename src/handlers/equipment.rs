@@ -1,10 +1,18 @@
 use crate::AppState;
+use crate::assignments::{self, Violation};
+use crate::config::DepreciationMethod;
+use crate::export::{self, ExportQuery, Sheet};
+use crate::sites::{self, MoveForm};
+use crate::telematics;
+use crate::transfers;
+use crate::valuation::{Terms, METHODS};
+use crate::webhooks;
 use axum::{
-    extract::{Extension, Form, Path},
-    response::{Html, Redirect},
+    extract::{Extension, Form, Path, Query, RawQuery},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 //use chrono::{DateTime, Utc};
-use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use log::{error, info, warn};
 use serde::Deserialize;
 use serde::Serialize;
@@ -21,10 +29,22 @@ pub struct Equipment {
     pub acquisition_date: DateTime<Utc>,
     pub category_id: i32,
     pub category_name: String,
-    pub insurance_renewal: Option<DateTime<Utc>>,
+    /// End of the latest policy in force; `None` when uninsured
+    pub covered_until: Option<NaiveDate>,
+    /// Site the machine is on today; `None` when it is at the yard
+    pub site_id: Option<i32>,
+    pub site_name: Option<String>,
     pub next_maintenance: Option<DateTime<Utc>>,
     pub fuel_capacity: Option<f64>,
+    pub purchase_cost: Option<f64>,
+    pub salvage_value: Option<f64>,
+    pub useful_life_years: Option<i32>,
+    pub depreciation_method: Option<String>,
     pub status: String,
+    /// Latest telematics ping and position; `None` without a reporting unit
+    pub last_ping_at: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
 }
 
 #[derive(Debug, FromRow, Serialize)]
@@ -42,6 +62,25 @@ pub struct Category {
     pub name: String,
 }
 
+/// The telematics unit's own ID, for units that do not report the serial
+/// number; empty clears it.
+#[derive(Debug, Deserialize)]
+pub struct DeviceForm {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub device_id: Option<String>,
+}
+
+/// Filters shared by the equipment list and its exports.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EquipmentFilter {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub category_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub q: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EquipmentForm {
     pub name: String,
@@ -50,13 +89,32 @@ pub struct EquipmentForm {
     pub serial_number: String,
     pub acquisition_date: String,
     pub category_id: i32,
-    pub insurance_renewal: Option<String>,
     pub next_maintenance: Option<String>,
     pub fuel_capacity: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub purchase_cost: Option<f64>,
+    /// Depreciation terms; blank ones come from the category
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub salvage_value: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub useful_life_years: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub depreciation_method: Option<DepreciationMethod>,
     pub status: String,
     pub timezone_offset: Option<i32>,  
 }
 
+impl EquipmentForm {
+    pub fn terms(&self) -> Terms {
+        Terms::machine(self.salvage_value, self.useful_life_years, self.depreciation_method)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OperatorForm {
+    pub operator_id: i32,
+}
+
 // CREATE
 pub async fn create(
     Extension(state): Extension<Arc<AppState>>,
@@ -67,17 +125,20 @@ pub async fn create(
     // Get timezone offset from form (default to UTC)
     let tz_offset = form.timezone_offset.unwrap_or(0);
     
+    let mut payload = equipment_payload(&form);
+    form.terms().validate(form.purchase_cost)?;
     let acquisition_date = parse_timestamptz(&form.acquisition_date, tz_offset)?;
-    let insurance_renewal = parse_optional_timestamptz(form.insurance_renewal, tz_offset)?;
     let next_maintenance = parse_optional_timestamptz(form.next_maintenance, tz_offset)?;
 
-    sqlx::query!(
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let id = sqlx::query_scalar!(
         r#"
         INSERT INTO equipment (
             name, brand, model, serial_number, acquisition_date,
-            category_id, insurance_renewal,
-            next_maintenance, fuel_capacity, current_status
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            category_id, next_maintenance, fuel_capacity, purchase_cost,
+            salvage_value, useful_life_years, depreciation_method, current_status
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        RETURNING id
         "#,
         form.name,
         form.brand,
@@ -85,18 +146,27 @@ pub async fn create(
         form.serial_number,
         acquisition_date,
         form.category_id,
-        insurance_renewal,
         next_maintenance,
         form.fuel_capacity,
+        form.purchase_cost,
+        form.salvage_value,
+        form.useful_life_years,
+        form.depreciation_method.map(|m| m.as_str()),
         form.status
     )
-    .execute(&state.db)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| {
         error!("Equipment creation failed: {}", e);
         e.to_string()
     })?;
 
+    payload["id"] = id.into();
+    webhooks::enqueue(&mut tx, "equipment.created", payload, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
     info!("Equipment '{}' created successfully", form.name);
     Ok(Redirect::to("/equipment"))
 }
@@ -105,39 +175,72 @@ pub async fn create(
 // LIST
 pub async fn list(
     Extension(state): Extension<Arc<AppState>>,
+    Query(filter): Query<EquipmentFilter>,
+    RawQuery(query): RawQuery,
 ) -> Result<Html<String>, String> {
     info!("Listing equipment");
     
-    let equipment = sqlx::query_as!(
-        Equipment,
-        r#"
-        SELECT 
-            e.id, e.name, e.brand, e.model, e.serial_number, 
-            e.acquisition_date, e.category_id, c.name as category_name,
-            e.insurance_renewal, e.next_maintenance, e.fuel_capacity,
-            e.current_status as "status!"
-        FROM equipment e
-        JOIN categories c ON e.category_id = c.id
-        ORDER BY e.name
-        "#
-    )
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| {
-        error!("Failed to fetch equipment: {}", e);
-        e.to_string()
-    })?;
-
+    let equipment = fetch_equipment(&state.db, &filter).await?;
     let categories = get_categories(&state.db).await?;
 
     let mut ctx = tera::Context::new();
     ctx.insert("equipment", &equipment);
     ctx.insert("categories", &categories);
-    state.templates.render("equipment/index.html", &ctx)
+    ctx.insert("filter", &filter);
+    ctx.insert("query", &query.unwrap_or_default());
+    state.render("equipment/index.html", &ctx)
         .map_err(|e| e.to_string())
         .map(Html)
 }
 
+const EQUIPMENT_EXPORT_HEADERS: &[&str] = &[
+    "ID",
+    "Name",
+    "Brand",
+    "Model",
+    "Serial Number",
+    "Category",
+    "Status",
+    "Acquisition Date",
+    "Insured Until",
+    "Location",
+    "Next Maintenance",
+    "Fuel Capacity (L)",
+    "Purchase Cost",
+];
+
+// EXPORT
+pub async fn export(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+    Query(filter): Query<EquipmentFilter>,
+) -> Result<Response, String> {
+    info!("Exporting equipment register as {:?}", format);
+
+    let equipment = fetch_equipment(&state.db, &filter).await?;
+
+    let mut sheet = Sheet::new("Equipment", EQUIPMENT_EXPORT_HEADERS);
+    for e in equipment {
+        sheet.push(vec![
+            e.id.into(),
+            e.name.into(),
+            e.brand.into(),
+            e.model.into(),
+            e.serial_number.into(),
+            e.category_name.into(),
+            e.status.into(),
+            e.acquisition_date.into(),
+            e.covered_until.into(),
+            e.site_name.unwrap_or_else(|| "Yard".to_string()).into(),
+            e.next_maintenance.into(),
+            e.fuel_capacity.into(),
+            e.purchase_cost.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, "equipment")
+}
+
 // NEW FORM
 pub async fn new_form(
     Extension(state): Extension<Arc<AppState>>,
@@ -150,20 +253,19 @@ pub async fn new_form(
     #[derive(serde::Serialize)]
     struct EquipmentDefault {
         acquisition_date: Option<String>,
-        insurance_renewal: Option<String>,
         next_maintenance: Option<String>,
     }
     
     let equipment = EquipmentDefault {
         acquisition_date: None,
-        insurance_renewal: None,
         next_maintenance: None,
     };
 
     let mut ctx = tera::Context::new();
     ctx.insert("categories", &categories);
+    ctx.insert("methods", METHODS);
     ctx.insert("equipment", &equipment);  // Pass equipment to template
-    state.templates.render("equipment/new.html", &ctx)
+    state.render("equipment/new.html", &ctx)
         .map_err(|e| e.to_string())
         .map(Html)
 }
@@ -174,37 +276,7 @@ pub async fn edit_form(
     Extension(state): Extension<Arc<AppState>>,
 ) -> Result<Html<String>, String> {
     info!("Editing equipment ID: {}", id);
-    
-    // Reuse existing Equipment struct
-    let equipment = sqlx::query_as!(
-        Equipment,
-        r#"
-        SELECT 
-            e.id, e.name, e.brand, e.model, e.serial_number, 
-            e.acquisition_date, e.category_id, c.name as category_name,
-            e.insurance_renewal, e.next_maintenance, e.fuel_capacity,
-            e.current_status as "status!"
-        FROM equipment e
-        JOIN categories c ON e.category_id = c.id
-        WHERE e.id = $1
-        "#,
-        id
-    )
-    .fetch_one(&state.db)
-    .await
-    .map_err(|e| {
-        warn!("Equipment {} not found: {}", id, e);
-        e.to_string()
-    })?;
-
-    let categories = get_categories(&state.db).await?;
-
-    let mut ctx = tera::Context::new();
-    ctx.insert("equipment", &equipment);
-    ctx.insert("categories", &categories);
-    state.templates.render("equipment/edit.html", &ctx)
-        .map_err(|e| e.to_string())
-        .map(Html)
+    render_edit(&state, id, None).await
 }
 
 // UPDATE
@@ -218,10 +290,21 @@ pub async fn update(
     // Get timezone offset from form (default to UTC)
     let tz_offset = form.timezone_offset.unwrap_or(0);
     
+    let mut payload = equipment_payload(&form);
+    form.terms().validate(form.purchase_cost)?;
     let acquisition_date = parse_timestamptz(&form.acquisition_date, tz_offset)?;
-    let insurance_renewal = parse_optional_timestamptz(form.insurance_renewal, tz_offset)?;
     let next_maintenance = parse_optional_timestamptz(form.next_maintenance, tz_offset)?;
 
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let old_status = sqlx::query_scalar!(
+        "SELECT current_status FROM equipment WHERE id = $1 FOR UPDATE",
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Equipment {} not found", id))?;
+
     sqlx::query!(
         r#"
         UPDATE equipment SET
@@ -231,11 +314,14 @@ pub async fn update(
             serial_number = $4,
             acquisition_date = $5,
             category_id = $6,
-            insurance_renewal = $7,
-            next_maintenance = $8,
-            fuel_capacity = $9,
-            current_status = $10
-        WHERE id = $11
+            next_maintenance = $7,
+            fuel_capacity = $8,
+            purchase_cost = $9,
+            salvage_value = $10,
+            useful_life_years = $11,
+            depreciation_method = $12,
+            current_status = $13
+        WHERE id = $14
         "#,
         form.name,
         form.brand,
@@ -243,23 +329,45 @@ pub async fn update(
         form.serial_number,
         acquisition_date,
         form.category_id,
-        insurance_renewal,
         next_maintenance,
         form.fuel_capacity,
+        form.purchase_cost,
+        form.salvage_value,
+        form.useful_life_years,
+        form.depreciation_method.map(|m| m.as_str()),
         form.status,
         id
     )
-    .execute(&state.db)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         error!("Equipment update failed: {}", e);
         e.to_string()
     })?;
 
+    if old_status != form.status {
+        payload["id"] = id.into();
+        payload["previous_status"] = old_status.into();
+        webhooks::enqueue(&mut tx, "equipment.status_changed", payload, None)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
     info!("Equipment {} updated successfully", id);
     Ok(Redirect::to("/equipment"))
 }
 
+/// `data` of equipment webhook events, completed with the ID once known.
+fn equipment_payload(form: &EquipmentForm) -> serde_json::Value {
+    serde_json::json!({
+        "name": form.name,
+        "serial_number": form.serial_number,
+        "category_id": form.category_id,
+        "status": form.status,
+    })
+}
+
 // UPDATED DATE PARSING FUNCTIONS
 fn parse_timestamptz(datetime_str: &str, tz_offset: i32) -> Result<DateTime<Utc>, String> {
     if datetime_str.is_empty() {
@@ -293,6 +401,130 @@ fn parse_optional_timestamptz(
     }
 }
 
+// ADD OPERATOR
+pub async fn add_operator(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<OperatorForm>,
+) -> Result<Response, String> {
+    info!("Assigning staff {} to equipment {}", form.operator_id, id);
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let violations = assignments::add_operator(&mut tx, id, form.operator_id, &state.config.assignments)
+        .await
+        .map_err(|e| {
+            error!("Operator assignment failed: {}", e);
+            e.to_string()
+        })?;
+    if violations.iter().any(|v| v.blocking) {
+        warn!("Refusing to assign staff {} to equipment {}", form.operator_id, id);
+        let reasons: Vec<&str> = violations.iter().filter(|v| v.blocking).map(|v| v.message.as_str()).collect();
+        let message = format!("Not assigned: {}", reasons.join("; "));
+        return render_edit(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/equipment/{}/edit", id)).into_response())
+}
+
+// REMOVE OPERATOR
+pub async fn remove_operator(
+    Path((id, operator_id)): Path<(i32, i32)>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Redirect, String> {
+    info!("Ending assignment of staff {} to equipment {}", operator_id, id);
+
+    let mut conn = state.db.acquire().await.map_err(|e| e.to_string())?;
+    assignments::remove_operator(&mut conn, id, operator_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/equipment/{}/edit", id)))
+}
+
+// SET PRIMARY OPERATOR
+pub async fn set_primary_operator(
+    Path((id, operator_id)): Path<(i32, i32)>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Response, String> {
+    info!("Making staff {} the primary operator of equipment {}", operator_id, id);
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let updated = assignments::set_primary_operator(&mut tx, id, operator_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !updated {
+        let message = "Only a current operator can be the primary operator".to_string();
+        return render_edit(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/equipment/{}/edit", id)).into_response())
+}
+
+// MOVE TO SITE
+pub async fn move_to_site(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<MoveForm>,
+) -> Result<Response, String> {
+    info!("Moving equipment {} to site {:?} on {}", id, form.site_id, form.date);
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let mut problem = sites::move_problem(&mut tx, id, form.site_id, form.date)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Checked under the machine lock, which dispatching a transfer takes too
+    if problem.is_none() {
+        let transfer = transfers::open_transfer(&mut tx, id)
+            .await
+            .map_err(|e| e.to_string())?;
+        problem = transfer.map(|t| {
+            format!("The machine is in transit (transfer {}); record its arrival instead", t.id)
+        });
+    }
+    if let Some(message) = problem {
+        tx.rollback().await.map_err(|e| e.to_string())?;
+        return render_edit(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    sites::move_equipment(&mut tx, id, form.site_id, form.date, form.notes.as_deref())
+        .await
+        .map_err(|e| {
+            error!("Equipment {} move failed: {}", id, e);
+            e.to_string()
+        })?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/equipment/{}/edit", id)).into_response())
+}
+
+// TELEMATICS
+pub async fn set_device(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<DeviceForm>,
+) -> Result<Response, String> {
+    info!("Setting telematics device of equipment {} to {:?}", id, form.device_id);
+
+    let device_id = form.device_id.as_deref().map(str::trim);
+    if device_id.is_some_and(|d| d.len() > 100) {
+        let message = "Device IDs are at most 100 characters".to_string();
+        return render_edit(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    let taken = telematics::set_device_id(&state.db, id, device_id)
+        .await
+        .map_err(|e| {
+            error!("Equipment {} device update failed: {}", id, e);
+            e.to_string()
+        })?;
+    if let Some(other) = taken {
+        let message = format!("Device ID already reports for {}", other);
+        return render_edit(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    Ok(Redirect::to(&format!("/equipment/{}/edit", id)).into_response())
+}
+
 // DELETE
 pub async fn delete(
     Path(id): Path<i32>,
@@ -316,6 +548,138 @@ pub async fn delete(
 }
 
 // Helper functions
+/// The edit form with the machine's operators, the rules they break and
+/// where the machine has been deployed.
+async fn render_edit(
+    state: &AppState,
+    id: i32,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    // Reuse existing Equipment struct
+    let equipment = sqlx::query_as!(
+        Equipment,
+        r#"
+        SELECT 
+            e.id, e.name, e.brand, e.model, e.serial_number, 
+            e.acquisition_date, e.category_id, c.name as category_name,
+            cov.covered_until, loc.site_id, loc.site_name,
+            e.next_maintenance, e.fuel_capacity, e.purchase_cost,
+            e.salvage_value, e.useful_life_years, e.depreciation_method, e.current_status as "status!",
+            tel.last_ping_at as "last_ping_at?", tel.latitude as "latitude?", tel.longitude as "longitude?"
+        FROM equipment e
+        JOIN categories c ON e.category_id = c.id
+        JOIN equipment_coverage cov ON cov.equipment_id = e.id
+        LEFT JOIN equipment_location loc ON loc.equipment_id = e.id
+        LEFT JOIN equipment_telematics tel ON tel.equipment_id = e.id
+        WHERE e.id = $1
+        "#,
+        id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        warn!("Equipment {} not found: {}", id, e);
+        e.to_string()
+    })?;
+
+    let categories = get_categories(&state.db).await?;
+    let operators = assignments::current_operators(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut conn = state.db.acquire().await.map_err(|e| e.to_string())?;
+    let conflicts: Vec<Violation> = assignments::equipment_conflicts(&mut conn, id, &state.config.assignments)
+        .await
+        .map_err(|e| e.to_string())?;
+    let staff = sqlx::query!("SELECT id, full_name FROM staff ORDER BY full_name")
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|s| !operators.iter().any(|o| o.operator_id == s.id))
+        .map(|s| serde_json::json!({ "id": s.id, "full_name": s.full_name }))
+        .collect::<Vec<_>>();
+    let stays = sites::deployments(&state.db, None, Some(id))
+        .await
+        .map_err(|e| e.to_string())?;
+    let open_sites: Vec<_> = sites::list_sites(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|s| s.state != "completed")
+        .collect();
+    let transfer = transfers::open_transfer(&mut conn, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let device_id = telematics::device_id(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let last_known = telematics::last_known(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let pings = telematics::recent_pings(&state.db, id, 10)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("equipment", &equipment);
+    ctx.insert("categories", &categories);
+    ctx.insert("methods", METHODS);
+    ctx.insert("operators", &operators);
+    ctx.insert("conflicts", &conflicts);
+    ctx.insert("staff", &staff);
+    ctx.insert("stays", &stays);
+    ctx.insert("sites", &open_sites);
+    ctx.insert("transfer", &transfer);
+    ctx.insert("device_id", &device_id);
+    ctx.insert("telematics", &last_known);
+    ctx.insert("pings", &pings);
+    ctx.insert("today", &Utc::now().with_timezone(&state.config.locale.tz()).date_naive());
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("equipment/edit.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+pub async fn fetch_equipment(
+    pool: &PgPool,
+    filter: &EquipmentFilter,
+) -> Result<Vec<Equipment>, String> {
+    sqlx::query_as!(
+        Equipment,
+        r#"
+        SELECT 
+            e.id, e.name, e.brand, e.model, e.serial_number, 
+            e.acquisition_date, e.category_id, c.name as category_name,
+            cov.covered_until, loc.site_id, loc.site_name,
+            e.next_maintenance, e.fuel_capacity, e.purchase_cost,
+            e.salvage_value, e.useful_life_years, e.depreciation_method, e.current_status as "status!",
+            tel.last_ping_at as "last_ping_at?", tel.latitude as "latitude?", tel.longitude as "longitude?"
+        FROM equipment e
+        JOIN categories c ON e.category_id = c.id
+        JOIN equipment_coverage cov ON cov.equipment_id = e.id
+        LEFT JOIN equipment_location loc ON loc.equipment_id = e.id
+        LEFT JOIN equipment_telematics tel ON tel.equipment_id = e.id
+        WHERE ($1::text IS NULL OR e.current_status = $1)
+            AND ($2::int IS NULL OR e.category_id = $2)
+            AND ($3::text IS NULL
+                OR e.name ILIKE '%' || $3 || '%'
+                OR e.serial_number ILIKE '%' || $3 || '%')
+        ORDER BY e.name
+        "#,
+        filter.status,
+        filter.category_id,
+        filter.q
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch equipment: {}", e);
+        e.to_string()
+    })
+}
+
 async fn get_categories(pool: &PgPool) -> Result<Vec<Category>, String> {
     sqlx::query_as!(
         Category,
@@ -0,0 +1,122 @@
+use crate::geofences::{self, GeofenceForm, GEOFENCE_KINDS};
+use crate::sites;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Path},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use log::{error, info, warn};
+use std::sync::Arc;
+
+/// Most events shown in a site's log.
+const LOG_LIMIT: i64 = 200;
+
+// LIST
+pub async fn index(
+    Path(site_id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Showing geofences of site {}", site_id);
+    render_index(&state, site_id, None, None).await
+}
+
+// CREATE
+pub async fn create(
+    Path(site_id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<GeofenceForm>,
+) -> Result<Response, String> {
+    info!("Adding {} geofence '{}' to site {}", form.kind, form.name, site_id);
+
+    let vertices = match form.validate() {
+        Ok(vertices) => vertices,
+        Err(message) => {
+            return render_index(&state, site_id, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+        }
+    };
+    let id = geofences::create_geofence(&state.db, site_id, &form, vertices)
+        .await
+        .map_err(|e| {
+            error!("Geofence creation failed: {}", e);
+            e.to_string()
+        })?;
+    if id.is_none() {
+        let message = format!("The site already has a zone named '{}'", form.name.trim());
+        return render_index(&state, site_id, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    Ok(Redirect::to(&format!("/sites/{}/geofences", site_id)).into_response())
+}
+
+// DELETE
+pub async fn delete(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Redirect, String> {
+    info!("Deleting geofence {}", id);
+
+    let site_id = geofences::delete_geofence(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            warn!("Geofence {} not found", id);
+            format!("Geofence {} not found", id)
+        })?;
+
+    Ok(Redirect::to(&format!("/sites/{}/geofences", site_id)))
+}
+
+// Helper functions
+/// The site's zones, the form to draw another and the arrival and departure
+/// log; `submitted` refills the form after a validation error.
+async fn render_index(
+    state: &AppState,
+    site_id: i32,
+    submitted: Option<&GeofenceForm>,
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let site = sites::get_site(&state.db, site_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Site {} not found", site_id))?;
+    let fences = geofences::site_geofences(&state.db, site_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let events = geofences::site_events(&state.db, site_id, LOG_LIMIT)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    if let Some(form) = submitted {
+        ctx.insert("geofence", form);
+    } else {
+        // A circle around the site's own position is the usual start
+        ctx.insert(
+            "geofence",
+            &serde_json::json!({
+                "name": "",
+                "kind": "radius",
+                "center_latitude": site.latitude,
+                "center_longitude": site.longitude,
+                "radius_m": 300,
+                "vertices": null,
+            }),
+        );
+    }
+    ctx.insert("site", &site);
+    ctx.insert("fences", &fences);
+    ctx.insert("events", &events);
+    ctx.insert("kinds", GEOFENCE_KINDS);
+    ctx.insert("timezone", &state.config.locale.timezone);
+    ctx.insert("work", &serde_json::json!({
+        "days": state.config.geofences.work_days.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+        "start": state.config.geofences.work_start.format("%H:%M").to_string(),
+        "end": state.config.geofences.work_end.format("%H:%M").to_string(),
+    }));
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("geofences/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
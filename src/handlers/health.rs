@@ -0,0 +1,90 @@
+use crate::{db, AppState};
+use axum::{
+    extract::Extension,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use log::{error, warn};
+use sqlx::migrate::Migrate;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+// LIVENESS
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+// READINESS
+pub async fn readyz(Extension(state): Extension<Arc<AppState>>) -> (StatusCode, String) {
+    match check_ready(&state.db).await {
+        Ok(()) => (StatusCode::OK, "ready".to_string()),
+        Err(reason) => {
+            warn!("Readiness check failed: {}", reason);
+            (StatusCode::SERVICE_UNAVAILABLE, reason)
+        }
+    }
+}
+
+// METRICS
+pub async fn metrics(Extension(state): Extension<Arc<AppState>>) -> Result<Response, String> {
+    let metrics = &state.metrics;
+
+    let idle = state.db.num_idle() as i64;
+    metrics.db_connections.with_label_values(&["idle"]).set(idle);
+    metrics
+        .db_connections
+        .with_label_values(&["in_use"])
+        .set(state.db.size() as i64 - idle);
+    metrics
+        .db_max_connections
+        .set(state.db.options().get_max_connections() as i64);
+
+    // Domain gauges are refreshed on scrape; a failed query keeps the last values
+    match crate::fetch_status_counts(&state.db).await {
+        Ok(counts) => {
+            metrics.equipment_by_status.with_label_values(&["active"]).set(counts.active);
+            metrics.equipment_by_status.with_label_values(&["maintenance"]).set(counts.maintenance);
+            metrics.equipment_by_status.with_label_values(&["retired"]).set(counts.retired);
+        }
+        Err(e) => error!("Failed to refresh equipment gauges: {}", e),
+    }
+    match crate::count_overdue_maintenance(&state.db).await {
+        Ok(overdue) => metrics.maintenance_overdue.set(overdue),
+        Err(e) => error!("Failed to refresh overdue maintenance gauge: {}", e),
+    }
+
+    let body = metrics.encode()?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        body,
+    )
+        .into_response())
+}
+
+// Helper functions
+
+/// The database answers and every embedded migration has been applied.
+async fn check_ready(pool: &PgPool) -> Result<(), String> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| format!("database unreachable: {}", e))?;
+    sqlx::query!("SELECT 1 AS ok")
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("database unreachable: {}", e))?;
+
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .map_err(|e| format!("cannot read migrations: {}", e))?;
+    let pending = db::MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+        .count();
+    if pending > 0 {
+        return Err(format!("{} migrations pending", pending));
+    }
+    Ok(())
+}
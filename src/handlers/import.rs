@@ -0,0 +1,704 @@
+use crate::insurance;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Multipart, Path},
+    response::{Html, Redirect},
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Entities that can be bulk-loaded from a CSV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportEntity {
+    Categories,
+    Equipment,
+    Staff,
+}
+
+impl ImportEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportEntity::Categories => "categories",
+            ImportEntity::Equipment => "equipment",
+            ImportEntity::Staff => "staff",
+        }
+    }
+
+    pub fn fields(&self) -> &'static [FieldSpec] {
+        match self {
+            ImportEntity::Categories => CATEGORY_FIELDS,
+            ImportEntity::Equipment => EQUIPMENT_FIELDS,
+            ImportEntity::Staff => STAFF_FIELDS,
+        }
+    }
+}
+
+/// A target column that CSV columns can be mapped onto.
+#[derive(Debug, Serialize)]
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub required: bool,
+}
+
+const CATEGORY_FIELDS: &[FieldSpec] = &[
+    FieldSpec { name: "name", label: "Category Name", required: true },
+];
+
+const STAFF_FIELDS: &[FieldSpec] = &[
+    FieldSpec { name: "full_name", label: "Full Name", required: true },
+    FieldSpec { name: "contact_info", label: "Contact Information", required: false },
+    FieldSpec { name: "license_number", label: "License Number", required: false },
+];
+
+const EQUIPMENT_FIELDS: &[FieldSpec] = &[
+    FieldSpec { name: "name", label: "Equipment Name", required: true },
+    FieldSpec { name: "brand", label: "Brand", required: true },
+    FieldSpec { name: "model", label: "Model", required: true },
+    FieldSpec { name: "serial_number", label: "Serial Number", required: true },
+    FieldSpec { name: "acquisition_date", label: "Acquisition Date", required: true },
+    FieldSpec { name: "category", label: "Category Name", required: true },
+    FieldSpec { name: "insurance_renewal", label: "Insurance Renewal", required: false },
+    FieldSpec { name: "next_maintenance", label: "Next Maintenance", required: false },
+    FieldSpec { name: "fuel_capacity", label: "Fuel Capacity (L)", required: false },
+    FieldSpec { name: "status", label: "Status", required: false },
+];
+
+const EQUIPMENT_STATUSES: &[&str] = &["active", "maintenance", "retired"];
+
+/// Accepted date layouts, tried in order. Dates without a time are taken as midnight UTC.
+/// Slashed dates are read day first, as they are written locally: 01/04/2023 is
+/// 1 April. There is no month-first layout, so 01/13/2023 is refused rather than
+/// guessed, but a month-first 04/01/2023 is read as 4 January.
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%d/%m/%Y"];
+const DATETIME_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"];
+
+/// Target field name -> zero-based CSV column index.
+pub type ColumnMapping = HashMap<String, usize>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EquipmentRecord {
+    pub name: String,
+    pub brand: String,
+    pub model: String,
+    pub serial_number: String,
+    pub acquisition_date: DateTime<Utc>,
+    pub category: String,
+    pub insurance_renewal: Option<DateTime<Utc>>,
+    pub next_maintenance: Option<DateTime<Utc>>,
+    pub fuel_capacity: Option<f64>,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StaffRecord {
+    pub full_name: String,
+    pub contact_info: Option<String>,
+    pub license_number: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ImportRecord {
+    Category { name: String },
+    Equipment(EquipmentRecord),
+    Staff(StaffRecord),
+}
+
+/// One CSV data row after mapping and validation.
+#[derive(Debug, Serialize)]
+pub struct ImportRow {
+    /// Line number in the source file (the header is line 1).
+    pub line: usize,
+    /// Mapped raw values, in the order of the entity's fields.
+    pub values: Vec<String>,
+    pub errors: Vec<String>,
+    #[serde(skip)]
+    pub record: Option<ImportRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportPreview {
+    pub entity: ImportEntity,
+    pub rows: Vec<ImportRow>,
+    pub valid_count: usize,
+    pub error_count: usize,
+}
+
+impl ImportPreview {
+    fn new(entity: ImportEntity, rows: Vec<ImportRow>) -> Self {
+        let error_count = rows.iter().filter(|r| !r.errors.is_empty()).count();
+        ImportPreview {
+            entity,
+            valid_count: rows.len() - error_count,
+            error_count,
+            rows,
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count > 0
+    }
+}
+
+// Pure CSV handling
+
+/// Parses CSV text into its header row and data records.
+pub fn read_csv(data: &str) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let data = data.trim_start_matches('\u{feff}');
+    let delimiter = detect_delimiter(data);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if headers.iter().all(|h| h.is_empty()) {
+        return Err("CSV file has no header row".to_string());
+    }
+
+    let mut records = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Invalid CSV on line {}: {}", i + 2, e))?;
+        records.push(record.iter().map(str::to_string).collect());
+    }
+    Ok((headers, records))
+}
+
+// Spreadsheets exported with a French or Malagasy locale use ';' as separator
+fn detect_delimiter(data: &str) -> u8 {
+    let first_line = data.lines().next().unwrap_or_default();
+    if first_line.matches(';').count() > first_line.matches(',').count() {
+        b';'
+    } else {
+        b','
+    }
+}
+
+/// Guesses a column mapping by matching normalized header names against field names and labels.
+pub fn suggest_mapping(entity: ImportEntity, headers: &[String]) -> ColumnMapping {
+    let normalized = headers.iter().map(|h| normalize_header(h)).collect::<Vec<_>>();
+    let mut mapping = ColumnMapping::new();
+    for field in entity.fields() {
+        let candidates = [normalize_header(field.name), normalize_header(field.label)];
+        if let Some(index) = normalized.iter().position(|h| candidates.contains(h)) {
+            mapping.insert(field.name.to_string(), index);
+        }
+    }
+    mapping
+}
+
+fn normalize_header(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Maps and validates every record without touching the database.
+pub fn parse_rows(
+    entity: ImportEntity,
+    records: &[Vec<String>],
+    mapping: &ColumnMapping,
+) -> Vec<ImportRow> {
+    let mut seen_keys = HashSet::new();
+
+    records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| record.iter().any(|v| !v.is_empty()))
+        .map(|(i, record)| {
+            let values = entity
+                .fields()
+                .iter()
+                .map(|f| {
+                    mapping
+                        .get(f.name)
+                        .and_then(|&col| record.get(col))
+                        .cloned()
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+
+            let mut errors = Vec::new();
+            let record = build_record(entity, &values, &mut errors);
+
+            // Rows sharing a unique key inside the same file
+            if let Some(key) = record.as_ref().and_then(unique_key)
+                && !seen_keys.insert(key.to_lowercase())
+            {
+                errors.push(format!("Duplicate '{}' within the file", key));
+            }
+
+            ImportRow {
+                line: i + 2,
+                values,
+                record: if errors.is_empty() { record } else { None },
+                errors,
+            }
+        })
+        .collect()
+}
+
+fn unique_key(record: &ImportRecord) -> Option<&str> {
+    match record {
+        ImportRecord::Category { name } => Some(name),
+        ImportRecord::Equipment(e) => Some(&e.serial_number),
+        ImportRecord::Staff(_) => None,
+    }
+}
+
+fn build_record(
+    entity: ImportEntity,
+    values: &[String],
+    errors: &mut Vec<String>,
+) -> Option<ImportRecord> {
+    let fields = entity.fields();
+    let get = |name: &str| -> &str {
+        fields
+            .iter()
+            .position(|f| f.name == name)
+            .map(|i| values[i].as_str())
+            .unwrap_or_default()
+    };
+
+    for (field, value) in fields.iter().zip(values) {
+        if field.required && value.is_empty() {
+            errors.push(format!("Missing required value for {}", field.label));
+        }
+    }
+
+    let record = match entity {
+        ImportEntity::Categories => ImportRecord::Category { name: get("name").to_string() },
+        ImportEntity::Staff => ImportRecord::Staff(StaffRecord {
+            full_name: get("full_name").to_string(),
+            contact_info: optional(get("contact_info")),
+            license_number: optional(get("license_number")),
+        }),
+        ImportEntity::Equipment => {
+            let acquisition_date = match get("acquisition_date") {
+                "" => None,
+                s => record_date(s, "acquisition_date", errors),
+            };
+            let insurance_renewal = match get("insurance_renewal") {
+                "" => None,
+                s => record_date(s, "insurance_renewal", errors),
+            };
+            let next_maintenance = match get("next_maintenance") {
+                "" => None,
+                s => record_date(s, "next_maintenance", errors),
+            };
+            let fuel_capacity = match get("fuel_capacity") {
+                "" => None,
+                s => match s.replace(',', ".").parse::<f64>() {
+                    Ok(v) if v >= 0.0 => Some(v),
+                    _ => {
+                        errors.push(format!("Invalid number '{}' in fuel_capacity", s));
+                        None
+                    }
+                },
+            };
+            let status = match get("status").to_lowercase() {
+                s if s.is_empty() => "active".to_string(),
+                s if EQUIPMENT_STATUSES.contains(&s.as_str()) => s,
+                s => {
+                    errors.push(format!(
+                        "Unknown status '{}' (expected one of {})",
+                        s,
+                        EQUIPMENT_STATUSES.join(", ")
+                    ));
+                    s
+                }
+            };
+
+            ImportRecord::Equipment(EquipmentRecord {
+                name: get("name").to_string(),
+                brand: get("brand").to_string(),
+                model: get("model").to_string(),
+                serial_number: get("serial_number").to_string(),
+                acquisition_date: acquisition_date.unwrap_or_default(),
+                category: get("category").to_string(),
+                insurance_renewal,
+                next_maintenance,
+                fuel_capacity,
+                status,
+            })
+        }
+    };
+
+    if errors.is_empty() { Some(record) } else { None }
+}
+
+fn record_date(value: &str, field: &str, errors: &mut Vec<String>) -> Option<DateTime<Utc>> {
+    match parse_import_date(value) {
+        Some(date) => Some(date),
+        None => {
+            errors.push(format!(
+                "Invalid date '{}' in {} (expected YYYY-MM-DD or YYYY-MM-DD HH:MM)",
+                value, field
+            ));
+            None
+        }
+    }
+}
+
+/// Parses the date layouts accepted in import files.
+pub fn parse_import_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in DATETIME_FORMATS {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(dt.and_utc());
+        }
+    }
+    for format in DATE_FORMATS {
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            return date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc());
+        }
+    }
+    None
+}
+
+fn optional(value: &str) -> Option<String> {
+    if value.is_empty() { None } else { Some(value.to_string()) }
+}
+
+// Database-backed validation and commit
+
+/// Validates the file against the database without writing anything.
+pub async fn dry_run(
+    pool: &PgPool,
+    entity: ImportEntity,
+    csv_data: &str,
+    mapping: &ColumnMapping,
+) -> Result<ImportPreview, String> {
+    let (_, records) = read_csv(csv_data)?;
+    let mut rows = parse_rows(entity, &records, mapping);
+
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    check_against_database(&mut conn, entity, &mut rows).await?;
+
+    Ok(ImportPreview::new(entity, rows))
+}
+
+/// Validates and inserts all rows in a single transaction. Nothing is written if any row fails.
+pub async fn commit(
+    pool: &PgPool,
+    entity: ImportEntity,
+    csv_data: &str,
+    mapping: &ColumnMapping,
+) -> Result<ImportPreview, String> {
+    let (_, records) = read_csv(csv_data)?;
+    let mut rows = parse_rows(entity, &records, mapping);
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    check_against_database(&mut tx, entity, &mut rows).await?;
+
+    let preview = ImportPreview::new(entity, rows);
+    if preview.has_errors() {
+        warn!("{} import rejected: {} rows with errors", entity.as_str(), preview.error_count);
+        return Ok(preview);
+    }
+
+    for row in &preview.rows {
+        if let Some(record) = &row.record {
+            insert_record(&mut tx, record).await.map_err(|e| {
+                error!("Import failed on line {}: {}", row.line, e);
+                format!("Line {}: {}", row.line, e)
+            })?;
+        }
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    info!("Imported {} {} rows", preview.valid_count, entity.as_str());
+    Ok(preview)
+}
+
+async fn check_against_database(
+    conn: &mut sqlx::PgConnection,
+    entity: ImportEntity,
+    rows: &mut [ImportRow],
+) -> Result<(), String> {
+    // Raw values are checked so rows with format errors still report every problem
+    let value_of = |row: &ImportRow, name: &str| -> String {
+        entity
+            .fields()
+            .iter()
+            .position(|f| f.name == name)
+            .map(|i| row.values[i].clone())
+            .unwrap_or_default()
+    };
+    let (category_field, serial_field) = match entity {
+        ImportEntity::Categories => (Some("name"), None),
+        ImportEntity::Equipment => (Some("category"), Some("serial_number")),
+        ImportEntity::Staff => (None, None),
+    };
+
+    let category_names = category_field
+        .map(|f| rows.iter().map(|r| value_of(r, f)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let serial_numbers = serial_field
+        .map(|f| rows.iter().map(|r| value_of(r, f)).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    // Category names and unique keys are matched ignoring case, as they are
+    // within the file
+    let lowercase = |values: &[String]| values.iter().map(|v| v.to_lowercase()).collect::<Vec<_>>();
+    let existing_categories = sqlx::query_scalar!(
+        r#"SELECT LOWER(name) as "name!" FROM categories WHERE LOWER(name) = ANY($1)"#,
+        &lowercase(&category_names)
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?
+    .into_iter()
+    .collect::<HashSet<_>>();
+    let existing_serials = sqlx::query_scalar!(
+        r#"SELECT LOWER(serial_number) as "serial_number!" FROM equipment WHERE LOWER(serial_number) = ANY($1)"#,
+        &lowercase(&serial_numbers)
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?
+    .into_iter()
+    .collect::<HashSet<_>>();
+
+    for row in rows.iter_mut() {
+        let mut errors = Vec::new();
+        match entity {
+            ImportEntity::Categories => {
+                let name = value_of(row, "name");
+                if existing_categories.contains(&name.to_lowercase()) {
+                    errors.push(format!("Category '{}' already exists", name));
+                }
+            }
+            ImportEntity::Equipment => {
+                let category = value_of(row, "category");
+                if !category.is_empty() && !existing_categories.contains(&category.to_lowercase()) {
+                    errors.push(format!("Unknown category '{}'", category));
+                }
+                let serial_number = value_of(row, "serial_number");
+                if existing_serials.contains(&serial_number.to_lowercase()) {
+                    errors.push(format!("Serial number '{}' is already registered", serial_number));
+                }
+            }
+            ImportEntity::Staff => {}
+        }
+        if !errors.is_empty() {
+            row.errors.extend(errors);
+            row.record = None;
+        }
+    }
+
+    Ok(())
+}
+
+async fn insert_record(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    record: &ImportRecord,
+) -> Result<(), sqlx::Error> {
+    match record {
+        ImportRecord::Category { name } => {
+            sqlx::query!("INSERT INTO categories (name) VALUES ($1)", name)
+                .execute(&mut **tx)
+                .await?;
+        }
+        ImportRecord::Staff(s) => {
+            sqlx::query!(
+                "INSERT INTO staff (full_name, contact_info, license_number) VALUES ($1, $2, $3)",
+                s.full_name,
+                s.contact_info,
+                s.license_number
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        // Category names are resolved with a subselect, like the seed data does,
+        // ignoring case as the checks do; an exact match wins if both exist
+        ImportRecord::Equipment(e) => {
+            let id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO equipment (
+                    name, brand, model, serial_number, acquisition_date,
+                    category_id, next_maintenance, fuel_capacity, current_status
+                ) VALUES (
+                    $1, $2, $3, $4, $5,
+                    (
+                        SELECT id FROM categories WHERE LOWER(name) = LOWER($6)
+                        ORDER BY name = $6 DESC, id LIMIT 1
+                    ),
+                    $7, $8, $9
+                )
+                RETURNING id
+                "#,
+                e.name,
+                e.brand,
+                e.model,
+                e.serial_number,
+                e.acquisition_date,
+                e.category,
+                e.next_maintenance,
+                e.fuel_capacity,
+                e.status
+            )
+            .fetch_one(&mut **tx)
+            .await?;
+            // A bare renewal date becomes a placeholder policy to complete later
+            if let Some(renewal) = e.insurance_renewal {
+                insurance::create_legacy_policy(tx, id, renewal.date_naive()).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// HTTP handlers
+
+#[derive(Debug, Serialize)]
+struct MappingOption<'a> {
+    field: &'a FieldSpec,
+    selected: Option<usize>,
+}
+
+// UPLOAD FORM
+pub async fn upload_form(
+    Path(entity): Path<ImportEntity>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Serving {} import form", entity.as_str());
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("entity", &entity);
+    ctx.insert("fields", entity.fields());
+    state.render("import/upload.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// UPLOAD (column mapping step)
+pub async fn upload(
+    Path(entity): Path<ImportEntity>,
+    Extension(state): Extension<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Html<String>, String> {
+    info!("Receiving {} import file", entity.as_str());
+
+    let mut csv_data = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        if field.name() == Some("file") {
+            let bytes = field.bytes().await.map_err(|e| e.to_string())?;
+            csv_data = Some(String::from_utf8(bytes.to_vec()).map_err(|_| {
+                warn!("Import file is not valid UTF-8");
+                "The file must be UTF-8 encoded CSV".to_string()
+            })?);
+        }
+    }
+    let csv_data = csv_data.ok_or("No file uploaded")?;
+
+    let (headers, records) = read_csv(&csv_data)?;
+    let suggested = suggest_mapping(entity, &headers);
+    let options = entity
+        .fields()
+        .iter()
+        .map(|field| MappingOption {
+            field,
+            selected: suggested.get(field.name).copied(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("entity", &entity);
+    ctx.insert("headers", &headers);
+    ctx.insert("options", &options);
+    ctx.insert("row_count", &records.len());
+    ctx.insert("csv_data", &csv_data);
+    state.render("import/mapping.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// Splits the submitted form into the CSV payload and the `map_<field>` column choices.
+fn mapping_from_form(entity: ImportEntity, form: &HashMap<String, String>) -> ColumnMapping {
+    entity
+        .fields()
+        .iter()
+        .filter_map(|f| {
+            form.get(&format!("map_{}", f.name))
+                .and_then(|v| v.parse::<usize>().ok())
+                .map(|col| (f.name.to_string(), col))
+        })
+        .collect()
+}
+
+// PREVIEW (dry run)
+pub async fn preview(
+    Path(entity): Path<ImportEntity>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Html<String>, String> {
+    info!("Previewing {} import", entity.as_str());
+
+    let csv_data = form.get("csv_data").cloned().unwrap_or_default();
+    let mapping = mapping_from_form(entity, &form);
+    let preview = dry_run(&state.db, entity, &csv_data, &mapping).await?;
+
+    render_preview(&state, &preview, &csv_data, &form, false)
+}
+
+// COMMIT
+pub async fn commit_import(
+    Path(entity): Path<ImportEntity>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<axum::response::Response, String> {
+    use axum::response::IntoResponse;
+    info!("Committing {} import", entity.as_str());
+
+    let csv_data = form.get("csv_data").cloned().unwrap_or_default();
+    let mapping = mapping_from_form(entity, &form);
+    let preview = commit(&state.db, entity, &csv_data, &mapping).await?;
+
+    if preview.has_errors() {
+        // Data changed since the preview; show the new errors instead of importing
+        return render_preview(&state, &preview, &csv_data, &form, true).map(IntoResponse::into_response);
+    }
+
+    Ok(Redirect::to(&format!("/{}", entity.as_str())).into_response())
+}
+
+fn render_preview(
+    state: &AppState,
+    preview: &ImportPreview,
+    csv_data: &str,
+    form: &HashMap<String, String>,
+    rejected: bool,
+) -> Result<Html<String>, String> {
+    let mapping_fields = form
+        .iter()
+        .filter(|(k, _)| k.starts_with("map_"))
+        .collect::<HashMap<_, _>>();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("entity", &preview.entity);
+    ctx.insert("fields", preview.entity.fields());
+    ctx.insert("preview", preview);
+    ctx.insert("csv_data", csv_data);
+    ctx.insert("mapping_fields", &mapping_fields);
+    ctx.insert("rejected", &rejected);
+    state.render("import/preview.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
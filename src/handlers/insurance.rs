@@ -0,0 +1,318 @@
+use crate::claims::{self, ClaimFilter, CLAIM_STATUSES};
+use crate::handlers::equipment::{fetch_equipment, EquipmentFilter};
+use crate::insurance::{self, PolicyForm, COVERAGE_TYPES};
+use crate::uploads;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Multipart, Path},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::Form; // Repeated equipment_ids checkboxes
+use chrono::{Days, Months};
+use log::{error, info, warn};
+use std::sync::Arc;
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Listing insurance policies");
+
+    let policies = insurance::list_policies(&state.db)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch policies: {}", e);
+            e.to_string()
+        })?;
+    let uncovered = insurance::uncovered_equipment(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("policies", &policies);
+    ctx.insert("uncovered", &uncovered);
+    ctx.insert("coverage_types", COVERAGE_TYPES);
+    state.render("insurance/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// NEW FORM
+pub async fn new_form(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Serving new policy form");
+    render_form(&state, None, None, None).await
+}
+
+// CREATE
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<PolicyForm>,
+) -> Result<Response, String> {
+    info!("Creating policy {} with {}", form.policy_number, form.insurer);
+
+    if let Err(message) = form.validate() {
+        return render_form(&state, None, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let id = insurance::create_policy(&mut tx, &form, None)
+        .await
+        .map_err(|e| {
+            error!("Policy creation failed: {}", e);
+            e.to_string()
+        })?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    info!("Policy {} created", id);
+    Ok(Redirect::to(&format!("/insurance/{}", id)).into_response())
+}
+
+// SHOW
+pub async fn show(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Showing policy {}", id);
+    render_show(&state, id, None).await
+}
+
+// EDIT FORM
+pub async fn edit_form(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Editing policy {}", id);
+    render_form(&state, Some(id), None, None).await
+}
+
+// UPDATE
+pub async fn update(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<PolicyForm>,
+) -> Result<Response, String> {
+    info!("Updating policy {}", id);
+
+    if let Err(message) = form.validate() {
+        return render_form(&state, Some(id), Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let updated = insurance::update_policy(&mut tx, id, &form)
+        .await
+        .map_err(|e| {
+            error!("Policy {} update failed: {}", id, e);
+            e.to_string()
+        })?;
+    if !updated {
+        return Err(format!("Policy {} not found", id));
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/insurance/{}", id)).into_response())
+}
+
+// RENEW
+pub async fn renew(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<PolicyForm>,
+) -> Result<Response, String> {
+    info!("Renewing policy {}", id);
+
+    if let Err(message) = form.validate() {
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    match insurance::renew_policy(&state.db, id, &form).await {
+        Ok(Some(new_id)) => {
+            info!("Policy {} renewed as {}", id, new_id);
+            Ok(Redirect::to(&format!("/insurance/{}", new_id)).into_response())
+        }
+        Ok(None) => {
+            let message = "Only an active policy can be renewed".to_string();
+            render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response)
+        }
+        Err(e) => {
+            warn!("Policy {} renewal failed: {}", id, e);
+            Err(e.to_string())
+        }
+    }
+}
+
+// CANCEL
+pub async fn cancel(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Redirect, String> {
+    info!("Cancelling policy {}", id);
+
+    insurance::cancel_policy(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/insurance/{}", id)))
+}
+
+// UPLOAD DOCUMENT
+pub async fn upload_document(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Html<String>, String> {
+    info!("Uploading document for policy {}", id);
+
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        if field.name() == Some("file") {
+            let filename = uploads::sanitize_filename(field.file_name().unwrap_or_default(), "policy.pdf");
+            let data = field.bytes().await.map_err(|e| e.to_string())?;
+            upload = Some((filename, data));
+        }
+    }
+    let Some((filename, data)) = upload.filter(|(_, data)| !data.is_empty()) else {
+        return render_show(&state, id, Some(("error", "Choose a file to upload".to_string()))).await;
+    };
+    if uploads::sniff(&data, &["application/pdf"]).is_none() {
+        return render_show(&state, id, Some(("error", format!("'{}' is not a PDF", filename)))).await;
+    }
+
+    let dir = insurance::document_dir(&state.config.paths.uploads);
+    let stored_name = uploads::stored_name(id, "pdf");
+    uploads::save(&dir, &stored_name, &data)
+        .await
+        .map_err(|e| {
+            error!("Failed to store policy document: {}", e);
+            e.to_string()
+        })?;
+
+    if let Err(e) = insurance::add_document(&state.db, id, &filename, &stored_name, data.len() as i64).await {
+        // Don't leave an orphan file behind (e.g. the policy was deleted)
+        let _ = tokio::fs::remove_file(dir.join(&stored_name)).await;
+        return Err(e.to_string());
+    }
+
+    render_show(&state, id, Some(("success", format!("{} attached", filename)))).await
+}
+
+// DOWNLOAD DOCUMENT
+pub async fn download_document(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Response, (StatusCode, String)> {
+    let document = insurance::get_document(&state.db, id)
+        .await
+        .map_err(|e| {
+            error!("Document lookup failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?
+        .ok_or((StatusCode::NOT_FOUND, "Page not found".to_string()))?;
+    info!("Serving policy document {}", document.stored_name);
+
+    let path = insurance::document_dir(&state.config.paths.uploads).join(&document.stored_name);
+    let body = tokio::fs::read(&path).await.map_err(|e| {
+        error!("Cannot read {}: {}", path.display(), e);
+        (StatusCode::NOT_FOUND, "Page not found".to_string())
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", uploads::sanitize_filename(&document.filename, "policy.pdf")),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+// Helper functions
+async fn render_show(
+    state: &AppState,
+    id: i32,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let policy = insurance::get_policy(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Policy {} not found", id))?;
+    let equipment = insurance::covered_equipment(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let history = insurance::renewal_history(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let documents = insurance::list_documents(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let filter = ClaimFilter { policy_id: Some(id), ..Default::default() };
+    let claims = claims::list_claims(&state.db, &filter)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // The renewal form proposes the next term, starting the day after this one ends
+    let renewal_start = policy.end_date.checked_add_days(Days::new(1)).unwrap_or(policy.end_date);
+    let renewal_end = policy.end_date.checked_add_months(Months::new(12)).unwrap_or(renewal_start);
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("policy", &policy);
+    ctx.insert("equipment", &equipment);
+    ctx.insert("history", &history);
+    ctx.insert("documents", &documents);
+    ctx.insert("claims", &claims);
+    ctx.insert("statuses", CLAIM_STATUSES);
+    ctx.insert("coverage_types", COVERAGE_TYPES);
+    ctx.insert("renewal_start", &renewal_start);
+    ctx.insert("renewal_end", &renewal_end);
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("insurance/show.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// The create/edit form; `submitted` refills it after a validation error.
+async fn render_form(
+    state: &AppState,
+    id: Option<i32>,
+    submitted: Option<&PolicyForm>,
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let equipment = fetch_equipment(&state.db, &EquipmentFilter::default()).await?;
+
+    let mut ctx = tera::Context::new();
+    if let Some(form) = submitted {
+        ctx.insert("policy", form);
+        ctx.insert("selected", &form.equipment_ids);
+    } else if let Some(id) = id {
+        let policy = insurance::get_policy(&state.db, id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Policy {} not found", id))?;
+        let selected: Vec<i32> = insurance::covered_equipment(&state.db, id)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        ctx.insert("policy", &policy);
+        ctx.insert("selected", &selected);
+    } else {
+        ctx.insert("selected", &Vec::<i32>::new());
+    }
+    ctx.insert("policy_id", &id);
+    ctx.insert("equipment", &equipment);
+    ctx.insert("coverage_types", COVERAGE_TYPES);
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("insurance/form.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -0,0 +1,267 @@
+use crate::export::{self, ExportQuery, Sheet};
+use crate::webhooks;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Query, RawQuery},
+    response::{Html, Redirect, Response},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct MaintenanceRecord {
+    pub id: i32,
+    pub maintenance_date: DateTime<Utc>,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub serial_number: String,
+    pub description: String,
+    pub technician: Option<String>,
+    pub cost: Option<f64>,
+    pub next_maintenance_due: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct EquipmentOption {
+    pub id: i32,
+    pub name: String,
+}
+
+/// Filters shared by the maintenance history page and its exports.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MaintenanceFilter {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub equipment_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub to: Option<NaiveDate>,
+}
+
+/// A service carried out, as entered on the maintenance page.
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceForm {
+    pub equipment_id: i32,
+    pub maintenance_date: NaiveDate,
+    pub description: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub technician: Option<String>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub cost: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub next_maintenance_due: Option<NaiveDate>,
+}
+
+// CREATE
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<MaintenanceForm>,
+) -> Result<Redirect, String> {
+    info!("Recording maintenance for equipment {}", form.equipment_id);
+
+    let description = form.description.trim();
+    if description.is_empty() {
+        return Err("A description of the work is required".to_string());
+    }
+    let tz = state.config.locale.tz();
+    let local_midnight = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|dt| dt.and_local_timezone(tz).earliest())
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok_or_else(|| format!("{} has no local midnight", date))
+    };
+    let maintenance_date = local_midnight(form.maintenance_date)?;
+    let next_due = form.next_maintenance_due.map(local_midnight).transpose()?;
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let record_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO maintenance_history
+            (equipment_id, maintenance_date, description, technician, cost, next_maintenance_due)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+        form.equipment_id,
+        maintenance_date,
+        description,
+        form.technician,
+        form.cost,
+        next_due
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        error!("Recording maintenance failed: {}", e);
+        e.to_string()
+    })?;
+
+    // The service just done replaces the schedule it fulfilled
+    if next_due.is_some() {
+        sqlx::query!(
+            r#"
+            UPDATE equipment SET next_maintenance = $2
+            WHERE id = $1 AND (next_maintenance IS NULL OR next_maintenance <= $3)
+            "#,
+            form.equipment_id,
+            next_due,
+            maintenance_date
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    let payload = serde_json::json!({
+        "id": record_id,
+        "equipment_id": form.equipment_id,
+        "maintenance_date": form.maintenance_date,
+        "description": description,
+        "technician": form.technician,
+        "cost": form.cost,
+        "next_maintenance_due": form.next_maintenance_due,
+    });
+    webhooks::enqueue(&mut tx, "maintenance.recorded", payload, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    info!("Maintenance record {} created", record_id);
+    Ok(Redirect::to(&format!("/maintenance?equipment_id={}", form.equipment_id)))
+}
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(filter): Query<MaintenanceFilter>,
+    RawQuery(query): RawQuery,
+) -> Result<Html<String>, String> {
+    info!("Listing maintenance history");
+
+    let records = fetch_history(&state.db, &filter).await?;
+    let total_cost: f64 = records.iter().filter_map(|r| r.cost).sum();
+
+    let equipment = sqlx::query_as!(
+        EquipmentOption,
+        "SELECT id, name FROM equipment ORDER BY name"
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("records", &records);
+    ctx.insert("total_cost", &total_cost);
+    ctx.insert("equipment", &equipment);
+    ctx.insert("filter", &filter);
+    ctx.insert("query", &query.unwrap_or_default());
+    ctx.insert("today", &Utc::now().with_timezone(&state.config.locale.tz()).date_naive());
+    state.render("maintenance/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+const MAINTENANCE_EXPORT_HEADERS: &[&str] = &[
+    "ID",
+    "Date",
+    "Equipment",
+    "Serial Number",
+    "Description",
+    "Technician",
+    "Cost",
+    "Next Due",
+];
+
+// EXPORT
+pub async fn export(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+    Query(filter): Query<MaintenanceFilter>,
+) -> Result<Response, String> {
+    info!("Exporting maintenance history as {:?}", format);
+
+    let records = fetch_history(&state.db, &filter).await?;
+
+    let mut sheet = Sheet::new("Maintenance", MAINTENANCE_EXPORT_HEADERS);
+    for r in records {
+        sheet.push(vec![
+            r.id.into(),
+            r.maintenance_date.into(),
+            r.equipment_name.into(),
+            r.serial_number.into(),
+            r.description.into(),
+            r.technician.into(),
+            r.cost.into(),
+            r.next_maintenance_due.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, "maintenance")
+}
+
+// Helper functions
+pub async fn fetch_history(
+    pool: &PgPool,
+    filter: &MaintenanceFilter,
+) -> Result<Vec<MaintenanceRecord>, String> {
+    sqlx::query_as!(
+        MaintenanceRecord,
+        r#"
+        SELECT
+            m.id,
+            m.maintenance_date,
+            m.equipment_id,
+            e.name as equipment_name,
+            e.serial_number,
+            m.description,
+            m.technician,
+            m.cost,
+            m.next_maintenance_due
+        FROM maintenance_history m
+        JOIN equipment e ON m.equipment_id = e.id
+        WHERE ($1::int IS NULL OR m.equipment_id = $1)
+            AND ($2::date IS NULL OR m.maintenance_date >= $2)
+            AND ($3::date IS NULL OR m.maintenance_date < $3 + 1)
+        ORDER BY m.maintenance_date DESC, m.id DESC
+        "#,
+        filter.equipment_id,
+        filter.from,
+        filter.to
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch maintenance history: {}", e);
+        e.to_string()
+    })
+}
+
+/// Rolls `equipment.next_maintenance` forward to the due date of the latest
+/// maintenance record when the scheduled service has already been carried out.
+/// Returns the number of machines whose schedule changed.
+pub async fn recompute_schedules(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE equipment e
+        SET next_maintenance = latest.next_maintenance_due
+        FROM (
+            SELECT DISTINCT ON (equipment_id)
+                equipment_id, maintenance_date, next_maintenance_due
+            FROM maintenance_history
+            ORDER BY equipment_id, maintenance_date DESC, id DESC
+        ) latest
+        WHERE e.id = latest.equipment_id
+            AND latest.next_maintenance_due IS NOT NULL
+            AND e.current_status != 'retired'
+            AND (e.next_maintenance IS NULL OR e.next_maintenance <= latest.maintenance_date)
+            AND e.next_maintenance IS DISTINCT FROM latest.next_maintenance_due
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    info!("Recomputed maintenance schedules for {} machines", result.rows_affected());
+    Ok(result.rows_affected())
+}
@@ -1,3 +1,22 @@
+pub mod archive;
+pub mod assignments;
+pub mod calendar;
+pub mod certifications;
+pub mod claims;
 pub mod categories;
+pub mod costs;
 pub mod equipment;
+pub mod geofences;
+pub mod health;
+pub mod import;
+pub mod insurance;
+pub mod maintenance;
+pub mod notifications;
+pub mod reservations;
+pub mod sites;
 pub mod staff;
+pub mod telematics;
+pub mod transfers;
+pub mod utilization;
+pub mod valuation;
+pub mod webhooks;
@@ -0,0 +1,147 @@
+use crate::notifications::{self, Mailer, SubscriptionForm, FREQUENCIES};
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Path},
+    response::{Html, Redirect},
+};
+use lettre::message::Mailbox;
+use log::{error, info, warn};
+use std::sync::Arc;
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Listing notification subscriptions");
+    render_index(&state, None).await
+}
+
+// CREATE
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<SubscriptionForm>,
+) -> Result<Html<String>, String> {
+    let email = form.email.trim().to_lowercase();
+    info!("Subscribing {} to deadline digests", email);
+
+    if let Err(e) = email.parse::<Mailbox>() {
+        return render_index(&state, Some(("error", format!("Invalid email address: {}", e)))).await;
+    }
+    if !FREQUENCIES.contains(&form.frequency.as_str()) {
+        return Err(format!("Invalid frequency '{}'", form.frequency));
+    }
+    if !(1..=365).contains(&form.days_ahead) {
+        return render_index(&state, Some(("error", "Days ahead must be between 1 and 365".to_string()))).await;
+    }
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO notification_subscriptions
+            (email, frequency, days_ahead, include_maintenance, include_insurance)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        email,
+        form.frequency,
+        form.days_ahead,
+        form.include_maintenance.is_some(),
+        form.include_insurance.is_some()
+    )
+    .execute(&state.db)
+    .await;
+
+    let flash = match result {
+        Ok(_) => ("success", format!("{} will receive {} digests", email, form.frequency)),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            ("error", format!("{} is already subscribed", email))
+        }
+        Err(e) => {
+            warn!("Subscription creation failed: {}", e);
+            return Err(e.to_string());
+        }
+    };
+    render_index(&state, Some(flash)).await
+}
+
+// TOGGLE
+pub async fn toggle(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Redirect, String> {
+    info!("Toggling notification subscription {}", id);
+
+    sqlx::query!(
+        "UPDATE notification_subscriptions SET active = NOT active WHERE id = $1",
+        id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to("/notifications"))
+}
+
+// DELETE
+pub async fn delete(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Redirect, String> {
+    info!("Deleting notification subscription {}", id);
+
+    sqlx::query!("DELETE FROM notification_subscriptions WHERE id = $1", id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to("/notifications"))
+}
+
+// SEND
+pub async fn send_now(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Sending due deadline digests on demand");
+
+    let Some(mailer) = Mailer::from_config(&state.config.smtp)? else {
+        return render_index(&state, Some(("error", "SMTP is not configured".to_string()))).await;
+    };
+    let outcomes = notifications::send_due_digests(&state.db, &state.templates, &state.config, Some(&mailer))
+        .await
+        .map_err(|e| {
+            error!("Sending digests failed: {}", e);
+            e.to_string()
+        })?;
+
+    let sent = outcomes.iter().filter(|o| o.sent).count();
+    let failed: Vec<_> = outcomes
+        .iter()
+        .filter_map(|o| o.error.as_ref().map(|e| format!("{}: {}", o.email, e)))
+        .collect();
+    let flash = if failed.is_empty() {
+        ("success", format!("{} digests sent, {} subscribers had nothing new", sent, outcomes.len() - sent))
+    } else {
+        ("error", format!("{} digests sent; failed: {}", sent, failed.join("; ")))
+    };
+    render_index(&state, Some(flash)).await
+}
+
+// Helper functions
+async fn render_index(
+    state: &AppState,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let subscriptions = notifications::list_subscriptions(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("subscriptions", &subscriptions);
+    ctx.insert("frequencies", FREQUENCIES);
+    ctx.insert("smtp_enabled", &state.config.smtp.is_enabled());
+    ctx.insert("default_days", &state.config.reminders.maintenance_days);
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("notifications/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -0,0 +1,297 @@
+use crate::handlers::categories::fetch_categories;
+use crate::handlers::equipment::{fetch_equipment, EquipmentFilter};
+use crate::reservations::{
+    self, Conflict, DecisionForm, ReservationFilter, ReservationForm, RESERVATION_STATUSES,
+};
+use crate::sites;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Path, Query},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use chrono::{Datelike, Months, Utc};
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Preselects the machine, site and first day when booking from another page.
+#[derive(Debug, Default, Deserialize)]
+pub struct NewReservationQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub equipment_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub site_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub start_date: Option<String>,
+}
+
+/// The calendar shows one category for one month (`YYYY-MM`).
+#[derive(Debug, Default, Deserialize)]
+pub struct CalendarQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub category_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub month: Option<String>,
+}
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(filter): Query<ReservationFilter>,
+) -> Result<Html<String>, String> {
+    info!("Listing reservations");
+
+    let reservations = reservations::list_reservations(&state.db, &filter)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch reservations: {}", e);
+            e.to_string()
+        })?;
+    let categories = fetch_categories(&state.db).await?;
+    let sites = sites::list_sites(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("reservations", &reservations);
+    ctx.insert("filter", &filter);
+    ctx.insert("categories", &categories);
+    ctx.insert("sites", &sites);
+    ctx.insert("statuses", RESERVATION_STATUSES);
+    state.render("reservations/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// NEW FORM
+pub async fn new_form(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<NewReservationQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving new reservation form");
+    render_form(&state, &query, None, &[], None).await
+}
+
+// CREATE
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<ReservationForm>,
+) -> Result<Response, String> {
+    info!("Requesting equipment {} for site {} from {} to {}", form.equipment_id, form.site_id, form.start_date, form.end_date);
+
+    let query = NewReservationQuery::default();
+    if let Err(message) = form.validate() {
+        return render_form(&state, &query, Some(&form), &[], Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let availability = reservations::availability(&mut tx, form.equipment_id, None, &state.config.locale.timezone)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Equipment {} not found", form.equipment_id))?;
+    let conflicts = reservations::find_conflicts(&availability, form.start_date, form.end_date);
+    if conflicts.iter().any(|c| c.blocking) {
+        warn!("Refusing reservation of equipment {}: machine unavailable", form.equipment_id);
+        let message = format!("{} is not available for these dates", availability.equipment_name);
+        return render_form(&state, &query, Some(&form), &conflicts, Some(message)).await.map(IntoResponse::into_response);
+    }
+    let id = reservations::create_reservation(&mut tx, &form)
+        .await
+        .map_err(|e| {
+            error!("Reservation creation failed: {}", e);
+            e.to_string()
+        })?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    info!("Reservation {} requested", id);
+    Ok(Redirect::to(&format!("/reservations/{}", id)).into_response())
+}
+
+// SHOW
+pub async fn show(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Showing reservation {}", id);
+    render_show(&state, id, None).await
+}
+
+// DECIDE
+pub async fn decide(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<DecisionForm>,
+) -> Result<Response, String> {
+    info!("Moving reservation {} to {}", id, form.status);
+
+    let reservation = reservations::get_reservation(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Reservation {} not found", id))?;
+    if let Err(message) = reservations::check_transition(&reservation.status, &form.status) {
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    if form.decided_by.trim().is_empty() {
+        let message = "Enter your name to record the decision".to_string();
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    if form.status == "approved" {
+        // Recheck under the machine lock: another booking may have been approved since
+        let availability = reservations::availability(&mut tx, reservation.equipment_id, Some(id), &state.config.locale.timezone)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Equipment {} not found", reservation.equipment_id))?;
+        let conflicts = reservations::find_conflicts(&availability, reservation.start_date, reservation.end_date);
+        let reasons: Vec<&str> = conflicts.iter().filter(|c| c.blocking).map(|c| c.message.as_str()).collect();
+        if !reasons.is_empty() {
+            warn!("Refusing to approve reservation {}", id);
+            let message = format!("Cannot approve: {}", reasons.join("; "));
+            // Release the machine lock before the page reloads availability
+            tx.rollback().await.map_err(|e| e.to_string())?;
+            return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+        }
+    }
+    let updated = reservations::decide(&mut tx, id, &reservation.status, &form)
+        .await
+        .map_err(|e| {
+            error!("Reservation {} decision failed: {}", id, e);
+            e.to_string()
+        })?;
+    if !updated {
+        warn!("Reservation {} was decided concurrently", id);
+        let message = "The reservation was decided in the meantime".to_string();
+        tx.rollback().await.map_err(|e| e.to_string())?;
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/reservations/{}", id)).into_response())
+}
+
+// CALENDAR
+pub async fn calendar(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<CalendarQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving booking calendar for category {:?}", query.category_id);
+
+    let categories = fetch_categories(&state.db).await?;
+    let today = Utc::now().with_timezone(&state.config.locale.tz()).date_naive();
+    let first = query
+        .month
+        .as_deref()
+        .and_then(reservations::parse_month)
+        .unwrap_or_else(|| today.with_day(1).unwrap_or(today));
+    let days = reservations::month_days(first);
+    let last = days.last().copied().unwrap_or(first);
+    let category_id = query.category_id.or_else(|| categories.first().map(|c| c.id));
+
+    let rows = match category_id {
+        Some(category_id) => {
+            let machines = reservations::calendar_machines(&state.db, category_id, &state.config.locale.timezone)
+                .await
+                .map_err(|e| e.to_string())?;
+            let bookings = reservations::bookings_between(&state.db, category_id, first, last)
+                .await
+                .map_err(|e| e.to_string())?;
+            reservations::build_calendar(machines, &bookings, &days)
+        }
+        None => Vec::new(),
+    };
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("categories", &categories);
+    ctx.insert("category_id", &category_id);
+    ctx.insert("month", &first);
+    ctx.insert("previous", &first.checked_sub_months(Months::new(1)).unwrap_or(first).format("%Y-%m").to_string());
+    ctx.insert("next", &first.checked_add_months(Months::new(1)).unwrap_or(first).format("%Y-%m").to_string());
+    ctx.insert("days", &days);
+    ctx.insert("rows", &rows);
+    ctx.insert("today", &today);
+    state.render("reservations/calendar.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// Helper functions
+/// The reservation with what currently stands in its way, if it is still open.
+async fn render_show(
+    state: &AppState,
+    id: i32,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let reservation = reservations::get_reservation(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Reservation {} not found", id))?;
+
+    let mut conflicts = Vec::new();
+    if matches!(reservation.status.as_str(), "pending" | "approved") {
+        let mut conn = state.db.acquire().await.map_err(|e| e.to_string())?;
+        let availability =
+            reservations::current_availability(&mut conn, reservation.equipment_id, Some(id), &state.config.locale.timezone)
+                .await
+                .map_err(|e| e.to_string())?;
+        if let Some(availability) = availability {
+            conflicts = reservations::find_conflicts(&availability, reservation.start_date, reservation.end_date);
+        }
+    }
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("reservation", &reservation);
+    ctx.insert("conflicts", &conflicts);
+    ctx.insert("statuses", RESERVATION_STATUSES);
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("reservations/show.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// The request form; `submitted` refills it after a validation error or a
+/// blocking conflict.
+async fn render_form(
+    state: &AppState,
+    query: &NewReservationQuery,
+    submitted: Option<&ReservationForm>,
+    conflicts: &[Conflict],
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let equipment: Vec<_> = fetch_equipment(&state.db, &EquipmentFilter::default())
+        .await?
+        .into_iter()
+        .filter(|e| e.status != "retired")
+        .collect();
+    let sites: Vec<_> = sites::list_sites(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|s| s.state != "completed")
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    if let Some(form) = submitted {
+        ctx.insert("reservation", form);
+    } else {
+        ctx.insert(
+            "reservation",
+            &serde_json::json!({
+                "equipment_id": query.equipment_id,
+                "site_id": query.site_id,
+                "start_date": query.start_date,
+            }),
+        );
+    }
+    ctx.insert("equipment", &equipment);
+    ctx.insert("sites", &sites);
+    ctx.insert("conflicts", conflicts);
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("reservations/form.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -0,0 +1,225 @@
+use crate::handlers::equipment::{fetch_equipment, EquipmentFilter};
+use crate::sites::{self, DeployForm, SiteForm};
+use crate::transfers;
+use crate::AppState;
+use axum::{
+    extract::{Extension, Form, Path},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::sync::Arc;
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Listing sites");
+
+    let sites = sites::list_sites(&state.db)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch sites: {}", e);
+            e.to_string()
+        })?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("sites", &sites);
+    state.render("sites/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// NEW FORM
+pub async fn new_form(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Serving new site form");
+    render_form(&state, None, None, None).await
+}
+
+// CREATE
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<SiteForm>,
+) -> Result<Response, String> {
+    info!("Creating site {}", form.name);
+
+    if let Err(message) = form.validate() {
+        return render_form(&state, None, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let id = sites::create_site(&state.db, &form)
+        .await
+        .map_err(|e| {
+            error!("Site creation failed: {}", e);
+            e.to_string()
+        })?;
+
+    info!("Site {} created", id);
+    Ok(Redirect::to(&format!("/sites/{}", id)).into_response())
+}
+
+// SHOW
+pub async fn show(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Showing site {}", id);
+    render_show(&state, id, None).await
+}
+
+// EDIT FORM
+pub async fn edit_form(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Editing site {}", id);
+    render_form(&state, Some(id), None, None).await
+}
+
+// UPDATE
+pub async fn update(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<SiteForm>,
+) -> Result<Response, String> {
+    info!("Updating site {}", id);
+
+    if let Err(message) = form.validate() {
+        return render_form(&state, Some(id), Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let updated = sites::update_site(&state.db, id, &form)
+        .await
+        .map_err(|e| {
+            error!("Site {} update failed: {}", id, e);
+            e.to_string()
+        })?;
+    if !updated {
+        return Err(format!("Site {} not found", id));
+    }
+
+    Ok(Redirect::to(&format!("/sites/{}", id)).into_response())
+}
+
+// DELETE
+pub async fn delete(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Response, String> {
+    info!("Deleting site {}", id);
+
+    let problem = sites::delete_site(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(message) = problem {
+        warn!("Refusing to delete site {}: {}", id, message);
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+
+    Ok(Redirect::to("/sites").into_response())
+}
+
+// DEPLOY
+pub async fn deploy(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<DeployForm>,
+) -> Result<Response, String> {
+    info!("Deploying equipment {} to site {} on {}", form.equipment_id, id, form.date);
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let mut problem = sites::move_problem(&mut tx, form.equipment_id, Some(id), form.date)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Checked under the machine lock, which dispatching a transfer takes too
+    if problem.is_none() {
+        let transfer = transfers::open_transfer(&mut tx, form.equipment_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        problem = transfer.map(|t| {
+            format!("{} is in transit (transfer {}); record its arrival instead", t.equipment_name, t.id)
+        });
+    }
+    if let Some(message) = problem {
+        warn!("Refusing to deploy equipment {} to site {}: {}", form.equipment_id, id, message);
+        tx.rollback().await.map_err(|e| e.to_string())?;
+        return render_show(&state, id, Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    sites::move_equipment(&mut tx, form.equipment_id, Some(id), form.date, form.notes.as_deref())
+        .await
+        .map_err(|e| {
+            error!("Deployment to site {} failed: {}", id, e);
+            e.to_string()
+        })?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to(&format!("/sites/{}", id)).into_response())
+}
+
+// Helper functions
+/// The site with its machines and operators today, the machines that can
+/// be brought in and the site's deployment history.
+async fn render_show(
+    state: &AppState,
+    id: i32,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let site = sites::get_site(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Site {} not found", id))?;
+    let history = sites::deployments(&state.db, Some(id), None)
+        .await
+        .map_err(|e| e.to_string())?;
+    let operators = sites::site_operators(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let available: Vec<_> = fetch_equipment(&state.db, &EquipmentFilter::default())
+        .await?
+        .into_iter()
+        .filter(|e| e.status != "retired" && e.site_id != Some(id))
+        .collect();
+    let (machines, history): (Vec<_>, Vec<_>) = history.into_iter().partition(|d| d.current);
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("site", &site);
+    ctx.insert("machines", &machines);
+    ctx.insert("operators", &operators);
+    ctx.insert("history", &history);
+    ctx.insert("available", &available);
+    ctx.insert("today", &Utc::now().with_timezone(&state.config.locale.tz()).date_naive());
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("sites/show.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// The create/edit form; `submitted` refills it after a validation error.
+async fn render_form(
+    state: &AppState,
+    id: Option<i32>,
+    submitted: Option<&SiteForm>,
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let mut ctx = tera::Context::new();
+    if let Some(form) = submitted {
+        ctx.insert("site", form);
+    } else if let Some(id) = id {
+        let site = sites::get_site(&state.db, id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Site {} not found", id))?;
+        ctx.insert("site", &site);
+    }
+    ctx.insert("site_id", &id);
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("sites/form.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -1,7 +1,9 @@
+use crate::assignments::{self, Violation};
+use crate::export::{self, ExportQuery, Sheet};
 use crate::AppState;
 use axum::{
-    extract::{Extension, Path},
-    response::{Html, Redirect},
+    extract::{Extension, Path, Query},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::Form; // Use axum_extra's Form
 use log::{info, warn};
@@ -27,7 +29,7 @@ pub struct EquipmentShort {
     pub status: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct StaffForm {
     pub full_name: String,
     pub contact_info: Option<String>,
@@ -49,7 +51,7 @@ pub struct StaffList {
 pub async fn create(
     Extension(state): Extension<Arc<AppState>>,
     Form(form): Form<StaffForm>,
-) -> Result<Redirect, String> {
+) -> Result<Response, String> {
     info!("Creating new staff: {}", form.full_name);
     
     let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
@@ -72,12 +74,24 @@ pub async fn create(
     })?
     .id;
     
-    update_equipment_assignments(&mut tx, staff_id, &form.assigned_equipment).await?;
+    let violations = assignments::set_operator_equipment(
+        &mut tx,
+        staff_id,
+        &form.assigned_equipment,
+        &state.config.assignments,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    if violations.iter().any(|v| v.blocking) {
+        // Dropping the transaction discards the new staff member too
+        warn!("Refusing assignments for new staff '{}'", form.full_name);
+        return render_form(&state, None, Some(&form), &violations).await.map(IntoResponse::into_response);
+    }
 
     tx.commit().await.map_err(|e| e.to_string())?;
 
     info!("Staff '{}' created successfully", form.full_name);
-    Ok(Redirect::to("/staff"))
+    Ok(saved_redirect(staff_id, &violations).into_response())
 }
 
 // LIST
@@ -86,69 +100,52 @@ pub async fn list(
 ) -> Result<Html<String>, String> {
     info!("Listing staff");
     
-    let staff = sqlx::query!(
-        r#"
-        SELECT 
-            s.id, 
-            s.full_name, 
-            s.contact_info as "contact_info?",
-            s.license_number as "license_number?",
-            COALESCE(
-                ARRAY_AGG(e.name) FILTER (WHERE e.name IS NOT NULL), 
-                ARRAY[]::text[]
-            ) as "equipment_names!: Vec<String>"
-        FROM staff s
-        LEFT JOIN equipment_operator eo ON eo.operator_id = s.id
-        LEFT JOIN equipment e ON eo.equipment_id = e.id
-        GROUP BY s.id
-        ORDER BY s.full_name
-        "#
-    )
-    .map(|row| StaffList {
-        id: row.id,
-        full_name: row.full_name,
-        contact_info: row.contact_info,
-        license_number: row.license_number,
-        equipment_names: row.equipment_names,
-    })
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| {
-        warn!("Failed to fetch staff: {}", e);
-        e.to_string()
-    })?;
+    let staff = fetch_staff_list(&state.db).await?;
 
     let mut ctx = tera::Context::new();
     ctx.insert("staff", &staff);
-    state.templates.render("staff/index.html", &ctx)
+    state.render("staff/index.html", &ctx)
         .map_err(|e| e.to_string())
         .map(Html)
 }
 
+const STAFF_EXPORT_HEADERS: &[&str] = &[
+    "ID",
+    "Full Name",
+    "Contact Information",
+    "License Number",
+    "Assigned Equipment",
+];
+
+// EXPORT
+pub async fn export(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+) -> Result<Response, String> {
+    info!("Exporting staff as {:?}", format);
+
+    let staff = fetch_staff_list(&state.db).await?;
+
+    let mut sheet = Sheet::new("Staff", STAFF_EXPORT_HEADERS);
+    for person in staff {
+        sheet.push(vec![
+            person.id.into(),
+            person.full_name.into(),
+            person.contact_info.into(),
+            person.license_number.into(),
+            person.equipment_names.join(", ").into(),
+        ]);
+    }
+
+    export::download(&sheet, format, "staff")
+}
+
 // NEW FORM
 pub async fn new_form(
     Extension(state): Extension<Arc<AppState>>,
 ) -> Result<Html<String>, String> {
     info!("Serving new staff form");
-        
-    let equipment = sqlx::query_as!(
-        EquipmentShort,
-        "SELECT id, name, brand, model, current_status as \"status!\" FROM equipment ORDER BY name"
-    )
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
-
-    // Create an empty vector for assigned equipment IDs
-    let assigned_equipment_ids: Vec<i32> = Vec::new();
-
-    let mut ctx = tera::Context::new();
-    ctx.insert("equipment", &equipment);
-    ctx.insert("assigned_equipment_ids", &assigned_equipment_ids);  // Add this line
-    
-    state.templates.render("staff/new.html", &ctx)
-        .map_err(|e| e.to_string())
-        .map(Html)
+    render_form(&state, None, None, &[]).await
 }
 
 // EDIT FORM
@@ -157,53 +154,17 @@ pub async fn edit_form(
     Extension(state): Extension<Arc<AppState>>,
 ) -> Result<Html<String>, String> {
     info!("Editing staff ID: {}", id);
-    
-    #[derive(Serialize)]
-    struct StaffRecord {
-        id: i32,
-        full_name: String,
-        contact_info: Option<String>,
-        license_number: Option<String>,
-    }
-    
-    let staff = sqlx::query_as!(
-        StaffRecord,
-        "SELECT id, full_name, contact_info, license_number FROM staff WHERE id = $1",
-        id
-    )
-    .fetch_one(&state.db)
-    .await
-    .map_err(|e| {
-        warn!("Staff {} not found: {}", id, e);
-        e.to_string()
-    })?;
 
-    let equipment = sqlx::query_as!(
-        EquipmentShort,
-        "SELECT id, name, brand, model, current_status as \"status!\" FROM equipment ORDER BY name"
-    )
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
-
-    let assigned_equipment = sqlx::query!(
-        "SELECT equipment_id FROM equipment_operator WHERE operator_id = $1",
-        id
-    )
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?
-    .into_iter()
-    .map(|r| r.equipment_id)
-    .collect::<Vec<_>>();
+    // Rules the current assignments break, e.g. since a certification lapsed
+    let assigned = assignments::current_equipment_ids(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut conn = state.db.acquire().await.map_err(|e| e.to_string())?;
+    let violations = assignments::check(&mut conn, Some(id), &assigned, &state.config.assignments)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    let mut ctx = tera::Context::new();
-    ctx.insert("staff", &staff);
-    ctx.insert("equipment", &equipment);
-    ctx.insert("assigned_equipment_ids", &assigned_equipment);
-    state.templates.render("staff/edit.html", &ctx)
-        .map_err(|e| e.to_string())
-        .map(Html)
+    render_form(&state, Some(id), None, &violations).await
 }
 
 // UPDATE
@@ -211,7 +172,7 @@ pub async fn update(
     Path(id): Path<i32>,
     Extension(state): Extension<Arc<AppState>>,
     Form(form): Form<StaffForm>,
-) -> Result<Redirect, String> {
+) -> Result<Response, String> {
     info!("Updating staff ID: {}", id);
     
     let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
@@ -236,12 +197,23 @@ pub async fn update(
         e.to_string()
     })?;
     
-    update_equipment_assignments(&mut tx, id, &form.assigned_equipment).await?;
+    let violations = assignments::set_operator_equipment(
+        &mut tx,
+        id,
+        &form.assigned_equipment,
+        &state.config.assignments,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    if violations.iter().any(|v| v.blocking) {
+        warn!("Refusing assignments for staff {}", id);
+        return render_form(&state, Some(id), Some(&form), &violations).await.map(IntoResponse::into_response);
+    }
 
     tx.commit().await.map_err(|e| e.to_string())?;
 
     info!("Staff {} updated successfully", id);
-    Ok(Redirect::to("/staff"))
+    Ok(saved_redirect(id, &violations).into_response())
 }
 
 // DELETE
@@ -252,7 +224,7 @@ pub async fn delete(
     info!("Deleting staff ID: {}", id);
     
     let assignment_count: i64 = sqlx::query_scalar!(
-        "SELECT COUNT(*) FROM equipment_operator WHERE operator_id = $1",
+        "SELECT COUNT(*) FROM equipment_operator WHERE operator_id = $1 AND assigned_to IS NULL",
         id
     )
     .fetch_one(&state.db)
@@ -281,30 +253,101 @@ pub async fn delete(
 }
 
 // Helper functions
-async fn update_equipment_assignments(
-    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-    staff_id: i32,
-    equipment_ids: &[i32],
-) -> Result<(), String> {
+pub async fn fetch_staff_list(pool: &PgPool) -> Result<Vec<StaffList>, String> {
     sqlx::query!(
-        "DELETE FROM equipment_operator WHERE operator_id = $1",
-        staff_id
+        r#"
+        SELECT 
+            s.id, 
+            s.full_name, 
+            s.contact_info as "contact_info?",
+            s.license_number as "license_number?",
+            COALESCE(
+                ARRAY_AGG(e.name ORDER BY e.name) FILTER (WHERE e.name IS NOT NULL), 
+                ARRAY[]::text[]
+            ) as "equipment_names!: Vec<String>"
+        FROM staff s
+        LEFT JOIN equipment_operator eo ON eo.operator_id = s.id AND eo.assigned_to IS NULL
+        LEFT JOIN equipment e ON eo.equipment_id = e.id
+        GROUP BY s.id
+        ORDER BY s.full_name
+        "#
     )
-    .execute(&mut **tx)
+    .map(|row| StaffList {
+        id: row.id,
+        full_name: row.full_name,
+        contact_info: row.contact_info,
+        license_number: row.license_number,
+        equipment_names: row.equipment_names,
+    })
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        warn!("Failed to fetch staff: {}", e);
+        e.to_string()
+    })
+}
+
+/// Back to the list, or to the edit form when the save left warnings to read.
+fn saved_redirect(staff_id: i32, violations: &[Violation]) -> Redirect {
+    if violations.is_empty() {
+        Redirect::to("/staff")
+    } else {
+        Redirect::to(&format!("/staff/{}/edit", staff_id))
+    }
+}
+
+/// The new/edit form; `submitted` refills it after assignments were refused.
+async fn render_form(
+    state: &AppState,
+    id: Option<i32>,
+    submitted: Option<&StaffForm>,
+    violations: &[Violation],
+) -> Result<Html<String>, String> {
+    let equipment = sqlx::query_as!(
+        EquipmentShort,
+        "SELECT id, name, brand, model, current_status as \"status!\" FROM equipment ORDER BY name"
+    )
+    .fetch_all(&state.db)
     .await
     .map_err(|e| e.to_string())?;
-    
-    for equipment_id in equipment_ids {
-        sqlx::query!(
-            "INSERT INTO equipment_operator (operator_id, equipment_id) VALUES ($1, $2)",
-            staff_id,
-            equipment_id
+
+    let mut ctx = tera::Context::new();
+    if let Some(form) = submitted {
+        ctx.insert("staff", &serde_json::json!({
+            "id": id,
+            "full_name": form.full_name,
+            "contact_info": form.contact_info,
+            "license_number": form.license_number,
+        }));
+        ctx.insert("assigned_equipment_ids", &form.assigned_equipment);
+    } else if let Some(id) = id {
+        let staff = sqlx::query_as!(
+            Staff,
+            "SELECT id, full_name, contact_info, license_number FROM staff WHERE id = $1",
+            id
         )
-        .execute(&mut **tx)
+        .fetch_one(&state.db)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| {
+            warn!("Staff {} not found: {}", id, e);
+            e.to_string()
+        })?;
+        ctx.insert("staff", &staff);
+        let assigned = assignments::current_equipment_ids(&state.db, id)
+            .await
+            .map_err(|e| e.to_string())?;
+        ctx.insert("assigned_equipment_ids", &assigned);
+    } else {
+        ctx.insert("assigned_equipment_ids", &Vec::<i32>::new());
     }
-    
-    Ok(())
+    ctx.insert("equipment", &equipment);
+    ctx.insert("violations", violations);
+    if violations.iter().any(|v| v.blocking) {
+        let message = "Some assignments break the assignment rules; nothing was saved";
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    let template = if id.is_some() { "staff/edit.html" } else { "staff/new.html" };
+    state.render(template, &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
 }
-
@@ -0,0 +1,52 @@
+use crate::telematics::{self, IngestReport, PingBatch};
+use crate::AppState;
+use axum::{
+    extract::Extension,
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::sync::Arc;
+
+// INGEST
+pub async fn ingest(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(batch): Json<PingBatch>,
+) -> Result<Json<IngestReport>, (StatusCode, String)> {
+    let config = &state.config.telematics;
+    let Some(api_key) = config.api_key.as_deref() else {
+        // Ingestion is off until a key is configured
+        return Err((StatusCode::NOT_FOUND, "Page not found".to_string()));
+    };
+    let authorization = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+    if !telematics::authorized(authorization, api_key) {
+        warn!("Telematics batch refused: missing or wrong API key");
+        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
+    }
+    if batch.pings.len() > config.max_batch {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("At most {} pings per batch", config.max_batch),
+        ));
+    }
+
+    let report = telematics::ingest(&state.db, &batch.pings, Utc::now(), &state.config)
+        .await
+        .map_err(|e| {
+            error!("Telematics ingestion failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+    info!(
+        "Telematics batch of {}: {} accepted, {} duplicates, {} rejected",
+        batch.pings.len(),
+        report.accepted,
+        report.duplicates,
+        report.rejected.len()
+    );
+    if report.alerts > 0 {
+        warn!("{} machines left their site outside working hours", report.alerts);
+    }
+    Ok(Json(report))
+}
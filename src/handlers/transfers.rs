@@ -0,0 +1,245 @@
+use crate::handlers::equipment::{fetch_equipment, EquipmentFilter};
+use crate::handlers::staff::fetch_staff_list;
+use crate::sites;
+use crate::transfers::{
+    self, ArrivalForm, DispatchForm, TransferFilter, CHECKLIST_ITEMS, CONDITIONS, TRANSFER_STATUSES,
+};
+use crate::AppState;
+use axum::{
+    extract::{Extension, Path, Query},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::Form; // Repeated item/condition checklist fields
+use chrono::Utc;
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Preselects the machine when dispatching from its page.
+#[derive(Debug, Default, Deserialize)]
+pub struct NewTransferQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub equipment_id: Option<i32>,
+}
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(filter): Query<TransferFilter>,
+) -> Result<Html<String>, String> {
+    info!("Listing transfers");
+
+    let transfers = transfers::list_transfers(&state.db, &filter)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch transfers: {}", e);
+            e.to_string()
+        })?;
+    let sites = sites::list_sites(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("transfers", &transfers);
+    ctx.insert("filter", &filter);
+    ctx.insert("sites", &sites);
+    ctx.insert("statuses", TRANSFER_STATUSES);
+    state.render("transfers/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// NEW FORM
+pub async fn new_form(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<NewTransferQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving new transfer form");
+    render_form(&state, &query, None, None).await
+}
+
+// DISPATCH
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<DispatchForm>,
+) -> Result<Response, String> {
+    info!("Dispatching equipment {} to site {:?} on {}", form.equipment_id, form.destination_site_id, form.dispatch_date);
+
+    let query = NewTransferQuery::default();
+    if let Err(message) = form.validate() {
+        return render_form(&state, &query, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let problem = transfers::dispatch_problem(&mut tx, &form)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(message) = problem {
+        warn!("Refusing to dispatch equipment {}: {}", form.equipment_id, message);
+        tx.rollback().await.map_err(|e| e.to_string())?;
+        return render_form(&state, &query, Some(&form), Some(message)).await.map(IntoResponse::into_response);
+    }
+    let id = transfers::dispatch(&mut tx, &form)
+        .await
+        .map_err(|e| {
+            error!("Transfer creation failed: {}", e);
+            e.to_string()
+        })?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    info!("Transfer {} dispatched", id);
+    Ok(Redirect::to(&format!("/transfers/{}", id)).into_response())
+}
+
+// SHOW
+pub async fn show(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Showing transfer {}", id);
+    render_show(&state, id, None, None).await
+}
+
+// RECEIVE
+pub async fn receive(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<ArrivalForm>,
+) -> Result<Response, String> {
+    info!("Receiving transfer {} on {}", id, form.arrival_date);
+
+    let transfer = transfers::get_transfer(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Transfer {} not found", id))?;
+    let dispatched = transfers::transfer_checks(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut tx = state.db.begin().await.map_err(|e| e.to_string())?;
+    let problem = transfers::arrival_problem(&mut tx, &transfer, &form)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(message) = problem {
+        warn!("Refusing to receive transfer {}: {}", id, message);
+        tx.rollback().await.map_err(|e| e.to_string())?;
+        return render_show(&state, id, Some(&form), Some(("error", message))).await.map(IntoResponse::into_response);
+    }
+    let received = transfers::receive(&mut tx, &transfer, &dispatched, &form)
+        .await
+        .map_err(|e| {
+            error!("Transfer {} arrival failed: {}", id, e);
+            e.to_string()
+        })?;
+    let Some(damage_candidate) = received else {
+        tx.rollback().await.map_err(|e| e.to_string())?;
+        let message = "The machine was received in the meantime".to_string();
+        return render_show(&state, id, None, Some(("error", message))).await.map(IntoResponse::into_response);
+    };
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    if damage_candidate {
+        warn!("Transfer {} arrived with damage to {}", id, transfer.equipment_name);
+    }
+    Ok(Redirect::to(&format!("/transfers/{}", id)).into_response())
+}
+
+// Helper functions
+/// The transfer with both checklists side by side; `submitted` refills the
+/// arrival form after it was refused.
+async fn render_show(
+    state: &AppState,
+    id: i32,
+    submitted: Option<&ArrivalForm>,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let transfer = transfers::get_transfer(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Transfer {} not found", id))?;
+    let checks = transfers::transfer_checks(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let rows = transfers::compare_checklists(&checks);
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("transfer", &transfer);
+    ctx.insert("rows", &rows);
+    ctx.insert("items", CHECKLIST_ITEMS);
+    ctx.insert("conditions", CONDITIONS);
+    ctx.insert("today", &Utc::now().with_timezone(&state.config.locale.tz()).date_naive());
+    if let Some(form) = submitted {
+        ctx.insert("arrival", form);
+        ctx.insert("checked", &checked(&form.item, &form.condition));
+    } else {
+        // Start the arrival checklist from what was recorded at dispatch
+        let dispatched: HashMap<&str, Option<&str>> = rows.iter().map(|r| (r.item, r.dispatched.as_deref())).collect();
+        ctx.insert("checked", &dispatched);
+    }
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("transfers/show.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// The dispatch form; `submitted` refills it after a validation error or a
+/// refused move.
+async fn render_form(
+    state: &AppState,
+    query: &NewTransferQuery,
+    submitted: Option<&DispatchForm>,
+    error: Option<String>,
+) -> Result<Html<String>, String> {
+    let equipment: Vec<_> = fetch_equipment(&state.db, &EquipmentFilter::default())
+        .await?
+        .into_iter()
+        .filter(|e| e.status != "retired")
+        .collect();
+    let sites: Vec<_> = sites::list_sites(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|s| s.state != "completed")
+        .collect();
+    let staff = fetch_staff_list(&state.db).await?;
+
+    let mut ctx = tera::Context::new();
+    if let Some(form) = submitted {
+        ctx.insert("transfer", form);
+        ctx.insert("checked", &checked(&form.item, &form.condition));
+    } else {
+        ctx.insert(
+            "transfer",
+            &serde_json::json!({
+                "equipment_id": query.equipment_id,
+                "destination_site_id": null,
+                "driver_id": null,
+                "dispatch_date": Utc::now().with_timezone(&state.config.locale.tz()).date_naive(),
+            }),
+        );
+        ctx.insert("checked", &HashMap::<&str, &str>::new());
+    }
+    ctx.insert("equipment", &equipment);
+    ctx.insert("sites", &sites);
+    ctx.insert("staff", &staff);
+    ctx.insert("items", CHECKLIST_ITEMS);
+    ctx.insert("conditions", CONDITIONS);
+    if let Some(message) = error {
+        ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+    }
+    state.render("transfers/form.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+/// The condition submitted for each checklist item, to preselect it again.
+fn checked<'a>(items: &'a [String], conditions: &'a [String]) -> HashMap<&'a str, &'a str> {
+    items
+        .iter()
+        .zip(conditions)
+        .map(|(item, condition)| (item.as_str(), condition.as_str()))
+        .collect()
+}
@@ -0,0 +1,165 @@
+use crate::export::{self, ExportQuery, Sheet};
+use crate::handlers::categories::fetch_categories;
+use crate::utilization::{self, Totals, UtilizationQuery, PERIODS};
+use crate::AppState;
+use axum::{
+    extract::{Extension, Query},
+    response::{Html, Response},
+};
+use chrono::{NaiveDate, Utc};
+use log::{error, info};
+use std::sync::Arc;
+
+/// Weeks or months shown in the trend.
+const TREND_PERIODS: usize = 12;
+
+// OVERVIEW
+pub async fn index(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<UtilizationQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving utilization for category {:?}", query.category_id);
+
+    let categories = fetch_categories(&state.db).await?;
+    let period = match query.period.as_deref() {
+        Some("month") => "month",
+        _ => "week",
+    };
+    let mut ctx = tera::Context::new();
+    let (from, to) = match range(&state, query.from, query.to) {
+        Ok(range) => range,
+        Err(message) => {
+            ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+            range(&state, None, None)?
+        }
+    };
+
+    let periods = utilization::periods(period, to, TREND_PERIODS);
+    let earliest = periods.first().map(|p| p.1).unwrap_or(from).min(from);
+    let fleet = utilization::load(&state.db, earliest, to, &state.config.locale.timezone, query.category_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to load utilization: {}", e);
+            e.to_string()
+        })?;
+    let work_days = &state.config.geofences.work_days;
+    let machines = fleet.usage(from, to, work_days, &state.config.utilization);
+    let trend = fleet.trend(&periods, work_days, &state.config.utilization);
+
+    ctx.insert("categories", &categories);
+    ctx.insert("category_id", &query.category_id);
+    ctx.insert("from", &from);
+    ctx.insert("to", &to);
+    ctx.insert("period", period);
+    ctx.insert("periods", PERIODS);
+    ctx.insert("totals", &Totals::of(&machines));
+    ctx.insert("by_category", &utilization::by_category(&machines));
+    ctx.insert("trend", &trend);
+    ctx.insert("machines", &machines);
+    ctx.insert("shift_hours", &state.config.utilization.shift_hours);
+    ctx.insert("threshold", &state.config.utilization.underused_idle_percent);
+    state.render("utilization/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+// UNDERUSED REPORT
+pub async fn underused(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<UtilizationQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving underused assets report");
+
+    let categories = fetch_categories(&state.db).await?;
+    let mut ctx = tera::Context::new();
+    let (from, to) = match range(&state, query.from, query.to) {
+        Ok(range) => range,
+        Err(message) => {
+            ctx.insert("flash", &serde_json::json!({ "type": "error", "message": message }));
+            range(&state, None, None)?
+        }
+    };
+    let machines = underused_between(&state, from, to, query.category_id).await?;
+
+    ctx.insert("categories", &categories);
+    ctx.insert("category_id", &query.category_id);
+    ctx.insert("from", &from);
+    ctx.insert("to", &to);
+    ctx.insert("machines", &machines);
+    ctx.insert("threshold", &state.config.utilization.underused_idle_percent);
+    state.render("utilization/underused.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+const UNDERUSED_EXPORT_HEADERS: &[&str] = &[
+    "Rank",
+    "Equipment",
+    "Serial Number",
+    "Category",
+    "Working Days",
+    "Idle Days",
+    "Idle Share",
+    "Deployed Days",
+    "Hours Used",
+    "Hours Available",
+    "Utilization",
+];
+
+// UNDERUSED EXPORT
+pub async fn export_underused(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<UtilizationQuery>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+) -> Result<Response, String> {
+    info!("Exporting underused assets report as {:?}", format);
+
+    let (from, to) = range(&state, query.from, query.to)?;
+    let machines = underused_between(&state, from, to, query.category_id).await?;
+
+    let mut sheet = Sheet::new("Underused Assets", UNDERUSED_EXPORT_HEADERS);
+    for (rank, m) in machines.into_iter().enumerate() {
+        sheet.push(vec![
+            (rank as i64 + 1).into(),
+            m.name.into(),
+            m.serial_number.into(),
+            m.category_name.into(),
+            m.working_days.into(),
+            m.idle_days.into(),
+            m.idle_share.into(),
+            m.deployed_days.into(),
+            m.hours_used.into(),
+            m.hours_available.into(),
+            m.utilization.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, "underused-assets")
+}
+
+// Helper functions
+/// The range asked for, or the configured window up to today.
+fn range(
+    state: &AppState,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let today = Utc::now().with_timezone(&state.config.locale.tz()).date_naive();
+    utilization::date_range(from, to, today, state.config.utilization.window_days)
+}
+
+async fn underused_between(
+    state: &AppState,
+    from: NaiveDate,
+    to: NaiveDate,
+    category_id: Option<i32>,
+) -> Result<Vec<utilization::MachineUsage>, String> {
+    let fleet = utilization::load(&state.db, from, to, &state.config.locale.timezone, category_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to load utilization: {}", e);
+            e.to_string()
+        })?;
+    let machines = fleet.usage(from, to, &state.config.geofences.work_days, &state.config.utilization);
+    Ok(utilization::underused(&machines, state.config.utilization.underused_idle_percent))
+}
@@ -0,0 +1,216 @@
+use crate::config::DepreciationMethod;
+use crate::export::{self, ExportQuery, Sheet};
+use crate::handlers::categories::fetch_categories;
+use crate::valuation::{self, RegisterQuery, RegisterTotals, ScheduleQuery, METHODS};
+use crate::AppState;
+use axum::{
+    extract::{Extension, Path, Query},
+    response::{Html, Response},
+};
+use chrono::{Datelike, NaiveDate, Utc};
+use log::{error, info};
+use std::sync::Arc;
+
+// REGISTER
+pub async fn register(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<RegisterQuery>,
+) -> Result<Html<String>, String> {
+    info!("Serving fixed-asset register for category {:?}", query.category_id);
+
+    let categories = fetch_categories(&state.db).await?;
+    let as_of = query.as_of.unwrap_or_else(|| today(&state));
+    let entries = register_entries(&state, as_of, query.category_id).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("categories", &categories);
+    ctx.insert("category_id", &query.category_id);
+    ctx.insert("as_of", &as_of);
+    ctx.insert("totals", &RegisterTotals::of(&entries));
+    ctx.insert("entries", &entries);
+    ctx.insert("methods", METHODS);
+    ctx.insert("currency", &state.config.locale.currency);
+    state.render("valuation/register.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+const REGISTER_EXPORT_HEADERS: &[&str] = &[
+    "Equipment",
+    "Serial Number",
+    "Category",
+    "Status",
+    "Acquired",
+    "Purchase Cost",
+    "Salvage Value",
+    "Useful Life (years)",
+    "Method",
+    "Fully Depreciated On",
+    "Depreciation This Year",
+    "Accumulated Depreciation",
+    "Book Value",
+];
+
+// REGISTER EXPORT
+pub async fn export_register(
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<RegisterQuery>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+) -> Result<Response, String> {
+    info!("Exporting fixed-asset register as {:?}", format);
+
+    let as_of = query.as_of.unwrap_or_else(|| today(&state));
+    let entries = register_entries(&state, as_of, query.category_id).await?;
+
+    let mut sheet = Sheet::new("Fixed Asset Register", REGISTER_EXPORT_HEADERS);
+    for entry in entries {
+        let asset = entry.asset.as_ref();
+        sheet.push(vec![
+            entry.name.into(),
+            entry.serial_number.into(),
+            entry.category_name.into(),
+            entry.status.into(),
+            entry.acquired.into(),
+            asset.map(|a| a.purchase_cost).into(),
+            asset.map(|a| a.salvage_value).into(),
+            asset.map(|a| a.useful_life_years as i64).into(),
+            asset.map(|a| method_label(a.method)).into(),
+            asset.map(|a| a.fully_depreciated_on).into(),
+            entry.year_depreciation.into(),
+            entry.accumulated.into(),
+            entry.book_value.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, &format!("fixed-asset-register-{}", as_of))
+}
+
+// SCHEDULE
+pub async fn schedule(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<ScheduleQuery>,
+) -> Result<Html<String>, String> {
+    info!("Showing depreciation schedule of equipment {}", id);
+
+    let today = today(&state);
+    let row = asset_row(&state, id).await?;
+    let entry = valuation::register_entry(row, today, &state.config.costs);
+    let yearly = entry.asset.as_ref().map(|a| a.schedule(false)).unwrap_or_default();
+    let years: Vec<i32> = yearly.iter().map(|line| line.from.year()).collect();
+    // The year asked for, else this year while the machine depreciates
+    let year = query
+        .year
+        .or(Some(today.year()))
+        .filter(|year| years.contains(year))
+        .or_else(|| years.first().copied());
+    let monthly: Vec<_> = entry
+        .asset
+        .as_ref()
+        .map(|a| a.schedule(true))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|line| Some(line.from.year()) == year)
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("entry", &entry);
+    ctx.insert("yearly", &yearly);
+    ctx.insert("monthly", &monthly);
+    ctx.insert("years", &years);
+    ctx.insert("year", &year);
+    ctx.insert("methods", METHODS);
+    ctx.insert("currency", &state.config.locale.currency);
+    ctx.insert("today", &today);
+    state.render("valuation/equipment.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
+
+const SCHEDULE_EXPORT_HEADERS: &[&str] = &[
+    "Period",
+    "From",
+    "To",
+    "Opening Value",
+    "Depreciation",
+    "Closing Value",
+    "Accumulated Depreciation",
+];
+
+// SCHEDULE EXPORT
+pub async fn export_schedule(
+    Path(id): Path<i32>,
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<ScheduleQuery>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+) -> Result<Response, String> {
+    let monthly = query.period.as_deref() == Some("monthly");
+    let period = if monthly { "monthly" } else { "yearly" };
+    info!("Exporting {} depreciation schedule of equipment {} as {:?}", period, id, format);
+
+    let row = asset_row(&state, id).await?;
+    let serial_number = row.serial_number.clone();
+    let asset = row
+        .asset(&state.config.costs)
+        .ok_or_else(|| format!("Equipment {} has no purchase cost", id))?;
+
+    let mut sheet = Sheet::new("Depreciation Schedule", SCHEDULE_EXPORT_HEADERS);
+    for line in asset.schedule(monthly) {
+        let label = if monthly {
+            line.from.format("%Y-%m").to_string()
+        } else {
+            line.from.year().to_string()
+        };
+        sheet.push(vec![
+            label.into(),
+            line.from.into(),
+            line.to.into(),
+            line.opening.into(),
+            line.depreciation.into(),
+            line.closing.into(),
+            line.accumulated.into(),
+        ]);
+    }
+
+    export::download(&sheet, format, &format!("depreciation-{}-{}", serial_number, period))
+}
+
+// Helper functions
+fn today(state: &AppState) -> NaiveDate {
+    Utc::now().with_timezone(&state.config.locale.tz()).date_naive()
+}
+
+fn method_label(method: DepreciationMethod) -> &'static str {
+    METHODS
+        .iter()
+        .find(|(key, _)| *key == method)
+        .map(|(_, label)| *label)
+        .unwrap_or(method.as_str())
+}
+
+/// Machines acquired by `as_of`, valued on that day.
+async fn register_entries(
+    state: &AppState,
+    as_of: NaiveDate,
+    category_id: Option<i32>,
+) -> Result<Vec<valuation::RegisterEntry>, String> {
+    let rows = valuation::asset_rows(&state.db, &state.config.locale.timezone, category_id, None)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch assets: {}", e);
+            e.to_string()
+        })?;
+    Ok(rows
+        .into_iter()
+        .filter(|row| row.acquired <= as_of)
+        .map(|row| valuation::register_entry(row, as_of, &state.config.costs))
+        .collect())
+}
+
+async fn asset_row(state: &AppState, id: i32) -> Result<valuation::AssetRow, String> {
+    valuation::asset_rows(&state.db, &state.config.locale.timezone, None, Some(id))
+        .await
+        .map_err(|e| e.to_string())?
+        .pop()
+        .ok_or_else(|| format!("Equipment {} not found", id))
+}
@@ -0,0 +1,127 @@
+use crate::webhooks::{self, WebhookForm, EVENT_TYPES};
+use crate::AppState;
+use axum::{
+    extract::{Extension, Path},
+    response::{Html, Redirect},
+};
+use axum_extra::extract::Form; // Repeated event_types checkboxes
+use log::{info, warn};
+use std::sync::Arc;
+
+// LIST
+pub async fn list(
+    Extension(state): Extension<Arc<AppState>>,
+) -> Result<Html<String>, String> {
+    info!("Listing webhooks");
+    render_index(&state, None).await
+}
+
+// CREATE
+pub async fn create(
+    Extension(state): Extension<Arc<AppState>>,
+    Form(form): Form<WebhookForm>,
+) -> Result<Html<String>, String> {
+    let url = form.url.trim().to_string();
+    info!("Registering webhook {}", url);
+
+    match reqwest::Url::parse(&url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        _ => {
+            return render_index(&state, Some(("error", format!("'{}' is not an http(s) URL", url)))).await;
+        }
+    }
+    if form.event_types.is_empty() {
+        return render_index(&state, Some(("error", "Select at least one event type".to_string()))).await;
+    }
+    if let Some(unknown) = form.event_types.iter().find(|t| !EVENT_TYPES.contains(&t.as_str())) {
+        return Err(format!("Unknown event type '{}'", unknown));
+    }
+
+    sqlx::query!(
+        "INSERT INTO webhooks (url, secret, event_types) VALUES ($1, $2, $3)",
+        url,
+        webhooks::generate_secret(),
+        &form.event_types
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| {
+        warn!("Webhook creation failed: {}", e);
+        e.to_string()
+    })?;
+
+    render_index(&state, Some(("success", format!("Webhook {} registered", url)))).await
+}
+
+// TOGGLE
+pub async fn toggle(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Redirect, String> {
+    info!("Toggling webhook {}", id);
+
+    sqlx::query!("UPDATE webhooks SET active = NOT active WHERE id = $1", id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to("/webhooks"))
+}
+
+// DELETE
+pub async fn delete(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Redirect, String> {
+    info!("Deleting webhook {}", id);
+
+    sqlx::query!("DELETE FROM webhooks WHERE id = $1", id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Redirect::to("/webhooks"))
+}
+
+// RETRY
+pub async fn retry(
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Html<String>, String> {
+    info!("Retrying webhook delivery {}", id);
+
+    let queued = webhooks::retry(&state.db, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let flash = if queued {
+        ("success", format!("Delivery {} will be retried shortly", id))
+    } else {
+        ("error", format!("Delivery {} was already delivered", id))
+    };
+    render_index(&state, Some(flash)).await
+}
+
+// Helper functions
+async fn render_index(
+    state: &AppState,
+    flash: Option<(&str, String)>,
+) -> Result<Html<String>, String> {
+    let hooks = webhooks::list_webhooks(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let deliveries = webhooks::recent_deliveries(&state.db, 50)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("webhooks", &hooks);
+    ctx.insert("deliveries", &deliveries);
+    ctx.insert("event_types", EVENT_TYPES);
+    ctx.insert("dispatcher_enabled", &state.config.webhooks.enabled);
+    if let Some((kind, message)) = flash {
+        ctx.insert("flash", &serde_json::json!({ "type": kind, "message": message }));
+    }
+    state.render("webhooks/index.html", &ctx)
+        .map_err(|e| e.to_string())
+        .map(Html)
+}
@@ -0,0 +1,383 @@
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::path::{Path, PathBuf};
+
+/// Coverage types with their display labels.
+pub const COVERAGE_TYPES: &[(&str, &str)] = &[
+    ("all_risks", "All risks"),
+    ("third_party", "Third-party liability"),
+    ("fire_theft", "Fire & theft"),
+    ("breakdown", "Machinery breakdown"),
+    ("other", "Other"),
+];
+
+/// A policy with its state on the current date: `in_force`, `upcoming`,
+/// `expired`, `renewed` or `cancelled`.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Policy {
+    pub id: i32,
+    pub insurer: String,
+    pub policy_number: String,
+    pub coverage_type: String,
+    pub premium: Option<f64>,
+    pub deductible: Option<f64>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub status: String,
+    pub state: String,
+    pub renewed_from_id: Option<i32>,
+    pub notes: Option<String>,
+    pub equipment_count: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PolicyForm {
+    pub insurer: String,
+    pub policy_number: String,
+    pub coverage_type: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub premium: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub deductible: Option<f64>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub equipment_ids: Vec<i32>,
+}
+
+impl PolicyForm {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.insurer.trim().is_empty() || self.policy_number.trim().is_empty() {
+            return Err("Insurer and policy number are required".to_string());
+        }
+        if !COVERAGE_TYPES.iter().any(|(value, _)| *value == self.coverage_type) {
+            return Err(format!("Unknown coverage type '{}'", self.coverage_type));
+        }
+        if self.end_date < self.start_date {
+            return Err("The policy cannot end before it starts".to_string());
+        }
+        if self.premium.is_some_and(|p| p < 0.0) || self.deductible.is_some_and(|d| d < 0.0) {
+            return Err("Premium and deductible cannot be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct CoveredEquipment {
+    pub id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub status: String,
+}
+
+/// A machine in service with no policy in force today.
+#[derive(Debug, FromRow, Serialize)]
+pub struct UncoveredEquipment {
+    pub id: i32,
+    pub name: String,
+    pub serial_number: String,
+    /// End of the most recent policy, if it ever had one
+    pub last_covered: Option<NaiveDate>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct PolicyDocument {
+    pub id: i32,
+    pub policy_id: i32,
+    pub filename: String,
+    pub stored_name: String,
+    pub size_bytes: i64,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+pub async fn list_policies(pool: &PgPool) -> Result<Vec<Policy>, sqlx::Error> {
+    sqlx::query_as!(
+        Policy,
+        r#"
+        SELECT p.id, p.insurer, p.policy_number, p.coverage_type, p.premium, p.deductible,
+            p.start_date, p.end_date, p.status,
+            policy_state(p.status, p.start_date, p.end_date) as "state!",
+            p.renewed_from_id, p.notes,
+            (SELECT COUNT(*) FROM policy_equipment pe WHERE pe.policy_id = p.id) as "equipment_count!"
+        FROM insurance_policies p
+        ORDER BY p.status = 'active' DESC, p.end_date, p.insurer
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_policy(pool: &PgPool, id: i32) -> Result<Option<Policy>, sqlx::Error> {
+    sqlx::query_as!(
+        Policy,
+        r#"
+        SELECT p.id, p.insurer, p.policy_number, p.coverage_type, p.premium, p.deductible,
+            p.start_date, p.end_date, p.status,
+            policy_state(p.status, p.start_date, p.end_date) as "state!",
+            p.renewed_from_id, p.notes,
+            (SELECT COUNT(*) FROM policy_equipment pe WHERE pe.policy_id = p.id) as "equipment_count!"
+        FROM insurance_policies p
+        WHERE p.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Every policy in the renewal chain `id` belongs to, oldest first.
+pub async fn renewal_history(pool: &PgPool, id: i32) -> Result<Vec<Policy>, sqlx::Error> {
+    sqlx::query_as!(
+        Policy,
+        r#"
+        WITH RECURSIVE earlier AS (
+            SELECT id, renewed_from_id FROM insurance_policies WHERE id = $1
+            UNION ALL
+            SELECT p.id, p.renewed_from_id
+            FROM insurance_policies p JOIN earlier ON p.id = earlier.renewed_from_id
+        ), later AS (
+            SELECT id FROM insurance_policies WHERE id = $1
+            UNION ALL
+            SELECT p.id FROM insurance_policies p JOIN later ON p.renewed_from_id = later.id
+        )
+        SELECT p.id, p.insurer, p.policy_number, p.coverage_type, p.premium, p.deductible,
+            p.start_date, p.end_date, p.status,
+            policy_state(p.status, p.start_date, p.end_date) as "state!",
+            p.renewed_from_id, p.notes,
+            (SELECT COUNT(*) FROM policy_equipment pe WHERE pe.policy_id = p.id) as "equipment_count!"
+        FROM insurance_policies p
+        WHERE p.id IN (SELECT id FROM earlier UNION SELECT id FROM later)
+        ORDER BY p.start_date, p.id
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn covered_equipment(pool: &PgPool, policy_id: i32) -> Result<Vec<CoveredEquipment>, sqlx::Error> {
+    sqlx::query_as!(
+        CoveredEquipment,
+        r#"
+        SELECT e.id, e.name, e.serial_number, e.current_status as "status!"
+        FROM policy_equipment pe
+        JOIN equipment e ON e.id = pe.equipment_id
+        WHERE pe.policy_id = $1
+        ORDER BY e.name
+        "#,
+        policy_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Non-retired machines that no policy covers today.
+pub async fn uncovered_equipment(pool: &PgPool) -> Result<Vec<UncoveredEquipment>, sqlx::Error> {
+    sqlx::query_as!(
+        UncoveredEquipment,
+        r#"
+        SELECT e.id, e.name, e.serial_number,
+            (SELECT MAX(p.end_date)
+             FROM policy_equipment pe
+             JOIN insurance_policies p ON p.id = pe.policy_id
+             WHERE pe.equipment_id = e.id AND p.status != 'cancelled'
+                AND p.start_date <= CURRENT_DATE) as last_covered
+        FROM equipment e
+        JOIN equipment_coverage c ON c.equipment_id = e.id
+        WHERE c.covered_until IS NULL AND e.current_status != 'retired'
+        ORDER BY e.name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Inserts a policy and its covered machines; returns the new ID.
+pub async fn create_policy(
+    conn: &mut PgConnection,
+    form: &PolicyForm,
+    renewed_from_id: Option<i32>,
+) -> Result<i32, sqlx::Error> {
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO insurance_policies (
+            insurer, policy_number, coverage_type, premium, deductible,
+            start_date, end_date, renewed_from_id, notes
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id
+        "#,
+        form.insurer.trim(),
+        form.policy_number.trim(),
+        form.coverage_type,
+        form.premium,
+        form.deductible,
+        form.start_date,
+        form.end_date,
+        renewed_from_id,
+        form.notes
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    set_equipment(conn, id, &form.equipment_ids).await?;
+    Ok(id)
+}
+
+pub async fn update_policy(conn: &mut PgConnection, id: i32, form: &PolicyForm) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE insurance_policies SET
+            insurer = $2, policy_number = $3, coverage_type = $4, premium = $5,
+            deductible = $6, start_date = $7, end_date = $8, notes = $9
+        WHERE id = $1
+        "#,
+        id,
+        form.insurer.trim(),
+        form.policy_number.trim(),
+        form.coverage_type,
+        form.premium,
+        form.deductible,
+        form.start_date,
+        form.end_date,
+        form.notes
+    )
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    set_equipment(conn, id, &form.equipment_ids).await?;
+    Ok(true)
+}
+
+async fn set_equipment(conn: &mut PgConnection, policy_id: i32, equipment_ids: &[i32]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM policy_equipment WHERE policy_id = $1 AND NOT (equipment_id = ANY($2))",
+        policy_id,
+        equipment_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO policy_equipment (policy_id, equipment_id)
+        SELECT $1, UNNEST($2::int[])
+        ON CONFLICT DO NOTHING
+        "#,
+        policy_id,
+        equipment_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Records the successor of an active policy and marks it renewed, keeping
+/// the old policy and its documents as history. Returns the new policy's ID,
+/// or `None` if the policy is not active.
+pub async fn renew_policy(pool: &PgPool, id: i32, form: &PolicyForm) -> Result<Option<i32>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let renewed = sqlx::query!(
+        "UPDATE insurance_policies SET status = 'renewed' WHERE id = $1 AND status = 'active'",
+        id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if renewed == 0 {
+        return Ok(None);
+    }
+    let new_id = create_policy(&mut tx, form, Some(id)).await?;
+    tx.commit().await?;
+    Ok(Some(new_id))
+}
+
+pub async fn cancel_policy(pool: &PgPool, id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE insurance_policies SET status = 'cancelled' WHERE id = $1 AND status = 'active'",
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A one-year placeholder policy ending on `renewal`, for renewal dates
+/// that arrive without policy details (CSV imports, version 1 archives).
+pub async fn create_legacy_policy(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    renewal: NaiveDate,
+) -> Result<i32, sqlx::Error> {
+    let form = PolicyForm {
+        insurer: "Unknown".to_string(),
+        policy_number: format!("LEGACY-{}", equipment_id),
+        coverage_type: "other".to_string(),
+        premium: None,
+        deductible: None,
+        start_date: renewal.checked_sub_months(Months::new(12)).unwrap_or(renewal),
+        end_date: renewal,
+        notes: Some("Created from an insurance renewal date".to_string()),
+        equipment_ids: vec![equipment_id],
+    };
+    create_policy(conn, &form, None).await
+}
+
+pub async fn list_documents(pool: &PgPool, policy_id: i32) -> Result<Vec<PolicyDocument>, sqlx::Error> {
+    sqlx::query_as!(
+        PolicyDocument,
+        r#"
+        SELECT id, policy_id, filename, stored_name, size_bytes, uploaded_at
+        FROM policy_documents
+        WHERE policy_id = $1
+        ORDER BY uploaded_at DESC
+        "#,
+        policy_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_document(pool: &PgPool, id: i32) -> Result<Option<PolicyDocument>, sqlx::Error> {
+    sqlx::query_as!(
+        PolicyDocument,
+        r#"
+        SELECT id, policy_id, filename, stored_name, size_bytes, uploaded_at
+        FROM policy_documents
+        WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn add_document(
+    pool: &PgPool,
+    policy_id: i32,
+    filename: &str,
+    stored_name: &str,
+    size_bytes: i64,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO policy_documents (policy_id, filename, stored_name, size_bytes)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        policy_id,
+        filename,
+        stored_name,
+        size_bytes
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Where policy documents are kept under `paths.uploads`.
+pub fn document_dir(uploads: &Path) -> PathBuf {
+    uploads.join("policies")
+}
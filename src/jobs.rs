@@ -0,0 +1,332 @@
+use crate::config::Config;
+use crate::handlers::maintenance;
+use crate::notifications::{self, Mailer};
+use crate::webhooks;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use log::{error, info, warn};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::str::FromStr;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Background tasks run on a schedule by every server instance; the
+/// advisory lock and the `job_runs` slot make sure only one of them runs
+/// each occurrence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    /// Counts upcoming and overdue deadlines, mails the due digests and
+    /// publishes lapsed insurance
+    DeadlineScan,
+    /// Rolls maintenance schedules forward from the latest records
+    RecomputeSchedules,
+    /// Deletes old job run history, notification log entries and webhook events
+    Retention,
+}
+
+impl Job {
+    pub const ALL: [Job; 3] = [Job::DeadlineScan, Job::RecomputeSchedules, Job::Retention];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Job::DeadlineScan => "deadline_scan",
+            Job::RecomputeSchedules => "recompute_schedules",
+            Job::Retention => "retention",
+        }
+    }
+
+    fn schedule<'a>(&self, config: &'a Config) -> &'a str {
+        match self {
+            Job::DeadlineScan => &config.jobs.deadline_scan,
+            Job::RecomputeSchedules => &config.jobs.recompute_schedules,
+            Job::Retention => &config.jobs.retention,
+        }
+    }
+
+    /// Does the work and returns a one-line summary for the run history.
+    async fn perform(&self, pool: &PgPool, config: &Config) -> Result<String, String> {
+        match self {
+            Job::DeadlineScan => {
+                let reminders = &config.reminders;
+                let maintenance =
+                    crate::fetch_maintenance_alerts(pool, reminders.maintenance_days, i64::MAX)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                let insurance =
+                    crate::fetch_insurance_alerts(pool, reminders.insurance_days, i64::MAX)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                let overdue = crate::count_overdue_maintenance(pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if overdue > 0 {
+                    warn!("{} machines are overdue for maintenance", overdue);
+                }
+                let lapsed = webhooks::enqueue_insurance_lapses(pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let mut summary = format!(
+                    "{} maintenance due within {} days, {} insurance renewals within {} days, {} overdue, {} insurance lapses published",
+                    maintenance.len(),
+                    reminders.maintenance_days,
+                    insurance.len(),
+                    reminders.insurance_days,
+                    overdue,
+                    lapsed
+                );
+
+                if let Some(mailer) = Mailer::from_config(&config.smtp)? {
+                    let templates = crate::load_templates(config).map_err(|e| e.to_string())?;
+                    let outcomes =
+                        notifications::send_due_digests(pool, &templates, config, Some(&mailer))
+                            .await
+                            .map_err(|e| e.to_string())?;
+                    let sent = outcomes.iter().filter(|o| o.sent).count();
+                    let failed = outcomes.iter().filter(|o| o.error.is_some()).count();
+                    summary.push_str(&format!("; {} digests sent", sent));
+                    if failed > 0 {
+                        return Err(format!("{}, {} failed", summary, failed));
+                    }
+                }
+                Ok(summary)
+            }
+            Job::RecomputeSchedules => {
+                let changed = maintenance::recompute_schedules(pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(format!("{} maintenance schedules updated", changed))
+            }
+            Job::Retention => {
+                let deleted = sqlx::query!(
+                    r#"
+                    DELETE FROM job_runs
+                    WHERE started_at < NOW() - make_interval(days => $1)
+                        AND status != 'running'
+                    "#,
+                    config.jobs.retention_days
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .rows_affected();
+                // Deadlines long past will not be mailed again
+                let forgotten = sqlx::query!(
+                    "DELETE FROM notification_log WHERE due_date < CURRENT_DATE - $1::int",
+                    config.jobs.retention_days
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .rows_affected();
+                // Delivered or abandoned events; pending deliveries keep theirs
+                let events = sqlx::query!(
+                    r#"
+                    DELETE FROM webhook_events ev
+                    WHERE ev.created_at < NOW() - make_interval(days => $1)
+                        AND NOT EXISTS (
+                            SELECT 1 FROM webhook_deliveries d
+                            WHERE d.event_id = ev.id AND d.status = 'pending'
+                        )
+                    "#,
+                    config.jobs.retention_days
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .rows_affected();
+                let pings = crate::telematics::prune(pool, config.telematics.retention_days)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(format!(
+                    "{} old job runs, {} notification log entries, {} webhook events and {} telematics pings deleted",
+                    deleted, forgotten, events, pings
+                ))
+            }
+        }
+    }
+}
+
+impl FromStr for Job {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.replace('-', "_");
+        Job::ALL
+            .into_iter()
+            .find(|job| job.name() == normalized)
+            .ok_or_else(|| {
+                let names: Vec<_> = Job::ALL.iter().map(Job::name).collect();
+                format!("unknown job '{}', expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct JobRun {
+    pub id: i32,
+    pub job_name: String,
+    pub scheduled_for: DateTime<Utc>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: String,
+    pub detail: Option<String>,
+}
+
+/// What happened when an occurrence came due.
+#[derive(Debug)]
+pub enum Outcome {
+    /// Another instance holds the job's lock right now
+    Locked,
+    /// Another instance already ran this occurrence
+    AlreadyRan,
+    Finished(JobRun),
+}
+
+/// Runs one occurrence of `job` unless another instance is running it or
+/// already has. Job failures are recorded in the run, not returned.
+pub async fn execute(
+    pool: &PgPool,
+    config: &Config,
+    job: Job,
+    scheduled_for: DateTime<Utc>,
+) -> Result<Outcome, sqlx::Error> {
+    // Session-level lock, so it must be taken and released on one connection
+    let mut lock_conn = pool.acquire().await?;
+    let locked = sqlx::query_scalar!(
+        r#"SELECT pg_try_advisory_lock(hashtext('kfleet.job.' || $1)) as "locked!""#,
+        job.name()
+    )
+    .fetch_one(&mut *lock_conn)
+    .await?;
+    if !locked {
+        return Ok(Outcome::Locked);
+    }
+
+    let result = record_run(pool, config, job, scheduled_for).await;
+
+    sqlx::query_scalar!(
+        "SELECT pg_advisory_unlock(hashtext('kfleet.job.' || $1))",
+        job.name()
+    )
+    .fetch_one(&mut *lock_conn)
+    .await?;
+    result
+}
+
+async fn record_run(
+    pool: &PgPool,
+    config: &Config,
+    job: Job,
+    scheduled_for: DateTime<Utc>,
+) -> Result<Outcome, sqlx::Error> {
+    let run_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO job_runs (job_name, scheduled_for)
+        VALUES ($1, $2)
+        ON CONFLICT (job_name, scheduled_for) DO NOTHING
+        RETURNING id
+        "#,
+        job.name(),
+        scheduled_for
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(run_id) = run_id else {
+        return Ok(Outcome::AlreadyRan);
+    };
+
+    let (status, detail) = match job.perform(pool, config).await {
+        Ok(detail) => ("succeeded", detail),
+        Err(e) => ("failed", e),
+    };
+
+    let run = sqlx::query_as!(
+        JobRun,
+        r#"
+        UPDATE job_runs
+        SET finished_at = NOW(), status = $2, detail = $3
+        WHERE id = $1
+        RETURNING id, job_name, scheduled_for, started_at, finished_at, status, detail
+        "#,
+        run_id,
+        status,
+        detail
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(Outcome::Finished(run))
+}
+
+pub async fn recent_runs(pool: &PgPool, limit: i64) -> Result<Vec<JobRun>, sqlx::Error> {
+    sqlx::query_as!(
+        JobRun,
+        r#"
+        SELECT id, job_name, scheduled_for, started_at, finished_at, status, detail
+        FROM job_runs
+        ORDER BY started_at DESC, id DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Starts one task per job. Each waits for its next cron occurrence in the
+/// configured timezone and stops once `shutdown` flips to true; a job that is
+/// already running is allowed to finish.
+pub fn spawn_scheduler(
+    pool: PgPool,
+    config: Config,
+    shutdown: watch::Receiver<bool>,
+) -> Vec<JoinHandle<()>> {
+    if !config.jobs.enabled {
+        info!("Background jobs are disabled");
+        return Vec::new();
+    }
+
+    Job::ALL
+        .into_iter()
+        .map(|job| {
+            let pool = pool.clone();
+            let config = config.clone();
+            let mut shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                let schedule = match Schedule::from_str(job.schedule(&config)) {
+                    Ok(schedule) => schedule,
+                    Err(e) => {
+                        error!("Job {} has an invalid schedule: {}", job.name(), e);
+                        return;
+                    }
+                };
+                let tz = config.locale.tz();
+
+                while let Some(next) = schedule.upcoming(tz).next() {
+                    let next = next.with_timezone(&Utc);
+                    let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = shutdown.changed() => break,
+                    }
+
+                    match execute(&pool, &config, job, next).await {
+                        Ok(Outcome::Finished(run)) => info!(
+                            "Job {} {}: {}",
+                            job.name(),
+                            run.status,
+                            run.detail.unwrap_or_default()
+                        ),
+                        Ok(Outcome::Locked) => {
+                            info!("Job {} is running on another instance", job.name())
+                        }
+                        Ok(Outcome::AlreadyRan) => {
+                            info!("Job {} already ran for {}", job.name(), next)
+                        }
+                        Err(e) => error!("Job {} could not run: {}", job.name(), e),
+                    }
+                }
+            })
+        })
+        .collect()
+}
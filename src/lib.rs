@@ -1,20 +1,68 @@
 use axum::{
-    extract::Extension,
+    extract::{DefaultBodyLimit, Extension},
     http::StatusCode,
+    middleware,
     routing::{get, post},
     Router,
 };
 //use axum::response::Html;
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::postgres::PgPool;
 use sqlx::FromRow;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tera::Tera;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::services::ServeDir;
+
+pub mod archive;
+pub mod assignments;
+pub mod calendar;
+pub mod certifications;
+pub mod claims;
+pub mod config;
+pub mod costs;
+pub mod db;
+pub mod export;
+pub mod geofences;
+pub mod insurance;
+pub mod jobs;
+pub mod metrics;
+pub mod notifications;
+pub mod reservations;
+pub mod sites;
+pub mod telematics;
+pub mod telemetry;
+pub mod transfers;
+pub mod uploads;
+pub mod users;
+pub mod utilization;
+pub mod valuation;
+pub mod webhooks;
 
 pub mod handlers {
+    pub mod archive;
+    pub mod assignments;
+    pub mod calendar;
+    pub mod certifications;
+    pub mod claims;
     pub mod categories;
+    pub mod costs;
     pub mod equipment;
+    pub mod geofences;
+    pub mod health;
+    pub mod import;
+    pub mod insurance;
+    pub mod maintenance;
+    pub mod notifications;
+    pub mod reservations;
+    pub mod sites;
     pub mod staff;
+    pub mod telematics;
+    pub mod transfers;
+    pub mod utilization;
+    pub mod valuation;
+    pub mod webhooks;
 }
 
 // Shared application state
@@ -22,6 +70,51 @@ pub mod handlers {
 pub struct AppState {
     pub db: PgPool,
     pub templates: Tera,
+    pub config: config::Config,
+    pub metrics: metrics::Metrics,
+}
+
+impl AppState {
+    /// Renders a template, counting failures for `/metrics`.
+    pub fn render(&self, name: &str, ctx: &tera::Context) -> Result<String, tera::Error> {
+        self.templates.render(name, ctx).inspect_err(|e| {
+            self.metrics.template_failures.inc();
+            log::error!("Failed to render {}: {}", name, e);
+        })
+    }
+}
+
+/// Loads the templates from the configured directory and registers the
+/// `money` filter, which formats amounts in the configured currency.
+pub fn load_templates(config: &config::Config) -> Result<Tera, tera::Error> {
+    let mut tera = Tera::new(&config.paths.templates_glob())?;
+    tera.autoescape_on(vec!["html"]);
+
+    let locale = config.locale.clone();
+    tera.register_filter(
+        "money",
+        move |value: &tera::Value, _: &HashMap<String, tera::Value>| {
+            let amount = value
+                .as_f64()
+                .ok_or_else(|| tera::Error::msg("money filter expects a number"))?;
+            Ok(tera::Value::String(locale.format_money(amount)))
+        },
+    );
+    Ok(tera)
+}
+
+/// Treats empty query/form values (e.g. an unselected `<select>`) as absent.
+pub fn empty_string_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(s) => s.parse::<T>().map(Some).map_err(serde::de::Error::custom),
+    }
 }
 
 // Define serializable structs
@@ -34,14 +127,32 @@ pub struct StatusCounts {
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct MaintenanceAlert {
+    pub equipment_id: i32,
     pub name: String,
     pub next_maintenance: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// A covered machine whose active policy ends within the reminder horizon.
 #[derive(Debug, Serialize, FromRow)]
 pub struct InsuranceAlert {
+    pub equipment_id: i32,
+    pub policy_id: i32,
     pub name: String,
-    pub insurance_renewal: Option<chrono::DateTime<chrono::Utc>>,
+    pub insurer: String,
+    pub policy_number: String,
+    pub insurance_renewal: chrono::NaiveDate,
+}
+
+/// An operator certification that has expired or expires within the
+/// reminder horizon, and that no later certification of the same type replaces.
+#[derive(Debug, Serialize, FromRow)]
+pub struct CertificationAlert {
+    pub staff_id: i32,
+    pub certification_id: i32,
+    pub full_name: String,
+    pub license_type: String,
+    pub expiry_date: chrono::NaiveDate,
+    pub days_left: i32,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -61,12 +172,18 @@ pub struct RecentMaintenance {
     pub cost: Option<f64>,
 }
 
+// Whole-fleet archives are much larger than form posts
+const ARCHIVE_UPLOAD_LIMIT: usize = 64 * 1024 * 1024;
+
 pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
+    let static_files = state.config.paths.static_files.clone();
+
+    let router = Router::new()
         // Category routes
         .route("/categories", get(handlers::categories::list)
                              .post(handlers::categories::create))
         .route("/categories/new", get(handlers::categories::new_form))
+        .route("/categories/export", get(handlers::categories::export))
         .route("/categories/{id}/edit", get(handlers::categories::edit_form))
         .route("/categories/{id}", post(handlers::categories::update))
         .route("/categories/{id}/delete", post(handlers::categories::delete))
@@ -75,25 +192,168 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/equipment", get(handlers::equipment::list)
                             .post(handlers::equipment::create))
         .route("/equipment/new", get(handlers::equipment::new_form))
+        .route("/equipment/export", get(handlers::equipment::export))
         .route("/equipment/{id}/edit", get(handlers::equipment::edit_form))
         .route("/equipment/{id}", post(handlers::equipment::update))
         .route("/equipment/{id}/delete", post(handlers::equipment::delete))
+        .route("/equipment/{id}/assignments", get(handlers::assignments::equipment_history))
+        .route("/equipment/{id}/operators", post(handlers::equipment::add_operator))
+        .route("/equipment/{id}/operators/{operator_id}/primary", post(handlers::equipment::set_primary_operator))
+        .route("/equipment/{id}/operators/{operator_id}/delete", post(handlers::equipment::remove_operator))
+        .route("/equipment/{id}/site", post(handlers::equipment::move_to_site))
+        .route("/equipment/{id}/telematics", post(handlers::equipment::set_device))
         
         // Staff routes
         .route("/staff", get(handlers::staff::list)
                         .post(handlers::staff::create))
         .route("/staff/new", get(handlers::staff::new_form))
+        .route("/staff/export", get(handlers::staff::export))
         .route("/staff/{id}/edit", get(handlers::staff::edit_form))
         .route("/staff/{id}", post(handlers::staff::update))
         .route("/staff/{id}/delete", post(handlers::staff::delete))
+        .route("/staff/{id}/assignments", get(handlers::assignments::staff_history))
+
+        // Maintenance history routes
+        .route("/maintenance", get(handlers::maintenance::list)
+                              .post(handlers::maintenance::create))
+        .route("/maintenance/export", get(handlers::maintenance::export))
+
+        // Deadline notification routes
+        .route("/notifications", get(handlers::notifications::list)
+                                .post(handlers::notifications::create))
+        .route("/notifications/send", post(handlers::notifications::send_now))
+        .route("/notifications/{id}/toggle", post(handlers::notifications::toggle))
+        .route("/notifications/{id}/delete", post(handlers::notifications::delete))
+
+        // Outgoing webhook routes
+        .route("/webhooks", get(handlers::webhooks::list)
+                           .post(handlers::webhooks::create))
+        .route("/webhooks/{id}/toggle", post(handlers::webhooks::toggle))
+        .route("/webhooks/{id}/delete", post(handlers::webhooks::delete))
+        .route("/webhooks/deliveries/{id}/retry", post(handlers::webhooks::retry))
+
+        // Insurance policy routes
+        .route("/insurance", get(handlers::insurance::list)
+                            .post(handlers::insurance::create))
+        .route("/insurance/new", get(handlers::insurance::new_form))
+        .route("/insurance/{id}", get(handlers::insurance::show)
+                                 .post(handlers::insurance::update))
+        .route("/insurance/{id}/edit", get(handlers::insurance::edit_form))
+        .route("/insurance/{id}/renew", post(handlers::insurance::renew))
+        .route("/insurance/{id}/cancel", post(handlers::insurance::cancel))
+        .route("/insurance/{id}/documents", post(handlers::insurance::upload_document)
+                                           .layer(DefaultBodyLimit::max(uploads::MAX_UPLOAD_SIZE)))
+        .route("/insurance/documents/{id}", get(handlers::insurance::download_document))
+
+        // Insurance claim routes
+        .route("/claims", get(handlers::claims::list)
+                         .post(handlers::claims::create))
+        .route("/claims/new", get(handlers::claims::new_form))
+        .route("/claims/report", get(handlers::claims::report))
+        .route("/claims/report/export", get(handlers::claims::export_report))
+        .route("/claims/{id}", get(handlers::claims::show))
+        .route("/claims/{id}/status", post(handlers::claims::update_status))
+        .route("/claims/{id}/repairs", post(handlers::claims::link_repair))
+        .route("/claims/{id}/repairs/{maintenance_id}/delete", post(handlers::claims::unlink_repair))
+
+        // Operator certification routes
+        .route("/staff/{id}/certifications", get(handlers::certifications::list)
+                                             .post(handlers::certifications::create))
+        .route("/certifications/{id}", post(handlers::certifications::update))
+        .route("/certifications/{id}/edit", get(handlers::certifications::edit_form))
+        .route("/certifications/{id}/delete", post(handlers::certifications::delete))
+        .route("/certifications/{id}/document", get(handlers::certifications::download_document)
+                                                .post(handlers::certifications::upload_document)
+                                                .layer(DefaultBodyLimit::max(uploads::MAX_UPLOAD_SIZE)))
+
+        // Site routes
+        .route("/sites", get(handlers::sites::list)
+                        .post(handlers::sites::create))
+        .route("/sites/new", get(handlers::sites::new_form))
+        .route("/sites/{id}", get(handlers::sites::show)
+                             .post(handlers::sites::update))
+        .route("/sites/{id}/edit", get(handlers::sites::edit_form))
+        .route("/sites/{id}/delete", post(handlers::sites::delete))
+        .route("/sites/{id}/deployments", post(handlers::sites::deploy))
+        .route("/sites/{id}/geofences", get(handlers::geofences::index)
+                                       .post(handlers::geofences::create))
+        .route("/geofences/{id}/delete", post(handlers::geofences::delete))
+
+        // Reservation routes
+        .route("/reservations", get(handlers::reservations::list)
+                               .post(handlers::reservations::create))
+        .route("/reservations/new", get(handlers::reservations::new_form))
+        .route("/reservations/calendar", get(handlers::reservations::calendar))
+        .route("/reservations/{id}", get(handlers::reservations::show))
+        .route("/reservations/{id}/status", post(handlers::reservations::decide))
+
+        // Transfer routes
+        .route("/transfers", get(handlers::transfers::list)
+                            .post(handlers::transfers::create))
+        .route("/transfers/new", get(handlers::transfers::new_form))
+        .route("/transfers/{id}", get(handlers::transfers::show))
+        .route("/transfers/{id}/arrival", post(handlers::transfers::receive))
+
+        // Utilization routes
+        .route("/utilization", get(handlers::utilization::index))
+        .route("/utilization/underused", get(handlers::utilization::underused))
+        .route("/utilization/underused/export", get(handlers::utilization::export_underused))
+
+        // Cost routes
+        .route("/costs", get(handlers::costs::report))
+        .route("/costs/export", get(handlers::costs::export_report))
+        .route("/equipment/{id}/costs", get(handlers::costs::equipment)
+                                       .post(handlers::costs::create_expense))
+        .route("/expenses/{id}/delete", post(handlers::costs::delete_expense))
+
+        // Valuation routes
+        .route("/assets", get(handlers::valuation::register))
+        .route("/assets/export", get(handlers::valuation::export_register))
+        .route("/equipment/{id}/depreciation", get(handlers::valuation::schedule))
+        .route("/equipment/{id}/depreciation/export", get(handlers::valuation::export_schedule))
+
+        // Calendar feed routes (the token is the credential)
+        .route("/calendar/{token}/feed.ics", get(handlers::calendar::feed))
+
+        // Telematics ingestion routes (the API key is the credential)
+        .route("/api/telematics/pings", post(handlers::telematics::ingest))
+
+        // CSV import routes
+        .route("/import/{entity}", get(handlers::import::upload_form)
+                                  .post(handlers::import::upload))
+        .route("/import/{entity}/preview", post(handlers::import::preview))
+        .route("/import/{entity}/commit", post(handlers::import::commit_import))
+
+        // Fleet archive routes
+        .route("/archive", get(handlers::archive::index))
+        .route("/archive/export", get(handlers::archive::export))
+        .route("/archive/import", post(handlers::archive::restore)
+                                 .layer(DefaultBodyLimit::max(ARCHIVE_UPLOAD_LIMIT)))
 
         // Mobile App
         .route("/app", get(mobile))
         
+        // Operations endpoints
+        .route("/healthz", get(handlers::health::healthz))
+        .route("/readyz", get(handlers::health::readyz))
+        .route("/metrics", get(handlers::health::metrics))
+
         // Dashboard
         .route("/", get(dashboard))
-        .layer(Extension(state))
         .fallback(not_found)
+        .layer(middleware::from_fn_with_state(Arc::clone(&state), metrics::track_requests))
+        .layer(Extension(state));
+
+    let router = match static_files {
+        Some(dir) => router.nest_service("/static", ServeDir::new(dir)),
+        None => router,
+    };
+
+    // Outermost last: assign a request ID, trace with it, echo it back
+    router
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(telemetry::trace_layer())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
 }
 
 
@@ -102,21 +362,16 @@ pub async fn mobile(
 ) -> Result<axum::response::Html<String>, String> {
     log::info!("serving mobile");
 
-    let mut ctx = tera::Context::new();
+    let ctx = tera::Context::new();
     //ctx.insert("app", &task);
-    state.templates.render("app.html", &ctx)
+    state.render("app.html", &ctx)
         .map_err(|e| e.to_string())
         .map(axum::response::Html)
 }
 
-/* Business Logic: Dashboard shows critical maintenance deadlines */
-pub async fn dashboard(
-    Extension(state): Extension<Arc<AppState>>
-) -> Result<axum::response::Html<String>, (axum::http::StatusCode, String)> {
-    log::info!("Serving dashboard");
-    
-    // Fetch status counts (using COALESCE to ensure non-null)
-    let status_counts = sqlx::query_as!(
+/// Equipment counts per `current_status` (COALESCE ensures non-null).
+pub async fn fetch_status_counts(pool: &PgPool) -> Result<StatusCounts, sqlx::Error> {
+    sqlx::query_as!(
         StatusCounts,
         r#"SELECT
             COALESCE(COUNT(*) FILTER (WHERE current_status = 'active'), 0) as "active!",
@@ -124,46 +379,168 @@ pub async fn dashboard(
             COALESCE(COUNT(*) FILTER (WHERE current_status = 'retired'), 0) as "retired!"
         FROM equipment"#
     )
-    .fetch_one(&state.db)
+    .fetch_one(pool)
     .await
-    .map_err(|err| {
-        log::error!("Failed to fetch status counts: {}", err);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
-    })?;
+}
+
+/// Non-retired equipment whose next maintenance date has already passed.
+pub async fn count_overdue_maintenance(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!"
+        FROM equipment
+        WHERE next_maintenance < CURRENT_DATE
+            AND current_status != 'retired'"#
+    )
+    .fetch_one(pool)
+    .await
+}
 
-    // Fetch upcoming maintenance (next 30 days)
-    let maintenance_alerts = sqlx::query_as!(
+/// Non-retired equipment whose next maintenance falls within the coming `horizon_days`.
+pub async fn fetch_maintenance_alerts(
+    pool: &PgPool,
+    horizon_days: i32,
+    limit: i64,
+) -> Result<Vec<MaintenanceAlert>, sqlx::Error> {
+    sqlx::query_as!(
         MaintenanceAlert,
-        r#"SELECT name, next_maintenance 
+        r#"SELECT id as equipment_id, name, next_maintenance 
         FROM equipment 
-        WHERE next_maintenance BETWEEN CURRENT_DATE AND CURRENT_DATE + INTERVAL '30 days'
+        WHERE next_maintenance BETWEEN CURRENT_DATE AND CURRENT_DATE + make_interval(days => $1)
             AND current_status != 'retired'
         ORDER BY next_maintenance
-        LIMIT 5"#
+        LIMIT $2"#,
+        horizon_days,
+        limit
     )
-    .fetch_all(&state.db)
+    .fetch_all(pool)
     .await
-    .map_err(|err| {
-        log::error!("Failed to fetch maintenance alerts: {}", err);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
-    })?;
+}
 
-    // Fetch insurance renewals (next 30 days)
-    let insurance_alerts = sqlx::query_as!(
+/// Non-retired equipment covered by an active policy that ends within the
+/// coming `horizon_days`, one row per machine and policy.
+pub async fn fetch_insurance_alerts(
+    pool: &PgPool,
+    horizon_days: i32,
+    limit: i64,
+) -> Result<Vec<InsuranceAlert>, sqlx::Error> {
+    sqlx::query_as!(
         InsuranceAlert,
-        r#"SELECT name, insurance_renewal 
-        FROM equipment 
-        WHERE insurance_renewal BETWEEN CURRENT_DATE AND CURRENT_DATE + INTERVAL '30 days'
-            AND current_status != 'retired'
-        ORDER BY insurance_renewal
-        LIMIT 5"#
+        r#"SELECT e.id as equipment_id, p.id as policy_id, e.name, p.insurer,
+            p.policy_number, p.end_date as insurance_renewal
+        FROM insurance_policies p
+        JOIN policy_equipment pe ON pe.policy_id = p.id
+        JOIN equipment e ON e.id = pe.equipment_id
+        WHERE p.status = 'active'
+            AND p.end_date BETWEEN CURRENT_DATE AND CURRENT_DATE + $1::int
+            AND e.current_status != 'retired'
+        ORDER BY p.end_date, e.name
+        LIMIT $2"#,
+        horizon_days,
+        limit
     )
-    .fetch_all(&state.db)
+    .fetch_all(pool)
     .await
-    .map_err(|err| {
-        log::error!("Failed to fetch insurance alerts: {}", err);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
-    })?;
+}
+
+/// Certifications expiring within the coming `horizon_days`, or already
+/// expired, whose holder has no later certification of the same type.
+pub async fn fetch_certification_alerts(
+    pool: &PgPool,
+    horizon_days: i32,
+    limit: i64,
+) -> Result<Vec<CertificationAlert>, sqlx::Error> {
+    sqlx::query_as!(
+        CertificationAlert,
+        r#"SELECT s.id as staff_id, c.id as certification_id, s.full_name,
+            c.license_type, c.expiry_date as "expiry_date!",
+            c.expiry_date - CURRENT_DATE as "days_left!"
+        FROM certifications c
+        JOIN staff s ON s.id = c.staff_id
+        WHERE c.expiry_date <= CURRENT_DATE + $1::int
+            AND NOT EXISTS (
+                SELECT 1 FROM certifications later
+                WHERE later.staff_id = c.staff_id
+                    AND later.license_type = c.license_type
+                    AND later.id != c.id
+                    AND (later.expiry_date IS NULL OR later.expiry_date > c.expiry_date)
+            )
+        ORDER BY c.expiry_date, s.full_name
+        LIMIT $2"#,
+        horizon_days,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/* Business Logic: Dashboard shows critical maintenance deadlines */
+pub async fn dashboard(
+    Extension(state): Extension<Arc<AppState>>
+) -> Result<axum::response::Html<String>, (axum::http::StatusCode, String)> {
+    log::info!("Serving dashboard");
+    
+    // Fetch status counts
+    let status_counts = fetch_status_counts(&state.db)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to fetch status counts: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+
+    let reminders = &state.config.reminders;
+
+    // Fetch upcoming maintenance within the reminder horizon
+    let maintenance_alerts = fetch_maintenance_alerts(&state.db, reminders.maintenance_days, 5)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to fetch maintenance alerts: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+
+    // Fetch insurance renewals within the reminder horizon
+    let insurance_alerts = fetch_insurance_alerts(&state.db, reminders.insurance_days, 5)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to fetch insurance alerts: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+
+    // Operator certifications expiring within the reminder horizon
+    let certification_alerts = fetch_certification_alerts(&state.db, reminders.certification_days, 5)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to fetch certification alerts: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+
+    // Machines in service without a policy in force
+    let uncovered_equipment = insurance::uncovered_equipment(&state.db)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to fetch uncovered equipment: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+
+    // Machines that left their site after hours
+    let geofence_alerts = geofences::recent_alerts(&state.db, 7, 5)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to fetch geofence alerts: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+
+    // Fleet utilization over the last week, with the most idle machines
+    let usage_config = &state.config.utilization;
+    let usage_to = chrono::Utc::now().with_timezone(&state.config.locale.tz()).date_naive();
+    let usage_from = usage_to - chrono::Duration::days(utilization::DASHBOARD_DAYS - 1);
+    let fleet = utilization::load(&state.db, usage_from, usage_to, &state.config.locale.timezone, None)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to load utilization: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
+        })?;
+    let usage = fleet.usage(usage_from, usage_to, &state.config.geofences.work_days, usage_config);
+    let underused = utilization::underused(&usage, usage_config.underused_idle_percent);
 
     // Fetch recent equipment
     let recent_equipment = sqlx::query_as!(
@@ -211,11 +588,22 @@ pub async fn dashboard(
     context.insert("status_counts", &status_counts);
     context.insert("maintenance_alerts", &maintenance_alerts);
     context.insert("insurance_alerts", &insurance_alerts);
+    context.insert("uncovered_equipment", &uncovered_equipment);
+    context.insert("certification_alerts", &certification_alerts);
+    context.insert("geofence_alerts", &geofence_alerts);
+    context.insert("utilization", &utilization::Totals::of(&usage));
+    context.insert("underused_count", &underused.len());
+    context.insert("underused", &underused.iter().take(3).collect::<Vec<_>>());
+    context.insert("utilization_days", &utilization::DASHBOARD_DAYS);
+    context.insert("utilization_from", &usage_from);
+    context.insert("utilization_to", &usage_to);
+    context.insert("timezone", &state.config.locale.timezone);
+    context.insert("license_types", certifications::LICENSE_TYPES);
     context.insert("recent_equipment", &recent_equipment);
     context.insert("recent_maintenance", &recent_maintenance);
 
     // Render template
-    match state.templates.render("index.html", &context) {
+    match state.render("index.html", &context) {
         Ok(content) => Ok(axum::response::Html(content)),
         Err(err) => {
             log::error!("Dashboard template error: {}", err);
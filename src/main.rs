@@ -1,70 +1,127 @@
-use kfleet::{create_router, AppState};
-use log::info;
-use sqlx::postgres::PgPoolOptions;
+use anyhow::Context;
+use kfleet::config::Config;
+use kfleet::metrics::Metrics;
+use kfleet::{create_router, db, jobs, load_templates, telemetry, webhooks, AppState};
+use log::{info, warn};
 use std::sync::Arc;
-use tera::Tera;
 use tokio::net::TcpListener;
+use tokio::sync::{watch, Notify};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logger
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .format_timestamp(None)
-        .init();
-    
+    // Load configuration (kfleet.toml, then environment overrides)
+    dotenvy::dotenv().ok();
+    let config = Config::load()?;
+    let addr = config.server.bind_addr;
+    let drain_timeout = config.server.shutdown_timeout();
+
+    // Initialize logging
+    telemetry::init(config.logging.format);
     info!("Starting fleet management system");
 
-    // Load environment variables
-    dotenvy::dotenv().ok();
-    let db_url = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set in .env file");
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "3000".to_string())
-        .parse::<u16>()?;
-    let addr = format!("0.0.0.0:{port}");
+    std::fs::create_dir_all(&config.paths.uploads).with_context(|| {
+        format!("Cannot create upload directory {}", config.paths.uploads.display())
+    })?;
 
     // Create database connection pool
     info!("Connecting to database...");
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
-        .await?;
+    let pool = db::connect(&config.database).await?;
     info!("Database connection established");
 
     // Run database migrations
     info!("Running database migrations");
-    sqlx::migrate!().run(&pool).await?;
+    db::MIGRATOR.run(&pool).await?;
     info!("Migrations completed");
 
     // Initialize template engine
     info!("Loading templates");
-    let mut tera = match Tera::new("templates/**/*") {
+    let tera = match load_templates(&config) {
         Ok(t) => t,
         Err(e) => {
             println!("Template parsing error: {}", e);
             return Err(e.into());
         }
     };
-    tera.autoescape_on(vec!["html"]);
     info!("{} templates loaded", tera.templates.len());
 
     // Create shared application state
     let state = Arc::new(AppState {
         db: pool,
         templates: tera,
+        config,
+        metrics: Metrics::new(),
     });
 
+    // Start background jobs and webhook delivery
+    let (stop_jobs, jobs_shutdown) = watch::channel(false);
+    let mut job_tasks = jobs::spawn_scheduler(state.db.clone(), state.config.clone(), jobs_shutdown.clone());
+    job_tasks.extend(webhooks::spawn_dispatcher(state.db.clone(), state.config.clone(), jobs_shutdown));
+
     // Create router
     let app = create_router(Arc::clone(&state));
 
     // Create TCP listener
     info!("Binding to {}", addr);
-    let listener = TcpListener::bind(&addr).await?;
+    let listener = TcpListener::bind(addr).await?;
     info!("Server listening on http://{}", addr);
 
-    // Start server with axum::serve
-    axum::serve(listener, app).await?;
+    // Serve until SIGTERM/SIGINT, then let in-flight requests finish
+    let shutdown_started = Arc::new(Notify::new());
+    let server = axum::serve(listener, app).with_graceful_shutdown({
+        let shutdown_started = Arc::clone(&shutdown_started);
+        async move {
+            shutdown_signal().await;
+            info!("Shutdown requested, draining requests for up to {:?}", drain_timeout);
+            shutdown_started.notify_one();
+        }
+    });
+
+    tokio::select! {
+        result = server.into_future() => result?,
+        _ = async {
+            shutdown_started.notified().await;
+            tokio::time::sleep(drain_timeout).await;
+        } => warn!("Drain timeout elapsed, dropping remaining connections"),
+    }
+
+    // Stop the scheduler, letting a job that is already running finish
+    stop_jobs.send_replace(true);
+    let wait_for_jobs = async {
+        for task in job_tasks {
+            let _ = task.await;
+        }
+    };
+    if tokio::time::timeout(drain_timeout, wait_for_jobs).await.is_err() {
+        warn!("Background jobs still running at shutdown were abandoned");
+    }
+
+    // Let open transactions roll back cleanly before exiting
+    state.db.close().await;
+    info!("Database pool closed, exiting");
 
     Ok(())
 }
 
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
@@ -0,0 +1,134 @@
+use crate::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Prometheus collectors for one application instance.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_duration: HistogramVec,
+    pub template_failures: IntCounter,
+    pub db_connections: IntGaugeVec,
+    pub db_max_connections: IntGauge,
+    pub equipment_by_status: IntGaugeVec,
+    pub maintenance_overdue: IntGauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("kfleet".to_string()), None)
+            .expect("valid metrics prefix");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled, by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route"),
+            &["method", "route"],
+        )
+        .expect("valid metric");
+        let template_failures = IntCounter::new(
+            "template_render_failures_total",
+            "Templates that failed to render",
+        )
+        .expect("valid metric");
+        let db_connections = IntGaugeVec::new(
+            Opts::new("db_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .expect("valid metric");
+        let db_max_connections =
+            IntGauge::new("db_max_connections", "Configured database pool size")
+                .expect("valid metric");
+        let equipment_by_status = IntGaugeVec::new(
+            Opts::new("equipment", "Equipment count by current status"),
+            &["status"],
+        )
+        .expect("valid metric");
+        let maintenance_overdue = IntGauge::new(
+            "maintenance_overdue",
+            "Non-retired equipment whose next maintenance date has passed",
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_duration.clone()),
+            Box::new(template_failures.clone()),
+            Box::new(db_connections.clone()),
+            Box::new(db_max_connections.clone()),
+            Box::new(equipment_by_status.clone()),
+            Box::new(maintenance_overdue.clone()),
+        ] {
+            registry.register(collector).expect("metric registered once");
+        }
+
+        Metrics {
+            registry,
+            http_requests,
+            http_duration,
+            template_failures,
+            db_connections,
+            db_max_connections,
+            equipment_by_status,
+            maintenance_overdue,
+        }
+    }
+
+    /// Renders every collector in the Prometheus text exposition format.
+    pub fn encode(&self) -> Result<String, String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buffer).map_err(|e| e.to_string())
+    }
+}
+
+/// Middleware recording request counts and latencies per matched route.
+/// Unmatched paths share one label so scanners cannot blow up cardinality.
+pub async fn track_requests(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    state
+        .metrics
+        .http_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    state
+        .metrics
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    response
+}
@@ -0,0 +1,326 @@
+use crate::config::{Config, SmtpConfig, SmtpSecurity};
+use chrono::{NaiveDate, Utc};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tera::Tera;
+
+pub const FREQUENCIES: &[&str] = &["daily", "weekly"];
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Subscription {
+    pub id: i32,
+    pub email: String,
+    pub frequency: String,
+    pub days_ahead: i32,
+    pub include_maintenance: bool,
+    pub include_insurance: bool,
+    pub active: bool,
+    pub last_sent_at: Option<chrono::DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionForm {
+    pub email: String,
+    pub frequency: String,
+    pub days_ahead: i32,
+    #[serde(default)]
+    pub include_maintenance: Option<String>,
+    #[serde(default)]
+    pub include_insurance: Option<String>,
+}
+
+/// A maintenance or insurance deadline within a subscriber's horizon.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct DigestItem {
+    pub kind: String,
+    pub equipment_id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub due_date: NaiveDate,
+}
+
+impl DigestItem {
+    pub fn stage(&self, today: NaiveDate) -> &'static str {
+        if self.due_date < today { "overdue" } else { "upcoming" }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DigestEntry<'a> {
+    name: &'a str,
+    serial_number: &'a str,
+    due_date: NaiveDate,
+    days: i64,
+    overdue_days: i64,
+    overdue: bool,
+}
+
+/// Result of one subscriber's digest.
+#[derive(Debug, Serialize)]
+pub struct DigestOutcome {
+    pub email: String,
+    pub items: usize,
+    pub sent: bool,
+    pub error: Option<String>,
+}
+
+/// SMTP connection built from `[smtp]`.
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    /// `None` while no SMTP host is configured.
+    pub fn from_config(smtp: &SmtpConfig) -> Result<Option<Mailer>, String> {
+        let Some(host) = &smtp.host else {
+            return Ok(None);
+        };
+        let from = smtp
+            .from
+            .as_deref()
+            .unwrap_or_default()
+            .parse::<Mailbox>()
+            .map_err(|e| format!("Invalid smtp.from: {}", e))?;
+
+        let mut builder = match smtp.security {
+            // Plain SMTP, as spoken by MailHog and similar local catchers
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| e.to_string())?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|e| e.to_string())?,
+        }
+        .port(smtp.port);
+        if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Some(Mailer {
+            transport: builder.build(),
+            from,
+        }))
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, text: String, html: String) -> Result<(), String> {
+        let to = to.parse::<Mailbox>().map_err(|e| format!("Invalid recipient {}: {}", to, e))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .multipart(MultiPart::alternative_plain_html(text, html))
+            .map_err(|e| e.to_string())?;
+        self.transport.send(message).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+pub async fn list_subscriptions(pool: &PgPool) -> Result<Vec<Subscription>, sqlx::Error> {
+    sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT id, email, frequency, days_ahead, include_maintenance,
+            include_insurance, active, last_sent_at
+        FROM notification_subscriptions
+        ORDER BY LOWER(email)
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Active subscriptions whose daily or weekly digest is due. The margins
+/// absorb drift in when the nightly job actually runs.
+pub async fn due_subscriptions(pool: &PgPool) -> Result<Vec<Subscription>, sqlx::Error> {
+    sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT id, email, frequency, days_ahead, include_maintenance,
+            include_insurance, active, last_sent_at
+        FROM notification_subscriptions
+        WHERE active
+            AND (last_sent_at IS NULL
+                OR last_sent_at < NOW() - CASE frequency
+                    WHEN 'weekly' THEN INTERVAL '6 days 12 hours'
+                    ELSE INTERVAL '20 hours'
+                END)
+        ORDER BY id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Deadlines within the subscriber's horizon, or already past, that have
+/// not yet been mailed to them at their current stage.
+pub async fn pending_items(
+    pool: &PgPool,
+    subscription: &Subscription,
+    today: NaiveDate,
+    timezone: &str,
+) -> Result<Vec<DigestItem>, sqlx::Error> {
+    sqlx::query_as!(
+        DigestItem,
+        r#"
+        SELECT d.kind as "kind!", d.equipment_id as "equipment_id!", d.name as "name!",
+            d.serial_number as "serial_number!", d.due_date as "due_date!"
+        FROM (
+            SELECT 'maintenance' AS kind, e.id AS equipment_id, e.name, e.serial_number,
+                (e.next_maintenance AT TIME ZONE $6)::date AS due_date
+            FROM equipment e
+            WHERE $2 AND e.next_maintenance IS NOT NULL AND e.current_status != 'retired'
+            UNION ALL
+            SELECT DISTINCT 'insurance', e.id, e.name, e.serial_number, p.end_date
+            FROM insurance_policies p
+            JOIN policy_equipment pe ON pe.policy_id = p.id
+            JOIN equipment e ON e.id = pe.equipment_id
+            WHERE $3 AND p.status = 'active' AND e.current_status != 'retired'
+        ) d
+        WHERE d.due_date <= $4::date + $5::int
+            AND NOT EXISTS (
+                SELECT 1 FROM notification_log l
+                WHERE l.subscription_id = $1
+                    AND l.equipment_id = d.equipment_id
+                    AND l.kind = d.kind
+                    AND l.due_date = d.due_date
+                    AND l.stage = CASE WHEN d.due_date < $4::date THEN 'overdue' ELSE 'upcoming' END
+            )
+        ORDER BY d.due_date, d.name
+        "#,
+        subscription.id,
+        subscription.include_maintenance,
+        subscription.include_insurance,
+        today,
+        subscription.days_ahead,
+        timezone
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Renders the digest as (subject, plain text, HTML).
+pub fn render_digest(
+    templates: &Tera,
+    subscription: &Subscription,
+    items: &[DigestItem],
+    today: NaiveDate,
+) -> Result<(String, String, String), tera::Error> {
+    let entries = |kind: &str| -> Vec<DigestEntry> {
+        items
+            .iter()
+            .filter(|i| i.kind == kind)
+            .map(|i| DigestEntry {
+                name: &i.name,
+                serial_number: &i.serial_number,
+                due_date: i.due_date,
+                days: (i.due_date - today).num_days(),
+                overdue_days: (today - i.due_date).num_days(),
+                overdue: i.due_date < today,
+            })
+            .collect()
+    };
+    let maintenance = entries("maintenance");
+    let insurance = entries("insurance");
+    let overdue = items.iter().filter(|i| i.due_date < today).count();
+
+    let mut subject = format!("kFleet: {} upcoming deadlines", items.len());
+    if overdue > 0 {
+        subject = format!("kFleet: {} deadlines, {} overdue", items.len(), overdue);
+    }
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("subscription", subscription);
+    ctx.insert("maintenance", &maintenance);
+    ctx.insert("insurance", &insurance);
+    ctx.insert("today", &today);
+    let text = templates.render("emails/digest.txt", &ctx)?;
+    let html = templates.render("emails/digest.html", &ctx)?;
+    Ok((subject, text, html))
+}
+
+/// Sends every due digest that has something new in it and logs the items
+/// sent. Without a mailer nothing is sent or logged (dry run).
+pub async fn send_due_digests(
+    pool: &PgPool,
+    templates: &Tera,
+    config: &Config,
+    mailer: Option<&Mailer>,
+) -> Result<Vec<DigestOutcome>, sqlx::Error> {
+    let today = Utc::now().with_timezone(&config.locale.tz()).date_naive();
+    let mut outcomes = Vec::new();
+
+    for subscription in due_subscriptions(pool).await? {
+        let items = pending_items(pool, &subscription, today, &config.locale.timezone).await?;
+        let mut outcome = DigestOutcome {
+            email: subscription.email.clone(),
+            items: items.len(),
+            sent: false,
+            error: None,
+        };
+        if items.is_empty() {
+            outcomes.push(outcome);
+            continue;
+        }
+
+        let result = match (render_digest(templates, &subscription, &items, today), mailer) {
+            (Err(e), _) => Err(format!("Template error: {}", e)),
+            (Ok(_), None) => Ok(false),
+            (Ok((subject, text, html)), Some(mailer)) => mailer
+                .send(&subscription.email, &subject, text, html)
+                .await
+                .map(|_| true),
+        };
+
+        match result {
+            Ok(true) => {
+                record_sent(pool, &subscription, &items, today).await?;
+                info!("Sent digest of {} deadlines to {}", items.len(), subscription.email);
+                outcome.sent = true;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                error!("Digest to {} failed: {}", subscription.email, e);
+                outcome.error = Some(e);
+            }
+        }
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+async fn record_sent(
+    pool: &PgPool,
+    subscription: &Subscription,
+    items: &[DigestItem],
+    today: NaiveDate,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for item in items {
+        sqlx::query!(
+            r#"
+            INSERT INTO notification_log (subscription_id, equipment_id, kind, stage, due_date)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING
+            "#,
+            subscription.id,
+            item.equipment_id,
+            item.kind,
+            item.stage(today),
+            item.due_date
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query!(
+        "UPDATE notification_subscriptions SET last_sent_at = NOW() WHERE id = $1",
+        subscription.id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
@@ -0,0 +1,484 @@
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+
+/// Reservation statuses with their display labels.
+pub const RESERVATION_STATUSES: &[(&str, &str)] = &[
+    ("pending", "Pending approval"),
+    ("approved", "Approved"),
+    ("rejected", "Rejected"),
+    ("cancelled", "Cancelled"),
+];
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Reservation {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub category_id: i32,
+    pub category_name: String,
+    pub site_id: i32,
+    pub site_name: String,
+    pub requested_by: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub purpose: Option<String>,
+    pub status: String,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub decision_note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ReservationFilter {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub category_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub site_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReservationForm {
+    pub equipment_id: i32,
+    pub site_id: i32,
+    pub requested_by: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub purpose: Option<String>,
+}
+
+impl ReservationForm {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.requested_by.trim().is_empty() {
+            return Err("Enter who is requesting the machine".to_string());
+        }
+        if self.end_date < self.start_date {
+            return Err("The reservation cannot end before it starts".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// An approval decision or a cancellation, as submitted from the
+/// reservation page.
+#[derive(Debug, Deserialize)]
+pub struct DecisionForm {
+    pub status: String,
+    pub decided_by: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub note: Option<String>,
+}
+
+/// Something standing in the way of a booking. Blocking conflicts prevent
+/// requesting and approving it; competing requests are only flagged.
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    pub reservation_id: Option<i32>,
+    pub message: String,
+    pub blocking: bool,
+}
+
+/// What a booking of one machine is checked against: its status, the day
+/// its next maintenance is due and its other pending or approved bookings.
+#[derive(Debug, Clone)]
+pub struct Availability {
+    pub equipment_name: String,
+    pub status: String,
+    pub maintenance_date: Option<NaiveDate>,
+    pub bookings: Vec<Reservation>,
+}
+
+/// Conflicts of booking the machine from `start` to `end`, both inclusive.
+pub fn find_conflicts(availability: &Availability, start: NaiveDate, end: NaiveDate) -> Vec<Conflict> {
+    let name = &availability.equipment_name;
+    let mut conflicts = Vec::new();
+    match availability.status.as_str() {
+        "retired" => conflicts.push(Conflict {
+            reservation_id: None,
+            message: format!("{} is retired", name),
+            blocking: true,
+        }),
+        "maintenance" => conflicts.push(Conflict {
+            reservation_id: None,
+            message: format!("{} is in maintenance", name),
+            blocking: true,
+        }),
+        _ => {}
+    }
+    if let Some(due) = availability.maintenance_date.filter(|due| (start..=end).contains(due)) {
+        conflicts.push(Conflict {
+            reservation_id: None,
+            message: format!("{} is due for maintenance on {}", name, due),
+            blocking: true,
+        });
+    }
+    for booking in &availability.bookings {
+        if booking.start_date > end || booking.end_date < start {
+            continue;
+        }
+        let approved = booking.status == "approved";
+        let who = if approved { "Booked" } else { "Also requested" };
+        conflicts.push(Conflict {
+            reservation_id: Some(booking.id),
+            message: format!(
+                "{} by {} for {} from {} to {}",
+                who, booking.requested_by, booking.site_name, booking.start_date, booking.end_date
+            ),
+            blocking: approved,
+        });
+    }
+    conflicts
+}
+
+/// Checks a decision: pending requests are approved, rejected or
+/// cancelled; approved ones can only be cancelled.
+pub fn check_transition(from: &str, to: &str) -> Result<(), String> {
+    if to == "pending" || !RESERVATION_STATUSES.iter().any(|(value, _)| *value == to) {
+        return Err(format!("Unknown decision '{}'", to));
+    }
+    match (from, to) {
+        ("pending", _) | ("approved", "cancelled") => Ok(()),
+        ("approved", _) => Err("An approved reservation can only be cancelled".to_string()),
+        _ => Err(format!("The reservation is already {}", from)),
+    }
+}
+
+/// Loads what booking the machine is checked against, leaving out the
+/// reservation `exclude` itself. Locks the machine until the transaction
+/// ends so that two approvals cannot both pass. `None` if it doesn't exist.
+pub async fn availability(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    exclude: Option<i32>,
+    timezone: &str,
+) -> Result<Option<Availability>, sqlx::Error> {
+    let equipment = sqlx::query!(
+        r#"
+        SELECT name, current_status,
+            (next_maintenance AT TIME ZONE $2)::date as maintenance_date
+        FROM equipment
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        equipment_id,
+        timezone
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(equipment) = equipment else {
+        return Ok(None);
+    };
+
+    Ok(Some(Availability {
+        equipment_name: equipment.name,
+        status: equipment.current_status,
+        maintenance_date: equipment.maintenance_date,
+        bookings: bookings(conn, equipment_id, exclude).await?,
+    }))
+}
+
+/// Like [`availability`], but without locking the machine, for showing
+/// conflicts on a page. Viewing a request must not hold up decisions and
+/// moves; the locking variant checks again when one is made.
+pub async fn current_availability(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    exclude: Option<i32>,
+    timezone: &str,
+) -> Result<Option<Availability>, sqlx::Error> {
+    let equipment = sqlx::query!(
+        r#"
+        SELECT name, current_status,
+            (next_maintenance AT TIME ZONE $2)::date as maintenance_date
+        FROM equipment
+        WHERE id = $1
+        "#,
+        equipment_id,
+        timezone
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(equipment) = equipment else {
+        return Ok(None);
+    };
+
+    Ok(Some(Availability {
+        equipment_name: equipment.name,
+        status: equipment.current_status,
+        maintenance_date: equipment.maintenance_date,
+        bookings: bookings(conn, equipment_id, exclude).await?,
+    }))
+}
+
+/// The machine's pending and approved bookings other than `exclude`.
+async fn bookings(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    exclude: Option<i32>,
+) -> Result<Vec<Reservation>, sqlx::Error> {
+    sqlx::query_as!(
+        Reservation,
+        r#"
+        SELECT r.id, r.equipment_id, e.name as equipment_name, e.category_id,
+            c.name as category_name, r.site_id, s.name as site_name, r.requested_by,
+            r.start_date, r.end_date, r.purpose, r.status, r.decided_by, r.decided_at,
+            r.decision_note, r.created_at
+        FROM reservations r
+        JOIN equipment e ON e.id = r.equipment_id
+        JOIN categories c ON c.id = e.category_id
+        JOIN sites s ON s.id = r.site_id
+        WHERE r.equipment_id = $1
+            AND r.status IN ('pending', 'approved')
+            AND ($2::int IS NULL OR r.id != $2)
+        ORDER BY r.start_date
+        "#,
+        equipment_id,
+        exclude
+    )
+    .fetch_all(conn)
+    .await
+}
+
+pub async fn list_reservations(pool: &PgPool, filter: &ReservationFilter) -> Result<Vec<Reservation>, sqlx::Error> {
+    sqlx::query_as!(
+        Reservation,
+        r#"
+        SELECT r.id, r.equipment_id, e.name as equipment_name, e.category_id,
+            c.name as category_name, r.site_id, s.name as site_name, r.requested_by,
+            r.start_date, r.end_date, r.purpose, r.status, r.decided_by, r.decided_at,
+            r.decision_note, r.created_at
+        FROM reservations r
+        JOIN equipment e ON e.id = r.equipment_id
+        JOIN categories c ON c.id = e.category_id
+        JOIN sites s ON s.id = r.site_id
+        WHERE ($1::text IS NULL OR r.status = $1)
+            AND ($2::int IS NULL OR e.category_id = $2)
+            AND ($3::int IS NULL OR r.site_id = $3)
+        ORDER BY r.status != 'pending', r.start_date DESC, e.name
+        "#,
+        filter.status,
+        filter.category_id,
+        filter.site_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Approved bookings not over yet that start within the coming
+/// `horizon_days`, soonest first.
+pub async fn approved_reservations(pool: &PgPool, horizon_days: i32) -> Result<Vec<Reservation>, sqlx::Error> {
+    sqlx::query_as!(
+        Reservation,
+        r#"
+        SELECT r.id, r.equipment_id, e.name as equipment_name, e.category_id,
+            c.name as category_name, r.site_id, s.name as site_name, r.requested_by,
+            r.start_date, r.end_date, r.purpose, r.status, r.decided_by, r.decided_at,
+            r.decision_note, r.created_at
+        FROM reservations r
+        JOIN equipment e ON e.id = r.equipment_id
+        JOIN categories c ON c.id = e.category_id
+        JOIN sites s ON s.id = r.site_id
+        WHERE r.status = 'approved'
+            AND r.end_date >= CURRENT_DATE
+            AND r.start_date <= CURRENT_DATE + $1::int
+        ORDER BY r.start_date, e.name
+        "#,
+        horizon_days
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_reservation(pool: &PgPool, id: i32) -> Result<Option<Reservation>, sqlx::Error> {
+    sqlx::query_as!(
+        Reservation,
+        r#"
+        SELECT r.id, r.equipment_id, e.name as equipment_name, e.category_id,
+            c.name as category_name, r.site_id, s.name as site_name, r.requested_by,
+            r.start_date, r.end_date, r.purpose, r.status, r.decided_by, r.decided_at,
+            r.decision_note, r.created_at
+        FROM reservations r
+        JOIN equipment e ON e.id = r.equipment_id
+        JOIN categories c ON c.id = e.category_id
+        JOIN sites s ON s.id = r.site_id
+        WHERE r.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn create_reservation(conn: &mut PgConnection, form: &ReservationForm) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO reservations (equipment_id, site_id, requested_by, start_date, end_date, purpose)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+        form.equipment_id,
+        form.site_id,
+        form.requested_by.trim(),
+        form.start_date,
+        form.end_date,
+        form.purpose
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Records a decision if the reservation is still `from`. Returns false if
+/// it was decided in the meantime.
+pub async fn decide(
+    conn: &mut PgConnection,
+    id: i32,
+    from: &str,
+    form: &DecisionForm,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE reservations
+        SET status = $3, decided_by = $4, decision_note = $5, decided_at = NOW()
+        WHERE id = $1 AND status = $2
+        "#,
+        id,
+        from,
+        form.status,
+        form.decided_by.trim(),
+        form.note
+    )
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A machine as a row of the booking calendar.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CalendarMachine {
+    pub id: i32,
+    pub name: String,
+    pub status: String,
+    pub maintenance_date: Option<NaiveDate>,
+}
+
+/// One day of one machine: `free`, `pending`, `approved` or `maintenance`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarCell {
+    pub date: NaiveDate,
+    pub state: &'static str,
+    pub reservation_id: Option<i32>,
+    pub label: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalendarRow {
+    pub machine: CalendarMachine,
+    pub cells: Vec<CalendarCell>,
+}
+
+/// The first day of `month` given as `YYYY-MM`.
+pub fn parse_month(month: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d").ok()
+}
+
+/// Every day of the month starting on `first`.
+pub fn month_days(first: NaiveDate) -> Vec<NaiveDate> {
+    let first = first.with_day(1).unwrap_or(first);
+    let next = first.checked_add_months(Months::new(1)).unwrap_or(first);
+    first.iter_days().take_while(|day| *day < next).collect()
+}
+
+/// Lays bookings out per machine and day. An approved booking wins over a
+/// maintenance day, which wins over pending requests.
+pub fn build_calendar(machines: Vec<CalendarMachine>, bookings: &[Reservation], days: &[NaiveDate]) -> Vec<CalendarRow> {
+    machines
+        .into_iter()
+        .map(|machine| {
+            let cells = days
+                .iter()
+                .map(|&date| {
+                    let on_day: Vec<&Reservation> = bookings
+                        .iter()
+                        .filter(|b| b.equipment_id == machine.id && b.start_date <= date && date <= b.end_date)
+                        .collect();
+                    let approved = on_day.iter().find(|b| b.status == "approved");
+                    let pending = on_day.iter().find(|b| b.status == "pending");
+                    let (state, booking) = match (approved, pending) {
+                        (Some(b), _) => ("approved", Some(*b)),
+                        _ if machine.maintenance_date == Some(date) => ("maintenance", None),
+                        (None, Some(b)) => ("pending", Some(*b)),
+                        (None, None) => ("free", None),
+                    };
+                    CalendarCell {
+                        date,
+                        state,
+                        reservation_id: booking.map(|b| b.id),
+                        label: match booking {
+                            Some(b) => format!("{} ({})", b.site_name, b.requested_by),
+                            None if state == "maintenance" => "Maintenance due".to_string(),
+                            None => String::new(),
+                        },
+                    }
+                })
+                .collect();
+            CalendarRow { machine, cells }
+        })
+        .collect()
+}
+
+pub async fn calendar_machines(
+    pool: &PgPool,
+    category_id: i32,
+    timezone: &str,
+) -> Result<Vec<CalendarMachine>, sqlx::Error> {
+    sqlx::query_as!(
+        CalendarMachine,
+        r#"
+        SELECT id, name, current_status as status,
+            (next_maintenance AT TIME ZONE $2)::date as maintenance_date
+        FROM equipment
+        WHERE category_id = $1 AND current_status != 'retired'
+        ORDER BY name
+        "#,
+        category_id,
+        timezone
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Pending and approved bookings of the category's machines overlapping
+/// `first` to `last`.
+pub async fn bookings_between(
+    pool: &PgPool,
+    category_id: i32,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Result<Vec<Reservation>, sqlx::Error> {
+    sqlx::query_as!(
+        Reservation,
+        r#"
+        SELECT r.id, r.equipment_id, e.name as equipment_name, e.category_id,
+            c.name as category_name, r.site_id, s.name as site_name, r.requested_by,
+            r.start_date, r.end_date, r.purpose, r.status, r.decided_by, r.decided_at,
+            r.decision_note, r.created_at
+        FROM reservations r
+        JOIN equipment e ON e.id = r.equipment_id
+        JOIN categories c ON c.id = e.category_id
+        JOIN sites s ON s.id = r.site_id
+        WHERE e.category_id = $1
+            AND r.status IN ('pending', 'approved')
+            AND r.start_date <= $3 AND r.end_date >= $2
+        ORDER BY r.start_date
+        "#,
+        category_id,
+        first,
+        last
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,419 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+
+/// A site with its state on the current date (`planned`, `active` or
+/// `completed`) and the number of machines on it today.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Site {
+    pub id: i32,
+    pub name: String,
+    pub client: Option<String>,
+    pub address: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub state: String,
+    pub machine_count: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SiteForm {
+    pub name: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub client: Option<String>,
+    pub address: String,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub longitude: Option<f64>,
+    pub start_date: NaiveDate,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub end_date: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+impl SiteForm {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.address.trim().is_empty() {
+            return Err("Name and address are required".to_string());
+        }
+        match (self.latitude, self.longitude) {
+            (Some(lat), Some(lon)) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err("GPS coordinates are out of range".to_string());
+                }
+            }
+            (None, None) => {}
+            _ => return Err("Enter both latitude and longitude, or neither".to_string()),
+        }
+        if self.end_date.is_some_and(|end| end < self.start_date) {
+            return Err("The site cannot close before it opens".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Moves a machine to a site, or back to the yard when `site_id` is empty.
+#[derive(Debug, Deserialize)]
+pub struct MoveForm {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub site_id: Option<i32>,
+    pub date: NaiveDate,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+/// Brings a machine onto a site from the site page.
+#[derive(Debug, Deserialize)]
+pub struct DeployForm {
+    pub equipment_id: i32,
+    pub date: NaiveDate,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+/// A machine's stay on a site; `deployed_until` is the day it left.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Deployment {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub serial_number: String,
+    pub site_id: i32,
+    pub site_name: String,
+    pub deployed_from: NaiveDate,
+    pub deployed_until: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub current: bool,
+}
+
+/// An operator currently assigned to a machine on the site.
+#[derive(Debug, FromRow, Serialize)]
+pub struct SiteOperator {
+    pub id: i32,
+    pub full_name: String,
+    pub contact_info: Option<String>,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub is_primary: bool,
+    pub assigned_from: DateTime<Utc>,
+}
+
+/// Checks moving a machine on `date`, given its latest stay and the
+/// destination (`None` for the yard). Moves only go forward in time, so the
+/// date must come after the latest stay began and, if that stay has ended,
+/// not before it ended; the destination site must be open that day.
+pub fn check_move(latest: Option<&Deployment>, site: Option<&Site>, date: NaiveDate) -> Result<(), String> {
+    if let Some(stay) = latest {
+        if date <= stay.deployed_from {
+            return Err(format!(
+                "{} arrived at {} on {}; a move must come after that",
+                stay.equipment_name, stay.site_name, stay.deployed_from
+            ));
+        }
+        match stay.deployed_until {
+            Some(until) if date < until => {
+                return Err(format!(
+                    "{} was at {} until {}",
+                    stay.equipment_name, stay.site_name, until
+                ));
+            }
+            None if site.is_some_and(|s| s.id == stay.site_id) => {
+                return Err(format!("{} is already at {}", stay.equipment_name, stay.site_name));
+            }
+            _ => {}
+        }
+    }
+    if latest.is_none_or(|stay| stay.deployed_until.is_some()) && site.is_none() {
+        return Err("The machine is already at the yard".to_string());
+    }
+    if let Some(site) = site {
+        if date < site.start_date {
+            return Err(format!("{} opens on {}", site.name, site.start_date));
+        }
+        if let Some(end) = site.end_date.filter(|end| date > *end) {
+            return Err(format!("{} closed on {}", site.name, end));
+        }
+    }
+    Ok(())
+}
+
+pub async fn list_sites(pool: &PgPool) -> Result<Vec<Site>, sqlx::Error> {
+    sqlx::query_as!(
+        Site,
+        r#"
+        SELECT s.id, s.name, s.client, s.address, s.latitude, s.longitude,
+            s.start_date, s.end_date, s.notes,
+            CASE
+                WHEN s.start_date > CURRENT_DATE THEN 'planned'
+                WHEN s.end_date < CURRENT_DATE THEN 'completed'
+                ELSE 'active'
+            END as "state!",
+            (SELECT COUNT(*) FROM equipment_location l WHERE l.site_id = s.id) as "machine_count!"
+        FROM sites s
+        ORDER BY (s.end_date < CURRENT_DATE) IS TRUE, s.start_date > CURRENT_DATE, s.name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_site(pool: &PgPool, id: i32) -> Result<Option<Site>, sqlx::Error> {
+    sqlx::query_as!(
+        Site,
+        r#"
+        SELECT s.id, s.name, s.client, s.address, s.latitude, s.longitude,
+            s.start_date, s.end_date, s.notes,
+            CASE
+                WHEN s.start_date > CURRENT_DATE THEN 'planned'
+                WHEN s.end_date < CURRENT_DATE THEN 'completed'
+                ELSE 'active'
+            END as "state!",
+            (SELECT COUNT(*) FROM equipment_location l WHERE l.site_id = s.id) as "machine_count!"
+        FROM sites s
+        WHERE s.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn create_site(pool: &PgPool, form: &SiteForm) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO sites (name, client, address, latitude, longitude, start_date, end_date, notes)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+        form.name.trim(),
+        form.client,
+        form.address.trim(),
+        form.latitude,
+        form.longitude,
+        form.start_date,
+        form.end_date,
+        form.notes
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn update_site(pool: &PgPool, id: i32, form: &SiteForm) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sites
+        SET name = $2, client = $3, address = $4, latitude = $5, longitude = $6,
+            start_date = $7, end_date = $8, notes = $9
+        WHERE id = $1
+        "#,
+        id,
+        form.name.trim(),
+        form.client,
+        form.address.trim(),
+        form.latitude,
+        form.longitude,
+        form.start_date,
+        form.end_date,
+        form.notes
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes a site that no machine was ever deployed to, booked for or
+/// transferred to or from. Returns why it cannot be deleted if one was,
+/// since those records are kept as history. The site is locked first so
+/// nothing new can reference it in the meantime.
+pub async fn delete_site(pool: &PgPool, id: i32) -> Result<Option<String>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let uses = sqlx::query!(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM deployments d WHERE d.site_id = s.id) as "deployments!",
+            EXISTS (SELECT 1 FROM reservations r WHERE r.site_id = s.id) as "reservations!",
+            EXISTS (
+                SELECT 1 FROM transfers t
+                WHERE t.origin_site_id = s.id OR t.destination_site_id = s.id
+            ) as "transfers!"
+        FROM sites s
+        WHERE s.id = $1
+        FOR UPDATE
+        "#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(uses) = uses else {
+        return Ok(None);
+    };
+    if uses.deployments {
+        return Ok(Some("Machines have been deployed to this site; set an end date instead".to_string()));
+    }
+    if uses.reservations {
+        return Ok(Some("Machines have been reserved for this site; set an end date instead".to_string()));
+    }
+    if uses.transfers {
+        return Ok(Some("Machines have been transferred to or from this site; set an end date instead".to_string()));
+    }
+
+    sqlx::query!("DELETE FROM sites WHERE id = $1", id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(None)
+}
+
+/// Why the machine cannot move to `site_id` (the yard when `None`) on
+/// `date`, if it cannot. Locks the machine until the transaction ends so
+/// concurrent moves are checked one after the other.
+pub async fn move_problem(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    site_id: Option<i32>,
+    date: NaiveDate,
+) -> Result<Option<String>, sqlx::Error> {
+    let equipment = sqlx::query!(
+        "SELECT name, current_status FROM equipment WHERE id = $1 FOR UPDATE",
+        equipment_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(equipment) = equipment else {
+        return Ok(Some(format!("Equipment {} not found", equipment_id)));
+    };
+
+    let site = match site_id {
+        Some(site_id) => {
+            let site = sqlx::query_as!(
+                Site,
+                r#"
+                SELECT s.id, s.name, s.client, s.address, s.latitude, s.longitude,
+                    s.start_date, s.end_date, s.notes,
+                    '' as "state!", 0::bigint as "machine_count!"
+                FROM sites s
+                WHERE s.id = $1
+                "#,
+                site_id
+            )
+            .fetch_optional(&mut *conn)
+            .await?;
+            let Some(site) = site else {
+                return Ok(Some(format!("Site {} not found", site_id)));
+            };
+            if equipment.current_status == "retired" {
+                return Ok(Some(format!("{} is retired and cannot be deployed", equipment.name)));
+            }
+            Some(site)
+        }
+        None => None,
+    };
+
+    let latest = sqlx::query_as!(
+        Deployment,
+        r#"
+        SELECT d.id, d.equipment_id, e.name as equipment_name, e.serial_number,
+            d.site_id, s.name as site_name, d.deployed_from, d.deployed_until, d.notes,
+            (d.deployed_until IS NULL) as "current!"
+        FROM deployments d
+        JOIN equipment e ON e.id = d.equipment_id
+        JOIN sites s ON s.id = d.site_id
+        WHERE d.equipment_id = $1
+        ORDER BY d.deployed_from DESC
+        LIMIT 1
+        "#,
+        equipment_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(check_move(latest.as_ref(), site.as_ref(), date).err())
+}
+
+/// Ends the machine's open stay on `date` and, unless it returns to the
+/// yard, starts one on `site_id`. Check with `move_problem` first.
+pub async fn move_equipment(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    site_id: Option<i32>,
+    date: NaiveDate,
+    notes: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE deployments SET deployed_until = $2
+        WHERE equipment_id = $1 AND deployed_until IS NULL
+        "#,
+        equipment_id,
+        date
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if let Some(site_id) = site_id {
+        sqlx::query!(
+            r#"
+            INSERT INTO deployments (equipment_id, site_id, deployed_from, notes)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            equipment_id,
+            site_id,
+            date,
+            notes
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Stays on a site or of a machine, latest first.
+pub async fn deployments(
+    pool: &PgPool,
+    site_id: Option<i32>,
+    equipment_id: Option<i32>,
+) -> Result<Vec<Deployment>, sqlx::Error> {
+    sqlx::query_as!(
+        Deployment,
+        r#"
+        SELECT d.id, d.equipment_id, e.name as equipment_name, e.serial_number,
+            d.site_id, s.name as site_name, d.deployed_from, d.deployed_until, d.notes,
+            EXISTS (SELECT 1 FROM equipment_location l WHERE l.deployment_id = d.id) as "current!"
+        FROM deployments d
+        JOIN equipment e ON e.id = d.equipment_id
+        JOIN sites s ON s.id = d.site_id
+        WHERE ($1::int IS NULL OR d.site_id = $1)
+            AND ($2::int IS NULL OR d.equipment_id = $2)
+        ORDER BY d.deployed_from DESC, e.name
+        "#,
+        site_id,
+        equipment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Operators currently assigned to the machines on the site today.
+pub async fn site_operators(pool: &PgPool, site_id: i32) -> Result<Vec<SiteOperator>, sqlx::Error> {
+    sqlx::query_as!(
+        SiteOperator,
+        r#"
+        SELECT s.id, s.full_name, s.contact_info, e.id as equipment_id, e.name as equipment_name,
+            eo.is_primary, eo.assigned_from
+        FROM equipment_location l
+        JOIN equipment e ON e.id = l.equipment_id
+        JOIN equipment_operator eo ON eo.equipment_id = e.id AND eo.assigned_to IS NULL
+        JOIN staff s ON s.id = eo.operator_id
+        WHERE l.site_id = $1
+        ORDER BY s.full_name, e.name
+        "#,
+        site_id
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,351 @@
+use crate::config::{Config, TelematicsConfig};
+use crate::geofences::{self, Position};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+
+/// A batch as posted by a unit or a gateway. Pings are parsed one by one
+/// so a malformed ping is reported without refusing the rest.
+#[derive(Debug, Deserialize)]
+pub struct PingBatch {
+    pub pings: Vec<serde_json::Value>,
+}
+
+/// One reading from a unit. The machine is named by its serial number or
+/// by the telematics device ID set on its page; the readings are each
+/// optional but a ping must carry at least one.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ping {
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub device_id: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde(default)]
+    pub engine_hours: Option<f64>,
+    /// Percent of tank capacity
+    #[serde(default)]
+    pub fuel_level: Option<f64>,
+}
+
+/// How a ping names its machine.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MachineRef {
+    Serial(String),
+    Device(String),
+}
+
+impl Ping {
+    pub fn machine(&self) -> Result<MachineRef, String> {
+        let serial = self.serial_number.as_deref().map(str::trim).filter(|s| !s.is_empty());
+        let device = self.device_id.as_deref().map(str::trim).filter(|s| !s.is_empty());
+        match (serial, device) {
+            (Some(serial), None) => Ok(MachineRef::Serial(serial.to_string())),
+            (None, Some(device)) => Ok(MachineRef::Device(device.to_string())),
+            (None, None) => Err("serial_number or device_id is required".to_string()),
+            (Some(_), Some(_)) => Err("Give serial_number or device_id, not both".to_string()),
+        }
+    }
+
+    /// Checks the readings and that `recorded_at` falls between the
+    /// retention horizon and a little past `now`.
+    pub fn validate(&self, now: DateTime<Utc>, config: &TelematicsConfig) -> Result<(), String> {
+        self.machine()?;
+        if self.recorded_at > now + Duration::seconds(config.max_clock_skew_secs) {
+            return Err(format!("recorded_at {} is in the future", self.recorded_at));
+        }
+        if self.recorded_at < now - Duration::days(config.retention_days.into()) {
+            return Err(format!(
+                "recorded_at {} is older than the {} days kept",
+                self.recorded_at, config.retention_days
+            ));
+        }
+        match (self.latitude, self.longitude) {
+            (Some(lat), Some(lon)) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err("Position is out of range".to_string());
+                }
+            }
+            (None, None) => {}
+            _ => return Err("latitude and longitude must be sent together".to_string()),
+        }
+        if self.engine_hours.is_some_and(|h| !h.is_finite() || h < 0.0) {
+            return Err("engine_hours must not be negative".to_string());
+        }
+        if self.fuel_level.is_some_and(|f| !(0.0..=100.0).contains(&f)) {
+            return Err("fuel_level must be a percentage between 0 and 100".to_string());
+        }
+        if self.latitude.is_none() && self.engine_hours.is_none() && self.fuel_level.is_none() {
+            return Err("The ping carries no position, engine hours or fuel level".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A ping that was not stored, by its position in the batch.
+#[derive(Debug, Clone, Serialize)]
+pub struct Rejection {
+    pub index: usize,
+    pub error: String,
+}
+
+/// The response to a batch. Duplicates are pings already stored for the
+/// same machine and instant, typically resent after a lost response.
+#[derive(Debug, Default, Serialize)]
+pub struct IngestReport {
+    pub accepted: usize,
+    pub duplicates: usize,
+    pub rejected: Vec<Rejection>,
+    /// After-hours departures from a site raised by this batch
+    pub alerts: usize,
+}
+
+/// Parses and checks each ping of a batch. Returns the valid ones with
+/// their index in the batch; repeats within the batch count as duplicates.
+pub fn check_batch(
+    pings: &[serde_json::Value],
+    now: DateTime<Utc>,
+    config: &TelematicsConfig,
+) -> (Vec<(usize, Ping)>, IngestReport) {
+    let mut report = IngestReport::default();
+    let mut seen = HashSet::new();
+    let mut valid = Vec::new();
+    for (index, value) in pings.iter().enumerate() {
+        let ping = match Ping::deserialize(value) {
+            Ok(ping) => ping,
+            Err(e) => {
+                report.rejected.push(Rejection { index, error: format!("Malformed ping: {}", e) });
+                continue;
+            }
+        };
+        if let Err(error) = ping.validate(now, config) {
+            report.rejected.push(Rejection { index, error });
+            continue;
+        }
+        let key = (ping.machine().unwrap_or(MachineRef::Serial(String::new())), ping.recorded_at);
+        if !seen.insert(key) {
+            report.duplicates += 1;
+            continue;
+        }
+        valid.push((index, ping));
+    }
+    (valid, report)
+}
+
+/// Checks a batch, matches each ping to its machine and stores the new
+/// ones, then runs their positions through the site geofences. Pings for
+/// unknown machines are rejected.
+pub async fn ingest(
+    pool: &PgPool,
+    pings: &[serde_json::Value],
+    now: DateTime<Utc>,
+    config: &Config,
+) -> Result<IngestReport, sqlx::Error> {
+    let (valid, mut report) = check_batch(pings, now, &config.telematics);
+
+    let mut serials = Vec::new();
+    let mut devices = Vec::new();
+    for (_, ping) in &valid {
+        match ping.machine() {
+            Ok(MachineRef::Serial(serial)) => serials.push(serial),
+            Ok(MachineRef::Device(device)) => devices.push(device),
+            Err(_) => {}
+        }
+    }
+    let mut machines = HashMap::new();
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, serial_number, telematics_device_id
+        FROM equipment
+        WHERE serial_number = ANY($1) OR telematics_device_id = ANY($2)
+        "#,
+        &serials,
+        &devices
+    )
+    .fetch_all(pool)
+    .await?;
+    for row in rows {
+        machines.insert(MachineRef::Serial(row.serial_number), row.id);
+        if let Some(device) = row.telematics_device_id {
+            machines.insert(MachineRef::Device(device), row.id);
+        }
+    }
+
+    let mut equipment_ids = Vec::new();
+    let mut recorded = Vec::new();
+    let mut latitudes = Vec::new();
+    let mut longitudes = Vec::new();
+    let mut hours = Vec::new();
+    let mut fuel = Vec::new();
+    for (index, ping) in valid {
+        let machine = ping.machine().unwrap_or(MachineRef::Serial(String::new()));
+        let Some(&equipment_id) = machines.get(&machine) else {
+            let error = match machine {
+                MachineRef::Serial(serial) => format!("Unknown serial number '{}'", serial),
+                MachineRef::Device(device) => format!("Unknown device ID '{}'", device),
+            };
+            report.rejected.push(Rejection { index, error });
+            continue;
+        };
+        equipment_ids.push(equipment_id);
+        recorded.push(ping.recorded_at);
+        latitudes.push(ping.latitude);
+        longitudes.push(ping.longitude);
+        hours.push(ping.engine_hours);
+        fuel.push(ping.fuel_level);
+    }
+    report.rejected.sort_by_key(|r| r.index);
+
+    let mut tx = pool.begin().await?;
+    let stored = sqlx::query!(
+        r#"
+        INSERT INTO telematics_pings (equipment_id, recorded_at, latitude, longitude, engine_hours, fuel_level)
+        SELECT * FROM UNNEST($1::int[], $2::timestamptz[], $3::float8[], $4::float8[], $5::float8[], $6::float8[])
+        ON CONFLICT (equipment_id, recorded_at) DO NOTHING
+        RETURNING equipment_id, recorded_at, latitude, longitude
+        "#,
+        &equipment_ids,
+        &recorded,
+        &latitudes as &[Option<f64>],
+        &longitudes as &[Option<f64>],
+        &hours as &[Option<f64>],
+        &fuel as &[Option<f64>]
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    report.accepted = stored.len();
+    report.duplicates += equipment_ids.len() - stored.len();
+
+    // Only new pings move machines across zone boundaries
+    let mut positions: HashMap<i32, Vec<Position>> = HashMap::new();
+    for row in stored {
+        if let (Some(latitude), Some(longitude)) = (row.latitude, row.longitude) {
+            positions
+                .entry(row.equipment_id)
+                .or_default()
+                .push(Position { recorded_at: row.recorded_at, latitude, longitude });
+        }
+    }
+    report.alerts = geofences::evaluate(&mut tx, &positions, config.locale.tz(), &config.geofences).await?;
+    tx.commit().await?;
+    Ok(report)
+}
+
+/// A machine's latest readings, each from the last ping that carried it.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct LastKnown {
+    pub last_ping_at: DateTime<Utc>,
+    pub position_at: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub engine_hours: Option<f64>,
+    pub fuel_level: Option<f64>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct StoredPing {
+    pub recorded_at: DateTime<Utc>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub engine_hours: Option<f64>,
+    pub fuel_level: Option<f64>,
+    pub received_at: DateTime<Utc>,
+}
+
+pub async fn last_known(pool: &PgPool, equipment_id: i32) -> Result<Option<LastKnown>, sqlx::Error> {
+    sqlx::query_as!(
+        LastKnown,
+        r#"
+        SELECT last_ping_at as "last_ping_at!", position_at, latitude, longitude, engine_hours, fuel_level
+        FROM equipment_telematics
+        WHERE equipment_id = $1
+        "#,
+        equipment_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// The machine's latest pings, newest first.
+pub async fn recent_pings(pool: &PgPool, equipment_id: i32, limit: i64) -> Result<Vec<StoredPing>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredPing,
+        r#"
+        SELECT recorded_at, latitude, longitude, engine_hours, fuel_level, received_at
+        FROM telematics_pings
+        WHERE equipment_id = $1
+        ORDER BY recorded_at DESC
+        LIMIT $2
+        "#,
+        equipment_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn device_id(pool: &PgPool, equipment_id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT telematics_device_id FROM equipment WHERE id = $1",
+        equipment_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map(Option::flatten)
+}
+
+/// Sets the device ID the machine's unit reports under, or clears it.
+/// Returns the name of the machine already using the ID, if another one does.
+pub async fn set_device_id(
+    pool: &PgPool,
+    equipment_id: i32,
+    device_id: Option<&str>,
+) -> Result<Option<String>, sqlx::Error> {
+    if let Some(device_id) = device_id {
+        let taken = sqlx::query_scalar!(
+            "SELECT name FROM equipment WHERE telematics_device_id = $1 AND id != $2",
+            device_id,
+            equipment_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        if taken.is_some() {
+            return Ok(taken);
+        }
+    }
+    sqlx::query!(
+        "UPDATE equipment SET telematics_device_id = $2 WHERE id = $1",
+        equipment_id,
+        device_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(None)
+}
+
+/// Deletes pings recorded before the retention period.
+pub async fn prune(pool: &PgPool, retention_days: i32) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM telematics_pings WHERE recorded_at < NOW() - make_interval(days => $1)",
+        retention_days
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Checks an `Authorization: Bearer` header against the configured key,
+/// comparing in constant time.
+pub fn authorized(header: Option<&str>, api_key: &str) -> bool {
+    let Some(token) = header.and_then(|h| h.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    let (token, key) = (token.trim().as_bytes(), api_key.as_bytes());
+    token.len() == key.len() && token.iter().zip(key).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
@@ -0,0 +1,457 @@
+use crate::sites;
+use crate::webhooks;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgConnection, PgPool};
+
+/// Items of the handover checklist with their display labels, in the order
+/// they are inspected.
+pub const CHECKLIST_ITEMS: &[(&str, &str)] = &[
+    ("bodywork", "Bodywork and paint"),
+    ("glass", "Glass and mirrors"),
+    ("lights", "Lights and signals"),
+    ("tyres", "Tyres or tracks"),
+    ("hydraulics", "Hydraulics (no leaks)"),
+    ("fluids", "Fluid levels"),
+    ("attachments", "Attachments and tools"),
+    ("documents", "Keys and papers"),
+];
+
+/// Conditions an item can be found in, from best to worst.
+pub const CONDITIONS: &[(&str, &str)] = &[
+    ("ok", "OK"),
+    ("worn", "Worn"),
+    ("damaged", "Damaged"),
+    ("missing", "Missing"),
+];
+
+/// Transfer list filters: machines on the road, received ones and those
+/// whose arrival checklist flagged damage.
+pub const TRANSFER_STATUSES: &[(&str, &str)] = &[
+    ("in_transit", "In transit"),
+    ("arrived", "Arrived"),
+    ("damage", "Damage candidate"),
+];
+
+/// A transfer with its locations named; a missing origin or destination is
+/// the yard.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Transfer {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub equipment_name: String,
+    pub serial_number: String,
+    pub origin_site_id: Option<i32>,
+    pub origin_name: Option<String>,
+    pub destination_site_id: Option<i32>,
+    pub destination_name: Option<String>,
+    pub driver_id: Option<i32>,
+    pub driver_name: Option<String>,
+    pub dispatch_date: NaiveDate,
+    pub dispatch_meter: f64,
+    pub dispatch_notes: Option<String>,
+    pub arrival_date: Option<NaiveDate>,
+    pub arrival_meter: Option<f64>,
+    pub arrival_notes: Option<String>,
+    pub damage_candidate: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TransferFilter {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub equipment_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub site_id: Option<i32>,
+}
+
+/// One line of a handover checklist.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Check {
+    pub stage: String,
+    pub item: String,
+    pub condition: String,
+}
+
+/// Dispatches a machine from where it is now to `destination_site_id`, or
+/// to the yard when empty. The checklist comes as repeated `item` and
+/// `condition` fields, one pair per checklist item.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DispatchForm {
+    pub equipment_id: i32,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub destination_site_id: Option<i32>,
+    pub driver_id: i32,
+    pub dispatch_date: NaiveDate,
+    pub dispatch_meter: f64,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub item: Vec<String>,
+    #[serde(default)]
+    pub condition: Vec<String>,
+}
+
+impl DispatchForm {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.dispatch_meter.is_finite() || self.dispatch_meter < 0.0 {
+            return Err("Enter the meter reading at dispatch".to_string());
+        }
+        read_checklist("dispatch", &self.item, &self.condition).map(|_| ())
+    }
+}
+
+/// Receives a machine at its destination.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ArrivalForm {
+    pub arrival_date: NaiveDate,
+    pub arrival_meter: f64,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub item: Vec<String>,
+    #[serde(default)]
+    pub condition: Vec<String>,
+}
+
+impl ArrivalForm {
+    pub fn validate(&self, transfer: &Transfer) -> Result<(), String> {
+        if let Some(arrived) = transfer.arrival_date {
+            return Err(format!("The machine was already received on {}", arrived));
+        }
+        if self.arrival_date < transfer.dispatch_date {
+            return Err(format!("The machine left on {}; it cannot arrive before that", transfer.dispatch_date));
+        }
+        if !self.arrival_meter.is_finite() || self.arrival_meter < transfer.dispatch_meter {
+            return Err(format!(
+                "The meter read {} at dispatch; the arrival reading cannot be lower",
+                transfer.dispatch_meter
+            ));
+        }
+        read_checklist("arrival", &self.item, &self.condition).map(|_| ())
+    }
+}
+
+/// Pairs submitted items with their conditions, requiring every checklist
+/// item exactly once and a known condition for each.
+pub fn read_checklist(stage: &str, items: &[String], conditions: &[String]) -> Result<Vec<Check>, String> {
+    if items.len() != conditions.len() {
+        return Err("Check every item of the handover checklist".to_string());
+    }
+    let mut checks = Vec::new();
+    for (item, _) in CHECKLIST_ITEMS {
+        let found: Vec<_> = items.iter().zip(conditions).filter(|(i, _)| i == item).collect();
+        let [(_, condition)] = found.as_slice() else {
+            return Err("Check every item of the handover checklist".to_string());
+        };
+        if !CONDITIONS.iter().any(|(value, _)| value == condition) {
+            return Err(format!("Unknown condition '{}'", condition));
+        }
+        checks.push(Check {
+            stage: stage.to_string(),
+            item: item.to_string(),
+            condition: condition.to_string(),
+        });
+    }
+    if checks.len() != items.len() {
+        return Err("The handover checklist has unknown items".to_string());
+    }
+    Ok(checks)
+}
+
+/// A checklist item as found at both ends of the transfer.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecklistRow {
+    pub item: &'static str,
+    pub label: &'static str,
+    pub dispatched: Option<String>,
+    pub arrived: Option<String>,
+    /// Found in a worse condition on arrival than at dispatch.
+    pub discrepancy: bool,
+}
+
+fn severity(condition: &str) -> Option<usize> {
+    CONDITIONS.iter().position(|(value, _)| *value == condition)
+}
+
+/// Lines up the dispatch and arrival checklists item by item and flags the
+/// items that got worse on the way.
+pub fn compare_checklists(checks: &[Check]) -> Vec<ChecklistRow> {
+    let condition = |stage: &str, item: &str| {
+        checks
+            .iter()
+            .find(|c| c.stage == stage && c.item == item)
+            .map(|c| c.condition.clone())
+    };
+    CHECKLIST_ITEMS
+        .iter()
+        .map(|(item, label)| {
+            let dispatched = condition("dispatch", item);
+            let arrived = condition("arrival", item);
+            let discrepancy = match (dispatched.as_deref().and_then(severity), arrived.as_deref().and_then(severity)) {
+                (Some(before), Some(after)) => after > before,
+                _ => false,
+            };
+            ChecklistRow { item, label, dispatched, arrived, discrepancy }
+        })
+        .collect()
+}
+
+pub async fn list_transfers(pool: &PgPool, filter: &TransferFilter) -> Result<Vec<Transfer>, sqlx::Error> {
+    sqlx::query_as!(
+        Transfer,
+        r#"
+        SELECT t.id, t.equipment_id, e.name as equipment_name, e.serial_number,
+            t.origin_site_id, o.name as "origin_name?", t.destination_site_id,
+            d.name as "destination_name?", t.driver_id, st.full_name as "driver_name?",
+            t.dispatch_date, t.dispatch_meter, t.dispatch_notes,
+            t.arrival_date, t.arrival_meter, t.arrival_notes, t.damage_candidate, t.created_at
+        FROM transfers t
+        JOIN equipment e ON e.id = t.equipment_id
+        LEFT JOIN sites o ON o.id = t.origin_site_id
+        LEFT JOIN sites d ON d.id = t.destination_site_id
+        LEFT JOIN staff st ON st.id = t.driver_id
+        WHERE ($1::text IS NULL
+                OR ($1 = 'in_transit' AND t.arrival_date IS NULL)
+                OR ($1 = 'arrived' AND t.arrival_date IS NOT NULL)
+                OR ($1 = 'damage' AND t.damage_candidate))
+            AND ($2::int IS NULL OR t.equipment_id = $2)
+            AND ($3::int IS NULL OR t.origin_site_id = $3 OR t.destination_site_id = $3)
+        ORDER BY t.arrival_date IS NOT NULL, t.dispatch_date DESC, t.id DESC
+        "#,
+        filter.status,
+        filter.equipment_id,
+        filter.site_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_transfer(pool: &PgPool, id: i32) -> Result<Option<Transfer>, sqlx::Error> {
+    sqlx::query_as!(
+        Transfer,
+        r#"
+        SELECT t.id, t.equipment_id, e.name as equipment_name, e.serial_number,
+            t.origin_site_id, o.name as "origin_name?", t.destination_site_id,
+            d.name as "destination_name?", t.driver_id, st.full_name as "driver_name?",
+            t.dispatch_date, t.dispatch_meter, t.dispatch_notes,
+            t.arrival_date, t.arrival_meter, t.arrival_notes, t.damage_candidate, t.created_at
+        FROM transfers t
+        JOIN equipment e ON e.id = t.equipment_id
+        LEFT JOIN sites o ON o.id = t.origin_site_id
+        LEFT JOIN sites d ON d.id = t.destination_site_id
+        LEFT JOIN staff st ON st.id = t.driver_id
+        WHERE t.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// The machine's transfer still on the road, if any.
+pub async fn open_transfer(conn: &mut PgConnection, equipment_id: i32) -> Result<Option<Transfer>, sqlx::Error> {
+    sqlx::query_as!(
+        Transfer,
+        r#"
+        SELECT t.id, t.equipment_id, e.name as equipment_name, e.serial_number,
+            t.origin_site_id, o.name as "origin_name?", t.destination_site_id,
+            d.name as "destination_name?", t.driver_id, st.full_name as "driver_name?",
+            t.dispatch_date, t.dispatch_meter, t.dispatch_notes,
+            t.arrival_date, t.arrival_meter, t.arrival_notes, t.damage_candidate, t.created_at
+        FROM transfers t
+        JOIN equipment e ON e.id = t.equipment_id
+        LEFT JOIN sites o ON o.id = t.origin_site_id
+        LEFT JOIN sites d ON d.id = t.destination_site_id
+        LEFT JOIN staff st ON st.id = t.driver_id
+        WHERE t.equipment_id = $1 AND t.arrival_date IS NULL
+        "#,
+        equipment_id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+pub async fn transfer_checks(pool: &PgPool, id: i32) -> Result<Vec<Check>, sqlx::Error> {
+    sqlx::query_as!(
+        Check,
+        "SELECT stage, item, condition FROM transfer_checks WHERE transfer_id = $1",
+        id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Why the machine cannot be dispatched as the form says, if it cannot:
+/// the move itself must be possible on the dispatch date, the machine must
+/// not already be on the road and the driver must exist. Locks the machine
+/// until the transaction ends.
+pub async fn dispatch_problem(conn: &mut PgConnection, form: &DispatchForm) -> Result<Option<String>, sqlx::Error> {
+    if let Some(problem) = sites::move_problem(conn, form.equipment_id, form.destination_site_id, form.dispatch_date).await? {
+        return Ok(Some(problem));
+    }
+    let open = sqlx::query!(
+        r#"
+        SELECT t.id, e.name, t.dispatch_date
+        FROM transfers t
+        JOIN equipment e ON e.id = t.equipment_id
+        WHERE t.equipment_id = $1 AND t.arrival_date IS NULL
+        "#,
+        form.equipment_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    if let Some(open) = open {
+        return Ok(Some(format!(
+            "{} has been in transit since {} (transfer {}); receive it first",
+            open.name, open.dispatch_date, open.id
+        )));
+    }
+    let driver = sqlx::query_scalar!("SELECT id FROM staff WHERE id = $1", form.driver_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    if driver.is_none() {
+        return Ok(Some(format!("Staff member {} not found", form.driver_id)));
+    }
+    Ok(None)
+}
+
+/// Records the dispatch with its checklist, starting from the machine's
+/// current site (or the yard). The machine stays at its origin until it is
+/// received. Check with `dispatch_problem` first.
+pub async fn dispatch(conn: &mut PgConnection, form: &DispatchForm) -> Result<i32, sqlx::Error> {
+    let origin = sqlx::query_scalar!(
+        "SELECT site_id FROM deployments WHERE equipment_id = $1 AND deployed_until IS NULL",
+        form.equipment_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO transfers (
+            equipment_id, origin_site_id, destination_site_id, driver_id,
+            dispatch_date, dispatch_meter, dispatch_notes
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+        form.equipment_id,
+        origin,
+        form.destination_site_id,
+        form.driver_id,
+        form.dispatch_date,
+        form.dispatch_meter,
+        form.notes
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let checks = read_checklist("dispatch", &form.item, &form.condition).unwrap_or_default();
+    insert_checks(conn, id, &checks).await?;
+    Ok(id)
+}
+
+/// Why the machine cannot be received as the form says, if it cannot. It
+/// rechecks the move under the machine lock, since the machine may have
+/// been moved by hand while on the road.
+pub async fn arrival_problem(
+    conn: &mut PgConnection,
+    transfer: &Transfer,
+    form: &ArrivalForm,
+) -> Result<Option<String>, sqlx::Error> {
+    if let Err(message) = form.validate(transfer) {
+        return Ok(Some(message));
+    }
+    sites::move_problem(conn, transfer.equipment_id, transfer.destination_site_id, form.arrival_date).await
+}
+
+/// Records the arrival and its checklist, flags the transfer as a damage
+/// candidate if any item got worse, and moves the machine to its
+/// destination on the arrival date. A damage candidate files a
+/// `damage_report.filed` webhook event with the items that got worse.
+/// `dispatched` is the checklist filled in at dispatch. Returns `None` if
+/// the transfer was received in the meantime, otherwise whether damage was
+/// flagged. Check with `arrival_problem` first.
+pub async fn receive(
+    conn: &mut PgConnection,
+    transfer: &Transfer,
+    dispatched: &[Check],
+    form: &ArrivalForm,
+) -> Result<Option<bool>, sqlx::Error> {
+    let checks = read_checklist("arrival", &form.item, &form.condition).unwrap_or_default();
+    let all: Vec<Check> = dispatched.iter().chain(&checks).cloned().collect();
+    let discrepancies: Vec<ChecklistRow> = compare_checklists(&all)
+        .into_iter()
+        .filter(|row| row.discrepancy)
+        .collect();
+    let damage_candidate = !discrepancies.is_empty();
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE transfers
+        SET arrival_date = $2, arrival_meter = $3, arrival_notes = $4, damage_candidate = $5
+        WHERE id = $1 AND arrival_date IS NULL
+        "#,
+        transfer.id,
+        form.arrival_date,
+        form.arrival_meter,
+        form.notes,
+        damage_candidate
+    )
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    insert_checks(conn, transfer.id, &checks).await?;
+
+    let note = format!("Transfer {}", transfer.id);
+    sites::move_equipment(conn, transfer.equipment_id, transfer.destination_site_id, form.arrival_date, Some(&note)).await?;
+
+    if damage_candidate {
+        let payload = json!({
+            "transfer": {
+                "id": transfer.id,
+                "origin_site_id": transfer.origin_site_id,
+                "origin_name": transfer.origin_name,
+                "destination_site_id": transfer.destination_site_id,
+                "destination_name": transfer.destination_name,
+                "driver_id": transfer.driver_id,
+                "driver_name": transfer.driver_name,
+                "dispatch_date": transfer.dispatch_date,
+                "arrival_date": form.arrival_date,
+                "arrival_notes": form.notes,
+            },
+            "equipment": {
+                "id": transfer.equipment_id,
+                "name": transfer.equipment_name,
+                "serial_number": transfer.serial_number,
+            },
+            "discrepancies": discrepancies,
+        });
+        webhooks::enqueue(conn, "damage_report.filed", payload, None).await?;
+    }
+    Ok(Some(damage_candidate))
+}
+
+async fn insert_checks(conn: &mut PgConnection, id: i32, checks: &[Check]) -> Result<(), sqlx::Error> {
+    let stages: Vec<String> = checks.iter().map(|c| c.stage.clone()).collect();
+    let items: Vec<String> = checks.iter().map(|c| c.item.clone()).collect();
+    let conditions: Vec<String> = checks.iter().map(|c| c.condition.clone()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO transfer_checks (transfer_id, stage, item, condition)
+        SELECT $1, * FROM UNNEST($2::text[], $3::text[], $4::text[])
+        "#,
+        id,
+        &stages,
+        &items,
+        &conditions
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use rand::RngCore;
+use std::path::Path;
+
+/// Largest attachment accepted, in bytes.
+pub const MAX_UPLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+/// Attachment types kept on disk, by their leading bytes.
+const KINDS: &[(&[u8], &str, &str)] = &[
+    (b"%PDF-", "application/pdf", "pdf"),
+    (b"\xFF\xD8\xFF", "image/jpeg", "jpg"),
+    (b"\x89PNG\r\n\x1a\n", "image/png", "png"),
+];
+
+/// The content type and file extension of an upload, judged by its header
+/// rather than its name. `None` for anything not in `accepted`.
+pub fn sniff(data: &[u8], accepted: &[&str]) -> Option<(&'static str, &'static str)> {
+    KINDS
+        .iter()
+        .find(|(magic, content_type, _)| data.starts_with(magic) && accepted.contains(content_type))
+        .map(|(_, content_type, extension)| (*content_type, *extension))
+}
+
+/// A fresh, unguessable file name for an attachment of `owner_id`.
+pub fn stored_name(owner_id: i32, extension: &str) -> String {
+    let mut bytes = [0u8; 16];
+    rand::rng().fill_bytes(&mut bytes);
+    format!("{}-{}.{}", owner_id, hex::encode(bytes), extension)
+}
+
+/// Writes an attachment, creating its directory on first use.
+pub async fn save(dir: &Path, stored_name: &str, data: &[u8]) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(dir.join(stored_name), data).await
+}
+
+/// Reduces an uploaded file name to a safe `Content-Disposition` value.
+pub fn sanitize_filename(name: &str, fallback: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '"' | ';'))
+        .take(200)
+        .collect();
+    match cleaned.trim() {
+        "" => fallback.to_string(),
+        name => name.to_string(),
+    }
+}
@@ -0,0 +1,152 @@
+use argon2::{
+    password_hash::{phc::PasswordHash, PasswordHasher, PasswordVerifier},
+    Argon2,
+};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+
+pub const ROLES: &[&str] = &["admin", "manager", "viewer"];
+const MIN_PASSWORD_LENGTH: usize = 10;
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct User {
+    pub id: i32,
+    pub email: String,
+    pub full_name: String,
+    pub role: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Hashes a password into a PHC string (Argon2id with a random salt).
+pub fn hash_password(password: &str) -> Result<String, String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    Argon2::default()
+        .hash_password(password.as_bytes())
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+pub async fn create_user(
+    pool: &PgPool,
+    email: &str,
+    full_name: &str,
+    role: &str,
+    password: &str,
+) -> Result<User, String> {
+    if !ROLES.contains(&role) {
+        return Err(format!("Unknown role '{}' (expected one of {})", role, ROLES.join(", ")));
+    }
+    let password_hash = hash_password(password)?;
+
+    sqlx::query_as!(
+        User,
+        r#"
+        INSERT INTO users (email, full_name, role, password_hash)
+        VALUES (LOWER($1), $2, $3, $4)
+        RETURNING id, email, full_name, role, active, created_at
+        "#,
+        email.trim(),
+        full_name,
+        role,
+        password_hash
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            format!("A user with email '{}' already exists", email)
+        }
+        e => e.to_string(),
+    })
+}
+
+pub async fn reset_password(pool: &PgPool, email: &str, password: &str) -> Result<(), String> {
+    let password_hash = hash_password(password)?;
+
+    let result = sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE email = LOWER($2)",
+        password_hash,
+        email.trim()
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if result.rows_affected() == 0 {
+        return Err(format!("No user with email '{}'", email));
+    }
+    Ok(())
+}
+
+/// Issues a new calendar feed token for the user, invalidating the old one.
+pub async fn rotate_calendar_token(pool: &PgPool, email: &str) -> Result<String, String> {
+    let mut bytes = [0u8; 24];
+    rand::rng().fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+
+    set_calendar_token(pool, email, Some(&token)).await?;
+    Ok(token)
+}
+
+/// Disables the user's calendar feed.
+pub async fn revoke_calendar_token(pool: &PgPool, email: &str) -> Result<(), String> {
+    set_calendar_token(pool, email, None).await
+}
+
+async fn set_calendar_token(pool: &PgPool, email: &str, token: Option<&str>) -> Result<(), String> {
+    let result = sqlx::query!(
+        "UPDATE users SET calendar_token = $1 WHERE email = LOWER($2)",
+        token,
+        email.trim()
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if result.rows_affected() == 0 {
+        return Err(format!("No user with email '{}'", email));
+    }
+    Ok(())
+}
+
+/// The active user a calendar feed token was issued to.
+pub async fn find_by_calendar_token(pool: &PgPool, token: &str) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        r#"
+        SELECT id, email, full_name, role, active, created_at
+        FROM users
+        WHERE calendar_token = $1 AND active
+        "#,
+        token
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn list_users(pool: &PgPool) -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        "SELECT id, email, full_name, role, active, created_at FROM users ORDER BY email"
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,459 @@
+//! How much machines actually work.
+//!
+//! Hours used come from engine-hour meters: telematics pings and the
+//! readings taken when a machine is dispatched or received on a transfer.
+//! A machine can run `utilization.shift_hours` on every working day since
+//! it was acquired. Working days between its first and last meter reading
+//! are metered: they count towards utilization and are idle when the meter
+//! barely moved. Outside that span the hours are unknown, so a working day
+//! is idle when the machine sat at the yard rather than on a site.
+
+use crate::config::UtilizationConfig;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::{BTreeMap, HashMap};
+
+/// How far before the first day meter readings are loaded, so hours run
+/// between a reading before the range and one inside it are not lost.
+const METER_LOOKBACK_DAYS: i64 = 90;
+
+/// Days covered by the dashboard widget. Kept to a week, since the
+/// dashboard loads it on every visit; the reports cover
+/// `utilization.window_days`.
+pub const DASHBOARD_DAYS: i64 = 7;
+
+/// Trend granularities offered on the utilization page.
+pub const PERIODS: &[(&str, &str)] = &[("week", "Weekly"), ("month", "Monthly")];
+
+/// Lowest and highest engine-hour reading of a machine on one day.
+#[derive(Debug, Clone, FromRow)]
+pub struct MeterDay {
+    pub equipment_id: i32,
+    pub day: NaiveDate,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// A machine's stay on a site; `until` is the day it left.
+#[derive(Debug, Clone, FromRow)]
+pub struct Stay {
+    pub equipment_id: i32,
+    pub from: NaiveDate,
+    pub until: Option<NaiveDate>,
+}
+
+impl Stay {
+    fn covers(&self, day: NaiveDate) -> bool {
+        self.from <= day && self.until.is_none_or(|until| day < until)
+    }
+}
+
+/// A machine still in the fleet.
+#[derive(Debug, Clone, FromRow)]
+pub struct Machine {
+    pub id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub category_id: i32,
+    pub category_name: String,
+    pub acquired: NaiveDate,
+}
+
+/// One machine over a date range.
+#[derive(Debug, Clone, Serialize)]
+pub struct MachineUsage {
+    pub equipment_id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub category_id: i32,
+    pub category_name: String,
+    /// Working days since the machine was acquired
+    pub working_days: i64,
+    /// Working days between its first and last meter reading
+    pub metered_days: i64,
+    pub hours_used: f64,
+    pub hours_available: f64,
+    /// Percent of available hours used; `None` without meter readings
+    pub utilization: Option<f64>,
+    pub deployed_days: i64,
+    pub idle_days: i64,
+    /// Percent of working days idle; `None` without working days
+    pub idle_share: Option<f64>,
+}
+
+/// Usage summed over several machines.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Totals {
+    pub machines: usize,
+    pub metered_machines: usize,
+    pub hours_used: f64,
+    pub hours_available: f64,
+    pub utilization: Option<f64>,
+    pub working_days: i64,
+    pub idle_days: i64,
+    pub idle_share: Option<f64>,
+}
+
+impl Totals {
+    pub fn of<'a>(usages: impl IntoIterator<Item = &'a MachineUsage>) -> Totals {
+        let mut totals = Totals::default();
+        for usage in usages {
+            totals.machines += 1;
+            if usage.metered_days > 0 {
+                totals.metered_machines += 1;
+            }
+            totals.hours_used += usage.hours_used;
+            totals.hours_available += usage.hours_available;
+            totals.working_days += usage.working_days;
+            totals.idle_days += usage.idle_days;
+        }
+        totals.utilization = percent(totals.hours_used, totals.hours_available);
+        totals.idle_share = percent(totals.idle_days as f64, totals.working_days as f64);
+        totals
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryUsage {
+    pub category_id: i32,
+    pub category_name: String,
+    #[serde(flatten)]
+    pub totals: Totals,
+}
+
+/// Fleet usage over one week or month of the trend.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendPoint {
+    pub label: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    #[serde(flatten)]
+    pub totals: Totals,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UtilizationQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub to: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub category_id: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub period: Option<String>,
+}
+
+fn percent(part: f64, whole: f64) -> Option<f64> {
+    (whole > 0.0).then(|| part / whole * 100.0)
+}
+
+/// Engine hours run per day, from daily meter readings of one machine
+/// sorted by day. The rise between two reading days is spread evenly over
+/// the days after the first up to the second; a meter that went backwards
+/// (replaced or reset) adds nothing.
+pub fn daily_hours(readings: &[MeterDay]) -> BTreeMap<NaiveDate, f64> {
+    let mut hours = BTreeMap::new();
+    let mut previous: Option<&MeterDay> = None;
+    for reading in readings {
+        let mut today = (reading.high - reading.low).max(0.0);
+        if let Some(previous) = previous {
+            let gap = (reading.low - previous.high).max(0.0);
+            let days = (reading.day - previous.day).num_days().max(1);
+            let share = gap / days as f64;
+            for n in 1..days {
+                *hours.entry(previous.day + Duration::days(n)).or_insert(0.0) += share;
+            }
+            today += share;
+        }
+        *hours.entry(reading.day).or_insert(0.0) += today;
+        previous = Some(reading);
+    }
+    hours
+}
+
+/// Usage of one machine from `from` to `to` inclusive, given its daily
+/// hours (see [`daily_hours`]) and its stays on sites.
+pub fn machine_usage(
+    machine: &Machine,
+    hours: &BTreeMap<NaiveDate, f64>,
+    stays: &[Stay],
+    from: NaiveDate,
+    to: NaiveDate,
+    work_days: &[Weekday],
+    config: &UtilizationConfig,
+) -> MachineUsage {
+    let metered = hours.keys().next().zip(hours.keys().next_back());
+    let mut usage = MachineUsage {
+        equipment_id: machine.id,
+        name: machine.name.clone(),
+        serial_number: machine.serial_number.clone(),
+        category_id: machine.category_id,
+        category_name: machine.category_name.clone(),
+        working_days: 0,
+        metered_days: 0,
+        hours_used: 0.0,
+        hours_available: 0.0,
+        utilization: None,
+        deployed_days: 0,
+        idle_days: 0,
+        idle_share: None,
+    };
+
+    for day in from.max(machine.acquired).iter_days().take_while(|day| *day <= to) {
+        let ran = hours.get(&day).copied().unwrap_or(0.0);
+        let is_metered = metered.is_some_and(|(first, last)| *first <= day && day <= *last);
+        if is_metered {
+            // Weekend work counts as used, without adding to what was available
+            usage.hours_used += ran;
+        }
+        if !work_days.contains(&day.weekday()) {
+            continue;
+        }
+        usage.working_days += 1;
+        let deployed = stays.iter().any(|stay| stay.covers(day));
+        if deployed {
+            usage.deployed_days += 1;
+        }
+        if is_metered {
+            usage.metered_days += 1;
+            usage.hours_available += config.shift_hours;
+            if ran < config.idle_hours {
+                usage.idle_days += 1;
+            }
+        } else if !deployed {
+            usage.idle_days += 1;
+        }
+    }
+
+    usage.utilization = percent(usage.hours_used, usage.hours_available);
+    usage.idle_share = percent(usage.idle_days as f64, usage.working_days as f64);
+    usage
+}
+
+/// Totals per category, in category name order.
+pub fn by_category(usages: &[MachineUsage]) -> Vec<CategoryUsage> {
+    let mut groups: BTreeMap<(&str, i32), Vec<&MachineUsage>> = BTreeMap::new();
+    for usage in usages {
+        groups.entry((&usage.category_name, usage.category_id)).or_default().push(usage);
+    }
+    groups
+        .into_iter()
+        .map(|((name, id), usages)| CategoryUsage {
+            category_id: id,
+            category_name: name.to_string(),
+            totals: Totals::of(usages),
+        })
+        .collect()
+}
+
+/// Machines idle on at least `threshold` percent of their working days,
+/// most idle first; among equals the least utilized come first.
+pub fn underused(usages: &[MachineUsage], threshold: f64) -> Vec<MachineUsage> {
+    let mut ranked: Vec<MachineUsage> = usages
+        .iter()
+        .filter(|usage| usage.idle_share.is_some_and(|share| share >= threshold))
+        .cloned()
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.idle_share
+            .partial_cmp(&a.idle_share)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| {
+                let a = a.utilization.unwrap_or(f64::INFINITY);
+                let b = b.utilization.unwrap_or(f64::INFINITY);
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    ranked
+}
+
+/// The last `count` weeks (Monday to Sunday) or calendar months up to and
+/// including the one holding `to`, oldest first. The last one ends at `to`.
+pub fn periods(period: &str, to: NaiveDate, count: usize) -> Vec<(String, NaiveDate, NaiveDate)> {
+    let mut periods = Vec::with_capacity(count);
+    let mut end = to;
+    for _ in 0..count {
+        let start = if period == "month" {
+            end.with_day(1).unwrap_or(end)
+        } else {
+            end - Duration::days(end.weekday().num_days_from_monday() as i64)
+        };
+        let label = if period == "month" {
+            start.format("%b %Y").to_string()
+        } else {
+            start.format("%G-W%V").to_string()
+        };
+        periods.push((label, start, end));
+        end = start - Duration::days(1);
+    }
+    periods.reverse();
+    periods
+}
+
+/// The date range asked for, defaulting to the `window_days` up to `today`.
+pub fn date_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    today: NaiveDate,
+    window_days: i64,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let to = to.unwrap_or(today);
+    let from = from.unwrap_or(to - Duration::days(window_days - 1));
+    if to > today {
+        return Err("The range cannot end after today".to_string());
+    }
+    if from > to {
+        return Err("The range must start before it ends".to_string());
+    }
+    if (to - from).num_days() >= 366 {
+        return Err("Pick a range of at most a year".to_string());
+    }
+    Ok((from, to))
+}
+
+/// Machines, meter readings and stays loaded for a date range.
+#[derive(Debug, Default)]
+pub struct Fleet {
+    pub machines: Vec<Machine>,
+    pub hours: HashMap<i32, BTreeMap<NaiveDate, f64>>,
+    pub stays: HashMap<i32, Vec<Stay>>,
+}
+
+impl Fleet {
+    /// Usage of every machine from `from` to `to` inclusive, by name.
+    pub fn usage(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        work_days: &[Weekday],
+        config: &UtilizationConfig,
+    ) -> Vec<MachineUsage> {
+        let no_hours = BTreeMap::new();
+        self.machines
+            .iter()
+            .map(|machine| {
+                machine_usage(
+                    machine,
+                    self.hours.get(&machine.id).unwrap_or(&no_hours),
+                    self.stays.get(&machine.id).map(Vec::as_slice).unwrap_or(&[]),
+                    from,
+                    to,
+                    work_days,
+                    config,
+                )
+            })
+            .collect()
+    }
+
+    /// Fleet totals for each period from [`periods`].
+    pub fn trend(
+        &self,
+        periods: &[(String, NaiveDate, NaiveDate)],
+        work_days: &[Weekday],
+        config: &UtilizationConfig,
+    ) -> Vec<TrendPoint> {
+        periods
+            .iter()
+            .map(|(label, start, end)| TrendPoint {
+                label: label.clone(),
+                start: *start,
+                end: *end,
+                totals: Totals::of(&self.usage(*start, *end, work_days, config)),
+            })
+            .collect()
+    }
+}
+
+/// Loads machines not retired (optionally of one category) with their meter
+/// readings and stays for `from` to `to`; days are local to `timezone`.
+pub async fn load(
+    pool: &PgPool,
+    from: NaiveDate,
+    to: NaiveDate,
+    timezone: &str,
+    category_id: Option<i32>,
+) -> Result<Fleet, sqlx::Error> {
+    let machines = sqlx::query_as!(
+        Machine,
+        r#"
+        SELECT e.id, e.name, e.serial_number, e.category_id, c.name as category_name,
+            (e.acquisition_date AT TIME ZONE $1)::date as "acquired!"
+        FROM equipment e
+        JOIN categories c ON c.id = e.category_id
+        WHERE e.current_status <> 'retired' AND ($2::int IS NULL OR e.category_id = $2)
+        ORDER BY e.name
+        "#,
+        timezone,
+        category_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let readings = sqlx::query_as!(
+        MeterDay,
+        r#"
+        SELECT m.equipment_id as "equipment_id!", m.day as "day!",
+            MIN(m.meter) as "low!", MAX(m.meter) as "high!"
+        FROM (
+            SELECT p.equipment_id, (p.recorded_at AT TIME ZONE $3)::date as day, p.engine_hours as meter
+            FROM telematics_pings p
+            WHERE p.engine_hours IS NOT NULL
+              AND p.recorded_at >= ($1::date - $5::int)::timestamp AT TIME ZONE $3
+              AND p.recorded_at < ($2::date + 1)::timestamp AT TIME ZONE $3
+            UNION ALL
+            SELECT t.equipment_id, t.dispatch_date, t.dispatch_meter FROM transfers t
+            WHERE t.dispatch_date BETWEEN $1::date - $5::int AND $2::date
+            UNION ALL
+            SELECT t.equipment_id, t.arrival_date, t.arrival_meter FROM transfers t
+            WHERE t.arrival_date BETWEEN $1::date - $5::int AND $2::date
+        ) m
+        JOIN equipment e ON e.id = m.equipment_id
+        WHERE e.current_status <> 'retired' AND ($4::int IS NULL OR e.category_id = $4)
+        GROUP BY m.equipment_id, m.day
+        ORDER BY m.equipment_id, m.day
+        "#,
+        from,
+        to,
+        timezone,
+        category_id,
+        METER_LOOKBACK_DAYS as i32
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let stays = sqlx::query_as!(
+        Stay,
+        r#"
+        SELECT d.equipment_id, d.deployed_from as "from", d.deployed_until as "until"
+        FROM deployments d
+        JOIN equipment e ON e.id = d.equipment_id
+        WHERE e.current_status <> 'retired' AND ($3::int IS NULL OR e.category_id = $3)
+          AND d.deployed_from <= $2 AND (d.deployed_until IS NULL OR d.deployed_until > $1)
+        "#,
+        from,
+        to,
+        category_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut by_machine: HashMap<i32, Vec<MeterDay>> = HashMap::new();
+    for reading in readings {
+        by_machine.entry(reading.equipment_id).or_default().push(reading);
+    }
+    let mut fleet = Fleet {
+        machines,
+        hours: by_machine
+            .into_iter()
+            .map(|(id, readings)| (id, daily_hours(&readings)))
+            .collect(),
+        stays: HashMap::new(),
+    };
+    for stay in stays {
+        fleet.stays.entry(stay.equipment_id).or_default().push(stay);
+    }
+    Ok(fleet)
+}
+
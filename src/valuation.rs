@@ -0,0 +1,350 @@
+//! Depreciation and the fixed-asset register.
+//!
+//! A machine is written off from its purchase cost down to its salvage value
+//! over its useful life, straight-line or by declining balance. Terms left
+//! blank on a machine come from its category's defaults, then from the
+//! `[costs]` configuration. Depreciation runs day by day from acquisition,
+//! so schedules by month or year add up to the same totals.
+
+use crate::config::{CostsConfig, DepreciationMethod};
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+pub const METHODS: &[(DepreciationMethod, &str)] = &[
+    (DepreciationMethod::StraightLine, "Straight-line"),
+    (DepreciationMethod::DecliningBalance, "Declining balance"),
+];
+
+/// Depreciation terms set on a machine, or as defaults on its category;
+/// `None` leaves the term to the next level.
+#[derive(Debug, Clone, Default)]
+pub struct Terms {
+    pub salvage_value: Option<f64>,
+    /// Salvage value as a share of the purchase cost, for categories
+    pub salvage_percent: Option<f64>,
+    pub useful_life_years: Option<i32>,
+    pub depreciation_method: Option<DepreciationMethod>,
+}
+
+impl Terms {
+    pub fn machine(
+        salvage_value: Option<f64>,
+        useful_life_years: Option<i32>,
+        method: Option<DepreciationMethod>,
+    ) -> Terms {
+        Terms {
+            salvage_value,
+            salvage_percent: None,
+            useful_life_years,
+            depreciation_method: method,
+        }
+    }
+
+    pub fn category(
+        salvage_percent: Option<f64>,
+        useful_life_years: Option<i32>,
+        method: Option<DepreciationMethod>,
+    ) -> Terms {
+        Terms {
+            salvage_value: None,
+            salvage_percent,
+            useful_life_years,
+            depreciation_method: method,
+        }
+    }
+
+    pub fn validate(&self, purchase_cost: Option<f64>) -> Result<(), String> {
+        if let Some(salvage) = self.salvage_value {
+            if !salvage.is_finite() || salvage < 0.0 {
+                return Err("The salvage value cannot be negative".to_string());
+            }
+            if purchase_cost.is_some_and(|cost| salvage > cost) {
+                return Err("The salvage value cannot exceed the purchase cost".to_string());
+            }
+        }
+        if self.salvage_percent.is_some_and(|p| !(0.0..100.0).contains(&p)) {
+            return Err("The salvage value must be between 0 and 100% of the purchase cost".to_string());
+        }
+        if self.useful_life_years.is_some_and(|y| !(1..=50).contains(&y)) {
+            return Err("The useful life must be between 1 and 50 years".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A machine as written off: declining balance takes double the
+/// straight-line rate off the remaining value each year of its life,
+/// switching to straight-line once that writes off more.
+#[derive(Debug, Clone, Serialize)]
+pub struct Asset {
+    pub purchase_cost: f64,
+    pub salvage_value: f64,
+    pub acquired: NaiveDate,
+    pub useful_life_years: u32,
+    pub method: DepreciationMethod,
+    /// Last day of the useful life
+    pub fully_depreciated_on: NaiveDate,
+}
+
+impl Asset {
+    /// The machine's own terms first, then its category's, then `config`.
+    pub fn new(
+        purchase_cost: f64,
+        acquired: NaiveDate,
+        machine: &Terms,
+        category: &Terms,
+        config: &CostsConfig,
+    ) -> Asset {
+        let salvage_value = machine
+            .salvage_value
+            .or_else(|| category.salvage_percent.map(|p| purchase_cost * p / 100.0))
+            .unwrap_or(0.0)
+            .clamp(0.0, purchase_cost);
+        let useful_life_years = machine
+            .useful_life_years
+            .or(category.useful_life_years)
+            .and_then(|years| u32::try_from(years).ok())
+            .filter(|years| *years > 0)
+            .unwrap_or(config.useful_life_years);
+        let method = machine
+            .depreciation_method
+            .or(category.depreciation_method)
+            .unwrap_or(config.depreciation_method);
+        Asset {
+            purchase_cost,
+            salvage_value,
+            acquired,
+            useful_life_years,
+            method,
+            fully_depreciated_on: anniversary(acquired, useful_life_years) - Duration::days(1),
+        }
+    }
+
+    /// Value at the end of `on`; the purchase cost before the machine was
+    /// acquired and the salvage value once its life is over.
+    pub fn book_value(&self, on: NaiveDate) -> f64 {
+        if on < self.acquired {
+            return self.purchase_cost;
+        }
+        let life_end = self.fully_depreciated_on + Duration::days(1);
+        if self.method != DepreciationMethod::DecliningBalance {
+            let depreciable = self.purchase_cost - self.salvage_value;
+            return self.purchase_cost - depreciable * elapsed(self.acquired, life_end, on);
+        }
+        let rate = 2.0 / self.useful_life_years as f64;
+        let mut value = self.purchase_cost;
+        for year in 0..self.useful_life_years {
+            let remaining = (value - self.salvage_value).max(0.0);
+            let charge = (value * rate)
+                .max(remaining / (self.useful_life_years - year) as f64)
+                .min(remaining);
+            let end = anniversary(self.acquired, year + 1);
+            if on < end {
+                return value - charge * elapsed(anniversary(self.acquired, year), end, on);
+            }
+            value -= charge;
+        }
+        self.salvage_value
+    }
+
+    /// Depreciation from `from` to `to` inclusive.
+    pub fn depreciation(&self, from: NaiveDate, to: NaiveDate) -> f64 {
+        self.book_value(from - Duration::days(1)) - self.book_value(to)
+    }
+
+    /// Depreciation by calendar month or year over the useful life.
+    pub fn schedule(&self, monthly: bool) -> Vec<ScheduleLine> {
+        let mut lines = Vec::new();
+        let mut from = self.acquired;
+        while from <= self.fully_depreciated_on {
+            let next = if monthly {
+                from.with_day(1).and_then(|d| d.checked_add_months(Months::new(1)))
+            } else {
+                NaiveDate::from_ymd_opt(from.year() + 1, 1, 1)
+            };
+            let to = next
+                .map(|next| next - Duration::days(1))
+                .unwrap_or(NaiveDate::MAX)
+                .min(self.fully_depreciated_on);
+            let opening = self.book_value(from - Duration::days(1));
+            let closing = self.book_value(to);
+            lines.push(ScheduleLine {
+                from,
+                to,
+                opening,
+                depreciation: opening - closing,
+                closing,
+                accumulated: self.purchase_cost - closing,
+            });
+            from = to + Duration::days(1);
+        }
+        lines
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleLine {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub opening: f64,
+    pub depreciation: f64,
+    pub closing: f64,
+    pub accumulated: f64,
+}
+
+/// Share of `start` to `end` (exclusive) that has passed at the end of `on`.
+fn elapsed(start: NaiveDate, end: NaiveDate, on: NaiveDate) -> f64 {
+    let span = (end - start).num_days().max(1);
+    ((on - start).num_days() + 1).clamp(0, span) as f64 / span as f64
+}
+
+fn anniversary(acquired: NaiveDate, years: u32) -> NaiveDate {
+    acquired
+        .checked_add_months(Months::new(years * 12))
+        .unwrap_or(NaiveDate::MAX)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub as_of: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub category_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleQuery {
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub year: Option<i32>,
+    #[serde(default, deserialize_with = "crate::empty_string_as_none")]
+    pub period: Option<String>,
+}
+
+/// A machine with its depreciation terms and its category's defaults.
+#[derive(Debug, Clone, FromRow)]
+pub struct AssetRow {
+    pub equipment_id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub category_id: i32,
+    pub category_name: String,
+    pub status: String,
+    pub acquired: NaiveDate,
+    pub purchase_cost: Option<f64>,
+    pub salvage_value: Option<f64>,
+    pub useful_life_years: Option<i32>,
+    pub depreciation_method: Option<DepreciationMethod>,
+    pub default_salvage_percent: Option<f64>,
+    pub default_useful_life_years: Option<i32>,
+    pub default_depreciation_method: Option<DepreciationMethod>,
+}
+
+impl AssetRow {
+    pub fn asset(&self, config: &CostsConfig) -> Option<Asset> {
+        let machine = Terms::machine(self.salvage_value, self.useful_life_years, self.depreciation_method);
+        let category = Terms::category(
+            self.default_salvage_percent,
+            self.default_useful_life_years,
+            self.default_depreciation_method,
+        );
+        self.purchase_cost
+            .map(|cost| Asset::new(cost, self.acquired, &machine, &category, config))
+    }
+}
+
+/// A line of the fixed-asset register; the figures are `None` for machines
+/// without a purchase cost.
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisterEntry {
+    pub equipment_id: i32,
+    pub name: String,
+    pub serial_number: String,
+    pub category_id: i32,
+    pub category_name: String,
+    pub status: String,
+    pub acquired: NaiveDate,
+    pub asset: Option<Asset>,
+    /// Depreciation since the start of the year of `as_of`
+    pub year_depreciation: Option<f64>,
+    pub accumulated: Option<f64>,
+    pub book_value: Option<f64>,
+}
+
+pub fn register_entry(row: AssetRow, as_of: NaiveDate, config: &CostsConfig) -> RegisterEntry {
+    let asset = row.asset(config);
+    let year_start = as_of.with_ordinal(1).expect("first day of year");
+    let book_value = asset.as_ref().map(|a| a.book_value(as_of));
+    RegisterEntry {
+        equipment_id: row.equipment_id,
+        name: row.name,
+        serial_number: row.serial_number,
+        category_id: row.category_id,
+        category_name: row.category_name,
+        status: row.status,
+        acquired: row.acquired,
+        year_depreciation: asset.as_ref().map(|a| a.depreciation(year_start, as_of)),
+        accumulated: asset.as_ref().zip(book_value).map(|(a, value)| a.purchase_cost - value),
+        book_value,
+        asset,
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RegisterTotals {
+    pub assets: usize,
+    /// Machines without a purchase cost, left out of the sums
+    pub unvalued: usize,
+    pub purchase_cost: f64,
+    pub year_depreciation: f64,
+    pub accumulated: f64,
+    pub book_value: f64,
+}
+
+impl RegisterTotals {
+    pub fn of(entries: &[RegisterEntry]) -> RegisterTotals {
+        let mut totals = RegisterTotals::default();
+        for entry in entries {
+            let Some(asset) = &entry.asset else {
+                totals.unvalued += 1;
+                continue;
+            };
+            totals.assets += 1;
+            totals.purchase_cost += asset.purchase_cost;
+            totals.year_depreciation += entry.year_depreciation.unwrap_or(0.0);
+            totals.accumulated += entry.accumulated.unwrap_or(0.0);
+            totals.book_value += entry.book_value.unwrap_or(0.0);
+        }
+        totals
+    }
+}
+
+/// Machines, retired ones included, with their depreciation terms; one
+/// machine with `equipment_id`. Acquisition days are local to `timezone`.
+pub async fn asset_rows(
+    pool: &PgPool,
+    timezone: &str,
+    category_id: Option<i32>,
+    equipment_id: Option<i32>,
+) -> Result<Vec<AssetRow>, sqlx::Error> {
+    sqlx::query_as!(
+        AssetRow,
+        r#"
+        SELECT e.id as equipment_id, e.name, e.serial_number, e.category_id, c.name as category_name,
+            e.current_status as "status!", (e.acquisition_date AT TIME ZONE $1)::date as "acquired!",
+            e.purchase_cost, e.salvage_value, e.useful_life_years,
+            e.depreciation_method as "depreciation_method: DepreciationMethod",
+            c.default_salvage_percent, c.default_useful_life_years,
+            c.default_depreciation_method as "default_depreciation_method: DepreciationMethod"
+        FROM equipment e
+        JOIN categories c ON c.id = e.category_id
+        WHERE ($2::int IS NULL OR e.category_id = $2) AND ($3::int IS NULL OR e.id = $3)
+        ORDER BY e.acquisition_date, e.name
+        "#,
+        timezone,
+        category_id,
+        equipment_id
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,436 @@
+use crate::config::{Config, WebhooksConfig};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Events endpoints can subscribe to. A damage report is filed when a
+/// transfer arrives with checklist items in a worse condition than at
+/// dispatch.
+pub const EVENT_TYPES: &[&str] = &[
+    "equipment.created",
+    "equipment.status_changed",
+    "maintenance.recorded",
+    "damage_report.filed",
+    "insurance.lapsed",
+    "geofence.after_hours_exit",
+];
+
+/// Deliveries claimed per poll.
+const BATCH_SIZE: i64 = 20;
+/// Retries never wait longer than this, however many attempts have failed.
+const MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    pub secret: String,
+    pub event_types: Vec<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookForm {
+    pub url: String,
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct DeliverySummary {
+    pub id: i64,
+    pub url: String,
+    pub event_type: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A delivery claimed by the dispatcher, with what it needs to send it.
+#[derive(Debug, FromRow)]
+struct Delivery {
+    id: i64,
+    attempts: i32,
+    url: String,
+    secret: String,
+    event_id: i64,
+    event_type: String,
+    payload: Value,
+    event_created_at: DateTime<Utc>,
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`. Receivers recompute it from
+/// the `X-Kfleet-Timestamp` header and the raw body, and should reject
+/// stale timestamps to prevent replays.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Delay before retrying after `attempts` failed attempts: the base delay,
+/// doubled for each further failure.
+pub fn backoff(attempts: i32, base_secs: i64) -> chrono::Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 30) as u32;
+    let secs = base_secs.saturating_mul(1 << exponent).min(MAX_BACKOFF_SECS);
+    chrono::Duration::seconds(secs)
+}
+
+/// Random 256-bit signing secret, hex encoded.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// The JSON body posted to receivers.
+pub fn envelope(event_id: i64, event_type: &str, created_at: DateTime<Utc>, data: &Value) -> Value {
+    json!({
+        "id": event_id,
+        "type": event_type,
+        "created_at": created_at,
+        "data": data,
+    })
+}
+
+/// Records an event in the outbox along with one pending delivery per active
+/// webhook subscribed to it. Call it on the transaction making the change so
+/// the event is only published if the change commits. Events with a
+/// `dedupe_key` that was already used are dropped; returns the event ID
+/// otherwise.
+pub async fn enqueue(
+    conn: &mut PgConnection,
+    event_type: &str,
+    data: Value,
+    dedupe_key: Option<&str>,
+) -> Result<Option<i64>, sqlx::Error> {
+    debug_assert!(EVENT_TYPES.contains(&event_type));
+
+    let event_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO webhook_events (event_type, payload, dedupe_key)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (dedupe_key) DO NOTHING
+        RETURNING id
+        "#,
+        event_type,
+        data,
+        dedupe_key
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(event_id) = event_id else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_deliveries (webhook_id, event_id)
+        SELECT id, $1 FROM webhooks
+        WHERE active AND $2 = ANY(event_types)
+        "#,
+        event_id,
+        event_type
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(Some(event_id))
+}
+
+/// Publishes `insurance.lapsed` for every machine in service covered by an
+/// active policy whose end date has passed, once per policy and machine.
+/// Renewing or cancelling the policy stops further reports. Returns the
+/// number of new events.
+pub async fn enqueue_insurance_lapses(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let lapsed = sqlx::query!(
+        r#"
+        SELECT e.id, e.name, e.serial_number, e.current_status, p.id as policy_id,
+            p.insurer, p.policy_number, p.end_date
+        FROM insurance_policies p
+        JOIN policy_equipment pe ON pe.policy_id = p.id
+        JOIN equipment e ON e.id = pe.equipment_id
+        WHERE p.status = 'active' AND p.end_date < CURRENT_DATE
+            AND e.current_status != 'retired'
+        ORDER BY p.id, e.id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let mut published = 0;
+    for row in lapsed {
+        let dedupe_key = format!("insurance.lapsed:{}:{}", row.policy_id, row.id);
+        let data = json!({
+            "id": row.id,
+            "name": row.name,
+            "serial_number": row.serial_number,
+            "status": row.current_status,
+            "policy": {
+                "id": row.policy_id,
+                "insurer": row.insurer,
+                "policy_number": row.policy_number,
+                "end_date": row.end_date,
+            },
+        });
+        if enqueue(&mut tx, "insurance.lapsed", data, Some(&dedupe_key)).await?.is_some() {
+            published += 1;
+        }
+    }
+    tx.commit().await?;
+    Ok(published)
+}
+
+pub async fn list_webhooks(pool: &PgPool) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        "SELECT id, url, secret, event_types, active, created_at FROM webhooks ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn recent_deliveries(pool: &PgPool, limit: i64) -> Result<Vec<DeliverySummary>, sqlx::Error> {
+    sqlx::query_as!(
+        DeliverySummary,
+        r#"
+        SELECT d.id, w.url, ev.event_type, d.status, d.attempts, d.next_attempt_at,
+            d.last_status_code, d.last_error, d.created_at
+        FROM webhook_deliveries d
+        JOIN webhooks w ON w.id = d.webhook_id
+        JOIN webhook_events ev ON ev.id = d.event_id
+        ORDER BY d.created_at DESC, d.id DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Queues a delivery to be sent again on the next poll with a fresh set of
+/// attempts.
+pub async fn retry(pool: &PgPool, delivery_id: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'pending', attempts = 0, next_attempt_at = NOW()
+        WHERE id = $1 AND status != 'succeeded'
+        "#,
+        delivery_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub fn http_client(config: &WebhooksConfig) -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .user_agent(concat!("kfleet-webhooks/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// Sends every delivery that is due and records the outcome. Claimed rows
+/// are leased by pushing `next_attempt_at` past the request timeout, so other
+/// instances skip them and a crash mid-send only delays the retry.
+pub async fn dispatch_due(
+    pool: &PgPool,
+    client: &reqwest::Client,
+    config: &WebhooksConfig,
+) -> Result<usize, sqlx::Error> {
+    let lease_secs = (config.timeout_secs + 30) as f64;
+    let deliveries = sqlx::query_as!(
+        Delivery,
+        r#"
+        UPDATE webhook_deliveries d
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM webhooks w, webhook_events ev
+        WHERE d.id IN (
+                SELECT pd.id FROM webhook_deliveries pd
+                JOIN webhooks pw ON pw.id = pd.webhook_id
+                WHERE pd.status = 'pending' AND pd.next_attempt_at <= NOW() AND pw.active
+                ORDER BY pd.next_attempt_at
+                LIMIT $1
+                FOR UPDATE OF pd SKIP LOCKED
+            )
+            AND w.id = d.webhook_id
+            AND ev.id = d.event_id
+        RETURNING d.id, d.attempts, w.url, w.secret, ev.id as event_id, ev.event_type,
+            ev.payload, ev.created_at as event_created_at
+        "#,
+        BATCH_SIZE,
+        lease_secs
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for delivery in &deliveries {
+        let started = Instant::now();
+        let result = send(client, delivery).await;
+        let duration_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+        record_attempt(pool, config, delivery, result, duration_ms).await?;
+    }
+    Ok(deliveries.len())
+}
+
+/// Posts one delivery. Any 2xx response counts as delivered.
+async fn send(client: &reqwest::Client, delivery: &Delivery) -> (Option<i32>, Option<String>) {
+    let body = envelope(
+        delivery.event_id,
+        &delivery.event_type,
+        delivery.event_created_at,
+        &delivery.payload,
+    )
+    .to_string();
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(&delivery.secret, timestamp, body.as_bytes());
+
+    let response = client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Kfleet-Event", &delivery.event_type)
+        .header("X-Kfleet-Delivery", delivery.id.to_string())
+        .header("X-Kfleet-Timestamp", timestamp.to_string())
+        .header("X-Kfleet-Signature", format!("sha256={}", signature))
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_success() => {
+            (Some(response.status().as_u16() as i32), None)
+        }
+        Ok(response) => {
+            let status = response.status();
+            (Some(status.as_u16() as i32), Some(format!("Receiver responded {}", status)))
+        }
+        Err(e) => (None, Some(error_chain(&e))),
+    }
+}
+
+/// reqwest hides the cause ("connection refused", "timed out") in the
+/// source chain; receivers' admins need it.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+async fn record_attempt(
+    pool: &PgPool,
+    config: &WebhooksConfig,
+    delivery: &Delivery,
+    (status_code, error): (Option<i32>, Option<String>),
+    duration_ms: i32,
+) -> Result<(), sqlx::Error> {
+    let attempts = delivery.attempts + 1;
+    let status = match &error {
+        None => "succeeded",
+        Some(_) if attempts >= config.max_attempts => "failed",
+        Some(_) => "pending",
+    };
+    let retry_in = backoff(attempts, config.backoff_base_secs).num_seconds() as f64;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_attempts (delivery_id, status_code, error, duration_ms)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        delivery.id,
+        status_code,
+        error,
+        duration_ms
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2::varchar, attempts = $3, last_status_code = $4, last_error = $5,
+            next_attempt_at = NOW() + make_interval(secs => $6),
+            delivered_at = CASE WHEN $2::varchar = 'succeeded' THEN NOW() END
+        WHERE id = $1
+        "#,
+        delivery.id,
+        status,
+        attempts,
+        status_code,
+        error,
+        retry_in
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    match (status, &error) {
+        ("succeeded", _) => info!("Delivered {} to {}", delivery.event_type, delivery.url),
+        ("failed", Some(e)) => error!(
+            "Giving up on {} to {} after {} attempts: {}",
+            delivery.event_type, delivery.url, attempts, e
+        ),
+        (_, e) => warn!(
+            "Delivery of {} to {} failed ({}), retrying in {}s",
+            delivery.event_type,
+            delivery.url,
+            e.as_deref().unwrap_or_default(),
+            retry_in
+        ),
+    }
+    Ok(())
+}
+
+/// Polls the outbox until `shutdown` flips to true. A batch that is already
+/// being sent is allowed to finish.
+pub fn spawn_dispatcher(
+    pool: PgPool,
+    config: Config,
+    mut shutdown: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    let config = config.webhooks;
+    if !config.enabled {
+        info!("Webhook delivery is disabled");
+        return None;
+    }
+    let client = match http_client(&config) {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Cannot build the webhook HTTP client: {}", e);
+            return None;
+        }
+    };
+
+    Some(tokio::spawn(async move {
+        let interval = Duration::from_secs(config.poll_interval_secs);
+        loop {
+            if let Err(e) = dispatch_due(&pool, &client, &config).await {
+                error!("Webhook dispatch failed: {}", e);
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = shutdown.changed() => break,
+            }
+        }
+    }))
+}
@@ -1,11 +1,8 @@
 use axum_test::TestServer;
-use kfleet::{
-    app, 
-    AppState,
-    handlers::{staff, equipment, categories}
-};
+use kfleet::config::Config;
+use kfleet::metrics::Metrics;
+use kfleet::{create_router, load_templates, AppState};
 use sqlx::postgres::PgPoolOptions;
-use tera::Tera;
 use std::sync::Arc;
 
 pub async fn setup_test_server() -> TestServer {
@@ -28,18 +25,20 @@ pub async fn setup_test_server() -> TestServer {
         .expect("Failed to run migrations");
     
     // Initialize templates
-    let mut tera = Tera::new("templates/**/*")
+    let config = Config::default();
+    let tera = load_templates(&config)
         .expect("Failed to create Tera instance");
-    tera.autoescape_on(vec!["html"]);
     
     // Create application state
     let state = Arc::new(AppState {
         db: pool.clone(),
         templates: tera,
+        config,
+        metrics: Metrics::default(),
     });
     
     // Create test server
-    TestServer::new(app(state).into_make_service())
+    TestServer::new(create_router(state).into_make_service())
         .expect("Failed to create test server")
 }
 
@@ -68,14 +67,15 @@ pub async fn create_test_equipment(server: &TestServer) -> i32 {
 }
 
 pub async fn create_test_staff(server: &TestServer, equipment_ids: &[i32]) -> i32 {
+    let ids: Vec<String> = equipment_ids.iter().map(|id| id.to_string()).collect();
     let mut form = vec![
         ("full_name", "Test Operator"),
         ("license_number", "OP-123"),
         ("contact_info", "test@example.com"),
     ];
     
-    for id in equipment_ids {
-        form.push(("assigned_equipment", &id.to_string()));
+    for id in &ids {
+        form.push(("assigned_equipment", id));
     }
     
     let response = server.post("/staff")
@@ -86,6 +86,6 @@ pub async fn create_test_staff(server: &TestServer, equipment_ids: &[i32]) -> i3
     
     // Extract staff ID from redirect location
     let location = response.headers().get("location").unwrap().to_str().unwrap();
-    location.split('/').last().unwrap().parse().unwrap()
+    location.split('/').next_back().unwrap().parse().unwrap()
 }
 
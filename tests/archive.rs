@@ -0,0 +1,78 @@
+use kfleet::archive::{parse, ARCHIVE_FORMAT, ARCHIVE_VERSION};
+use serde_json::json;
+
+fn archive_json(format: &str, version: u32) -> Vec<u8> {
+    json!({
+        "format": format,
+        "version": version,
+        "exported_at": "2024-03-12T08:00:00Z",
+        "categories": [{ "id": 7, "name": "Grue", "created_at": "2024-01-01T00:00:00Z" }],
+        "staff": [],
+        "equipment": [],
+        "assignments": [],
+        "maintenance": []
+    })
+    .to_string()
+    .into_bytes()
+}
+
+#[test]
+fn test_parse_accepts_current_version() {
+    let archive = parse(&archive_json(ARCHIVE_FORMAT, ARCHIVE_VERSION)).unwrap();
+    assert_eq!(archive.categories[0].name, "Grue");
+    assert_eq!(archive.categories[0].id, 7);
+}
+
+#[test]
+fn test_parse_rejects_newer_versions() {
+    let err = parse(&archive_json(ARCHIVE_FORMAT, ARCHIVE_VERSION + 1)).unwrap_err();
+    assert!(err.contains("Unsupported archive version"));
+}
+
+#[test]
+fn test_parse_rejects_foreign_files() {
+    assert!(parse(&archive_json("something-else", 1)).unwrap_err().contains("Not a kFleet archive"));
+    assert!(parse(b"id,name\n1,Grue\n").unwrap_err().contains("Invalid archive"));
+}
+
+#[test]
+fn test_parse_reads_version_1_renewal_dates() {
+    let data = json!({
+        "format": ARCHIVE_FORMAT,
+        "version": 1,
+        "exported_at": "2024-03-12T08:00:00Z",
+        "categories": [{ "id": 7, "name": "Grue", "created_at": "2024-01-01T00:00:00Z" }],
+        "staff": [],
+        "equipment": [{
+            "id": 3, "name": "Grue 1", "brand": "Liebherr", "model": "LTM 1050",
+            "serial_number": "LB-1", "acquisition_date": "2023-05-01T00:00:00Z",
+            "category_id": 7, "insurance_renewal": "2024-06-30T00:00:00Z",
+            "next_maintenance": null, "fuel_capacity": null, "last_inspection": null,
+            "current_status": "active", "created_at": "2023-05-01T00:00:00Z"
+        }],
+        "assignments": [],
+        "maintenance": []
+    });
+    let archive = parse(data.to_string().as_bytes()).unwrap();
+    assert!(archive.policies.is_empty());
+    assert!(archive.equipment[0].insurance_renewal.is_some());
+
+    // Version 2 archives carry policies instead
+    let written = serde_json::to_value(&archive).unwrap();
+    assert!(written["equipment"][0].get("insurance_renewal").is_none());
+}
+
+#[test]
+fn test_parse_reads_undated_assignments_as_current() {
+    let mut data: serde_json::Value = serde_json::from_slice(&archive_json(ARCHIVE_FORMAT, 2)).unwrap();
+    data["assignments"] = json!([
+        { "operator_id": 1, "equipment_id": 3, "assigned_date": "2024-01-15T07:00:00Z" },
+        {
+            "operator_id": 2, "equipment_id": 3, "assigned_date": "2023-06-01T07:00:00Z",
+            "assigned_to": "2024-01-15T07:00:00Z"
+        }
+    ]);
+    let archive = parse(data.to_string().as_bytes()).unwrap();
+    assert!(archive.assignments[0].assigned_to.is_none());
+    assert!(archive.assignments[1].assigned_to.is_some());
+}
@@ -0,0 +1,358 @@
+mod test_utils;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use kfleet::assignments::{self, evaluate, AssignmentCheck};
+use kfleet::config::{AssignmentsConfig, RuleAction};
+use sqlx::PgPool;
+use test_utils::{insert_category, insert_equipment, insert_staff, test_pool};
+
+fn check() -> AssignmentCheck {
+    AssignmentCheck {
+        operator_name: Some("Rakoto Jean".to_string()),
+        equipment_id: 7,
+        equipment_name: "CAT 320".to_string(),
+        status: "active".to_string(),
+        category_name: "Excavators".to_string(),
+        already_assigned: false,
+        certified: true,
+        other_operators: 0,
+    }
+}
+
+#[test]
+fn test_qualified_operator_passes() {
+    assert!(evaluate(&[check()], &AssignmentsConfig::default()).is_empty());
+}
+
+#[test]
+fn test_rules_follow_their_configured_action() {
+    let mut retired = check();
+    retired.status = "retired".to_string();
+    retired.certified = false;
+    let violations = evaluate(&[retired], &AssignmentsConfig::default());
+    let rules: Vec<(&str, bool)> = violations.iter().map(|v| (v.rule, v.blocking)).collect();
+    assert_eq!(rules, vec![("retired", true), ("uncertified", false)]);
+
+    let strict = AssignmentsConfig { uncertified: RuleAction::Block, ..Default::default() };
+    let mut uncertified = check();
+    uncertified.certified = false;
+    assert!(evaluate(&[uncertified], &strict)[0].blocking);
+}
+
+#[test]
+fn test_capacity_counts_other_operators() {
+    let rules = AssignmentsConfig { max_operators: 2, ..Default::default() };
+    let mut machine = check();
+    machine.other_operators = 1;
+    assert!(evaluate(&[machine.clone()], &rules).is_empty());
+
+    machine.other_operators = 2;
+    let violations = evaluate(&[machine], &rules);
+    assert_eq!(violations[0].rule, "over_capacity");
+    assert!(violations[0].blocking);
+}
+
+#[test]
+fn test_existing_assignments_only_warn() {
+    let mut lapsed = check();
+    lapsed.already_assigned = true;
+    lapsed.status = "retired".to_string();
+    let violations = evaluate(&[lapsed], &AssignmentsConfig::default());
+    assert_eq!(violations.len(), 1);
+    assert!(!violations[0].blocking);
+}
+
+#[test]
+fn test_capacity_message_counts_an_existing_operator() {
+    let rules = AssignmentsConfig { max_operators: 2, ..Default::default() };
+    let mut crowded = check();
+    crowded.already_assigned = true;
+    crowded.other_operators = 2;
+    let violations = evaluate(&[crowded], &rules);
+    assert_eq!(violations[0].message, "CAT 320 has 3 operators (at most 2)");
+    assert_eq!(violations[0].operator_name.as_deref(), Some("Rakoto Jean"));
+}
+
+fn at(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+fn day(s: &str) -> NaiveDate {
+    s.parse().unwrap()
+}
+
+/// The operator's assignment periods on a machine as (from, to), oldest first.
+async fn periods(pool: &PgPool, staff_id: i32, equipment_id: i32) -> Vec<(DateTime<Utc>, Option<DateTime<Utc>>)> {
+    sqlx::query_as(
+        r#"
+        SELECT assigned_from, assigned_to FROM equipment_operator
+        WHERE operator_id = $1 AND equipment_id = $2
+        ORDER BY assigned_from
+        "#,
+    )
+    .bind(staff_id)
+    .bind(equipment_id)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+/// Operators on the machine at some point of `date`, latest first.
+async fn on_day(pool: &PgPool, equipment_id: i32, date: &str, timezone: &str) -> Vec<i32> {
+    assignments::history(pool, None, Some(equipment_id), Some(day(date)), timezone)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|a| a.operator_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_set_operator_equipment_ends_dropped_assignments() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let kept = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let dropped = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let added = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let staff = insert_staff(&pool).await;
+    let rules = AssignmentsConfig::default();
+
+    let mut conn = pool.acquire().await.unwrap();
+    assignments::set_operator_equipment(&mut conn, staff, &[kept, dropped], &rules).await.unwrap();
+    // Backdated so that a restarted assignment would show
+    sqlx::query("UPDATE equipment_operator SET assigned_from = '2025-01-06T08:00:00Z' WHERE operator_id = $1")
+        .bind(staff)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+    assignments::set_operator_equipment(&mut conn, staff, &[kept, added], &rules).await.unwrap();
+
+    assert_eq!(periods(&pool, staff, kept).await, vec![(at("2025-01-06T08:00:00Z"), None)]);
+    let ended = periods(&pool, staff, dropped).await;
+    assert_eq!(ended.len(), 1);
+    assert!(ended[0].1.is_some());
+    let started = periods(&pool, staff, added).await;
+    assert_eq!(started.len(), 1);
+    assert!(started[0].0 > at("2025-01-06T08:00:00Z") && started[0].1.is_none());
+
+    let mut current = assignments::current_equipment_ids(&pool, staff).await.unwrap();
+    current.sort();
+    assert_eq!(current, vec![kept, added]);
+
+    // Assigned again later, the machine gets a second period
+    assignments::set_operator_equipment(&mut conn, staff, &[kept, added, dropped], &rules).await.unwrap();
+    let periods = periods(&pool, staff, dropped).await;
+    assert_eq!(periods.len(), 2);
+    assert!(periods[0].1.is_some() && periods[1].1.is_none());
+}
+
+#[tokio::test]
+async fn test_history_on_a_day_uses_the_local_calendar() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let night_shift = insert_staff(&pool).await;
+    let day_shift = insert_staff(&pool).await;
+    // 01:00 on the 2nd to 00:30 on the 6th in Antananarivo (UTC+3)
+    sqlx::query(
+        r#"
+        INSERT INTO equipment_operator (operator_id, equipment_id, assigned_from, assigned_to)
+        VALUES ($1, $3, '2025-03-01T22:00:00Z', '2025-03-05T21:30:00Z'),
+            ($2, $3, '2025-03-06T05:00:00Z', NULL)
+        "#,
+    )
+    .bind(night_shift)
+    .bind(day_shift)
+    .bind(machine)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let tz = "Indian/Antananarivo";
+    assert_eq!(on_day(&pool, machine, "2025-03-01", tz).await, Vec::<i32>::new());
+    assert_eq!(on_day(&pool, machine, "2025-03-02", tz).await, vec![night_shift]);
+    // Both were on the machine on the 6th, latest first
+    assert_eq!(on_day(&pool, machine, "2025-03-06", tz).await, vec![day_shift, night_shift]);
+    assert_eq!(on_day(&pool, machine, "2025-03-07", tz).await, vec![day_shift]);
+
+    // The same periods read in UTC fall on other days
+    assert_eq!(on_day(&pool, machine, "2025-03-01", "UTC").await, vec![night_shift]);
+    assert_eq!(on_day(&pool, machine, "2025-03-06", "UTC").await, vec![day_shift]);
+}
+
+#[tokio::test]
+async fn test_add_and_remove_operator() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let staff = insert_staff(&pool).await;
+    let rules = AssignmentsConfig::default();
+    let mut conn = pool.acquire().await.unwrap();
+
+    assignments::add_operator(&mut conn, machine, staff, &rules).await.unwrap();
+    // Adding a current operator again changes nothing
+    assignments::add_operator(&mut conn, machine, staff, &rules).await.unwrap();
+    assert_eq!(periods(&pool, staff, machine).await.len(), 1);
+
+    assert!(assignments::remove_operator(&mut conn, machine, staff).await.unwrap());
+    assert!(!assignments::remove_operator(&mut conn, machine, staff).await.unwrap());
+    let ended = periods(&pool, staff, machine).await;
+    assert_eq!(ended.len(), 1);
+    assert!(ended[0].1.is_some());
+    assert!(assignments::current_operators(&pool, machine).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_add_operator_blocks_over_capacity() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let first = insert_staff(&pool).await;
+    let second = insert_staff(&pool).await;
+    let rules = AssignmentsConfig { max_operators: 1, ..Default::default() };
+    let mut conn = pool.acquire().await.unwrap();
+
+    assignments::add_operator(&mut conn, machine, first, &rules).await.unwrap();
+    let violations = assignments::add_operator(&mut conn, machine, second, &rules).await.unwrap();
+    assert!(violations.iter().any(|v| v.rule == "over_capacity" && v.blocking));
+    assert!(periods(&pool, second, machine).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_set_primary_operator_moves_the_flag() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let first = insert_staff(&pool).await;
+    let second = insert_staff(&pool).await;
+    let rules = AssignmentsConfig::default();
+    let mut conn = pool.acquire().await.unwrap();
+    assignments::add_operator(&mut conn, machine, first, &rules).await.unwrap();
+    assignments::add_operator(&mut conn, machine, second, &rules).await.unwrap();
+
+    let primaries = |operators: Vec<assignments::CurrentOperator>| {
+        operators.into_iter().filter(|o| o.is_primary).map(|o| o.operator_id).collect::<Vec<_>>()
+    };
+    assert!(assignments::set_primary_operator(&mut conn, machine, first).await.unwrap());
+    assert_eq!(primaries(assignments::current_operators(&pool, machine).await.unwrap()), vec![first]);
+    // The old primary is cleared before the new one is set, or the index would refuse it
+    assert!(assignments::set_primary_operator(&mut conn, machine, second).await.unwrap());
+    assert_eq!(primaries(assignments::current_operators(&pool, machine).await.unwrap()), vec![second]);
+    // Setting the same primary twice is fine
+    assert!(assignments::set_primary_operator(&mut conn, machine, second).await.unwrap());
+
+    // Someone not on the machine is refused, and the handler rolls back
+    let outsider = insert_staff(&pool).await;
+    let mut tx = pool.begin().await.unwrap();
+    assert!(!assignments::set_primary_operator(&mut tx, machine, outsider).await.unwrap());
+    tx.rollback().await.unwrap();
+    assert_eq!(primaries(assignments::current_operators(&pool, machine).await.unwrap()), vec![second]);
+}
+
+#[tokio::test]
+async fn test_one_open_primary_per_machine() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let first = insert_staff(&pool).await;
+    let second = insert_staff(&pool).await;
+    let rules = AssignmentsConfig::default();
+    let mut conn = pool.acquire().await.unwrap();
+    assignments::add_operator(&mut conn, machine, first, &rules).await.unwrap();
+    assignments::add_operator(&mut conn, machine, second, &rules).await.unwrap();
+    assignments::set_primary_operator(&mut conn, machine, first).await.unwrap();
+
+    let second_primary = sqlx::query(
+        "UPDATE equipment_operator SET is_primary = TRUE WHERE equipment_id = $1 AND operator_id = $2",
+    )
+    .bind(machine)
+    .bind(second)
+    .execute(&mut *conn)
+    .await;
+    assert!(second_primary.unwrap_err().to_string().contains("idx_equipment_operator_primary"));
+
+    // An ended assignment keeps its flag as history without holding the place
+    assert!(assignments::remove_operator(&mut conn, machine, first).await.unwrap());
+    assert!(assignments::set_primary_operator(&mut conn, machine, second).await.unwrap());
+    let flags: Vec<bool> = sqlx::query_scalar(
+        "SELECT is_primary FROM equipment_operator WHERE equipment_id = $1 ORDER BY operator_id",
+    )
+    .bind(machine)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(flags, vec![true, true]);
+}
+
+#[tokio::test]
+async fn test_concurrent_assignments_wait_for_the_machine_lock() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let first = insert_staff(&pool).await;
+    let second = insert_staff(&pool).await;
+    let rules = AssignmentsConfig { max_operators: 1, ..Default::default() };
+
+    let mut tx = pool.begin().await.unwrap();
+    let violations = assignments::set_operator_equipment(&mut tx, first, &[machine], &rules).await.unwrap();
+    assert!(!violations.iter().any(|v| v.blocking));
+
+    let competing = {
+        let pool = pool.clone();
+        let rules = rules.clone();
+        tokio::spawn(async move {
+            let mut tx = pool.begin().await.unwrap();
+            let violations = assignments::set_operator_equipment(&mut tx, second, &[machine], &rules).await.unwrap();
+            tx.commit().await.unwrap();
+            violations
+        })
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!competing.is_finished(), "the second assignment should wait for the lock");
+
+    tx.commit().await.unwrap();
+    let violations = competing.await.unwrap();
+    assert!(violations.iter().any(|v| v.rule == "over_capacity" && v.blocking));
+    assert!(periods(&pool, second, machine).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_concurrent_primary_changes_take_turns() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, at("2024-01-01T00:00:00Z")).await;
+    let first = insert_staff(&pool).await;
+    let second = insert_staff(&pool).await;
+    let rules = AssignmentsConfig::default();
+    let mut conn = pool.acquire().await.unwrap();
+    assignments::add_operator(&mut conn, machine, first, &rules).await.unwrap();
+    assignments::add_operator(&mut conn, machine, second, &rules).await.unwrap();
+
+    let mut tx = pool.begin().await.unwrap();
+    assert!(assignments::set_primary_operator(&mut tx, machine, first).await.unwrap());
+
+    let competing = {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            let mut tx = pool.begin().await.unwrap();
+            let updated = assignments::set_primary_operator(&mut tx, machine, second).await;
+            tx.commit().await.unwrap();
+            updated
+        })
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!competing.is_finished(), "the second change should wait for the lock");
+
+    tx.commit().await.unwrap();
+    // Without the lock this would be a unique violation on the primary index
+    assert!(competing.await.unwrap().unwrap());
+    let primaries: Vec<i32> = assignments::current_operators(&pool, machine)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|o| o.is_primary)
+        .map(|o| o.operator_id)
+        .collect();
+    assert_eq!(primaries, vec![second]);
+}
@@ -0,0 +1,161 @@
+mod test_utils;
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use kfleet::calendar::{self, deadline_events, render_feed, CalendarEvent};
+use kfleet::config::Config;
+use kfleet::reservations::{self, DecisionForm, Reservation, ReservationForm};
+use kfleet::sites::{self, SiteForm};
+use kfleet::{InsuranceAlert, MaintenanceAlert};
+use test_utils::{insert_category, insert_equipment, test_pool, unique};
+
+#[test]
+fn test_deadline_events_use_stable_uids_and_local_dates() {
+    let maintenance = vec![MaintenanceAlert {
+        equipment_id: 7,
+        name: "Excavator #1".to_string(),
+        // 22:30 UTC is already the next day in Antananarivo (UTC+3)
+        next_maintenance: Some(Utc.with_ymd_and_hms(2026, 11, 2, 22, 30, 0).unwrap()),
+    }];
+    let insurance = vec![InsuranceAlert {
+        equipment_id: 7,
+        policy_id: 3,
+        name: "Excavator #1".to_string(),
+        insurer: "ARO".to_string(),
+        policy_number: "ARO-2026-114".to_string(),
+        insurance_renewal: NaiveDate::from_ymd_opt(2026, 10, 30).unwrap(),
+    }];
+
+    let events = deadline_events(&maintenance, &insurance, &[], Tz::Indian__Antananarivo, "https://fleet.example.mg/");
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].uid, "insurance-3-7@kfleet");
+    assert_eq!(events[0].description, "Policy ARO-2026-114 with ARO covering Excavator #1 ends.");
+    assert_eq!(events[0].date, NaiveDate::from_ymd_opt(2026, 10, 30).unwrap());
+    assert_eq!(events[1].uid, "maintenance-7@kfleet");
+    assert_eq!(events[1].date, NaiveDate::from_ymd_opt(2026, 11, 3).unwrap());
+    assert_eq!(events[1].url.as_deref(), Some("https://fleet.example.mg/equipment/7/edit"));
+}
+
+#[test]
+fn test_render_feed_escapes_folds_and_adds_alarms() {
+    let events = vec![CalendarEvent {
+        uid: "maintenance-7@kfleet".to_string(),
+        date: NaiveDate::from_ymd_opt(2026, 11, 3).unwrap(),
+        last_date: None,
+        summary: "Maintenance due: Grue; Tana, 50t".to_string(),
+        description: "Révision complète de la grue mobile Liebherr LTM 1050 avant la saison des pluies".to_string(),
+        url: None,
+    }];
+    let now = Utc.with_ymd_and_hms(2026, 10, 19, 6, 0, 0).unwrap();
+
+    let feed = render_feed("kFleet deadlines", &events, 7, now);
+
+    assert!(feed.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(feed.ends_with("END:VCALENDAR\r\n"));
+    assert!(feed.contains("\r\nUID:maintenance-7@kfleet\r\n"));
+    assert!(feed.contains("\r\nDTSTAMP:20261019T060000Z\r\n"));
+    assert!(feed.contains("\r\nDTSTART;VALUE=DATE:20261103\r\nDTEND;VALUE=DATE:20261104\r\n"));
+    assert!(feed.contains("\r\nSUMMARY:Maintenance due: Grue\\; Tana\\, 50t\r\n"));
+    assert!(feed.contains("\r\nTRIGGER:-P7D\r\n"));
+
+    for line in feed.split("\r\n") {
+        assert!(line.len() <= 75, "line too long: {:?}", line);
+    }
+    let unfolded = feed.replace("\r\n ", "");
+    assert!(unfolded.contains("DESCRIPTION:Révision complète de la grue mobile Liebherr LTM 1050 avant la saison des pluies\r\n"));
+}
+
+#[test]
+fn test_render_feed_without_alarm() {
+    let events = vec![CalendarEvent {
+        uid: "insurance-1@kfleet".to_string(),
+        date: NaiveDate::from_ymd_opt(2026, 12, 31).unwrap(),
+        last_date: None,
+        summary: "Insurance renewal: Dozer".to_string(),
+        description: "The insurance for Dozer must be renewed.".to_string(),
+        url: None,
+    }];
+
+    let feed = render_feed("kFleet deadlines", &events, 0, Utc::now());
+
+    assert_eq!(feed.matches("BEGIN:VEVENT").count(), 1);
+    assert!(!feed.contains("VALARM"));
+}
+
+#[test]
+fn test_reservations_span_their_days() {
+    let date = |month, day| NaiveDate::from_ymd_opt(2026, month, day).unwrap();
+    let reservation = Reservation {
+        id: 12,
+        equipment_id: 2,
+        equipment_name: "Grue Liebherr LTM 1050".to_string(),
+        category_id: 1,
+        category_name: "Cranes".to_string(),
+        site_id: 4,
+        site_name: "Port de Toamasina".to_string(),
+        requested_by: "Hery".to_string(),
+        start_date: date(11, 2),
+        end_date: date(11, 6),
+        purpose: None,
+        status: "approved".to_string(),
+        decided_by: Some("Fara".to_string()),
+        decided_at: None,
+        decision_note: None,
+        created_at: Utc::now(),
+    };
+
+    let events = deadline_events(&[], &[], &[reservation], Tz::Indian__Antananarivo, "https://fleet.example.mg");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].uid, "reservation-12@kfleet");
+    assert_eq!((events[0].date, events[0].last_date), (date(11, 2), Some(date(11, 6))));
+    assert_eq!(events[0].url.as_deref(), Some("https://fleet.example.mg/reservations/12"));
+
+    let feed = render_feed("kFleet deadlines", &events, 0, Utc::now());
+    assert!(feed.contains("\r\nDTSTART;VALUE=DATE:20261102\r\nDTEND;VALUE=DATE:20261107\r\n"));
+}
+
+#[tokio::test]
+async fn test_feed_lists_approved_reservations_only() {
+    let pool = test_pool().await;
+    let today: NaiveDate = sqlx::query_scalar("SELECT CURRENT_DATE").fetch_one(&pool).await.unwrap();
+    let category = insert_category(&pool).await;
+    let crane = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let site = sites::create_site(
+        &pool,
+        &SiteForm {
+            name: unique("Toamasina - quai C"),
+            client: None,
+            address: "Boulevard Ratsimilaho, Toamasina".to_string(),
+            latitude: None,
+            longitude: None,
+            start_date: today,
+            end_date: None,
+            notes: None,
+        },
+    )
+    .await
+    .unwrap();
+    let request = |start: i64, end: i64| ReservationForm {
+        equipment_id: crane,
+        site_id: site,
+        requested_by: "Hery".to_string(),
+        start_date: today + Duration::days(start),
+        end_date: today + Duration::days(end),
+        purpose: None,
+    };
+    let mut conn = pool.acquire().await.unwrap();
+    let approved = reservations::create_reservation(&mut conn, &request(7, 9)).await.unwrap();
+    let pending = reservations::create_reservation(&mut conn, &request(20, 22)).await.unwrap();
+    let beyond = reservations::create_reservation(&mut conn, &request(400, 402)).await.unwrap();
+    let approval = DecisionForm { status: "approved".to_string(), decided_by: "Fara".to_string(), note: None };
+    for id in [approved, beyond] {
+        assert!(reservations::decide(&mut conn, id, "pending", &approval).await.unwrap());
+    }
+
+    let feed = calendar::fleet_feed(&pool, &Config::default()).await.unwrap();
+    assert!(feed.contains(&format!("UID:reservation-{}@kfleet", approved)));
+    assert!(!feed.contains(&format!("UID:reservation-{}@kfleet", pending)));
+    // Past the default 365-day horizon
+    assert!(!feed.contains(&format!("UID:reservation-{}@kfleet", beyond)));
+}
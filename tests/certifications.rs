@@ -0,0 +1,64 @@
+mod test_utils;
+
+use chrono::{Duration, NaiveDate};
+use kfleet::certifications::{self, CertificationForm};
+use kfleet::fetch_certification_alerts;
+use sqlx::PgPool;
+use test_utils::{insert_staff, test_pool};
+
+fn form(license_type: &str, expiry_date: Option<NaiveDate>) -> CertificationForm {
+    CertificationForm {
+        license_type: license_type.to_string(),
+        license_number: Some("GR-4471".to_string()),
+        issuing_authority: "Ministère des Travaux Publics".to_string(),
+        issue_date: NaiveDate::from_ymd_opt(2020, 3, 1).unwrap(),
+        expiry_date,
+        notes: None,
+        category_ids: vec![],
+    }
+}
+
+async fn certify(pool: &PgPool, staff_id: i32, license_type: &str, expiry_date: Option<NaiveDate>) -> i32 {
+    let mut conn = pool.acquire().await.unwrap();
+    certifications::create_certification(&mut conn, staff_id, &form(license_type, expiry_date))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_dashboard_alerts_on_expiring_certifications() {
+    let pool = test_pool().await;
+    let today: NaiveDate = sqlx::query_scalar("SELECT CURRENT_DATE").fetch_one(&pool).await.unwrap();
+    let days = Duration::days;
+    let crane_operator = insert_staff(&pool).await;
+    let driver = insert_staff(&pool).await;
+    let lapsed = insert_staff(&pool).await;
+
+    let expiring = certify(&pool, crane_operator, "crane", Some(today + days(10))).await;
+    // Renewed: the expired certification is replaced by a later one of the same type
+    certify(&pool, crane_operator, "earthmoving", Some(today - days(5))).await;
+    certify(&pool, crane_operator, "earthmoving", Some(today + days(700))).await;
+    // Beyond the horizon, or never expiring
+    certify(&pool, driver, "heavy_vehicle", Some(today + days(31))).await;
+    certify(&pool, driver, "driving_licence", None).await;
+    let expired = certify(&pool, lapsed, "forklift", Some(today - days(5))).await;
+
+    let alerts: Vec<(i32, i32, i32)> = fetch_certification_alerts(&pool, 30, i64::MAX)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|a| [crane_operator, driver, lapsed].contains(&a.staff_id))
+        .map(|a| (a.staff_id, a.certification_id, a.days_left))
+        .collect();
+    // Soonest first, expired ones with negative days left
+    assert_eq!(alerts, vec![(lapsed, expired, -5), (crane_operator, expiring, 10)]);
+
+    let wider: Vec<i32> = fetch_certification_alerts(&pool, 31, i64::MAX)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|a| a.staff_id == driver)
+        .map(|a| a.days_left)
+        .collect();
+    assert_eq!(wider, vec![31]);
+}
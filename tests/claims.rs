@@ -0,0 +1,169 @@
+mod test_utils;
+
+use chrono::NaiveDate;
+use kfleet::claims::{self, check_transition, ClaimForm, ClaimStatusForm};
+use kfleet::insurance::{self, PolicyForm};
+use sqlx::PgPool;
+use test_utils::{insert_category, insert_equipment, test_pool, unique};
+
+fn date(s: &str) -> NaiveDate {
+    s.parse().unwrap()
+}
+
+fn form(policy_id: i32, equipment_id: i32, claimed_amount: f64) -> ClaimForm {
+    ClaimForm {
+        policy_id,
+        equipment_id,
+        claim_number: unique("SIN"),
+        incident_date: date("2026-03-12"),
+        filed_date: Some(date("2026-03-14")),
+        description: "Boom struck a retaining wall".to_string(),
+        claimed_amount,
+        notes: None,
+    }
+}
+
+/// A 2026 policy from `insurer` covering `equipment_ids`.
+async fn insert_policy(pool: &PgPool, insurer: &str, equipment_ids: Vec<i32>) -> i32 {
+    let form = PolicyForm {
+        insurer: insurer.to_string(),
+        policy_number: unique("FL"),
+        coverage_type: "all_risks".to_string(),
+        premium: None,
+        deductible: None,
+        start_date: date("2026-01-01"),
+        end_date: date("2026-12-31"),
+        notes: None,
+        equipment_ids,
+    };
+    let mut conn = pool.acquire().await.unwrap();
+    insurance::create_policy(&mut conn, &form, None).await.unwrap()
+}
+
+async fn insert_repair(pool: &PgPool, equipment_id: i32, cost: f64) -> i32 {
+    insert_repair_on(pool, equipment_id, "2026-03-20T08:00:00Z", cost).await
+}
+
+async fn insert_repair_on(pool: &PgPool, equipment_id: i32, on: &str, cost: f64) -> i32 {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO maintenance_history (equipment_id, maintenance_date, description, cost)
+        VALUES ($1, $2::timestamptz, 'Boom repair', $3)
+        RETURNING id
+        "#,
+    )
+    .bind(equipment_id)
+    .bind(on)
+    .bind(cost)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn close(pool: &PgPool, id: i32, status: &str, paid_amount: Option<f64>) {
+    let form = ClaimStatusForm { status: status.to_string(), paid_amount, notes: None };
+    assert!(claims::update_status(pool, id, &form).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_outstanding_claims_are_totalled_per_insurer() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let acquired = "2020-01-01T00:00:00Z".parse().unwrap();
+    let excavator = insert_equipment(&pool, category, acquired).await;
+    let loader = insert_equipment(&pool, category, acquired).await;
+    let insurer = unique("ARO");
+    let policy = insert_policy(&pool, &insurer, vec![excavator, loader]).await;
+
+    let filed = claims::create_claim(&pool, &form(policy, excavator, 1_000.0)).await.unwrap();
+    let repair = insert_repair(&pool, excavator, 300.0).await;
+    assert!(claims::link_repair(&pool, filed, repair).await.unwrap());
+    let mut earlier = form(policy, loader, 500.0);
+    earlier.filed_date = Some(date("2026-03-13"));
+    let reviewed = claims::create_claim(&pool, &earlier).await.unwrap();
+    close(&pool, reviewed, "under_review", None).await;
+    // Settled claims only count towards the ratio
+    let paid = claims::create_claim(&pool, &form(policy, loader, 400.0)).await.unwrap();
+    close(&pool, paid, "paid", Some(300.0)).await;
+    let rejected = claims::create_claim(&pool, &form(policy, excavator, 600.0)).await.unwrap();
+    close(&pool, rejected, "rejected", None).await;
+
+    let report = claims::outstanding_by_insurer(&pool).await.unwrap();
+    let row = report.iter().find(|r| r.insurer == insurer).unwrap();
+    assert_eq!((row.open_claims, row.under_review), (2, 1));
+    assert_eq!(row.claimed_amount, 1_500.0);
+    assert_eq!(row.repair_cost, 300.0);
+    assert_eq!(row.oldest_filed, date("2026-03-13"));
+    assert_eq!(row.settled_ratio, Some(0.3));
+
+    // Once nothing is open the insurer drops out of the report
+    close(&pool, filed, "rejected", None).await;
+    close(&pool, reviewed, "paid", Some(500.0)).await;
+    let report = claims::outstanding_by_insurer(&pool).await.unwrap();
+    assert!(report.iter().all(|r| r.insurer != insurer));
+}
+
+#[tokio::test]
+async fn test_link_repair_only_takes_the_claimed_machine() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let acquired = "2020-01-01T00:00:00Z".parse().unwrap();
+    let excavator = insert_equipment(&pool, category, acquired).await;
+    let loader = insert_equipment(&pool, category, acquired).await;
+    let policy = insert_policy(&pool, &unique("ARO"), vec![excavator, loader]).await;
+    let claim = claims::create_claim(&pool, &form(policy, excavator, 1_000.0)).await.unwrap();
+    let other = claims::create_claim(&pool, &form(policy, excavator, 200.0)).await.unwrap();
+
+    let loader_repair = insert_repair(&pool, loader, 250.0).await;
+    assert!(!claims::link_repair(&pool, claim, loader_repair).await.unwrap());
+    assert!(claims::linked_repairs(&pool, claim).await.unwrap().is_empty());
+
+    // A repair belongs to one claim at most
+    let repair = insert_repair(&pool, excavator, 300.0).await;
+    assert!(claims::link_repair(&pool, claim, repair).await.unwrap());
+    assert!(!claims::link_repair(&pool, other, repair).await.unwrap());
+    let linked: Vec<i32> = claims::linked_repairs(&pool, claim).await.unwrap().iter().map(|r| r.id).collect();
+    assert_eq!(linked, vec![repair]);
+    let claim = claims::get_claim(&pool, claim).await.unwrap().unwrap();
+    assert_eq!(claim.repair_cost, 300.0);
+}
+
+#[tokio::test]
+async fn test_link_repair_refuses_repairs_before_the_incident() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let excavator = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let policy = insert_policy(&pool, &unique("ARO"), vec![excavator]).await;
+    let claim = claims::create_claim(&pool, &form(policy, excavator, 1_000.0)).await.unwrap();
+
+    // The incident happened on 12 March
+    let earlier = insert_repair_on(&pool, excavator, "2026-03-10T08:00:00Z", 400.0).await;
+    assert!(!claims::link_repair(&pool, claim, earlier).await.unwrap());
+    let same_day = insert_repair_on(&pool, excavator, "2026-03-12T15:00:00Z", 150.0).await;
+    assert!(claims::link_repair(&pool, claim, same_day).await.unwrap());
+
+    let linked: Vec<i32> = claims::linked_repairs(&pool, claim).await.unwrap().iter().map(|r| r.id).collect();
+    assert_eq!(linked, vec![same_day]);
+}
+
+#[test]
+fn test_open_claims_move_forward() {
+    assert!(check_transition("filed", "under_review", None).is_ok());
+    assert!(check_transition("filed", "rejected", None).is_ok());
+    assert!(check_transition("under_review", "paid", Some(6_000_000.0)).is_ok());
+    assert!(check_transition("under_review", "filed", None).is_err());
+    assert!(check_transition("filed", "settled", None).unwrap_err().contains("Unknown"));
+}
+
+#[test]
+fn test_closed_claims_are_final() {
+    assert!(check_transition("paid", "under_review", None).is_err());
+    assert!(check_transition("rejected", "paid", Some(1.0)).is_err());
+}
+
+#[test]
+fn test_only_paid_claims_have_a_paid_amount() {
+    assert!(check_transition("under_review", "paid", None).is_err());
+    assert!(check_transition("under_review", "paid", Some(-5.0)).is_err());
+    assert!(check_transition("filed", "rejected", Some(100.0)).is_err());
+}
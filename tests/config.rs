@@ -0,0 +1,183 @@
+use chrono::{NaiveTime, Weekday};
+use kfleet::config::{Config, DepreciationMethod, LogFormat, RuleAction, SmtpSecurity};
+use std::collections::HashMap;
+
+const SAMPLE: &str = r#"
+[server]
+bind_addr = "127.0.0.1:8080"
+
+[database]
+url = "postgres://kfleet@localhost/kfleet"
+max_connections = 10
+
+[smtp]
+host = "localhost"
+port = 1025
+security = "none"
+from = "kfleet@depot.mg"
+
+[locale]
+timezone = "Indian/Antananarivo"
+currency = "MGA"
+locale = "fr-MG"
+"#;
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> =
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |name| vars.get(name).cloned()
+}
+
+#[test]
+fn test_from_toml_fills_defaults() {
+    let config = Config::from_toml(SAMPLE).unwrap();
+
+    assert_eq!(config.server.bind_addr.to_string(), "127.0.0.1:8080");
+    assert_eq!(config.database.max_connections, 10);
+    assert_eq!(config.database.acquire_timeout_secs, 30);
+    assert_eq!(config.smtp.security, SmtpSecurity::None);
+    assert_eq!(config.reminders.maintenance_days, 30);
+    assert_eq!(config.reminders.certification_days, 60);
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn test_geofence_working_hours() {
+    let toml = format!(
+        "{}\n[geofences]\nwork_days = [\"Mon\", \"Tue\"]\nwork_start = \"07:30:00\"\nwork_end = \"17:00:00\"\n",
+        SAMPLE
+    );
+    let mut config = Config::from_toml(&toml).unwrap();
+    assert_eq!(config.geofences.work_days, vec![Weekday::Mon, Weekday::Tue]);
+    assert_eq!(config.geofences.work_start, NaiveTime::from_hms_opt(7, 30, 0).unwrap());
+    assert!(config.validate().is_ok());
+
+    config.geofences.work_start = NaiveTime::from_hms_opt(20, 0, 0).unwrap();
+    assert!(config.validate().unwrap_err().to_string().contains("geofences.work_start"));
+}
+
+#[test]
+fn test_from_toml_rejects_unknown_keys() {
+    let err = Config::from_toml("[database]\nmax_conections = 3\n").unwrap_err();
+    assert!(format!("{:#}", err).contains("max_conections"));
+}
+
+#[test]
+fn test_from_toml_rejects_unknown_depreciation_methods() {
+    let err = Config::from_toml("[costs]\ndepreciation_method = \"sum_of_years\"\n").unwrap_err();
+    assert!(format!("{:#}", err).contains("sum_of_years"));
+
+    let mut config = Config::from_toml(SAMPLE).unwrap();
+    let err = config
+        .apply_env(env(&[("KFLEET_COSTS_DEPRECIATION_METHOD", "sum_of_years")]))
+        .unwrap_err();
+    assert!(err.to_string().contains("KFLEET_COSTS_DEPRECIATION_METHOD"));
+}
+
+#[test]
+fn test_env_overrides_file_values() {
+    let mut config = Config::from_toml(SAMPLE).unwrap();
+    config
+        .apply_env(env(&[
+            ("DATABASE_URL", "postgres://other/kfleet"),
+            ("PORT", "3100"),
+            ("KFLEET_SMTP_SECURITY", "starttls"),
+            ("KFLEET_MAINTENANCE_REMINDER_DAYS", "14"),
+            ("KFLEET_LOG_FORMAT", "JSON"),
+            ("KFLEET_ASSIGNMENT_RETIRED", "warn"),
+            ("KFLEET_ASSIGNMENT_UNCERTIFIED", "block"),
+            ("KFLEET_ASSIGNMENT_OVER_CAPACITY", "warn"),
+            ("KFLEET_TELEMATICS_API_KEY", "simulator-key-0123456789"),
+            ("KFLEET_UTILIZATION_SHIFT_HOURS", "10"),
+            ("KFLEET_COSTS_USEFUL_LIFE_YEARS", "8"),
+            ("KFLEET_COSTS_DEPRECIATION_METHOD", "declining_balance"),
+        ]))
+        .unwrap();
+
+    assert_eq!(config.database.url, "postgres://other/kfleet");
+    assert_eq!(config.server.bind_addr.to_string(), "127.0.0.1:3100");
+    assert_eq!(config.smtp.security, SmtpSecurity::StartTls);
+    assert_eq!(config.reminders.maintenance_days, 14);
+    assert_eq!(config.logging.format, LogFormat::Json);
+    assert_eq!(config.assignments.retired, RuleAction::Warn);
+    assert_eq!(config.assignments.uncertified, RuleAction::Block);
+    assert_eq!(config.assignments.over_capacity, RuleAction::Warn);
+    assert_eq!(config.telematics.api_key.as_deref(), Some("simulator-key-0123456789"));
+    assert_eq!(config.utilization.shift_hours, 10.0);
+    assert_eq!(config.costs.useful_life_years, 8);
+    assert_eq!(config.costs.depreciation_method, DepreciationMethod::DecliningBalance);
+
+    config.utilization.idle_hours = 12.0;
+    assert!(config.validate().unwrap_err().to_string().contains("utilization.idle_hours"));
+}
+
+#[test]
+fn test_env_overrides_reporting_settings() {
+    let mut config = Config::from_toml(SAMPLE).unwrap();
+    config
+        .apply_env(env(&[
+            ("KFLEET_GEOFENCES_WORK_DAYS", "Mon, Wed,Fri"),
+            ("KFLEET_GEOFENCES_WORK_START", "07:30:00"),
+            ("KFLEET_GEOFENCES_WORK_END", "16:00:00"),
+            ("KFLEET_CALENDAR_HORIZON_DAYS", "90"),
+            ("KFLEET_CALENDAR_ALARM_DAYS", "3"),
+            ("KFLEET_UTILIZATION_IDLE_HOURS", "1.5"),
+            ("KFLEET_UTILIZATION_UNDERUSED_IDLE_PERCENT", "40"),
+            ("KFLEET_COSTS_REPLACE_RATIO", "0.8"),
+            ("KFLEET_TELEMATICS_MAX_BATCH", "100"),
+            ("KFLEET_TELEMATICS_MAX_CLOCK_SKEW_SECS", "60"),
+        ]))
+        .unwrap();
+
+    assert_eq!(config.geofences.work_days, vec![Weekday::Mon, Weekday::Wed, Weekday::Fri]);
+    assert_eq!(config.geofences.work_start, NaiveTime::from_hms_opt(7, 30, 0).unwrap());
+    assert_eq!(config.geofences.work_end, NaiveTime::from_hms_opt(16, 0, 0).unwrap());
+    assert_eq!((config.calendar.horizon_days, config.calendar.alarm_days), (90, 3));
+    assert_eq!(config.utilization.idle_hours, 1.5);
+    assert_eq!(config.utilization.underused_idle_percent, 40.0);
+    assert_eq!(config.costs.replace_ratio, 0.8);
+    assert_eq!(config.telematics.max_batch, 100);
+    assert_eq!(config.telematics.max_clock_skew_secs, 60);
+    assert!(config.validate().is_ok());
+
+    let err = config
+        .apply_env(env(&[("KFLEET_GEOFENCES_WORK_DAYS", "Mon,Someday")]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("KFLEET_GEOFENCES_WORK_DAYS"));
+    assert!(err.contains("Someday"));
+}
+
+#[test]
+fn test_invalid_values_are_all_reported() {
+    let mut config = Config::from_toml(SAMPLE).unwrap();
+    let err = config
+        .apply_env(env(&[("KFLEET_DB_MAX_CONNECTIONS", "many"), ("PORT", "99999")]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("KFLEET_DB_MAX_CONNECTIONS"));
+    assert!(err.contains("PORT"));
+
+    config.locale.timezone = "Madagascar/Tana".to_string();
+    config.locale.currency = "ariary".to_string();
+    config.reminders.insurance_days = 0;
+    config.reminders.certification_days = 400;
+    config.assignments.max_operators = 0;
+    config.telematics.api_key = Some("short".to_string());
+    config.costs.replace_ratio = 0.0;
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("locale.timezone"));
+    assert!(err.contains("locale.currency"));
+    assert!(err.contains("reminders.insurance_days"));
+    assert!(err.contains("reminders.certification_days"));
+    assert!(err.contains("assignments.max_operators"));
+    assert!(err.contains("telematics.api_key"));
+    assert!(err.contains("costs.replace_ratio"));
+}
+
+#[test]
+fn test_format_money_follows_locale() {
+    let config = Config::from_toml(SAMPLE).unwrap();
+    assert_eq!(config.locale.format_money(1250.5), "1250,50 Ar");
+    assert_eq!(Config::default().locale.format_money(12.0), "12.00€");
+}
@@ -0,0 +1,161 @@
+mod test_utils;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use kfleet::config::CostsConfig;
+use kfleet::costs::{self, by_category, indicator, machine_cost, period_range, CostRow, CostTotals, ExpenseForm};
+use kfleet::insurance::{self, PolicyForm};
+use std::collections::BTreeMap;
+use test_utils::{insert_category, insert_equipment, test_pool, unique};
+
+fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+fn row(id: i32, category: &str, purchase_cost: Option<f64>, recent_repairs: f64) -> CostRow {
+    CostRow {
+        equipment_id: id,
+        name: format!("Machine {}", id),
+        serial_number: format!("SN-{}", id),
+        category_id: if category == "Excavators" { 1 } else { 2 },
+        category_name: category.to_string(),
+        acquired: date(2020, 1, 1),
+        purchase_cost,
+        salvage_value: None,
+        useful_life_years: None,
+        depreciation_method: None,
+        default_salvage_percent: None,
+        default_useful_life_years: None,
+        default_depreciation_method: None,
+        maintenance: recent_repairs,
+        fuel: 500.0,
+        expenses: 100.0,
+        insurance: 400.0,
+        recent_repairs,
+    }
+}
+
+#[test]
+fn test_indicator_compares_repairs_with_book_value() {
+    assert_eq!(indicator(1_000.0, None, 0.5), ("unknown", None));
+    assert_eq!(indicator(1_000.0, Some(10_000.0), 0.5), ("repair", Some(0.1)));
+    assert_eq!(indicator(3_000.0, Some(10_000.0), 0.5), ("watch", Some(0.3)));
+    assert_eq!(indicator(5_000.0, Some(10_000.0), 0.5), ("replace", Some(0.5)));
+    assert_eq!(indicator(100.0, Some(0.0), 0.5), ("replace", None));
+    assert_eq!(indicator(0.0, Some(0.0), 0.5), ("repair", None));
+}
+
+#[test]
+fn test_machine_cost_divides_by_engine_hours_in_period() {
+    let config = CostsConfig { useful_life_years: 10, ..CostsConfig::default() };
+    let (from, to) = (date(2025, 1, 1), date(2025, 12, 31));
+    let hours = BTreeMap::from([(date(2024, 12, 31), 50.0), (date(2025, 3, 1), 6.0), (date(2025, 3, 2), 4.0)]);
+
+    let cost = machine_cost(row(1, "Excavators", Some(100_000.0), 1_000.0), Some(&hours), from, to, &config);
+    assert!((cost.depreciation - 10_000.0).abs() < 50.0, "{}", cost.depreciation);
+    assert!((cost.tco - (cost.depreciation + 2_000.0)).abs() < 1e-6);
+    assert_eq!(cost.engine_hours, Some(10.0));
+    assert!((cost.cost_per_hour.unwrap() - cost.tco / 10.0).abs() < 1e-9);
+    assert_eq!(cost.indicator, "repair");
+
+    let mut category_terms = row(3, "Excavators", Some(100_000.0), 0.0);
+    category_terms.default_useful_life_years = Some(8);
+    category_terms.default_salvage_percent = Some(20.0);
+    let shorter = machine_cost(category_terms, None, from, to, &config);
+    assert!((shorter.depreciation - 10_000.0).abs() < 50.0, "{}", shorter.depreciation);
+
+    let unmetered = machine_cost(row(2, "Loaders", None, 0.0), None, from, to, &config);
+    assert_eq!(unmetered.depreciation, 0.0);
+    assert_eq!(unmetered.engine_hours, None);
+    assert_eq!(unmetered.cost_per_hour, None);
+    assert_eq!(unmetered.indicator, "unknown");
+}
+
+#[test]
+fn test_totals_only_count_metered_machines_per_hour() {
+    let config = CostsConfig { useful_life_years: 5, ..CostsConfig::default() };
+    let (from, to) = (date(2025, 1, 1), date(2025, 12, 31));
+    let hours = BTreeMap::from([(date(2025, 6, 1), 20.0)]);
+    let costs = vec![
+        machine_cost(row(1, "Excavators", None, 0.0), Some(&hours), from, to, &config),
+        machine_cost(row(2, "Excavators", Some(10_000.0), 2_000.0), None, from, to, &config),
+        machine_cost(row(3, "Loaders", None, 0.0), None, from, to, &config),
+    ];
+    // Written off by 2025, so any repair means replacing
+    assert_eq!(costs[1].indicator, "replace");
+
+    let totals = CostTotals::of(&costs);
+    assert_eq!(totals.machines, 3);
+    assert_eq!(totals.engine_hours, 20.0);
+    assert_eq!(totals.cost_per_hour, Some(costs[0].tco / 20.0));
+    assert_eq!(totals.replace, 1);
+
+    let categories = by_category(&costs);
+    assert_eq!(categories.len(), 2);
+    assert_eq!(categories[0].category_name, "Excavators");
+    assert_eq!(categories[0].totals.machines, 2);
+    assert_eq!(categories[1].totals.cost_per_hour, None);
+}
+
+#[test]
+fn test_period_range() {
+    let today = date(2026, 10, 19);
+    let earliest = date(2018, 5, 2);
+    assert_eq!(period_range("12m", None, None, today, earliest), Ok((date(2025, 10, 20), today)));
+    assert_eq!(period_range("ytd", None, None, today, earliest), Ok((date(2026, 1, 1), today)));
+    assert_eq!(period_range("lifetime", None, None, today, earliest), Ok((earliest, today)));
+    assert_eq!(
+        period_range("custom", Some(date(2026, 2, 1)), Some(date(2026, 2, 28)), today, earliest),
+        Ok((date(2026, 2, 1), date(2026, 2, 28)))
+    );
+    assert!(period_range("custom", Some(date(2026, 2, 1)), None, today, earliest).is_err());
+    assert!(period_range("custom", Some(date(2026, 3, 1)), Some(date(2026, 2, 1)), today, earliest).is_err());
+    assert!(period_range("custom", Some(date(2026, 3, 1)), Some(date(2026, 11, 1)), today, earliest).is_err());
+}
+
+#[test]
+fn test_expense_form_validation() {
+    let today = date(2026, 10, 19);
+    let form = |kind: &str, amount: f64, quantity: Option<f64>, day: NaiveDate| ExpenseForm {
+        expense_date: day,
+        kind: kind.to_string(),
+        amount,
+        quantity,
+        description: None,
+    };
+    assert!(form("fuel", 180.0, Some(120.0), today).validate(today).is_ok());
+    assert!(form("coffee", 3.0, None, today).validate(today).is_err());
+    assert!(form("parts", -1.0, None, today).validate(today).is_err());
+    assert!(form("fuel", 10.0, Some(0.0), today).validate(today).is_err());
+    assert!(form("tyres", 900.0, None, date(2026, 10, 20)).validate(today).is_err());
+}
+
+#[tokio::test]
+async fn test_cancelled_policies_add_no_insurance_cost() {
+    let pool = test_pool().await;
+    let category_id = insert_category(&pool).await;
+    let equipment_id = insert_equipment(&pool, category_id, Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()).await;
+
+    let mut ids = Vec::new();
+    for premium in [365.0, 1_000.0] {
+        let form = PolicyForm {
+            insurer: "ARO".to_string(),
+            policy_number: unique("FL"),
+            coverage_type: "all_risks".to_string(),
+            premium: Some(premium),
+            deductible: None,
+            start_date: date(2025, 1, 1),
+            end_date: date(2025, 12, 31),
+            notes: None,
+            equipment_ids: vec![equipment_id],
+        };
+        let mut conn = pool.acquire().await.unwrap();
+        ids.push(insurance::create_policy(&mut conn, &form, None).await.unwrap());
+    }
+    assert!(insurance::cancel_policy(&pool, ids[1]).await.unwrap());
+
+    let rows = costs::cost_rows(&pool, date(2025, 1, 1), date(2025, 1, 31), "UTC", None, Some(equipment_id))
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert!((rows[0].insurance - 31.0).abs() < 1e-9, "{}", rows[0].insurance);
+}
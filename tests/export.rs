@@ -0,0 +1,42 @@
+use chrono::NaiveDate;
+use kfleet::export::{Cell, Sheet};
+
+fn sample_sheet() -> Sheet {
+    let mut sheet = Sheet::new("Maintenance", &["Date", "Equipment", "Cost", "Technician"]);
+    sheet.push(vec![
+        NaiveDate::from_ymd_opt(2023, 9, 10).unwrap().into(),
+        "Excavator #1".into(),
+        1820.5.into(),
+        Cell::from(None::<String>),
+    ]);
+    sheet.push(vec![
+        NaiveDate::from_ymd_opt(2023, 7, 20).unwrap().into(),
+        "Dump Truck, Antsirabe III".into(),
+        Cell::Empty,
+        "Randria Jean".into(),
+    ]);
+    sheet
+}
+
+#[test]
+fn test_csv_has_stable_headers_and_plain_values() {
+    let csv = String::from_utf8(sample_sheet().to_csv().unwrap()).unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines[0], "Date,Equipment,Cost,Technician");
+    assert_eq!(lines[1], "2023-09-10,Excavator #1,1820.5,");
+    assert_eq!(lines[2], "2023-07-20,\"Dump Truck, Antsirabe III\",,Randria Jean");
+}
+
+#[test]
+fn test_xlsx_is_a_workbook() {
+    let xlsx = sample_sheet().to_xlsx().unwrap();
+    // XLSX files are zip archives
+    assert_eq!(&xlsx[..2], b"PK");
+}
+
+#[test]
+fn test_empty_sheet_still_has_headers() {
+    let sheet = Sheet::new("Staff", &["ID", "Full Name"]);
+    assert_eq!(sheet.to_csv().unwrap(), b"ID,Full Name\n");
+}
@@ -0,0 +1,216 @@
+mod test_utils;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use kfleet::config::{Config, GeofencesConfig};
+use kfleet::geofences::{
+    self, distance_m, in_polygon, is_working_time, parse_vertices, track, Geofence, GeofenceForm, Position,
+};
+use kfleet::sites::{self, SiteForm};
+use kfleet::telematics;
+use serde_json::json;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use test_utils::{insert_category, insert_equipment, test_pool, unique};
+
+const CENTER: (f64, f64) = (-18.8792, 47.5079);
+
+fn circle(id: i32, radius_m: f64) -> Geofence {
+    Geofence {
+        id,
+        site_id: 1,
+        site_name: "Antsirabe - RN7".to_string(),
+        name: format!("Zone {}", id),
+        kind: "radius".to_string(),
+        center_latitude: Some(CENTER.0),
+        center_longitude: Some(CENTER.1),
+        radius_m: Some(radius_m),
+        vertices: None,
+    }
+}
+
+fn square() -> Geofence {
+    Geofence {
+        id: 2,
+        kind: "polygon".to_string(),
+        center_latitude: None,
+        center_longitude: None,
+        radius_m: None,
+        vertices: Some(Json(vec![[-18.878, 47.506], [-18.878, 47.510], [-18.881, 47.510], [-18.881, 47.506]])),
+        ..circle(2, 1.0)
+    }
+}
+
+fn at(minutes: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 9, 10, 0, 0).unwrap() + Duration::minutes(minutes)
+}
+
+/// A position `metres` north of the centre.
+fn north(minutes: i64, metres: f64) -> Position {
+    Position { recorded_at: at(minutes), latitude: CENTER.0 + metres / 111_320.0, longitude: CENTER.1 }
+}
+
+#[test]
+fn test_circle_and_polygon_containment() {
+    assert!((distance_m(CENTER.0, CENTER.1, CENTER.0 + 0.01, CENTER.1) - 1112.0).abs() < 5.0);
+
+    let fence = circle(1, 300.0);
+    assert!(fence.contains(CENTER.0, CENTER.1));
+    assert!(fence.contains(north(0, 250.0).latitude, CENTER.1));
+    assert!(!fence.contains(north(0, 350.0).latitude, CENTER.1));
+
+    assert!(square().contains(CENTER.0, CENTER.1));
+    assert!(!square().contains(-18.877, 47.508));
+    assert!(!in_polygon(&[], CENTER.0, CENTER.1));
+}
+
+#[test]
+fn test_zone_form_validation() {
+    let form = GeofenceForm {
+        name: "Main gate".to_string(),
+        kind: "radius".to_string(),
+        center_latitude: Some(CENTER.0),
+        center_longitude: Some(CENTER.1),
+        radius_m: Some(300.0),
+        vertices: None,
+    };
+    assert_eq!(form.validate(), Ok(None));
+    assert!(GeofenceForm { radius_m: None, ..form }.validate().is_err());
+
+    let polygon = GeofenceForm {
+        name: "Quarry".to_string(),
+        kind: "polygon".to_string(),
+        center_latitude: None,
+        center_longitude: None,
+        radius_m: None,
+        vertices: Some("-18.878, 47.506\n\n-18.878, 47.510\n-18.881, 47.510\n".to_string()),
+    };
+    assert_eq!(polygon.validate().unwrap().unwrap().len(), 3);
+
+    assert!(parse_vertices("-18.878, 47.506\n-18.878, 47.510").unwrap_err().contains("three"));
+    assert!(parse_vertices("-18.878, 47.506\n-18.878 47.510\n-18.881, 47.510").unwrap_err().contains("Line 2"));
+    assert!(parse_vertices("-98, 47.506\n-18.878, 47.510\n-18.881, 47.510").is_err());
+}
+
+#[test]
+fn test_crossings_follow_positions_in_time_order() {
+    let fences = vec![circle(1, 300.0)];
+    let mut presence = HashMap::new();
+
+    // Out of order within the batch: sorted before walking
+    let crossings = track(&fences, &mut presence, &[north(10, 500.0), north(0, 0.0), north(5, 100.0)]);
+    assert_eq!(crossings.len(), 2);
+    assert_eq!((crossings[0].kind, crossings[0].occurred_at), ("enter", at(0)));
+    assert_eq!((crossings[1].kind, crossings[1].occurred_at), ("exit", at(10)));
+    assert!(!presence[&1].inside);
+
+    // A late ping from before the last one seen changes nothing
+    assert!(track(&fences, &mut presence, &[north(8, 0.0)]).is_empty());
+    assert_eq!(track(&fences, &mut presence, &[north(15, 0.0)])[0].kind, "enter");
+}
+
+#[test]
+fn test_first_sighting_outside_is_not_an_exit() {
+    let fences = vec![circle(1, 300.0)];
+    let mut presence = HashMap::new();
+    assert!(track(&fences, &mut presence, &[north(0, 2000.0), north(5, 1500.0)]).is_empty());
+    assert_eq!(presence.len(), 1);
+}
+
+#[test]
+fn test_working_hours_use_local_time() {
+    let config = GeofencesConfig::default();
+    let tz: chrono_tz::Tz = "Indian/Antananarivo".parse().unwrap();
+    // Monday 9 March 2026; Antananarivo is UTC+3
+    let utc = |h, m| Utc.with_ymd_and_hms(2026, 3, 9, h, m, 0).unwrap();
+    assert!(is_working_time(utc(3, 0), tz, &config));
+    assert!(!is_working_time(utc(2, 59), tz, &config));
+    assert!(!is_working_time(utc(16, 0), tz, &config));
+    // Sunday
+    assert!(!is_working_time(utc(9, 0) - Duration::days(1), tz, &config));
+}
+
+/// A site with a 200 m zone around `center`, open since `start_date`.
+async fn fenced_site(pool: &PgPool, center: (f64, f64), start_date: NaiveDate) -> i32 {
+    let form = SiteForm {
+        name: unique("Carrière Ibity"),
+        client: None,
+        address: "RN7, Antsirabe".to_string(),
+        latitude: Some(center.0),
+        longitude: Some(center.1),
+        start_date,
+        end_date: None,
+        notes: None,
+    };
+    let site = sites::create_site(pool, &form).await.unwrap();
+    let zone = GeofenceForm {
+        name: "Perimeter".to_string(),
+        kind: "radius".to_string(),
+        center_latitude: Some(center.0),
+        center_longitude: Some(center.1),
+        radius_m: Some(200.0),
+        vertices: None,
+    };
+    geofences::create_geofence(pool, site, &zone, None).await.unwrap().unwrap();
+    site
+}
+
+async fn move_to(pool: &PgPool, equipment_id: i32, site_id: i32, date: NaiveDate) {
+    let mut tx = pool.begin().await.unwrap();
+    assert_eq!(sites::move_problem(&mut tx, equipment_id, Some(site_id), date).await.unwrap(), None);
+    sites::move_equipment(&mut tx, equipment_id, Some(site_id), date, None).await.unwrap();
+    tx.commit().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_late_pings_are_judged_against_the_stay_at_the_time() {
+    let pool = test_pool().await;
+    let config = Config::default();
+    let today: NaiveDate = sqlx::query_scalar("SELECT CURRENT_DATE").fetch_one(&pool).await.unwrap();
+    // Somewhere no other test run has put a zone
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let quarry = (-40.0 - (nanos % 4_000) as f64 * 0.01, 60.0);
+    let port = (quarry.0, 61.0);
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let serial: String = sqlx::query_scalar("SELECT serial_number FROM equipment WHERE id = $1")
+        .bind(machine)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let at_quarry = fenced_site(&pool, quarry, today - Duration::days(60)).await;
+    let at_port = fenced_site(&pool, port, today - Duration::days(60)).await;
+    move_to(&pool, machine, at_quarry, today - Duration::days(30)).await;
+    move_to(&pool, machine, at_port, today - Duration::days(5)).await;
+
+    // Leaving the quarry at night ten days ago, while deployed there
+    let night = |days_ago: i64, minutes: i64| {
+        (today - Duration::days(days_ago)).and_time(NaiveTime::from_hms_opt(22, 0, 0).unwrap()).and_utc()
+            + Duration::minutes(minutes)
+    };
+    let ping = |recorded_at: DateTime<Utc>, (latitude, longitude): (f64, f64)| {
+        json!({ "serial_number": serial, "recorded_at": recorded_at, "latitude": latitude, "longitude": longitude })
+    };
+    let away = (quarry.0 + 0.01, quarry.1);
+    let late = [ping(night(10, 0), quarry), ping(night(10, 10), away)];
+    let report = telematics::ingest(&pool, &late, Utc::now(), &config).await.unwrap();
+    assert_eq!(report.accepted, 2);
+    assert_eq!(report.alerts, 1);
+
+    // Back at the quarry and leaving it again, now that it belongs at the port
+    let recent = [ping(night(2, 0), quarry), ping(night(2, 10), away)];
+    let report = telematics::ingest(&pool, &recent, Utc::now(), &config).await.unwrap();
+    assert_eq!(report.accepted, 2);
+    assert_eq!(report.alerts, 0);
+
+    let events: Vec<(String, bool)> = sqlx::query_as(
+        "SELECT kind, alert FROM geofence_events WHERE equipment_id = $1 ORDER BY occurred_at",
+    )
+    .bind(machine)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let expected = [("enter", false), ("exit", true), ("enter", false), ("exit", false)];
+    assert_eq!(events, expected.map(|(kind, alert)| (kind.to_string(), alert)));
+}
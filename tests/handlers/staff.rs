@@ -1,3 +1,5 @@
+use kfleet::config::Config;
+use kfleet::metrics::Metrics;
 use kfleet::handlers::staff;
 use kfleet::AppState;
 use axum::extract::Extension;
@@ -20,6 +22,8 @@ async fn test_list_staff_handler() {
     let state = Arc::new(AppState {
         db,
         templates: Tera::default(),
+        config: Config::default(),
+        metrics: Metrics::default(),
     });
     
     let result = staff::list(Extension(state)).await;
@@ -34,6 +38,8 @@ async fn test_new_form_handler() {
     let state = Arc::new(AppState {
         db,
         templates: Tera::default(),
+        config: Config::default(),
+        metrics: Metrics::default(),
     });
     
     let result = staff::new_form(Extension(state)).await;
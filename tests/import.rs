@@ -0,0 +1,159 @@
+mod test_utils;
+
+use kfleet::handlers::import::{
+    commit, dry_run, parse_import_date, parse_rows, read_csv, suggest_mapping, ColumnMapping, ImportEntity,
+};
+use sqlx::PgPool;
+use test_utils::{test_pool, unique};
+
+const EQUIPMENT_CSV: &str = "\
+Name,Brand,Model,Serial Number,Acquisition Date,Category,Status
+Excavator #2,Caterpillar,320D,CAT-MG-002,2023-04-01,Excavators,active
+Excavator #3,Caterpillar,320D,CAT-MG-002,2023-04-01,Excavators,
+Loader,Volvo,L60H,VOL-MG-010,01/13/2023,Forklifts,active
+";
+
+#[test]
+fn test_read_csv_detects_semicolons() {
+    let (headers, records) = read_csv("full_name;license_number\nRabe Hery;OP-MG-1\n").unwrap();
+    assert_eq!(headers, vec!["full_name", "license_number"]);
+    assert_eq!(records, vec![vec!["Rabe Hery".to_string(), "OP-MG-1".to_string()]]);
+}
+
+#[test]
+fn test_suggest_mapping_matches_labels() {
+    let (headers, _) = read_csv(EQUIPMENT_CSV).unwrap();
+    let mapping = suggest_mapping(ImportEntity::Equipment, &headers);
+
+    assert_eq!(mapping.get("serial_number"), Some(&3));
+    assert_eq!(mapping.get("acquisition_date"), Some(&4));
+    assert_eq!(mapping.get("status"), Some(&6));
+    assert!(!mapping.contains_key("fuel_capacity"));
+}
+
+#[test]
+fn test_parse_rows_reports_row_errors() {
+    let (headers, records) = read_csv(EQUIPMENT_CSV).unwrap();
+    let mut mapping = suggest_mapping(ImportEntity::Equipment, &headers);
+    mapping.insert("category".to_string(), 5);
+    let rows = parse_rows(ImportEntity::Equipment, &records, &mapping);
+
+    assert_eq!(rows.len(), 3);
+    assert!(rows[0].errors.is_empty());
+    assert!(rows[0].record.is_some());
+
+    assert_eq!(rows[1].line, 3);
+    assert!(rows[1].errors[0].contains("Duplicate 'CAT-MG-002'"));
+
+    assert!(rows[2].errors[0].contains("Invalid date '01/13/2023'"));
+    assert!(rows[2].record.is_none());
+}
+
+#[test]
+fn test_parse_rows_requires_mapped_fields() {
+    let (_, records) = read_csv("name\nGrue\n").unwrap();
+    let rows = parse_rows(ImportEntity::Categories, &records, &Default::default());
+
+    assert_eq!(rows[0].errors, vec!["Missing required value for Category Name"]);
+}
+
+#[test]
+fn test_parse_import_date_formats() {
+    let expected = "2023-04-01T00:00:00Z";
+    assert_eq!(parse_import_date("2023-04-01").unwrap().to_rfc3339_opts(chrono::SecondsFormat::Secs, true), expected);
+    assert_eq!(parse_import_date("01/04/2023").unwrap().to_rfc3339_opts(chrono::SecondsFormat::Secs, true), expected);
+    assert!(parse_import_date("2023-04-01 08:30").is_some());
+    assert!(parse_import_date("2023-04-01T08:30:00+03:00").is_some());
+    assert!(parse_import_date("April 1st").is_none());
+}
+
+/// Equipment CSV with the category column mapped, as the upload form would.
+fn equipment_file(rows: &[String]) -> (String, ColumnMapping) {
+    let csv = format!("Name,Brand,Model,Serial Number,Acquisition Date,Category\n{}\n", rows.join("\n"));
+    let (headers, _) = read_csv(&csv).unwrap();
+    let mut mapping = suggest_mapping(ImportEntity::Equipment, &headers);
+    mapping.insert("category".to_string(), 5);
+    (csv, mapping)
+}
+
+async fn serial_count(pool: &PgPool, serials: &[&str]) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM equipment WHERE serial_number = ANY($1)")
+        .bind(serials)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_dry_run_writes_nothing_and_commit_inserts() {
+    let pool = test_pool().await;
+    let category = unique("Cranes");
+    let category_id: i32 = sqlx::query_scalar("INSERT INTO categories (name) VALUES ($1) RETURNING id")
+        .bind(&category)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let (first, second) = (unique("IMP"), unique("IMP"));
+    // The second row spells the category in lower case
+    let (csv, mapping) = equipment_file(&[
+        format!("Crane A,Liebherr,LTM,{first},2023-04-01,{category}"),
+        format!("Crane B,Liebherr,LTM,{second},01/04/2023,{}", category.to_lowercase()),
+    ]);
+
+    let preview = dry_run(&pool, ImportEntity::Equipment, &csv, &mapping).await.unwrap();
+    assert!(!preview.has_errors(), "{:?}", preview.rows);
+    assert_eq!(preview.valid_count, 2);
+    assert_eq!(serial_count(&pool, &[&first, &second]).await, 0);
+
+    let preview = commit(&pool, ImportEntity::Equipment, &csv, &mapping).await.unwrap();
+    assert_eq!(preview.valid_count, 2);
+    let categories: Vec<i32> =
+        sqlx::query_scalar("SELECT category_id FROM equipment WHERE serial_number = ANY($1)")
+            .bind([&first, &second])
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(categories, vec![category_id, category_id]);
+
+    // Importing the same file again finds the serials taken
+    let preview = dry_run(&pool, ImportEntity::Equipment, &csv, &mapping).await.unwrap();
+    assert_eq!(preview.error_count, 2);
+}
+
+#[tokio::test]
+async fn test_commit_writes_nothing_when_a_row_is_invalid() {
+    let pool = test_pool().await;
+    let category = unique("Cranes");
+    sqlx::query("INSERT INTO categories (name) VALUES ($1)").bind(&category).execute(&pool).await.unwrap();
+    let (first, second) = (unique("IMP"), unique("IMP"));
+    let (csv, mapping) = equipment_file(&[
+        format!("Crane A,Liebherr,LTM,{first},2023-04-01,{category}"),
+        format!("Crane B,Liebherr,LTM,{second},2023-04-01,{}", unique("Unknown")),
+    ]);
+
+    let preview = commit(&pool, ImportEntity::Equipment, &csv, &mapping).await.unwrap();
+    assert!(preview.has_errors());
+    assert_eq!(preview.error_count, 1);
+    assert_eq!(serial_count(&pool, &[&first, &second]).await, 0);
+}
+
+#[tokio::test]
+async fn test_commit_rolls_back_when_an_insert_fails() {
+    let pool = test_pool().await;
+    let category = unique("Cranes");
+    sqlx::query("INSERT INTO categories (name) VALUES ($1)").bind(&category).execute(&pool).await.unwrap();
+    let (first, second) = (unique("IMP"), unique("IMP"));
+    // Passes validation but is longer than the name column allows
+    let long_name = "Crane ".repeat(20);
+    let (csv, mapping) = equipment_file(&[
+        format!("Crane A,Liebherr,LTM,{first},2023-04-01,{category}"),
+        format!("{long_name},Liebherr,LTM,{second},2023-04-01,{category}"),
+    ]);
+
+    let preview = dry_run(&pool, ImportEntity::Equipment, &csv, &mapping).await.unwrap();
+    assert!(!preview.has_errors());
+
+    let error = commit(&pool, ImportEntity::Equipment, &csv, &mapping).await.unwrap_err();
+    assert!(error.starts_with("Line 3:"), "{error}");
+    assert_eq!(serial_count(&pool, &[&first, &second]).await, 0);
+}
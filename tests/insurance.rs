@@ -0,0 +1,247 @@
+mod test_utils;
+
+use chrono::{Duration, NaiveDate};
+use kfleet::insurance::{self, PolicyForm};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use std::str::FromStr;
+use test_utils::{insert_category, insert_equipment, test_database_url, test_pool, unique};
+
+fn form(equipment_ids: Vec<i32>, start_date: NaiveDate, end_date: NaiveDate) -> PolicyForm {
+    PolicyForm {
+        insurer: "ARO".to_string(),
+        policy_number: unique("FL"),
+        coverage_type: "all_risks".to_string(),
+        premium: Some(1_200_000.0),
+        deductible: Some(150_000.0),
+        start_date,
+        end_date,
+        notes: None,
+        equipment_ids,
+    }
+}
+
+async fn today(pool: &PgPool) -> NaiveDate {
+    sqlx::query_scalar("SELECT CURRENT_DATE").fetch_one(pool).await.unwrap()
+}
+
+async fn create_policy(pool: &PgPool, form: &PolicyForm) -> i32 {
+    let mut conn = pool.acquire().await.unwrap();
+    insurance::create_policy(&mut conn, form, None).await.unwrap()
+}
+
+fn ids(policies: &[insurance::Policy]) -> Vec<i32> {
+    policies.iter().map(|p| p.id).collect()
+}
+
+#[tokio::test]
+async fn test_renewal_history_follows_the_chain_both_ways() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let year = |y| NaiveDate::from_ymd_opt(y, 1, 1).unwrap();
+
+    let first = create_policy(&pool, &form(vec![machine], year(2023), year(2024))).await;
+    let second = insurance::renew_policy(&pool, first, &form(vec![machine], year(2024), year(2025)))
+        .await
+        .unwrap()
+        .unwrap();
+    let third = insurance::renew_policy(&pool, second, &form(vec![machine], year(2025), year(2026)))
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Oldest first, whichever policy of the chain it starts from
+    for id in [first, second, third] {
+        let chain = insurance::renewal_history(&pool, id).await.unwrap();
+        assert_eq!(ids(&chain), vec![first, second, third]);
+    }
+    let chain = insurance::renewal_history(&pool, third).await.unwrap();
+    let statuses: Vec<&str> = chain.iter().map(|p| p.status.as_str()).collect();
+    assert_eq!(statuses, vec!["renewed", "renewed", "active"]);
+    assert_eq!(chain[2].renewed_from_id, Some(second));
+
+    // A policy never renewed is a chain of one
+    let alone = create_policy(&pool, &form(vec![machine], year(2025), year(2026))).await;
+    assert_eq!(ids(&insurance::renewal_history(&pool, alone).await.unwrap()), vec![alone]);
+}
+
+#[tokio::test]
+async fn test_policy_state_follows_status_and_dates() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let today = today(&pool).await;
+    let day = |offset| today + Duration::days(offset);
+
+    let upcoming = create_policy(&pool, &form(vec![machine], day(1), day(30))).await;
+    let in_force = create_policy(&pool, &form(vec![machine], day(-30), day(0))).await;
+    let expired = create_policy(&pool, &form(vec![machine], day(-60), day(-1))).await;
+    let cancelled = create_policy(&pool, &form(vec![machine], day(-30), day(30))).await;
+    assert!(insurance::cancel_policy(&pool, cancelled).await.unwrap());
+
+    let mut states = Vec::new();
+    for id in [upcoming, in_force, expired, cancelled] {
+        states.push(insurance::get_policy(&pool, id).await.unwrap().unwrap().state);
+    }
+    assert_eq!(states, vec!["upcoming", "in_force", "expired", "cancelled"]);
+
+    let listed: Vec<(i32, String)> = insurance::list_policies(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|p| [upcoming, in_force, expired, cancelled].contains(&p.id))
+        .map(|p| (p.id, p.state))
+        .collect();
+    assert_eq!(listed.len(), 4);
+    assert!(listed.contains(&(expired, "expired".to_string())));
+}
+
+#[tokio::test]
+async fn test_renew_policy_refuses_policies_that_are_not_active() {
+    let pool = test_pool().await;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let year = |y| NaiveDate::from_ymd_opt(y, 1, 1).unwrap();
+
+    let renewed = create_policy(&pool, &form(vec![machine], year(2024), year(2025))).await;
+    let successor = insurance::renew_policy(&pool, renewed, &form(vec![machine], year(2025), year(2026)))
+        .await
+        .unwrap()
+        .unwrap();
+    // Renewed already: a second successor would fork the chain
+    let again = insurance::renew_policy(&pool, renewed, &form(vec![machine], year(2025), year(2026))).await;
+    assert_eq!(again.unwrap(), None);
+
+    assert!(insurance::cancel_policy(&pool, successor).await.unwrap());
+    assert!(!insurance::cancel_policy(&pool, successor).await.unwrap());
+    let after_cancel = insurance::renew_policy(&pool, successor, &form(vec![machine], year(2026), year(2027))).await;
+    assert_eq!(after_cancel.unwrap(), None);
+
+    assert_eq!(ids(&insurance::renewal_history(&pool, renewed).await.unwrap()), vec![renewed, successor]);
+    let policies: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM policy_equipment WHERE equipment_id = $1")
+        .bind(machine)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(policies, 2);
+}
+
+#[tokio::test]
+async fn test_coverage_counts_policies_in_force_today() {
+    let pool = test_pool().await;
+    let today = today(&pool).await;
+    let category = insert_category(&pool).await;
+    let acquired = "2020-01-01T00:00:00Z".parse().unwrap();
+    let covered = insert_equipment(&pool, category, acquired).await;
+    let lapsed = insert_equipment(&pool, category, acquired).await;
+    let cancelled = insert_equipment(&pool, category, acquired).await;
+    let upcoming = insert_equipment(&pool, category, acquired).await;
+    let never = insert_equipment(&pool, category, acquired).await;
+    let retired = insert_equipment(&pool, category, acquired).await;
+    sqlx::query("UPDATE equipment SET current_status = 'retired' WHERE id = $1")
+        .bind(retired)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let days = Duration::days;
+    create_policy(&pool, &form(vec![covered], today - days(200), today + days(165))).await;
+    // A shorter policy on the same machine does not shorten its cover
+    create_policy(&pool, &form(vec![covered], today - days(10), today + days(20))).await;
+    create_policy(&pool, &form(vec![lapsed], today - days(395), today - days(30))).await;
+    let dropped = create_policy(&pool, &form(vec![cancelled], today - days(30), today + days(335))).await;
+    insurance::cancel_policy(&pool, dropped).await.unwrap();
+    create_policy(&pool, &form(vec![upcoming], today + days(30), today + days(395))).await;
+
+    let coverage: Vec<(i32, Option<NaiveDate>, i64)> = sqlx::query_as(
+        "SELECT equipment_id, covered_until, active_policies FROM equipment_coverage WHERE equipment_id = ANY($1) ORDER BY equipment_id",
+    )
+    .bind(vec![covered, lapsed, cancelled, upcoming])
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        coverage,
+        vec![
+            (covered, Some(today + days(165)), 2),
+            (lapsed, None, 0),
+            (cancelled, None, 0),
+            (upcoming, None, 0),
+        ]
+    );
+
+    let mut uncovered: Vec<(i32, Option<NaiveDate>)> = insurance::uncovered_equipment(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|e| [covered, lapsed, cancelled, upcoming, never, retired].contains(&e.id))
+        .map(|e| (e.id, e.last_covered))
+        .collect();
+    uncovered.sort();
+    // Retired machines need no cover; cancelled and future policies never covered anything
+    assert_eq!(
+        uncovered,
+        vec![(lapsed, Some(today - days(30))), (cancelled, None), (upcoming, None), (never, None)]
+    );
+}
+
+#[tokio::test]
+async fn test_insurance_renewal_dates_become_legacy_policies() {
+    // Migrated from scratch, since undoing migrations on the shared test
+    // database would pull it from under the other tests
+    let pool = test_pool().await;
+    let name = unique("kfleet_migration").replace('-', "_");
+    sqlx::query(&format!("CREATE DATABASE {}", name)).execute(&pool).await.unwrap();
+    let options = PgConnectOptions::from_str(&test_database_url()).unwrap().database(&name);
+    let scratch = PgPoolOptions::new().max_connections(1).connect_with(options).await.unwrap();
+
+    let migrator = sqlx::migrate!();
+    migrator.run(&scratch).await.unwrap();
+    // Back to the schema before insurance policies
+    migrator.undo(&scratch, 20261019130000).await.unwrap();
+    let category = insert_category(&scratch).await;
+    let machines: Vec<i32> = sqlx::query_scalar(
+        r#"
+        INSERT INTO equipment (name, brand, model, serial_number, acquisition_date, category_id, insurance_renewal)
+        VALUES ('CAT 320', 'CAT', '320', 'EXC-1', '2020-01-01T00:00:00Z', $1, '2026-03-31T12:00:00Z'),
+            ('CAT 950', 'CAT', '950', 'LDR-1', '2020-01-01T00:00:00Z', $1, NULL)
+        RETURNING id
+        "#,
+    )
+    .bind(category)
+    .fetch_all(&scratch)
+    .await
+    .unwrap();
+    let insured = machines[0];
+
+    migrator.run(&scratch).await.unwrap();
+    let policies: Vec<(String, String, String, NaiveDate, NaiveDate, String, i32)> = sqlx::query_as(
+        r#"
+        SELECT p.insurer, p.policy_number, p.coverage_type, p.start_date, p.end_date, p.status, pe.equipment_id
+        FROM insurance_policies p
+        JOIN policy_equipment pe ON pe.policy_id = p.id
+        WHERE pe.equipment_id = ANY($1)
+        "#,
+    )
+    .bind(&machines)
+    .fetch_all(&scratch)
+    .await
+    .unwrap();
+    scratch.close().await;
+    sqlx::query(&format!("DROP DATABASE {}", name)).execute(&pool).await.unwrap();
+
+    // Only the machine with a renewal date gets a placeholder, for the year before it
+    assert_eq!(
+        policies,
+        vec![(
+            "Unknown".to_string(),
+            format!("LEGACY-{}", insured),
+            "other".to_string(),
+            NaiveDate::from_ymd_opt(2025, 3, 31).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 3, 31).unwrap(),
+            "active".to_string(),
+            insured,
+        )]
+    );
+}
@@ -1,4 +1,6 @@
 use axum_test::{TestServer, TestResponse};
+use kfleet::config::Config;
+use kfleet::metrics::Metrics;
 use kfleet::{create_router, AppState};
 use sqlx::{Pool, Postgres, PgPoolOptions};
 use tera::Tera;
@@ -18,6 +20,8 @@ async fn test_server() -> TestServer {
     let state = Arc::new(AppState {
         db: pool,
         templates: Tera::default(),
+        config: Config::default(),
+        metrics: Metrics::default(),
     });
     
     TestServer::new(create_router(state)).unwrap()
@@ -0,0 +1,22 @@
+use kfleet::config::Config;
+use kfleet::jobs::Job;
+
+#[test]
+fn test_job_names_round_trip() {
+    for job in Job::ALL {
+        assert_eq!(job.name().parse::<Job>().unwrap(), job);
+    }
+    assert_eq!("recompute-schedules".parse::<Job>().unwrap(), Job::RecomputeSchedules);
+    assert!("backup".parse::<Job>().unwrap_err().contains("deadline_scan"));
+}
+
+#[test]
+fn test_invalid_cron_schedule_is_rejected() {
+    let mut config = Config::default();
+    config.database.url = "postgres://localhost/kfleet".to_string();
+    assert!(config.validate().is_ok());
+
+    config.jobs.deadline_scan = "every night".to_string();
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("jobs.deadline_scan"));
+}
@@ -0,0 +1,27 @@
+use kfleet::metrics::Metrics;
+
+#[test]
+fn test_encode_exposes_prefixed_metrics() {
+    let metrics = Metrics::new();
+    metrics
+        .http_requests
+        .with_label_values(&["GET", "/equipment/{id}/edit", "200"])
+        .inc();
+    metrics.equipment_by_status.with_label_values(&["active"]).set(7);
+    metrics.template_failures.inc();
+
+    let text = metrics.encode().unwrap();
+    assert!(text.contains(
+        r#"kfleet_http_requests_total{method="GET",route="/equipment/{id}/edit",status="200"} 1"#
+    ));
+    assert!(text.contains(r#"kfleet_equipment{status="active"} 7"#));
+    assert!(text.contains("kfleet_template_render_failures_total 1"));
+}
+
+#[test]
+fn test_instances_do_not_share_counters() {
+    let first = Metrics::new();
+    let second = Metrics::new();
+    first.template_failures.inc();
+    assert_eq!(second.template_failures.get(), 0);
+}
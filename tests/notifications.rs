@@ -0,0 +1,44 @@
+use chrono::NaiveDate;
+use kfleet::config::Config;
+use kfleet::load_templates;
+use kfleet::notifications::{render_digest, DigestItem, Subscription};
+
+fn item(kind: &str, name: &str, due: &str) -> DigestItem {
+    DigestItem {
+        kind: kind.to_string(),
+        equipment_id: 1,
+        name: name.to_string(),
+        serial_number: "CAT-MG-001".to_string(),
+        due_date: NaiveDate::parse_from_str(due, "%Y-%m-%d").unwrap(),
+    }
+}
+
+#[test]
+fn test_render_digest_splits_upcoming_and_overdue() {
+    let templates = load_templates(&Config::default()).unwrap();
+    let subscription = Subscription {
+        id: 1,
+        email: "chef@depot.mg".to_string(),
+        frequency: "weekly".to_string(),
+        days_ahead: 30,
+        include_maintenance: true,
+        include_insurance: true,
+        active: true,
+        last_sent_at: None,
+    };
+    let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+    let items = vec![
+        item("maintenance", "Excavator #1", "2026-10-12"),
+        item("insurance", "Grue <Tana>", "2026-10-29"),
+    ];
+
+    let (subject, text, html) = render_digest(&templates, &subscription, &items, today).unwrap();
+
+    assert_eq!(subject, "kFleet: 2 deadlines, 1 overdue");
+    assert!(text.contains("Excavator #1 (CAT-MG-001): 12/10/2026, OVERDUE by 7 days"));
+    assert!(text.contains("Grue <Tana> (CAT-MG-001): 29/10/2026, in 10 days"));
+    assert!(text.contains("weekly digest"));
+    assert!(html.contains("Grue &lt;Tana&gt;"));
+    assert_eq!(items[0].stage(today), "overdue");
+    assert_eq!(items[1].stage(today), "upcoming");
+}
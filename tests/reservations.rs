@@ -0,0 +1,237 @@
+mod test_utils;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use kfleet::reservations::{
+    self, build_calendar, check_transition, find_conflicts, month_days, parse_month, Availability,
+    CalendarMachine, DecisionForm, Reservation, ReservationForm,
+};
+use kfleet::sites::{self, SiteForm};
+use sqlx::PgPool;
+use std::time::Duration;
+use test_utils::{insert_category, insert_equipment, setup_test_server, test_pool, unique};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+}
+
+fn booking(id: i32, status: &str, start_date: NaiveDate, end_date: NaiveDate) -> Reservation {
+    Reservation {
+        id,
+        equipment_id: 2,
+        equipment_name: "Grue Liebherr LTM 1050".to_string(),
+        category_id: 1,
+        category_name: "Grue".to_string(),
+        site_id: 1,
+        site_name: "Toamasina - quai C".to_string(),
+        requested_by: "Hery".to_string(),
+        start_date,
+        end_date,
+        purpose: None,
+        status: status.to_string(),
+        decided_by: None,
+        decided_at: None,
+        decision_note: None,
+        created_at: Utc.with_ymd_and_hms(2026, 10, 1, 8, 0, 0).unwrap(),
+    }
+}
+
+fn crane(status: &str, bookings: Vec<Reservation>) -> Availability {
+    Availability {
+        equipment_name: "Grue Liebherr LTM 1050".to_string(),
+        status: status.to_string(),
+        maintenance_date: Some(date(10, 20)),
+        bookings,
+    }
+}
+
+#[test]
+fn test_approved_bookings_block_and_requests_compete() {
+    let availability = crane(
+        "active",
+        vec![booking(7, "approved", date(10, 1), date(10, 7)), booking(8, "pending", date(10, 8), date(10, 12))],
+    );
+
+    let conflicts = find_conflicts(&availability, date(10, 6), date(10, 9));
+    assert_eq!(conflicts.len(), 2);
+    assert!(conflicts[0].blocking && conflicts[0].reservation_id == Some(7));
+    assert!(!conflicts[1].blocking && conflicts[1].message.contains("Also requested"));
+
+    // Adjacent ranges don't overlap: end dates are inclusive
+    assert!(find_conflicts(&availability, date(10, 13), date(10, 19)).is_empty());
+}
+
+#[test]
+fn test_maintenance_blocks_bookings() {
+    let due = find_conflicts(&crane("active", vec![]), date(10, 19), date(10, 23));
+    assert!(due[0].blocking && due[0].message.contains("maintenance on 2026-10-20"));
+
+    let in_shop = find_conflicts(&crane("maintenance", vec![]), date(11, 2), date(11, 6));
+    assert!(in_shop[0].blocking && in_shop[0].message.contains("in maintenance"));
+
+    let retired = find_conflicts(&crane("retired", vec![]), date(11, 2), date(11, 6));
+    assert!(retired[0].message.contains("retired"));
+}
+
+#[test]
+fn test_decisions() {
+    assert!(check_transition("pending", "approved").is_ok());
+    assert!(check_transition("pending", "rejected").is_ok());
+    assert!(check_transition("approved", "cancelled").is_ok());
+    assert!(check_transition("approved", "rejected").is_err());
+    assert!(check_transition("rejected", "approved").unwrap_err().contains("already rejected"));
+    assert!(check_transition("pending", "pending").is_err());
+    assert!(check_transition("pending", "booked").is_err());
+}
+
+#[test]
+fn test_calendar_months() {
+    assert_eq!(parse_month("2026-02"), Some(date(2, 1)));
+    assert_eq!(parse_month("February"), None);
+    assert_eq!(month_days(date(2, 1)).len(), 28);
+    assert_eq!(month_days(date(10, 15)).first(), Some(&date(10, 1)));
+    assert_eq!(month_days(date(12, 1)).last(), Some(&date(12, 31)));
+}
+
+#[test]
+fn test_calendar_cells() {
+    let machines = vec![CalendarMachine {
+        id: 2,
+        name: "Grue Liebherr LTM 1050".to_string(),
+        status: "active".to_string(),
+        maintenance_date: Some(date(10, 20)),
+    }];
+    let bookings = vec![
+        booking(7, "approved", date(10, 1), date(10, 7)),
+        booking(8, "pending", date(10, 6), date(10, 9)),
+        booking(9, "pending", date(10, 19), date(10, 21)),
+    ];
+    let days = month_days(date(10, 1));
+
+    let rows = build_calendar(machines, &bookings, &days);
+    let cells = &rows[0].cells;
+    assert_eq!(cells.len(), 31);
+    assert_eq!((cells[0].state, cells[0].reservation_id), ("approved", Some(7)));
+    assert_eq!((cells[6].state, cells[6].reservation_id), ("approved", Some(7)));
+    assert_eq!((cells[7].state, cells[7].reservation_id), ("pending", Some(8)));
+    assert_eq!(cells[18].state, "pending");
+    assert_eq!(cells[19].state, "maintenance");
+    assert_eq!(cells[30].state, "free");
+}
+
+/// A crane on a site open from 2026, with two requests for overlapping
+/// weeks of October 2030.
+async fn competing_requests(pool: &PgPool) -> (i32, i32, i32) {
+    let category = insert_category(pool).await;
+    let crane = insert_equipment(pool, category, Utc.with_ymd_and_hms(2026, 1, 5, 0, 0, 0).unwrap()).await;
+    let site = sites::create_site(
+        pool,
+        &SiteForm {
+            name: unique("Toamasina - quai C"),
+            client: None,
+            address: "Boulevard Ratsimilaho, Toamasina".to_string(),
+            latitude: None,
+            longitude: None,
+            start_date: date(1, 5),
+            end_date: None,
+            notes: None,
+        },
+    )
+    .await
+    .unwrap();
+    let request = |start_date, end_date| ReservationForm {
+        equipment_id: crane,
+        site_id: site,
+        requested_by: "Hery".to_string(),
+        start_date,
+        end_date,
+        purpose: None,
+    };
+    let in_2030 = |month, day| NaiveDate::from_ymd_opt(2030, month, day).unwrap();
+    let mut conn = pool.acquire().await.unwrap();
+    let first = reservations::create_reservation(&mut conn, &request(in_2030(10, 1), in_2030(10, 7))).await.unwrap();
+    let second = reservations::create_reservation(&mut conn, &request(in_2030(10, 6), in_2030(10, 9))).await.unwrap();
+    (crane, first, second)
+}
+
+fn approval() -> DecisionForm {
+    DecisionForm { status: "approved".to_string(), decided_by: "Fara".to_string(), note: None }
+}
+
+/// The blocking conflicts of approving `reservation` on `crane`, as the
+/// decision handler checks them.
+async fn blocking(conn: &mut sqlx::PgConnection, crane: i32, reservation: &Reservation) -> Vec<Option<i32>> {
+    let availability = reservations::availability(conn, crane, Some(reservation.id), "UTC")
+        .await
+        .unwrap()
+        .unwrap();
+    find_conflicts(&availability, reservation.start_date, reservation.end_date)
+        .into_iter()
+        .filter(|c| c.blocking)
+        .map(|c| c.reservation_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_approval_waits_for_the_machine_lock() {
+    let pool = test_pool().await;
+    let (crane, first, second) = competing_requests(&pool).await;
+    let first = reservations::get_reservation(&pool, first).await.unwrap().unwrap();
+    let second = reservations::get_reservation(&pool, second).await.unwrap().unwrap();
+
+    // Both requests are free to approve until one of them is
+    let mut tx = pool.begin().await.unwrap();
+    assert!(blocking(&mut tx, crane, &first).await.is_empty());
+
+    let competing = {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            let mut tx = pool.begin().await.unwrap();
+            blocking(&mut tx, crane, &second).await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!competing.is_finished(), "the second approval should wait for the lock");
+
+    assert!(reservations::decide(&mut tx, first.id, "pending", &approval()).await.unwrap());
+    tx.commit().await.unwrap();
+    assert_eq!(competing.await.unwrap(), vec![Some(first.id)]);
+}
+
+#[tokio::test]
+async fn test_overlapping_approval_is_refused() {
+    let server = setup_test_server().await;
+    let pool = test_pool().await;
+    let (_, first, second) = competing_requests(&pool).await;
+
+    let decide = |id: i32| {
+        server
+            .post(&format!("/reservations/{}/status", id))
+            .form(&[("status", "approved"), ("decided_by", "Fara")])
+    };
+    assert_eq!(decide(first).await.status_code(), 303);
+    let refused = decide(second).await;
+    assert_eq!(refused.status_code(), 200);
+    assert!(refused.text().contains("Cannot approve"));
+
+    let second = reservations::get_reservation(&pool, second).await.unwrap().unwrap();
+    assert_eq!(second.status, "pending");
+}
+
+#[tokio::test]
+async fn test_viewing_a_request_does_not_wait_for_the_machine_lock() {
+    let server = setup_test_server().await;
+    let pool = test_pool().await;
+    let (crane, first, second) = competing_requests(&pool).await;
+    let first = reservations::get_reservation(&pool, first).await.unwrap().unwrap();
+
+    // An approval in progress holds the machine
+    let mut tx = pool.begin().await.unwrap();
+    assert!(blocking(&mut tx, crane, &first).await.is_empty());
+
+    let page = tokio::time::timeout(Duration::from_secs(5), server.get(&format!("/reservations/{}", second)))
+        .await
+        .expect("the page should not wait for the approval");
+    assert_eq!(page.status_code(), 200);
+    assert!(page.text().contains("Also requested"));
+    tx.rollback().await.unwrap();
+}
@@ -0,0 +1,196 @@
+mod test_utils;
+
+use chrono::{Duration, NaiveDate};
+use kfleet::assignments;
+use kfleet::config::AssignmentsConfig;
+use kfleet::reservations::{self, ReservationForm};
+use kfleet::sites::{self, check_move, Deployment, Site, SiteForm};
+use sqlx::PgPool;
+use test_utils::{insert_category, insert_equipment, insert_staff, test_pool, unique};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+}
+
+fn form(start_date: NaiveDate) -> SiteForm {
+    SiteForm {
+        name: unique("Port de Toamasina"),
+        client: Some("SPAT".to_string()),
+        address: "Boulevard Ratsimilaho, Toamasina".to_string(),
+        latitude: Some(-18.1443),
+        longitude: Some(49.4122),
+        start_date,
+        end_date: None,
+        notes: None,
+    }
+}
+
+fn site(id: i32, name: &str, start_date: NaiveDate, end_date: Option<NaiveDate>) -> Site {
+    Site {
+        id,
+        name: name.to_string(),
+        client: None,
+        address: name.to_string(),
+        latitude: None,
+        longitude: None,
+        start_date,
+        end_date,
+        notes: None,
+        state: "active".to_string(),
+        machine_count: 0,
+    }
+}
+
+fn stay(site: &Site, deployed_from: NaiveDate, deployed_until: Option<NaiveDate>) -> Deployment {
+    Deployment {
+        id: 1,
+        equipment_id: 4,
+        equipment_name: "Pelle CAT 320".to_string(),
+        serial_number: "CAT0320-118".to_string(),
+        site_id: site.id,
+        site_name: site.name.clone(),
+        deployed_from,
+        deployed_until,
+        notes: None,
+        current: deployed_until.is_none(),
+    }
+}
+
+#[test]
+fn test_machines_move_between_sites_weekly() {
+    let tana = site(1, "Antananarivo - Ankorondrano", date(1, 5), None);
+    let antsirabe = site(2, "Antsirabe - RN7", date(2, 1), None);
+
+    assert!(check_move(None, Some(&tana), date(3, 2)).is_ok());
+
+    let at_tana = stay(&tana, date(3, 2), None);
+    assert!(check_move(Some(&at_tana), Some(&antsirabe), date(3, 9)).is_ok());
+    assert!(check_move(Some(&at_tana), None, date(3, 9)).is_ok());
+    assert!(check_move(Some(&at_tana), Some(&tana), date(3, 9)).unwrap_err().contains("already at"));
+}
+
+#[test]
+fn test_moves_only_go_forward() {
+    let tana = site(1, "Antananarivo - Ankorondrano", date(1, 5), None);
+    let antsirabe = site(2, "Antsirabe - RN7", date(2, 1), None);
+
+    let at_tana = stay(&tana, date(3, 2), None);
+    assert!(check_move(Some(&at_tana), Some(&antsirabe), date(3, 2)).unwrap_err().contains("arrived"));
+    assert!(check_move(Some(&at_tana), Some(&antsirabe), date(2, 20)).is_err());
+
+    // Back at the yard since 9 March: an earlier move would overlap that stay
+    let left_tana = stay(&tana, date(3, 2), Some(date(3, 9)));
+    assert!(check_move(Some(&left_tana), Some(&antsirabe), date(3, 5)).unwrap_err().contains("until"));
+    assert!(check_move(Some(&left_tana), Some(&antsirabe), date(3, 9)).is_ok());
+    assert!(check_move(Some(&left_tana), None, date(3, 16)).unwrap_err().contains("yard"));
+    assert!(check_move(None, None, date(3, 16)).is_err());
+}
+
+#[test]
+fn test_sites_take_machines_while_open() {
+    let port = site(3, "Port de Toamasina - quai C", date(3, 2), Some(date(11, 30)));
+
+    assert!(check_move(None, Some(&port), date(2, 23)).unwrap_err().contains("opens"));
+    assert!(check_move(None, Some(&port), date(3, 2)).is_ok());
+    assert!(check_move(None, Some(&port), date(11, 30)).is_ok());
+    assert!(check_move(None, Some(&port), date(12, 1)).unwrap_err().contains("closed"));
+}
+
+/// Moves the machine the way the handlers do, checking the move first.
+async fn move_to(pool: &PgPool, equipment_id: i32, site_id: Option<i32>, date: NaiveDate) {
+    let mut tx = pool.begin().await.unwrap();
+    let problem = sites::move_problem(&mut tx, equipment_id, site_id, date).await.unwrap();
+    assert_eq!(problem, None);
+    sites::move_equipment(&mut tx, equipment_id, site_id, date, None).await.unwrap();
+    tx.commit().await.unwrap();
+}
+
+async fn location(pool: &PgPool, equipment_id: i32) -> Option<i32> {
+    sqlx::query_scalar("SELECT site_id FROM equipment_location WHERE equipment_id = $1")
+        .bind(equipment_id)
+        .fetch_optional(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_location_is_the_stay_covering_today() {
+    let pool = test_pool().await;
+    let today: NaiveDate = sqlx::query_scalar("SELECT CURRENT_DATE").fetch_one(&pool).await.unwrap();
+    let days = Duration::days;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let operator = insert_staff(&pool).await;
+    let mut conn = pool.acquire().await.unwrap();
+    assignments::add_operator(&mut conn, machine, operator, &AssignmentsConfig::default()).await.unwrap();
+    let port = sites::create_site(&pool, &form(today - days(60))).await.unwrap();
+    let quarry = sites::create_site(&pool, &form(today - days(60))).await.unwrap();
+
+    assert_eq!(location(&pool, machine).await, None);
+    move_to(&pool, machine, Some(port), today - days(30)).await;
+    // Planned for next week: still at the port until then
+    move_to(&pool, machine, Some(quarry), today + days(7)).await;
+    assert_eq!(location(&pool, machine).await, Some(port));
+    assert_eq!(sites::get_site(&pool, port).await.unwrap().unwrap().machine_count, 1);
+    assert_eq!(sites::get_site(&pool, quarry).await.unwrap().unwrap().machine_count, 0);
+
+    let stays: Vec<(i32, bool)> = sites::deployments(&pool, None, Some(machine))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.site_id, d.current))
+        .collect();
+    assert_eq!(stays, vec![(quarry, false), (port, true)]);
+
+    let crew: Vec<i32> = sites::site_operators(&pool, port).await.unwrap().iter().map(|o| o.id).collect();
+    assert_eq!(crew, vec![operator]);
+    assert!(sites::site_operators(&pool, quarry).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_machines_back_at_the_yard_have_no_location() {
+    let pool = test_pool().await;
+    let today: NaiveDate = sqlx::query_scalar("SELECT CURRENT_DATE").fetch_one(&pool).await.unwrap();
+    let days = Duration::days;
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let port = sites::create_site(&pool, &form(today - days(60))).await.unwrap();
+
+    move_to(&pool, machine, Some(port), today - days(30)).await;
+    // The stay ends the day it left
+    move_to(&pool, machine, None, today).await;
+    assert_eq!(location(&pool, machine).await, None);
+    assert_eq!(sites::get_site(&pool, port).await.unwrap().unwrap().machine_count, 0);
+
+    let mut tx = pool.begin().await.unwrap();
+    let problem = sites::move_problem(&mut tx, machine, None, today + days(1)).await.unwrap();
+    assert!(problem.unwrap().contains("yard"));
+}
+
+#[tokio::test]
+async fn test_sites_with_reservations_are_kept() {
+    let pool = test_pool().await;
+    let today: NaiveDate = sqlx::query_scalar("SELECT CURRENT_DATE").fetch_one(&pool).await.unwrap();
+    let category = insert_category(&pool).await;
+    let machine = insert_equipment(&pool, category, "2020-01-01T00:00:00Z".parse().unwrap()).await;
+    let port = sites::create_site(&pool, &form(today)).await.unwrap();
+    let empty = sites::create_site(&pool, &form(today)).await.unwrap();
+
+    let request = ReservationForm {
+        equipment_id: machine,
+        site_id: port,
+        requested_by: "Hery".to_string(),
+        start_date: today + Duration::days(7),
+        end_date: today + Duration::days(14),
+        purpose: None,
+    };
+    let mut conn = pool.acquire().await.unwrap();
+    reservations::create_reservation(&mut conn, &request).await.unwrap();
+
+    let problem = sites::delete_site(&pool, port).await.unwrap();
+    assert!(problem.unwrap().contains("reserved"));
+    assert!(sites::get_site(&pool, port).await.unwrap().is_some());
+
+    assert_eq!(sites::delete_site(&pool, empty).await.unwrap(), None);
+    assert!(sites::get_site(&pool, empty).await.unwrap().is_none());
+}
@@ -1,5 +1,7 @@
 use axum_test::TestServer;
 use insta::{assert_html_snapshot, with_settings};
+use kfleet::config::Config;
+use kfleet::metrics::Metrics;
 use kfleet::{create_router, AppState};
 use sqlx::{Pool, Postgres, PgPoolOptions};
 use tera::Tera;
@@ -33,6 +35,8 @@ async fn snapshot_server() -> TestServer {
     let state = Arc::new(AppState {
         db: pool,
         templates: Tera::new("templates/**/*").unwrap(),
+        config: Config::default(),
+        metrics: Metrics::default(),
     });
     
     TestServer::new(create_router(state)).unwrap()
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use kfleet::config::TelematicsConfig;
+use kfleet::telematics::{authorized, check_batch, MachineRef, Ping};
+use serde_json::json;
+
+fn now() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 9, 12, 0, 0).unwrap()
+}
+
+fn ping() -> Ping {
+    Ping {
+        serial_number: Some("CAT0320-118".to_string()),
+        device_id: None,
+        recorded_at: now() - Duration::minutes(5),
+        latitude: Some(-18.8792),
+        longitude: Some(47.5079),
+        engine_hours: Some(4210.5),
+        fuel_level: Some(62.0),
+    }
+}
+
+#[test]
+fn test_ping_names_exactly_one_machine() {
+    assert_eq!(ping().machine().unwrap(), MachineRef::Serial("CAT0320-118".to_string()));
+
+    let by_device = Ping { serial_number: None, device_id: Some(" TK-0042 ".to_string()), ..ping() };
+    assert_eq!(by_device.machine().unwrap(), MachineRef::Device("TK-0042".to_string()));
+
+    let both = Ping { device_id: Some("TK-0042".to_string()), ..ping() };
+    assert!(both.machine().is_err());
+    let neither = Ping { serial_number: Some("  ".to_string()), ..ping() };
+    assert!(neither.machine().is_err());
+}
+
+#[test]
+fn test_ping_validation() {
+    let config = TelematicsConfig::default();
+    assert!(ping().validate(now(), &config).is_ok());
+
+    let late = Ping { recorded_at: now() + Duration::minutes(2), ..ping() };
+    assert!(late.validate(now(), &config).is_ok(), "within the allowed clock skew");
+    let future = Ping { recorded_at: now() + Duration::hours(1), ..ping() };
+    assert!(future.validate(now(), &config).unwrap_err().contains("future"));
+    let stale = Ping { recorded_at: now() - Duration::days(200), ..ping() };
+    assert!(stale.validate(now(), &config).unwrap_err().contains("older"));
+
+    let half = Ping { longitude: None, ..ping() };
+    assert!(half.validate(now(), &config).unwrap_err().contains("together"));
+    let off_map = Ping { latitude: Some(95.0), ..ping() };
+    assert!(off_map.validate(now(), &config).is_err());
+    assert!(Ping { engine_hours: Some(-1.0), ..ping() }.validate(now(), &config).is_err());
+    assert!(Ping { fuel_level: Some(101.0), ..ping() }.validate(now(), &config).is_err());
+
+    let fuel_only = Ping { latitude: None, longitude: None, engine_hours: None, ..ping() };
+    assert!(fuel_only.validate(now(), &config).is_ok());
+    let empty = Ping { fuel_level: None, ..fuel_only };
+    assert!(empty.validate(now(), &config).unwrap_err().contains("no position"));
+}
+
+#[test]
+fn test_batch_reports_each_ping() {
+    let config = TelematicsConfig::default();
+    let at = (now() - Duration::minutes(5)).to_rfc3339();
+    let pings = vec![
+        json!({ "serial_number": "CAT0320-118", "recorded_at": at, "latitude": -18.88, "longitude": 47.51 }),
+        json!({ "serial_number": "CAT0320-118", "recorded_at": at, "latitude": -18.88, "longitude": 47.51 }),
+        json!({ "serial_number": "CAT0320-118", "recorded_at": "yesterday", "fuel_level": 40 }),
+        json!({ "device_id": "TK-0042", "recorded_at": at, "fuel_level": 40, "speed": 12 }),
+        json!({ "device_id": "TK-0042", "recorded_at": at, "fuel_level": 140 }),
+        json!({ "device_id": "TK-0042", "recorded_at": at, "engine_hours": 812.25 }),
+    ];
+    let (valid, report) = check_batch(&pings, now(), &config);
+
+    assert_eq!(valid.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![0, 5]);
+    assert_eq!(report.duplicates, 1);
+    assert_eq!(report.rejected.iter().map(|r| r.index).collect::<Vec<_>>(), vec![2, 3, 4]);
+    assert!(report.rejected[1].error.contains("speed"));
+}
+
+#[test]
+fn test_bearer_key_must_match() {
+    let key = "simulator-key-0123456789";
+    assert!(authorized(Some("Bearer simulator-key-0123456789"), key));
+    assert!(!authorized(Some("Bearer simulator-key-012345678"), key));
+    assert!(!authorized(Some("simulator-key-0123456789"), key));
+    assert!(!authorized(None, key));
+}
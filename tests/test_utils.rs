@@ -1,14 +1,26 @@
-use kfleet::{create_router, AppState};
+#![allow(dead_code)]
+
+use kfleet::config::Config;
+use kfleet::metrics::Metrics;
+use kfleet::{create_router, load_templates, AppState};
 use axum_test::TestServer;
+use chrono::{DateTime, Utc};
 use sqlx::postgres::PgPoolOptions;
-use tera::Tera;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub async fn setup_test_server() -> TestServer {
+pub fn test_database_url() -> String {
     // Use test database URL from environment
     dotenvy::from_filename(".env.test").ok();
-    let db_url = std::env::var("TEST_DATABASE_URL")
-        .expect("TEST_DATABASE_URL must be set in .env.test");
+    std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL must be set in .env.test")
+}
+
+/// Connects to the test database and brings it up to date.
+pub async fn test_pool() -> PgPool {
+    let db_url = test_database_url();
     
     // Create database pool
     let pool = PgPoolOptions::new()
@@ -22,16 +34,23 @@ pub async fn setup_test_server() -> TestServer {
         .run(&pool)
         .await
         .expect("Failed to run migrations");
+    pool
+}
+
+pub async fn setup_test_server() -> TestServer {
+    let pool = test_pool().await;
     
     // Initialize templates
-    let mut tera = Tera::new("templates/**/*")
+    let config = Config::default();
+    let tera = load_templates(&config)
         .expect("Failed to create Tera instance");
-    tera.autoescape_on(vec!["html"]);
     
     // Create application state
     let state = Arc::new(AppState {
         db: pool.clone(),
         templates: tera,
+        config,
+        metrics: Metrics::default(),
     });
     
     // Create router
@@ -47,7 +66,7 @@ pub async fn create_test_equipment(server: &TestServer) -> i32 {
     let category = server.post("/categories")
         .form(&[("name", "Test Equipment Category")])
         .await;
-    assert_eq!(category.status(), 303); // Redirect after create
+    assert_eq!(category.status_code(), 303); // Redirect after create
     
     // Create test equipment
     let equipment = server.post("/equipment")
@@ -61,30 +80,71 @@ pub async fn create_test_equipment(server: &TestServer) -> i32 {
             ("status", "active"),
         ])
         .await;
-    assert_eq!(equipment.status(), 303);
+    assert_eq!(equipment.status_code(), 303);
     
     1 // Return equipment ID
 }
 
 pub async fn create_test_staff(server: &TestServer, equipment_ids: &[i32]) -> i32 {
+    let ids: Vec<String> = equipment_ids.iter().map(|id| id.to_string()).collect();
     let mut form = vec![
         ("full_name", "Test Operator"),
         ("license_number", "OP-123"),
         ("contact_info", "test@example.com"),
     ];
     
-    for id in equipment_ids {
-        form.push(("assigned_equipment", &id.to_string()));
+    for id in &ids {
+        form.push(("assigned_equipment", id));
     }
     
     let response = server.post("/staff")
         .form(&form)
         .await;
     
-    assert_eq!(response.status(), 303);
+    assert_eq!(response.status_code(), 303);
     
     // Extract staff ID from redirect location
     let location = response.headers().get("location").unwrap().to_str().unwrap();
-    location.split('/').last().unwrap().parse().unwrap()
+    location.split('/').next_back().unwrap().parse().unwrap()
+}
+
+/// A name no other test or test run uses, since they all share the test
+/// database and never clean it up.
+pub fn unique(prefix: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    format!("{}-{:x}-{}", prefix, nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+pub async fn insert_category(pool: &PgPool) -> i32 {
+    sqlx::query_scalar("INSERT INTO categories (name) VALUES ($1) RETURNING id")
+        .bind(unique("Category"))
+        .fetch_one(pool)
+        .await
+        .expect("Failed to insert category")
 }
 
+/// An active machine in `category_id`, acquired on `acquired`.
+pub async fn insert_equipment(pool: &PgPool, category_id: i32, acquired: DateTime<Utc>) -> i32 {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO equipment (name, brand, model, serial_number, acquisition_date, category_id)
+        VALUES ($1, 'CAT', '320', $1, $2, $3)
+        RETURNING id
+        "#,
+    )
+    .bind(unique("EXC"))
+    .bind(acquired)
+    .bind(category_id)
+    .fetch_one(pool)
+    .await
+    .expect("Failed to insert equipment")
+}
+
+pub async fn insert_staff(pool: &PgPool) -> i32 {
+    sqlx::query_scalar("INSERT INTO staff (full_name) VALUES ($1) RETURNING id")
+        .bind(unique("Operator"))
+        .fetch_one(pool)
+        .await
+        .expect("Failed to insert staff")
+}
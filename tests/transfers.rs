@@ -0,0 +1,261 @@
+mod test_utils;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use kfleet::sites::{self, SiteForm};
+use kfleet::transfers::{
+    self, compare_checklists, read_checklist, ArrivalForm, Check, DispatchForm, Transfer, CHECKLIST_ITEMS,
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use test_utils::{insert_category, insert_equipment, insert_staff, setup_test_server, test_pool, unique};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+}
+
+/// Every checklist item in the given condition, as the form submits them.
+fn all(condition: &str) -> (Vec<String>, Vec<String>) {
+    CHECKLIST_ITEMS
+        .iter()
+        .map(|(item, _)| (item.to_string(), condition.to_string()))
+        .unzip()
+}
+
+fn transfer() -> Transfer {
+    Transfer {
+        id: 3,
+        equipment_id: 4,
+        equipment_name: "Pelle CAT 320".to_string(),
+        serial_number: "CAT0320-118".to_string(),
+        origin_site_id: None,
+        origin_name: None,
+        destination_site_id: Some(2),
+        destination_name: Some("Antsirabe - RN7".to_string()),
+        driver_id: Some(1),
+        driver_name: Some("Rakoto Jean".to_string()),
+        dispatch_date: date(3, 9),
+        dispatch_meter: 4210.5,
+        dispatch_notes: None,
+        arrival_date: None,
+        arrival_meter: None,
+        arrival_notes: None,
+        damage_candidate: false,
+        created_at: Utc.with_ymd_and_hms(2026, 3, 9, 6, 0, 0).unwrap(),
+    }
+}
+
+fn arrival(arrival_date: NaiveDate, arrival_meter: f64) -> ArrivalForm {
+    let (item, condition) = all("ok");
+    ArrivalForm { arrival_date, arrival_meter, notes: None, item, condition }
+}
+
+fn check(stage: &str, item: &str, condition: &str) -> Check {
+    Check { stage: stage.to_string(), item: item.to_string(), condition: condition.to_string() }
+}
+
+#[test]
+fn test_checklist_covers_every_item_once() {
+    let (items, conditions) = all("ok");
+    let checks = read_checklist("dispatch", &items, &conditions).unwrap();
+    assert_eq!(checks.len(), CHECKLIST_ITEMS.len());
+    assert!(checks.iter().all(|c| c.stage == "dispatch"));
+
+    assert!(read_checklist("dispatch", &items[1..], &conditions[1..]).is_err());
+    assert!(read_checklist("dispatch", &items, &conditions[1..]).is_err());
+
+    let mut twice = items.clone();
+    twice[1] = twice[0].clone();
+    assert!(read_checklist("dispatch", &twice, &conditions).is_err());
+
+    let mut extra = (items.clone(), conditions.clone());
+    extra.0.push("radio".to_string());
+    extra.1.push("ok".to_string());
+    assert!(read_checklist("dispatch", &extra.0, &extra.1).unwrap_err().contains("unknown items"));
+
+    let mut scratched = conditions.clone();
+    scratched[0] = "scratched".to_string();
+    assert!(read_checklist("dispatch", &items, &scratched).unwrap_err().contains("scratched"));
+}
+
+#[test]
+fn test_dispatch_form_validation() {
+    let (item, condition) = all("ok");
+    let form = DispatchForm {
+        equipment_id: 4,
+        destination_site_id: Some(2),
+        driver_id: 1,
+        dispatch_date: date(3, 9),
+        dispatch_meter: 4210.5,
+        notes: None,
+        item,
+        condition,
+    };
+    assert!(form.validate().is_ok());
+    assert!(DispatchForm { dispatch_meter: -1.0, ..form }.validate().is_err());
+}
+
+#[test]
+fn test_arrival_follows_dispatch() {
+    let transfer = transfer();
+    assert!(arrival(date(3, 10), 4212.0).validate(&transfer).is_ok());
+    assert!(arrival(date(3, 9), 4210.5).validate(&transfer).is_ok());
+    assert!(arrival(date(3, 8), 4212.0).validate(&transfer).unwrap_err().contains("left on 2026-03-09"));
+    assert!(arrival(date(3, 10), 4200.0).validate(&transfer).unwrap_err().contains("cannot be lower"));
+
+    let received = Transfer { arrival_date: Some(date(3, 10)), arrival_meter: Some(4212.0), ..transfer };
+    assert!(arrival(date(3, 11), 4212.0).validate(&received).unwrap_err().contains("already received"));
+}
+
+#[test]
+fn test_worse_condition_on_arrival_is_flagged() {
+    let checks = vec![
+        check("dispatch", "bodywork", "worn"),
+        check("dispatch", "glass", "ok"),
+        check("dispatch", "tyres", "damaged"),
+        check("arrival", "bodywork", "worn"),
+        check("arrival", "glass", "damaged"),
+        check("arrival", "tyres", "ok"),
+    ];
+    let rows = compare_checklists(&checks);
+    assert_eq!(rows.len(), CHECKLIST_ITEMS.len());
+
+    let row = |item: &str| rows.iter().find(|r| r.item == item).unwrap();
+    assert!(!row("bodywork").discrepancy);
+    assert!(row("glass").discrepancy);
+    // Repaired or cleaned up on the way is not damage
+    assert!(!row("tyres").discrepancy);
+    // Not yet received: nothing to compare
+    assert_eq!(row("lights").arrived, None);
+    assert!(!row("lights").discrepancy);
+}
+
+#[test]
+fn test_missing_items_are_flagged() {
+    let checks = vec![check("dispatch", "documents", "ok"), check("arrival", "documents", "missing")];
+    let rows = compare_checklists(&checks);
+    assert_eq!(rows.iter().filter(|r| r.discrepancy).count(), 1);
+}
+
+/// Sends a new machine from the yard to a new site on 9 March; returns the
+/// transfer, the machine and the site.
+async fn send(pool: &PgPool) -> (i32, i32, i32) {
+    let category = insert_category(pool).await;
+    let machine = insert_equipment(pool, category, Utc.with_ymd_and_hms(2026, 1, 5, 0, 0, 0).unwrap()).await;
+    let driver = insert_staff(pool).await;
+    let site = sites::create_site(
+        pool,
+        &SiteForm {
+            name: unique("Antsirabe - RN7"),
+            client: None,
+            address: "RN7, Antsirabe".to_string(),
+            latitude: None,
+            longitude: None,
+            start_date: date(1, 5),
+            end_date: None,
+            notes: None,
+        },
+    )
+    .await
+    .unwrap();
+    let (item, condition) = all("ok");
+    let dispatch = DispatchForm {
+        equipment_id: machine,
+        destination_site_id: Some(site),
+        driver_id: driver,
+        dispatch_date: date(3, 9),
+        dispatch_meter: 4210.5,
+        notes: None,
+        item,
+        condition,
+    };
+
+    let mut tx = pool.begin().await.unwrap();
+    assert_eq!(transfers::dispatch_problem(&mut tx, &dispatch).await.unwrap(), None);
+    let id = transfers::dispatch(&mut tx, &dispatch).await.unwrap();
+    tx.commit().await.unwrap();
+    (id, machine, site)
+}
+
+/// Sends a new machine as [`send`] does and receives it on 10 March with
+/// `arrived` checklist conditions; returns the transfer.
+async fn deliver(pool: &PgPool, arrived: (Vec<String>, Vec<String>)) -> i32 {
+    let (id, _, _) = send(pool).await;
+    let transfer = transfers::get_transfer(pool, id).await.unwrap().unwrap();
+    let dispatched = transfers::transfer_checks(pool, id).await.unwrap();
+    let (item, condition) = arrived;
+    let form = ArrivalForm { arrival_date: date(3, 10), arrival_meter: 4260.0, notes: None, item, condition };
+    let mut tx = pool.begin().await.unwrap();
+    assert_eq!(transfers::arrival_problem(&mut tx, &transfer, &form).await.unwrap(), None);
+    transfers::receive(&mut tx, &transfer, &dispatched, &form).await.unwrap().unwrap();
+    tx.commit().await.unwrap();
+    id
+}
+
+async fn damage_reports(pool: &PgPool, transfer_id: i32) -> Vec<Value> {
+    sqlx::query_scalar(
+        r#"
+        SELECT payload FROM webhook_events
+        WHERE event_type = 'damage_report.filed' AND (payload->'transfer'->>'id')::int = $1
+        "#,
+    )
+    .bind(transfer_id)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_damaged_arrival_files_a_damage_report() {
+    let pool = test_pool().await;
+    let (item, mut condition) = all("ok");
+    let glass = item.iter().position(|i| i == "glass").unwrap();
+    condition[glass] = "damaged".to_string();
+    let id = deliver(&pool, (item, condition)).await;
+
+    let transfer = transfers::get_transfer(&pool, id).await.unwrap().unwrap();
+    assert!(transfer.damage_candidate);
+    let reports = damage_reports(&pool, id).await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0]["equipment"]["id"], transfer.equipment_id);
+    assert_eq!(reports[0]["transfer"]["arrival_date"], "2026-03-10");
+    let items: Vec<(&str, &str, &str)> = reports[0]["discrepancies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            (row["item"].as_str().unwrap(), row["dispatched"].as_str().unwrap(), row["arrived"].as_str().unwrap())
+        })
+        .collect();
+    assert_eq!(items, vec![("glass", "ok", "damaged")]);
+}
+
+#[tokio::test]
+async fn test_clean_arrival_files_nothing() {
+    let pool = test_pool().await;
+    let id = deliver(&pool, all("ok")).await;
+    assert!(!transfers::get_transfer(&pool, id).await.unwrap().unwrap().damage_candidate);
+    assert!(damage_reports(&pool, id).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_machines_in_transit_cannot_be_moved_or_deployed() {
+    let server = setup_test_server().await;
+    let pool = test_pool().await;
+    let (id, machine, site) = send(&pool).await;
+
+    let moved = server
+        .post(&format!("/equipment/{}/site", machine))
+        .form(&[("site_id", site.to_string()), ("date", "2026-03-10".to_string())])
+        .await;
+    assert_eq!(moved.status_code(), 200);
+    assert!(moved.text().contains(&format!("in transit (transfer {})", id)));
+
+    let deployed = server
+        .post(&format!("/sites/{}/deployments", site))
+        .form(&[("equipment_id", machine.to_string()), ("date", "2026-03-10".to_string())])
+        .await;
+    assert_eq!(deployed.status_code(), 200);
+    assert!(deployed.text().contains(&format!("in transit (transfer {})", id)));
+
+    assert!(sites::deployments(&pool, None, Some(machine)).await.unwrap().is_empty());
+}
@@ -0,0 +1,29 @@
+use kfleet::uploads::{sanitize_filename, sniff, stored_name};
+
+#[test]
+fn test_sanitize_filename() {
+    assert_eq!(sanitize_filename("police ARO 2026.pdf", "policy.pdf"), "police ARO 2026.pdf");
+    assert_eq!(sanitize_filename("C:\\scans\\policy.pdf", "policy.pdf"), "policy.pdf");
+    assert_eq!(sanitize_filename("../../etc/passwd", "policy.pdf"), "passwd");
+    assert_eq!(sanitize_filename("a\"b;c\r\n.pdf", "policy.pdf"), "abc.pdf");
+    assert_eq!(sanitize_filename("", "licence.pdf"), "licence.pdf");
+}
+
+#[test]
+fn test_uploads_are_sniffed_by_content() {
+    let pdf_only = ["application/pdf"];
+    assert_eq!(sniff(b"%PDF-1.7\n...", &pdf_only), Some(("application/pdf", "pdf")));
+    assert_eq!(sniff(b"<html>", &pdf_only), None);
+
+    let scans = ["application/pdf", "image/jpeg", "image/png"];
+    assert_eq!(sniff(b"\xFF\xD8\xFF\xE0rest", &scans), Some(("image/jpeg", "jpg")));
+    assert_eq!(sniff(b"\x89PNG\r\n\x1a\nrest", &scans), Some(("image/png", "png")));
+    assert_eq!(sniff(b"\x89PNG\r\n\x1a\nrest", &pdf_only), None);
+}
+
+#[test]
+fn test_stored_names_are_unique() {
+    let name = stored_name(12, "pdf");
+    assert!(name.starts_with("12-") && name.ends_with(".pdf"));
+    assert_ne!(name, stored_name(12, "pdf"));
+}
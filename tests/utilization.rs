@@ -0,0 +1,161 @@
+use chrono::{NaiveDate, Weekday};
+use kfleet::config::UtilizationConfig;
+use kfleet::utilization::{
+    by_category, daily_hours, date_range, machine_usage, periods, underused, Machine, MachineUsage, MeterDay,
+    Stay, Totals,
+};
+use std::collections::BTreeMap;
+
+const WEEKDAYS: &[Weekday] = &[Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+
+fn day(d: u32) -> NaiveDate {
+    // March 2026 starts on a Sunday
+    NaiveDate::from_ymd_opt(2026, 3, d).unwrap()
+}
+
+fn reading(d: u32, low: f64, high: f64) -> MeterDay {
+    MeterDay { equipment_id: 1, day: day(d), low, high }
+}
+
+fn machine(id: i32, category: &str) -> Machine {
+    Machine {
+        id,
+        name: format!("Machine {}", id),
+        serial_number: format!("SN-{}", id),
+        category_id: if category == "Excavators" { 1 } else { 2 },
+        category_name: category.to_string(),
+        acquired: day(1),
+    }
+}
+
+fn usage(id: i32, idle_share: Option<f64>, utilization: Option<f64>) -> MachineUsage {
+    MachineUsage {
+        equipment_id: id,
+        name: format!("Machine {}", id),
+        serial_number: format!("SN-{}", id),
+        category_id: 1,
+        category_name: "Excavators".to_string(),
+        working_days: 10,
+        metered_days: if utilization.is_some() { 10 } else { 0 },
+        hours_used: 0.0,
+        hours_available: 0.0,
+        utilization,
+        deployed_days: 0,
+        idle_days: 0,
+        idle_share,
+    }
+}
+
+#[test]
+fn test_meter_rises_are_spread_over_the_days_between_readings() {
+    let hours = daily_hours(&[reading(2, 100.0, 106.0), reading(5, 112.0, 115.0), reading(6, 114.0, 114.0)]);
+
+    assert_eq!(hours[&day(2)], 6.0);
+    // 6 hours between the 2nd and the 5th, two a day
+    assert_eq!(hours[&day(3)], 2.0);
+    assert_eq!(hours[&day(4)], 2.0);
+    assert_eq!(hours[&day(5)], 5.0);
+    // The meter went backwards: nothing counted
+    assert_eq!(hours[&day(6)], 0.0);
+    assert_eq!(hours.len(), 5);
+}
+
+#[test]
+fn test_metered_machine_uses_hours_and_flags_quiet_days() {
+    let config = UtilizationConfig::default();
+    // Monday 2 to Friday 6, plus four hours on Saturday 7
+    let hours: BTreeMap<_, _> =
+        [(day(2), 8.0), (day(3), 4.0), (day(4), 0.2), (day(5), 0.0), (day(6), 8.0), (day(7), 4.0)].into();
+
+    let u = machine_usage(&machine(1, "Excavators"), &hours, &[], day(1), day(8), WEEKDAYS, &config);
+    assert_eq!((u.working_days, u.metered_days), (5, 5));
+    assert_eq!(u.hours_used, 24.2);
+    assert_eq!(u.hours_available, 40.0);
+    assert_eq!(u.idle_days, 2);
+    assert_eq!(u.idle_share, Some(40.0));
+    assert!((u.utilization.unwrap() - 60.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_unmetered_machine_is_idle_when_at_the_yard() {
+    let config = UtilizationConfig::default();
+    let stays = [Stay { equipment_id: 1, from: day(3), until: Some(day(5)) }];
+    let mut m = machine(1, "Compactors");
+    m.acquired = day(3);
+
+    let u = machine_usage(&m, &BTreeMap::new(), &stays, day(1), day(13), WEEKDAYS, &config);
+    // Working days from the 3rd: 3, 4, 5, 6, 9..13
+    assert_eq!(u.working_days, 9);
+    assert_eq!(u.deployed_days, 2);
+    assert_eq!(u.idle_days, 7);
+    assert_eq!(u.utilization, None);
+    assert_eq!(u.metered_days, 0);
+}
+
+#[test]
+fn test_days_outside_meter_readings_fall_back_to_deployments() {
+    let config = UtilizationConfig::default();
+    let hours: BTreeMap<_, _> = [(day(9), 8.0), (day(10), 8.0)].into();
+    let stays = [Stay { equipment_id: 1, from: day(2), until: None }];
+
+    let u = machine_usage(&machine(1, "Excavators"), &hours, &stays, day(2), day(13), WEEKDAYS, &config);
+    assert_eq!((u.working_days, u.metered_days, u.deployed_days), (10, 2, 10));
+    assert_eq!(u.idle_days, 0);
+    assert_eq!(u.utilization, Some(100.0));
+}
+
+#[test]
+fn test_totals_by_category() {
+    let config = UtilizationConfig::default();
+    let hours: BTreeMap<_, _> = [(day(2), 4.0)].into();
+    let usages = vec![
+        machine_usage(&machine(1, "Excavators"), &hours, &[], day(2), day(2), WEEKDAYS, &config),
+        machine_usage(&machine(2, "Compactors"), &BTreeMap::new(), &[], day(2), day(2), WEEKDAYS, &config),
+        machine_usage(&machine(3, "Excavators"), &BTreeMap::new(), &[], day(2), day(2), WEEKDAYS, &config),
+    ];
+
+    let categories = by_category(&usages);
+    assert_eq!(categories[0].category_name, "Compactors");
+    let excavators = &categories[1].totals;
+    assert_eq!((excavators.machines, excavators.metered_machines), (2, 1));
+    assert_eq!(excavators.utilization, Some(50.0));
+    assert_eq!(excavators.idle_share, Some(50.0));
+
+    let fleet = Totals::of(&usages);
+    assert_eq!((fleet.working_days, fleet.idle_days), (3, 2));
+}
+
+#[test]
+fn test_underused_ranks_most_idle_first() {
+    let usages = vec![
+        usage(1, Some(40.0), Some(50.0)),
+        usage(2, Some(80.0), None),
+        usage(3, Some(80.0), Some(10.0)),
+        usage(4, Some(60.0), Some(30.0)),
+        usage(5, None, None),
+    ];
+    let ranked: Vec<i32> = underused(&usages, 50.0).iter().map(|u| u.equipment_id).collect();
+    assert_eq!(ranked, vec![3, 2, 4]);
+}
+
+#[test]
+fn test_trend_periods_end_on_the_last_day() {
+    let weeks = periods("week", day(11), 3);
+    assert_eq!(weeks.len(), 3);
+    assert_eq!((weeks[0].1, weeks[0].2), (day(1) - chrono::Duration::days(6), day(1)));
+    assert_eq!((weeks[2].0.as_str(), weeks[2].1, weeks[2].2), ("2026-W11", day(9), day(11)));
+
+    let months = periods("month", day(11), 2);
+    assert_eq!(months[0].0, "Feb 2026");
+    assert_eq!((months[0].1, months[0].2), (NaiveDate::from_ymd_opt(2026, 2, 1).unwrap(), NaiveDate::from_ymd_opt(2026, 2, 28).unwrap()));
+    assert_eq!((months[1].1, months[1].2), (day(1), day(11)));
+}
+
+#[test]
+fn test_date_range_defaults_and_limits() {
+    assert_eq!(date_range(None, None, day(31), 30), Ok((day(2), day(31))));
+    assert_eq!(date_range(Some(day(5)), Some(day(9)), day(31), 30), Ok((day(5), day(9))));
+    assert!(date_range(Some(day(9)), Some(day(5)), day(31), 30).is_err());
+    assert!(date_range(None, Some(day(31)), day(30), 30).is_err());
+    assert!(date_range(Some(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()), None, day(31), 30).is_err());
+}
@@ -0,0 +1,136 @@
+use chrono::{Datelike, NaiveDate};
+use kfleet::config::{CostsConfig, DepreciationMethod};
+use kfleet::valuation::{register_entry, Asset, AssetRow, RegisterTotals, Terms};
+
+fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+fn asset(cost: f64, salvage: f64, years: i32, method: DepreciationMethod) -> Asset {
+    let machine = Terms::machine(Some(salvage), Some(years), Some(method));
+    Asset::new(cost, date(2020, 1, 1), &machine, &Terms::default(), &CostsConfig::default())
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+#[test]
+fn test_terms_fall_back_to_category_then_config() {
+    let config = CostsConfig::default();
+    let category = Terms::category(Some(10.0), Some(8), Some(DepreciationMethod::DecliningBalance));
+    let acquired = date(2024, 3, 1);
+
+    let defaults = Asset::new(50_000.0, acquired, &Terms::default(), &Terms::default(), &config);
+    assert_eq!((defaults.salvage_value, defaults.useful_life_years, defaults.method), (0.0, 10, DepreciationMethod::StraightLine));
+
+    let from_category = Asset::new(50_000.0, acquired, &Terms::default(), &category, &config);
+    assert_eq!(
+        (from_category.salvage_value, from_category.useful_life_years, from_category.method),
+        (5_000.0, 8, DepreciationMethod::DecliningBalance)
+    );
+    assert_eq!(from_category.fully_depreciated_on, date(2032, 2, 29));
+
+    let own = Terms::machine(Some(2_000.0), Some(4), Some(DepreciationMethod::StraightLine));
+    let from_machine = Asset::new(50_000.0, acquired, &own, &category, &config);
+    assert_eq!(
+        (from_machine.salvage_value, from_machine.useful_life_years, from_machine.method),
+        (2_000.0, 4, DepreciationMethod::StraightLine)
+    );
+}
+
+#[test]
+fn test_terms_validation() {
+    let straight_line = Some(DepreciationMethod::StraightLine);
+    assert!(Terms::machine(Some(1_000.0), Some(10), straight_line).validate(Some(5_000.0)).is_ok());
+    assert!(Terms::machine(Some(6_000.0), None, None).validate(Some(5_000.0)).is_err());
+    assert!(Terms::machine(Some(-1.0), None, None).validate(None).is_err());
+    assert!(Terms::machine(None, Some(0), None).validate(None).is_err());
+    assert!("sum_of_years".parse::<DepreciationMethod>().is_err());
+    assert!(Terms::category(Some(100.0), None, None).validate(None).is_err());
+    assert!(Terms::category(Some(15.0), Some(7), Some(DepreciationMethod::DecliningBalance)).validate(None).is_ok());
+}
+
+#[test]
+fn test_straight_line_writes_off_to_salvage() {
+    let a = asset(100_000.0, 10_000.0, 10, DepreciationMethod::StraightLine);
+    assert_eq!(a.book_value(date(2019, 12, 31)), 100_000.0);
+    let halfway = a.book_value(date(2024, 12, 31));
+    assert!((halfway - 55_000.0).abs() < 20.0, "{}", halfway);
+    assert!(close(a.book_value(date(2029, 12, 31)), 10_000.0));
+    assert!(close(a.book_value(date(2040, 1, 1)), 10_000.0));
+}
+
+#[test]
+fn test_declining_balance_switches_to_straight_line() {
+    let a = asset(100_000.0, 0.0, 5, DepreciationMethod::DecliningBalance);
+    // 40% of the remaining value each year: 60 000, 36 000, 21 600, then
+    // straight-line over the last two years
+    assert!(close(a.book_value(date(2020, 12, 31)), 60_000.0));
+    assert!(close(a.book_value(date(2021, 12, 31)), 36_000.0));
+    assert!(close(a.book_value(date(2022, 12, 31)), 21_600.0));
+    assert!(close(a.book_value(date(2023, 12, 31)), 10_800.0));
+    assert!(close(a.book_value(date(2024, 12, 31)), 0.0));
+
+    let with_salvage = asset(100_000.0, 30_000.0, 5, DepreciationMethod::DecliningBalance);
+    assert!(close(with_salvage.book_value(date(2021, 12, 31)), 36_000.0));
+    assert!(close(with_salvage.book_value(date(2022, 12, 31)), 30_000.0));
+    assert!(close(with_salvage.book_value(date(2030, 1, 1)), 30_000.0));
+}
+
+#[test]
+fn test_schedules_add_up_to_the_depreciable_amount() {
+    let mut a = asset(80_000.0, 8_000.0, 6, DepreciationMethod::DecliningBalance);
+    a = Asset { acquired: date(2021, 7, 15), fully_depreciated_on: date(2027, 7, 14), ..a };
+
+    let yearly = a.schedule(false);
+    assert_eq!(yearly.len(), 7);
+    assert_eq!((yearly[0].from, yearly[0].to), (date(2021, 7, 15), date(2021, 12, 31)));
+    assert_eq!(yearly[6].to, date(2027, 7, 14));
+    assert!(close(yearly.iter().map(|l| l.depreciation).sum(), 72_000.0));
+    assert!(close(yearly[6].closing, 8_000.0));
+    assert!(yearly.windows(2).all(|w| close(w[0].closing, w[1].opening)));
+
+    let monthly = a.schedule(true);
+    assert_eq!(monthly.len(), 6 * 12 + 1);
+    let in_2023: f64 = monthly.iter().filter(|l| l.from.year() == 2023).map(|l| l.depreciation).sum();
+    assert!(close(in_2023, yearly[2].depreciation));
+    assert!(close(monthly.last().unwrap().accumulated, 72_000.0));
+}
+
+#[test]
+fn test_register_values_machines_on_the_day_asked() {
+    let config = CostsConfig::default();
+    let row = |id: i32, purchase_cost: Option<f64>| AssetRow {
+        equipment_id: id,
+        name: format!("Machine {}", id),
+        serial_number: format!("SN-{}", id),
+        category_id: 1,
+        category_name: "Excavators".to_string(),
+        status: "available".to_string(),
+        acquired: date(2020, 1, 1),
+        purchase_cost,
+        salvage_value: None,
+        useful_life_years: Some(4),
+        depreciation_method: None,
+        default_salvage_percent: Some(20.0),
+        default_useful_life_years: None,
+        default_depreciation_method: None,
+    };
+    let as_of = date(2021, 12, 31);
+    let entries = vec![
+        register_entry(row(1, Some(100_000.0)), as_of, &config),
+        register_entry(row(2, None), as_of, &config),
+    ];
+    // 80 000 written off day by day over four years
+    let book_value = entries[0].book_value.unwrap();
+    assert!((book_value - 60_000.0).abs() < 50.0, "{}", book_value);
+    assert!(close(entries[0].accumulated.unwrap(), 100_000.0 - book_value));
+    assert!((entries[0].year_depreciation.unwrap() - 20_000.0).abs() < 50.0);
+    assert!(entries[1].book_value.is_none());
+
+    let totals = RegisterTotals::of(&entries);
+    assert_eq!((totals.assets, totals.unvalued), (1, 1));
+    assert!(close(totals.purchase_cost, 100_000.0));
+    assert!(close(totals.book_value, book_value));
+}
@@ -0,0 +1,71 @@
+use chrono::{TimeZone, Utc};
+use kfleet::config::Config;
+use kfleet::webhooks::{backoff, envelope, generate_secret, sign, EVENT_TYPES};
+use serde_json::json;
+
+#[test]
+fn test_signature_covers_timestamp_and_body() {
+    let body = br#"{"id":1,"type":"equipment.created"}"#;
+    let signature = sign("s3cret", 1_760_000_000, body);
+
+    assert_eq!(signature.len(), 64);
+    assert_eq!(signature, sign("s3cret", 1_760_000_000, body));
+    assert_ne!(signature, sign("s3cret", 1_760_000_001, body));
+    assert_ne!(signature, sign("other", 1_760_000_000, body));
+    assert_ne!(signature, sign("s3cret", 1_760_000_000, br#"{"id":2,"type":"equipment.created"}"#));
+}
+
+#[test]
+fn test_signature_matches_reference_hmac() {
+    // printf '0.' | openssl dgst -sha256 -hmac key
+    assert_eq!(
+        sign("key", 0, b""),
+        "85841b4efc3cd7776c3c8f9b7cca9e281c550e5d19889d78e9e669c6337f000d"
+    );
+}
+
+#[test]
+fn test_backoff_doubles_and_is_capped() {
+    assert_eq!(backoff(1, 30).num_seconds(), 30);
+    assert_eq!(backoff(2, 30).num_seconds(), 60);
+    assert_eq!(backoff(5, 30).num_seconds(), 480);
+    assert_eq!(backoff(20, 30).num_seconds(), 6 * 60 * 60);
+    assert_eq!(backoff(i32::MAX, 30).num_seconds(), 6 * 60 * 60);
+}
+
+#[test]
+fn test_envelope_wraps_event_data() {
+    let created_at = Utc.with_ymd_and_hms(2026, 10, 19, 8, 30, 0).unwrap();
+    let body = envelope(42, "equipment.status_changed", created_at, &json!({ "status": "retired" }));
+
+    assert_eq!(
+        body,
+        json!({
+            "id": 42,
+            "type": "equipment.status_changed",
+            "created_at": "2026-10-19T08:30:00Z",
+            "data": { "status": "retired" },
+        })
+    );
+    assert!(EVENT_TYPES.contains(&"equipment.status_changed"));
+}
+
+#[test]
+fn test_generated_secrets_are_unique_hex() {
+    let a = generate_secret();
+    assert_eq!(a.len(), 64);
+    assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(a, generate_secret());
+}
+
+#[test]
+fn test_invalid_webhook_settings_are_rejected() {
+    let mut config = Config::default();
+    config.database.url = "postgres://localhost/kfleet".to_string();
+    config.webhooks.max_attempts = 0;
+    config.webhooks.timeout_secs = 0;
+
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("webhooks.max_attempts"));
+    assert!(err.contains("webhooks.poll_interval_secs"));
+}